@@ -10,18 +10,43 @@ use crate::IntoData;
 pub struct Command {
     cmd: std::process::Command,
     stdin: Option<crate::Data>,
+    stdin_from: Option<std::process::Stdio>,
+    stdin_close: StdinClose,
     timeout: Option<std::time::Duration>,
+    timeout_grace: Option<std::time::Duration>,
+    signals: Vec<(std::time::Duration, i32)>,
+    max_output: Option<usize>,
     _stderr_to_stdout: bool,
     config: crate::Assert,
 }
 
+/// When [`Command`] closes the command's `stdin`, which it reads as the end of its input
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StdinClose {
+    /// Before writing anything, so the command reads no input
+    Immediately,
+    /// Once [`Command::stdin`] is written
+    #[default]
+    AfterWrite,
+    /// Only once the command exits, leaving the pipe open but empty after what was written
+    Never,
+}
+
+/// Appended to a stream cut short by [`Command::max_output`]
+pub const OUTPUT_LIMIT_MARKER: &str = "\n<output limit exceeded>\n";
+
 /// # Builder API
 impl Command {
     pub fn new(program: impl AsRef<std::ffi::OsStr>) -> Self {
         Self {
             cmd: std::process::Command::new(program),
             stdin: None,
+            stdin_from: None,
+            stdin_close: StdinClose::AfterWrite,
             timeout: None,
+            timeout_grace: None,
+            signals: Vec::new(),
+            max_output: None,
             _stderr_to_stdout: false,
             config: crate::Assert::new().action_env(crate::assert::DEFAULT_ACTION_ENV),
         }
@@ -32,7 +57,12 @@ impl Command {
         Self {
             cmd,
             stdin: None,
+            stdin_from: None,
+            stdin_close: StdinClose::AfterWrite,
             timeout: None,
+            timeout_grace: None,
+            signals: Vec::new(),
+            max_output: None,
             _stderr_to_stdout: false,
             config: crate::Assert::new().action_env(crate::assert::DEFAULT_ACTION_ENV),
         }
@@ -250,6 +280,47 @@ impl Command {
         self
     }
 
+    /// Read `stdin` from `stdio`, like another process's output, rather than [`Command::stdin`]
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use snapbox::cmd::Command;
+    ///
+    /// let echo = std::process::Command::new("echo")
+    ///     .arg("42")
+    ///     .stdout(std::process::Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    /// Command::new("cat")
+    ///     .stdin_from(echo.stdout.unwrap())
+    ///     .assert()
+    ///     .stdout_eq("42\n");
+    /// ```
+    pub fn stdin_from(mut self, stdio: impl Into<std::process::Stdio>) -> Self {
+        self.stdin_from = Some(stdio.into());
+        self
+    }
+
+    /// When to close `stdin`, defaulting to once [`Command::stdin`] is written
+    ///
+    /// With [`StdinClose::Never`], a command reading until the end of its input waits until it is
+    /// killed, like on [`Command::timeout`].
+    ///
+    /// ```rust,no_run
+    /// use snapbox::cmd::Command;
+    /// use snapbox::cmd::StdinClose;
+    ///
+    /// Command::new("cat")
+    ///     .stdin_close(StdinClose::Immediately)
+    ///     .assert()
+    ///     .stdout_eq("");
+    /// ```
+    pub fn stdin_close(mut self, close: StdinClose) -> Self {
+        self.stdin_close = close;
+        self
+    }
+
     /// Error out if a timeout is reached
     ///
     /// ```rust,no_run
@@ -268,6 +339,73 @@ impl Command {
         self
     }
 
+    /// On [`Command::timeout`], ask the command to stop and give it `grace` to exit before killing it
+    ///
+    /// This sends `SIGTERM` on Unix and `CTRL_BREAK` on Windows, where the command is started in
+    /// its own process group to receive it.  Output written while shutting down is captured.
+    ///
+    /// ```rust,no_run
+    /// use snapbox::cmd::Command;
+    /// use snapbox::cmd::cargo_bin;
+    ///
+    /// let assert = Command::new(cargo_bin("snap-fixture"))
+    ///     .timeout(std::time::Duration::from_secs(1))
+    ///     .timeout_grace(std::time::Duration::from_millis(500))
+    ///     .env("sleep", "100")
+    ///     .assert()
+    ///     .failure();
+    /// ```
+    #[cfg(feature = "cmd")]
+    pub fn timeout_grace(mut self, grace: std::time::Duration) -> Self {
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt as _;
+            self.cmd
+                .creation_flags(windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP);
+        }
+        self.timeout_grace = Some(grace);
+        self
+    }
+
+    /// Send `signal`, like `libc::SIGINT`, to the command `after` it was started, if it is still
+    /// running
+    ///
+    /// ```rust,no_run
+    /// use snapbox::cmd::Command;
+    /// use snapbox::cmd::cargo_bin;
+    ///
+    /// let assert = Command::new(cargo_bin("snap-fixture"))
+    ///     .signal(std::time::Duration::from_millis(500), 2)
+    ///     .env("sleep", "100")
+    ///     .assert()
+    ///     .interrupted();
+    /// ```
+    #[cfg(all(feature = "cmd", unix))]
+    pub fn signal(mut self, after: std::time::Duration, signal: i32) -> Self {
+        self.signals.push((after, signal));
+        self
+    }
+
+    /// Stop capturing a stream after `max` bytes
+    ///
+    /// The stream is closed, so a runaway command fails on its next write, and what was captured
+    /// ends with [`OUTPUT_LIMIT_MARKER`].
+    ///
+    /// ```rust,no_run
+    /// use snapbox::cmd::Command;
+    /// use snapbox::cmd::cargo_bin;
+    ///
+    /// let output = Command::new(cargo_bin("snap-fixture"))
+    ///     .max_output(10 * 1024 * 1024)
+    ///     .output()
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "cmd")]
+    pub fn max_output(mut self, max: usize) -> Self {
+        self.max_output = Some(max);
+        self
+    }
+
     /// Merge `stderr` into `stdout`
     #[cfg(feature = "cmd")]
     pub fn stderr_to_stdout(mut self) -> Self {
@@ -301,8 +439,18 @@ impl Command {
     }
 
     /// Run the command and capture the `Output`
-    #[cfg(feature = "cmd")]
     pub fn output(self) -> Result<std::process::Output, std::io::Error> {
+        self.chunked_output().map(|chunked| chunked.output)
+    }
+
+    /// Run the command and capture the `Output`, along with each read of it in order
+    ///
+    /// The chunks show how `stdout` and `stderr` interleaved.  With
+    /// [`Command::stderr_to_stdout`], both are read from a single pipe so the interleaving is
+    /// what the command wrote, on every platform, and each chunk is reported as `stdout`.
+    /// Otherwise, the streams are read separately and the order between them is best-effort.
+    #[cfg(feature = "cmd")]
+    pub fn chunked_output(self) -> Result<ChunkedOutput, std::io::Error> {
         if self._stderr_to_stdout {
             self.single_output()
         } else {
@@ -310,18 +458,22 @@ impl Command {
         }
     }
 
+    /// Run the command and capture the `Output`, along with each read of it in order
     #[cfg(not(feature = "cmd"))]
-    pub fn output(self) -> Result<std::process::Output, std::io::Error> {
+    pub fn chunked_output(self) -> Result<ChunkedOutput, std::io::Error> {
         self.split_output()
     }
 
     #[cfg(feature = "cmd")]
-    fn single_output(mut self) -> Result<std::process::Output, std::io::Error> {
-        self.cmd.stdin(std::process::Stdio::piped());
+    fn single_output(mut self) -> Result<ChunkedOutput, std::io::Error> {
+        let stdin = self.stdin_from.take();
+        self.cmd
+            .stdin(stdin.unwrap_or_else(std::process::Stdio::piped));
         let (reader, writer) = os_pipe::pipe()?;
         let writer_clone = writer.try_clone()?;
         self.cmd.stdout(writer);
         self.cmd.stderr(writer_clone);
+        let start = std::time::Instant::now();
         let mut child = self.cmd.spawn()?;
         // Avoid a deadlock! This parent process is still holding open pipe
         // writers (inside the Command object), and we have to close those
@@ -334,22 +486,42 @@ impl Command {
             .map(|d| d.to_bytes())
             .transpose()
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, e))?;
-        let stdout = process_single_io(&mut child, reader, stdin)?;
-
-        let status = wait(child, self.timeout)?;
-        let stdout = stdout.join().unwrap().ok().unwrap_or_default();
-
-        Ok(std::process::Output {
-            status,
-            stdout,
-            stderr: Default::default(),
+        let (stdout, open_stdin) = process_single_io(
+            &mut child,
+            reader,
+            stdin,
+            self.stdin_close,
+            start,
+            self.max_output,
+        )?;
+
+        let status = wait(
+            child,
+            start,
+            &self.signals,
+            self.timeout,
+            self.timeout_grace,
+        )?;
+        drop(open_stdin);
+        let (stdout, chunks) = stdout.join().unwrap().ok().unwrap_or_default();
+
+        Ok(ChunkedOutput {
+            output: std::process::Output {
+                status,
+                stdout,
+                stderr: Default::default(),
+            },
+            chunks,
         })
     }
 
-    fn split_output(mut self) -> Result<std::process::Output, std::io::Error> {
-        self.cmd.stdin(std::process::Stdio::piped());
+    fn split_output(mut self) -> Result<ChunkedOutput, std::io::Error> {
+        let stdin = self.stdin_from.take();
+        self.cmd
+            .stdin(stdin.unwrap_or_else(std::process::Stdio::piped));
         self.cmd.stdout(std::process::Stdio::piped());
         self.cmd.stderr(std::process::Stdio::piped());
+        let start = std::time::Instant::now();
         let mut child = self.cmd.spawn()?;
 
         let stdin = self
@@ -358,43 +530,104 @@ impl Command {
             .map(|d| d.to_bytes())
             .transpose()
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, e))?;
-        let (stdout, stderr) = process_split_io(&mut child, stdin)?;
-
-        let status = wait(child, self.timeout)?;
-        let stdout = stdout
+        let (stdout, stderr, open_stdin) =
+            process_split_io(&mut child, stdin, self.stdin_close, start, self.max_output)?;
+
+        let status = wait(
+            child,
+            start,
+            &self.signals,
+            self.timeout,
+            self.timeout_grace,
+        )?;
+        drop(open_stdin);
+        let (stdout, stdout_chunks) = stdout
             .and_then(|t| t.join().unwrap().ok())
             .unwrap_or_default();
-        let stderr = stderr
+        let (stderr, stderr_chunks) = stderr
             .and_then(|t| t.join().unwrap().ok())
             .unwrap_or_default();
-
-        Ok(std::process::Output {
-            status,
-            stdout,
-            stderr,
+        let mut chunks = stdout_chunks;
+        chunks.extend(stderr_chunks);
+        // Stable, so reads at the same instant stay in stream order
+        chunks.sort_by_key(|c| c.elapsed);
+
+        Ok(ChunkedOutput {
+            output: std::process::Output {
+                status,
+                stdout,
+                stderr,
+            },
+            chunks,
         })
     }
 }
 
-fn process_split_io(
-    child: &mut std::process::Child,
-    input: Option<Vec<u8>>,
-) -> std::io::Result<(Option<Stream>, Option<Stream>)> {
-    use std::io::Write;
+/// A command's [`Output`][std::process::Output] with how it was read, from
+/// [`Command::chunked_output`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkedOutput {
+    pub output: std::process::Output,
+    /// Each read of `stdout` and `stderr`, in the order they were read
+    pub chunks: Vec<OutputChunk>,
+}
 
-    let stdin = input.and_then(|i| {
-        child
-            .stdin
-            .take()
-            .map(|mut stdin| std::thread::spawn(move || stdin.write_all(&i)))
-    });
-    let stdout = child.stdout.take().map(threaded_read);
-    let stderr = child.stderr.take().map(threaded_read);
+/// One read of a command's output
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutputChunk {
+    pub stream: OutputStream,
+    /// Where the chunk starts within its stream
+    pub offset: usize,
+    pub len: usize,
+    /// When the chunk was read, relative to the command starting
+    pub elapsed: std::time::Duration,
+}
+
+/// Which of a command's streams an [`OutputChunk`] was read from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+impl std::fmt::Display for OutputStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
 
-    // Finish writing stdin before waiting, because waiting drops stdin.
-    stdin.and_then(|t| t.join().unwrap().ok());
+/// `stdin`, when held open until the command exits with [`StdinClose::Never`]
+type OpenStdin = Option<std::process::ChildStdin>;
 
-    Ok((stdout, stderr))
+fn process_split_io(
+    child: &mut std::process::Child,
+    input: Option<Vec<u8>>,
+    close: StdinClose,
+    start: std::time::Instant,
+    limit: Option<usize>,
+) -> std::io::Result<(Option<Stream>, Option<Stream>, OpenStdin)> {
+    let stdin = write_stdin(child, input, close);
+    let stdout = child
+        .stdout
+        .take()
+        .map(|s| threaded_read(s, OutputStream::Stdout, start, limit));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|s| threaded_read(s, OutputStream::Stderr, start, limit));
+
+    let stdin = finish_stdin(stdin, close);
+
+    Ok((stdout, stderr, stdin))
 }
 
 #[cfg(feature = "cmd")]
@@ -402,34 +635,94 @@ fn process_single_io(
     child: &mut std::process::Child,
     stdout: os_pipe::PipeReader,
     input: Option<Vec<u8>>,
-) -> std::io::Result<Stream> {
-    use std::io::Write;
-
-    let stdin = input.and_then(|i| {
-        child
-            .stdin
-            .take()
-            .map(|mut stdin| std::thread::spawn(move || stdin.write_all(&i)))
-    });
-    let stdout = threaded_read(stdout);
+    close: StdinClose,
+    start: std::time::Instant,
+    limit: Option<usize>,
+) -> std::io::Result<(Stream, OpenStdin)> {
+    let stdin = write_stdin(child, input, close);
+    let stdout = threaded_read(stdout, OutputStream::Stdout, start, limit);
     debug_assert!(child.stdout.is_none());
     debug_assert!(child.stderr.is_none());
 
-    // Finish writing stdin before waiting, because waiting drops stdin.
-    stdin.and_then(|t| t.join().unwrap().ok());
+    let stdin = finish_stdin(stdin, close);
+
+    Ok((stdout, stdin))
+}
+
+/// Write `input` on a thread, so the command's output can be read meanwhile
+fn write_stdin(
+    child: &mut std::process::Child,
+    input: Option<Vec<u8>>,
+    close: StdinClose,
+) -> Option<std::thread::JoinHandle<std::process::ChildStdin>> {
+    use std::io::Write;
+
+    let mut stdin = child.stdin.take()?;
+    if close == StdinClose::Immediately {
+        return None;
+    }
+    Some(std::thread::spawn(move || {
+        if let Some(input) = input {
+            let _ = stdin.write_all(&input);
+        }
+        stdin
+    }))
+}
 
-    Ok(stdout)
+/// Finish writing `stdin` before waiting, closing it unless it is to be held open
+fn finish_stdin(
+    stdin: Option<std::thread::JoinHandle<std::process::ChildStdin>>,
+    close: StdinClose,
+) -> OpenStdin {
+    let stdin = stdin.map(|t| t.join().unwrap());
+    stdin.filter(|_| close == StdinClose::Never)
 }
 
-type Stream = std::thread::JoinHandle<Result<Vec<u8>, std::io::Error>>;
+type Stream = std::thread::JoinHandle<Result<(Vec<u8>, Vec<OutputChunk>), std::io::Error>>;
 
-fn threaded_read<R>(mut input: R) -> Stream
+fn threaded_read<R>(
+    mut input: R,
+    stream: OutputStream,
+    start: std::time::Instant,
+    limit: Option<usize>,
+) -> Stream
 where
     R: std::io::Read + Send + 'static,
 {
     std::thread::spawn(move || {
         let mut ret = Vec::new();
-        input.read_to_end(&mut ret).map(|_| ret)
+        let mut chunks = Vec::new();
+        let mut buffer = [0; 8 * 1024];
+        loop {
+            // Read one past the limit to tell a full stream from an overflowing one
+            let want = match limit {
+                Some(limit) => (limit.saturating_add(1) - ret.len()).min(buffer.len()),
+                None => buffer.len(),
+            };
+            if want == 0 {
+                break;
+            }
+            let len = match input.read(&mut buffer[..want]) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            chunks.push(OutputChunk {
+                stream,
+                offset: ret.len(),
+                len,
+                elapsed: start.elapsed(),
+            });
+            ret.extend_from_slice(&buffer[..len]);
+        }
+        if let Some(limit) = limit {
+            if limit < ret.len() {
+                ret.truncate(limit);
+                ret.extend_from_slice(OUTPUT_LIMIT_MARKER.as_bytes());
+            }
+        }
+        Ok((ret, chunks))
     })
 }
 
@@ -823,11 +1116,39 @@ fn basic_exit_status(status: std::process::ExitStatus) -> String {
 #[cfg(feature = "cmd")]
 fn wait(
     mut child: std::process::Child,
+    start: std::time::Instant,
+    signals: &[(std::time::Duration, i32)],
     timeout: Option<std::time::Duration>,
+    grace: Option<std::time::Duration>,
 ) -> std::io::Result<std::process::ExitStatus> {
-    if let Some(timeout) = timeout {
+    let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+    let mut signals = signals.to_vec();
+    signals.sort_by_key(|(after, _)| *after);
+    for (after, _signal) in signals {
+        let at = start + after;
+        if deadline.map(|deadline| deadline <= at).unwrap_or(false) {
+            break;
+        }
+        let remaining = at.saturating_duration_since(std::time::Instant::now());
+        if let Some(status) = wait_timeout::ChildExt::wait_timeout(&mut child, remaining)? {
+            return Ok(status);
+        }
+        #[cfg(unix)]
+        // SAFETY: `kill` has no memory safety requirements
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, _signal);
+        }
+    }
+
+    if let Some(deadline) = deadline {
+        let timeout = deadline.saturating_duration_since(std::time::Instant::now());
         wait_timeout::ChildExt::wait_timeout(&mut child, timeout)
             .transpose()
+            .or_else(|| {
+                let grace = grace?;
+                terminate(&child);
+                wait_timeout::ChildExt::wait_timeout(&mut child, grace).transpose()
+            })
             .unwrap_or_else(|| {
                 let _ = child.kill();
                 child.wait()
@@ -840,17 +1161,49 @@ fn wait(
 #[cfg(not(feature = "cmd"))]
 fn wait(
     mut child: std::process::Child,
+    _start: std::time::Instant,
+    _signals: &[(std::time::Duration, i32)],
     _timeout: Option<std::time::Duration>,
+    _grace: Option<std::time::Duration>,
 ) -> std::io::Result<std::process::ExitStatus> {
     child.wait()
 }
 
+/// Ask `child` to shut down cleanly, ignoring failures as it is killed next anyways
+#[cfg(all(feature = "cmd", unix))]
+fn terminate(child: &std::process::Child) {
+    // SAFETY: `kill` has no memory safety requirements
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(all(feature = "cmd", windows))]
+fn terminate(child: &std::process::Child) {
+    use windows_sys::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+
+    // SAFETY: `GenerateConsoleCtrlEvent` has no memory safety requirements
+    unsafe {
+        GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, child.id());
+    }
+}
+
+#[cfg(all(feature = "cmd", not(any(unix, windows))))]
+fn terminate(_child: &std::process::Child) {}
+
 pub use snapbox_macros::cargo_bin;
 
 /// Look up the path to a cargo-built binary within an integration test.
 ///
+/// This uses the `CARGO_BIN_EXE_<name>` environment variable when cargo sets it while running
+/// tests, otherwise guessing the path from the location of the test binary.
+///
 /// **NOTE:** Prefer [`cargo_bin!`] as this makes assumptions about cargo
 pub fn cargo_bin(name: &str) -> std::path::PathBuf {
+    if let Some(path) = std::env::var_os(format!("CARGO_BIN_EXE_{name}")) {
+        return path.into();
+    }
+
     let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
     let target_dir = target_dir();
     target_dir.join(file_name)
@@ -872,7 +1225,7 @@ fn target_dir() -> std::path::PathBuf {
 }
 
 #[cfg(feature = "examples")]
-pub use examples::{compile_example, compile_examples};
+pub use examples::{compile_example, compile_examples, compile_workspace_bins};
 
 #[cfg(feature = "examples")]
 pub(crate) mod examples {
@@ -906,7 +1259,7 @@ pub(crate) mod examples {
                 .decode()
                 .map_err(|e| crate::assert::Error::new(e.to_string()))?;
             crate::debug!("Message: {:?}", message);
-            if let Some(bin) = decode_example_message(&message) {
+            if let Some(bin) = decode_build_message(&message, "example") {
                 let (name, bin) = bin?;
                 assert_eq!(target_name, name);
                 return bin;
@@ -952,7 +1305,7 @@ pub(crate) mod examples {
                 .decode()
                 .map_err(|e| crate::assert::Error::new(e.to_string()))?;
             crate::debug!("Message: {:?}", message);
-            if let Some(bin) = decode_example_message(&message) {
+            if let Some(bin) = decode_build_message(&message, "example") {
                 let (name, bin) = bin?;
                 examples.insert(name.to_owned(), bin);
             }
@@ -961,9 +1314,53 @@ pub(crate) mod examples {
         Ok(examples.into_iter())
     }
 
+    /// Prepare the binaries of all workspace members for testing
+    ///
+    /// Unlike `cargo_bin!`, this does not inherit all of the current compiler settings.  It
+    /// will match the current target and profile but will not get feature flags.  Pass those arguments
+    /// to the compiler via `args`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// let bins = snapbox::cmd::compile_workspace_bins([]).unwrap().collect::<Vec<_>>();
+    /// ```
+    #[cfg(feature = "examples")]
+    pub fn compile_workspace_bins<'a>(
+        args: impl IntoIterator<Item = &'a str>,
+    ) -> crate::assert::Result<
+        impl Iterator<Item = (String, crate::assert::Result<std::path::PathBuf>)>,
+    > {
+        crate::debug!("Compiling workspace bins");
+        let mut bins = std::collections::BTreeMap::new();
+
+        let messages = escargot::CargoBuild::new()
+            .current_target()
+            .current_release()
+            .arg("--workspace")
+            .arg("--bins")
+            .args(args)
+            .exec()
+            .map_err(|e| crate::assert::Error::new(e.to_string()))?;
+        for message in messages {
+            let message = message.map_err(|e| crate::assert::Error::new(e.to_string()))?;
+            let message = message
+                .decode()
+                .map_err(|e| crate::assert::Error::new(e.to_string()))?;
+            crate::debug!("Message: {:?}", message);
+            if let Some(bin) = decode_build_message(&message, "bin") {
+                let (name, bin) = bin?;
+                bins.insert(name.to_owned(), bin);
+            }
+        }
+
+        Ok(bins.into_iter())
+    }
+
     #[allow(clippy::type_complexity)]
-    fn decode_example_message<'m>(
+    fn decode_build_message<'m>(
         message: &'m escargot::format::Message<'_>,
+        kind: &str,
     ) -> Option<crate::assert::Result<(&'m str, crate::assert::Result<std::path::PathBuf>)>> {
         match message {
             escargot::format::Message::CompilerMessage(msg) => {
@@ -977,7 +1374,7 @@ pub(crate) mod examples {
                         .as_deref()
                         .unwrap_or_else(|| msg.message.message.as_ref())
                         .to_owned();
-                    if is_example_target(&msg.target) {
+                    if is_target_kind(&msg.target, kind) {
                         let bin = Err(crate::assert::Error::new(output));
                         Some(Ok((msg.target.name.as_ref(), bin)))
                     } else {
@@ -988,7 +1385,7 @@ pub(crate) mod examples {
                 }
             }
             escargot::format::Message::CompilerArtifact(artifact) => {
-                if !artifact.profile.test && is_example_target(&artifact.target) {
+                if !artifact.profile.test && is_target_kind(&artifact.target, kind) {
                     let path = artifact
                         .executable
                         .clone()
@@ -1003,7 +1400,7 @@ pub(crate) mod examples {
         }
     }
 
-    fn is_example_target(target: &escargot::format::Target<'_>) -> bool {
-        target.crate_types == ["bin"] && target.kind == ["example"]
+    fn is_target_kind(target: &escargot::format::Target<'_>, kind: &str) -> bool {
+        target.crate_types == ["bin"] && target.kind == [kind]
     }
 }
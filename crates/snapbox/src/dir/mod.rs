@@ -10,6 +10,10 @@ pub use diff::FileType;
 pub use diff::PathDiff;
 #[cfg(feature = "dir")]
 pub use ops::copy_template;
+#[cfg(feature = "dir")]
+pub use ops::copy_template_filtered;
+#[cfg(feature = "dir")]
+pub use ops::link_template_filtered;
 pub use ops::resolve_dir;
 pub use ops::strip_trailing_slash;
 #[cfg(feature = "dir")]
@@ -44,8 +44,49 @@ pub fn copy_template(
     source: impl AsRef<std::path::Path>,
     dest: impl AsRef<std::path::Path>,
 ) -> Result<(), crate::assert::Error> {
-    let source = source.as_ref();
-    let dest = dest.as_ref();
+    copy_template_filtered(source, dest, |_| true)
+}
+
+/// Copy a template into a [`DirRoot`][super::DirRoot], skipping paths `include` rejects
+///
+/// `include` is given paths relative to `source`.  The contents of rejected directories are
+/// skipped as well.
+///
+/// Note: Ignores `.keep` files
+#[cfg(feature = "dir")]
+pub fn copy_template_filtered(
+    source: impl AsRef<std::path::Path>,
+    dest: impl AsRef<std::path::Path>,
+    include: impl Fn(&std::path::Path) -> bool,
+) -> Result<(), crate::assert::Error> {
+    populate_template(source.as_ref(), dest.as_ref(), include, shallow_copy)
+}
+
+/// Hard-link a template's files into a [`DirRoot`][super::DirRoot], skipping paths `include`
+/// rejects
+///
+/// This is much faster than copying large templates, but the files are shared with `source`, so
+/// writing to one changes the template.  Creating, renaming, and removing files is safe, as is
+/// replacing a file by removing it first.  Files that can't be linked, like across filesystems,
+/// are copied.
+///
+/// Note: Ignores `.keep` files
+#[cfg(feature = "dir")]
+pub fn link_template_filtered(
+    source: impl AsRef<std::path::Path>,
+    dest: impl AsRef<std::path::Path>,
+    include: impl Fn(&std::path::Path) -> bool,
+) -> Result<(), crate::assert::Error> {
+    populate_template(source.as_ref(), dest.as_ref(), include, shallow_link)
+}
+
+#[cfg(feature = "dir")]
+fn populate_template(
+    source: &std::path::Path,
+    dest: &std::path::Path,
+    include: impl Fn(&std::path::Path) -> bool,
+    populate: fn(&std::path::Path, &std::path::Path) -> Result<(), crate::assert::Error>,
+) -> Result<(), crate::assert::Error> {
     let source = canonicalize(source)
         .map_err(|e| format!("Failed to canonicalize {}: {}", source.display(), e))?;
     std::fs::create_dir_all(dest)
@@ -53,12 +94,21 @@ pub fn copy_template(
     let dest = canonicalize(dest)
         .map_err(|e| format!("Failed to canonicalize {}: {}", dest.display(), e))?;
 
-    for current in Walk::new(&source) {
+    let walker = walkdir::WalkDir::new(&source)
+        .into_iter()
+        .filter_entry(|entry| {
+            let rel = entry.path().strip_prefix(&source).unwrap();
+            rel.as_os_str().is_empty() || include(rel)
+        });
+    for current in walker {
         let current = current.map_err(|e| e.to_string())?;
-        let rel = current.strip_prefix(&source).unwrap();
+        if current.file_name() == ".keep" {
+            continue;
+        }
+        let rel = current.path().strip_prefix(&source).unwrap();
         let target = dest.join(rel);
 
-        shallow_copy(&current, &target)?;
+        populate(current.path(), &target)?;
     }
 
     Ok(())
@@ -110,6 +160,22 @@ pub(crate) fn shallow_copy(
     Ok(())
 }
 
+/// Hard-link a file, falling back to [`shallow_copy`]
+#[cfg(feature = "dir")]
+fn shallow_link(
+    source: &std::path::Path,
+    dest: &std::path::Path,
+) -> Result<(), crate::assert::Error> {
+    let is_file = source
+        .symlink_metadata()
+        .map(|meta| meta.is_file())
+        .unwrap_or(false);
+    if is_file && std::fs::hard_link(source, dest).is_ok() {
+        return Ok(());
+    }
+    shallow_copy(source, dest)
+}
+
 #[cfg(feature = "dir")]
 fn copy_stats(
     source_meta: &std::fs::Metadata,
@@ -27,6 +27,26 @@ impl DirRoot {
     #[cfg(feature = "dir")]
     pub fn mutable_temp() -> Result<Self, crate::assert::Error> {
         let temp = tempfile::tempdir().map_err(|e| e.to_string())?;
+        Self::from_temp(temp)
+    }
+
+    /// A temporary directory within `parent`, rather than the OS's, creating `parent` if needed
+    #[cfg(feature = "dir")]
+    pub fn mutable_temp_in(parent: &std::path::Path) -> Result<Self, crate::assert::Error> {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        let temp = tempfile::tempdir_in(parent).map_err(|e| {
+            format!(
+                "Failed to create a directory in {}: {}",
+                parent.display(),
+                e
+            )
+        })?;
+        Self::from_temp(temp)
+    }
+
+    #[cfg(feature = "dir")]
+    fn from_temp(temp: tempfile::TempDir) -> Result<Self, crate::assert::Error> {
         // We need to get the `/private` prefix on Mac so variable substitutions work
         // correctly
         let path = crate::dir::canonicalize(temp.path())
@@ -46,6 +66,16 @@ impl DirRoot {
     pub fn with_template(
         self,
         template_root: &std::path::Path,
+    ) -> Result<Self, crate::assert::Error> {
+        self.with_template_filtered(template_root, |_| true)
+    }
+
+    /// Initialize from `template_root`, skipping paths (relative to it) that `include` rejects
+    #[cfg(feature = "dir")]
+    pub fn with_template_filtered(
+        self,
+        template_root: &std::path::Path,
+        include: impl Fn(&std::path::Path) -> bool,
     ) -> Result<Self, crate::assert::Error> {
         match &self.0 {
             DirRootInner::None | DirRootInner::Immutable(_) => {
@@ -57,7 +87,31 @@ impl DirRoot {
                     path.display(),
                     template_root.display()
                 );
-                super::copy_template(template_root, path)?;
+                super::copy_template_filtered(template_root, path, include)?;
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Initialize from `template_root` by hard-linking its files, see [`super::link_template_filtered`]
+    #[cfg(feature = "dir")]
+    pub fn with_linked_template_filtered(
+        self,
+        template_root: &std::path::Path,
+        include: impl Fn(&std::path::Path) -> bool,
+    ) -> Result<Self, crate::assert::Error> {
+        match &self.0 {
+            DirRootInner::None | DirRootInner::Immutable(_) => {
+                return Err("Sandboxing is disabled".into());
+            }
+            DirRootInner::MutablePath(path) | DirRootInner::MutableTemp { path, .. } => {
+                crate::debug!(
+                    "Linking {} from {}",
+                    path.display(),
+                    template_root.display()
+                );
+                super::link_template_filtered(template_root, path, include)?;
             }
         }
 
@@ -95,6 +149,21 @@ impl DirRoot {
             DirRootInner::MutableTemp { temp, .. } => temp.close(),
         }
     }
+
+    /// Stop managing the directory, leaving it in place for inspection
+    pub fn keep(self) -> Option<std::path::PathBuf> {
+        match self.0 {
+            DirRootInner::None => None,
+            DirRootInner::Immutable(path) => Some(path),
+            #[cfg(feature = "dir")]
+            DirRootInner::MutablePath(path) => Some(path),
+            #[cfg(feature = "dir")]
+            DirRootInner::MutableTemp { temp, path } => {
+                let _ = temp.into_path();
+                Some(path)
+            }
+        }
+    }
 }
 
 impl Default for DirRoot {
@@ -102,3 +171,17 @@ impl Default for DirRoot {
         Self::none()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "dir")]
+    fn keep_temp() {
+        let root = DirRoot::mutable_temp().unwrap();
+        let path = root.keep().unwrap();
+        assert!(path.exists());
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -34,3 +34,34 @@ fn file_type_detect_missing() {
     let actual = FileType::from_path(path);
     assert_eq!(actual, FileType::Missing);
 }
+
+#[test]
+#[cfg(feature = "dir")]
+fn link_template_shares_files() {
+    let template = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(template.path().join("nested")).unwrap();
+    std::fs::write(template.path().join("nested/data.txt"), "data").unwrap();
+    std::fs::write(template.path().join("skipped.txt"), "skipped").unwrap();
+
+    let root = DirRoot::mutable_temp()
+        .unwrap()
+        .with_linked_template_filtered(template.path(), |rel| {
+            rel != std::path::Path::new("skipped.txt")
+        })
+        .unwrap();
+    let root = root.path().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(root.join("nested/data.txt")).unwrap(),
+        "data"
+    );
+    assert!(!root.join("skipped.txt").exists());
+    let original = std::fs::metadata(template.path().join("nested/data.txt")).unwrap();
+    assert!(!original.permissions().readonly());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+        let linked = std::fs::metadata(root.join("nested/data.txt")).unwrap();
+        let original = std::fs::metadata(template.path().join("nested/data.txt")).unwrap();
+        assert_eq!(linked.ino(), original.ino());
+    }
+}
@@ -42,7 +42,8 @@ impl Redactions {
 
     /// Insert an additional match pattern
     ///
-    /// `placeholder` must be enclosed in `[` and `]`.
+    /// `placeholder` must be enclosed in `[` and `]`, and may end with a `:label` to tell apart
+    /// values of the same kind, like `[PORT:api]`.
     ///
     /// ```rust
     /// let mut subst = snapbox::Redactions::new();
@@ -330,12 +331,30 @@ fn validate_placeholder(placeholder: &'static str) -> crate::assert::Result<&'st
         return Err(format!("Key `{}` is not enclosed in []", placeholder).into());
     }
 
-    if placeholder[1..(placeholder.len() - 1)]
+    let inner = &placeholder[1..(placeholder.len() - 1)];
+    let (name, label) = match inner.split_once(':') {
+        Some((name, label)) => (name, Some(label)),
+        None => (inner, None),
+    };
+    if name
         .find(|c: char| !c.is_ascii_uppercase() && c != '_')
         .is_some()
     {
         return Err(format!("Key `{}` can only be A-Z but ", placeholder).into());
     }
+    if let Some(label) = label {
+        if label.is_empty()
+            || label
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-')
+                .is_some()
+        {
+            return Err(format!(
+                "Key `{}` can only be labeled with letters, digits, `_`, and `-`",
+                placeholder
+            )
+            .into());
+        }
+    }
 
     Ok(placeholder)
 }
@@ -353,6 +372,11 @@ mod test {
             ("[HELLO_WORLD]", true),
             ("[hello]", false),
             ("[HE  O]", false),
+            ("[PORT:api]", true),
+            ("[PORT:api-2]", true),
+            ("[PORT:]", false),
+            ("[PORT:a b]", false),
+            ("[port:api]", false),
         ];
         for (placeholder, expected) in cases {
             let actual = validate_placeholder(placeholder).is_ok();
@@ -1,5 +1,19 @@
 use crate::report::Styled;
 
+/// Environment variable overriding how many unchanged lines are shown around each change
+#[cfg(feature = "diff")]
+const CONTEXT_ENV: &str = "SNAPBOX_DIFF_CONTEXT";
+#[cfg(feature = "diff")]
+const DEFAULT_CONTEXT: usize = 5;
+
+/// Render the difference between `expected` and `actual`
+///
+/// With the `diff` feature, this is a line diff with changed spans within a line emphasized.
+/// Runs of unchanged lines are elided, with a count of how many, keeping 5 lines of context
+/// around each change.  Set `SNAPBOX_DIFF_CONTEXT` to the number of context lines to show
+/// instead, or to `all` to show every line.
+///
+/// Binary data is shown as a hex dump of the first rows that differ.
 pub fn write_diff(
     writer: &mut dyn std::fmt::Write,
     expected: &crate::Data,
@@ -35,6 +49,7 @@ pub fn write_diff(
             palette,
             expected_line_offset,
             actual_line_offset,
+            context_lines(),
         )?;
         rendered = true;
     } else if let (Some(expected), Some(actual)) = (expected.render(), actual.render()) {
@@ -49,10 +64,29 @@ pub fn write_diff(
             palette,
             expected_line_offset,
             actual_line_offset,
+            context_lines(),
         )?;
         rendered = true;
     }
 
+    if !rendered {
+        let is_binary = expected.format() == crate::data::DataFormat::Binary
+            || actual.format() == crate::data::DataFormat::Binary;
+        if let (true, Ok(expected), Ok(actual)) =
+            (is_binary, expected.to_bytes(), actual.to_bytes())
+        {
+            write_binary_diff(
+                writer,
+                &expected,
+                &actual,
+                expected_name,
+                actual_name,
+                palette,
+            )?;
+            rendered = true;
+        }
+    }
+
     if !rendered {
         if let Some(expected_name) = expected_name {
             writeln!(writer, "{} {}:", expected_name, palette.error("(expected)"))?;
@@ -70,6 +104,166 @@ pub fn write_diff(
     Ok(())
 }
 
+/// Bytes shown per row of a hex dump
+const HEX_ROW_LEN: usize = 16;
+/// Differing rows shown before the rest are summarized
+const HEX_MAX_ROWS: usize = 8;
+
+/// Report differing rows of a hex dump
+fn write_binary_diff(
+    writer: &mut dyn std::fmt::Write,
+    expected: &[u8],
+    actual: &[u8],
+    expected_name: Option<&dyn std::fmt::Display>,
+    actual_name: Option<&dyn std::fmt::Display>,
+    palette: crate::report::Palette,
+) -> Result<(), std::fmt::Error> {
+    writeln!(writer)?;
+    if let Some(expected_name) = expected_name {
+        writeln!(
+            writer,
+            "{}",
+            palette.error(format_args!(
+                "{:->4} expected: {} ({} bytes)",
+                "",
+                expected_name,
+                expected.len()
+            ))
+        )?;
+    } else {
+        writeln!(
+            writer,
+            "{}",
+            palette.error(format_args!("--- Expected ({} bytes)", expected.len()))
+        )?;
+    }
+    if let Some(actual_name) = actual_name {
+        writeln!(
+            writer,
+            "{}",
+            palette.info(format_args!(
+                "{:+>4} actual:   {} ({} bytes)",
+                "",
+                actual_name,
+                actual.len()
+            ))
+        )?;
+    } else {
+        writeln!(
+            writer,
+            "{}",
+            palette.info(format_args!("+++ Actual ({} bytes)", actual.len()))
+        )?;
+    }
+
+    let rows = (expected.len().max(actual.len()) + HEX_ROW_LEN - 1) / HEX_ROW_LEN;
+    let mut shown = 0;
+    let mut remaining = 0;
+    for row in 0..rows {
+        let offset = row * HEX_ROW_LEN;
+        let expected_row = hex_row(expected, offset);
+        let actual_row = hex_row(actual, offset);
+        if expected_row == actual_row {
+            continue;
+        }
+        if shown == HEX_MAX_ROWS {
+            remaining += 1;
+            continue;
+        }
+        shown += 1;
+
+        write_hex_row(
+            writer,
+            offset,
+            expected_row,
+            actual_row,
+            "-",
+            palette.expected,
+            palette.error,
+        )?;
+        write_hex_row(
+            writer,
+            offset,
+            actual_row,
+            expected_row,
+            "+",
+            palette.actual,
+            palette.info,
+        )?;
+    }
+    if remaining != 0 {
+        writeln!(
+            writer,
+            "{}",
+            palette.hint(format_args!("⋮ {remaining} more differing rows"))
+        )?;
+    }
+
+    Ok(())
+}
+
+fn hex_row(data: &[u8], offset: usize) -> &[u8] {
+    let start = offset.min(data.len());
+    let end = (offset + HEX_ROW_LEN).min(data.len());
+    &data[start..end]
+}
+
+fn write_hex_row(
+    writer: &mut dyn std::fmt::Write,
+    offset: usize,
+    row: &[u8],
+    other: &[u8],
+    sign: &str,
+    em_style: crate::report::Style,
+    style: crate::report::Style,
+) -> Result<(), std::fmt::Error> {
+    write!(writer, "{:08x} {} ", offset, Styled::new(sign, style))?;
+    for i in 0..HEX_ROW_LEN {
+        match row.get(i) {
+            Some(byte) => {
+                let cur_style = if other.get(i) == Some(byte) {
+                    style
+                } else {
+                    em_style
+                };
+                write!(
+                    writer,
+                    "{} ",
+                    Styled::new(format_args!("{byte:02x}"), cur_style)
+                )?;
+            }
+            None => write!(writer, "   ")?,
+        }
+    }
+    let ascii = row
+        .iter()
+        .map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        })
+        .collect::<String>();
+    writeln!(writer, "|{}|", Styled::new(ascii, style))?;
+
+    Ok(())
+}
+
+#[cfg(feature = "diff")]
+fn context_lines() -> usize {
+    parse_context(std::env::var(CONTEXT_ENV).ok().as_deref())
+}
+
+#[cfg(feature = "diff")]
+fn parse_context(value: Option<&str>) -> usize {
+    match value.map(str::trim) {
+        Some("all") => usize::MAX,
+        Some(value) => value.parse().unwrap_or(DEFAULT_CONTEXT),
+        None => DEFAULT_CONTEXT,
+    }
+}
+
 #[cfg(feature = "diff")]
 #[allow(clippy::too_many_arguments)]
 fn write_diff_inner(
@@ -81,10 +275,10 @@ fn write_diff_inner(
     palette: crate::report::Palette,
     expected_line_offset: usize,
     actual_line_offset: usize,
+    context: usize,
 ) -> Result<(), std::fmt::Error> {
     let timeout = std::time::Duration::from_millis(500);
     let min_elide = 20;
-    let context = 5;
 
     let changes = similar::TextDiff::configure()
         .algorithm(similar::Algorithm::Patience)
@@ -159,11 +353,16 @@ fn write_diff_inner(
     for (i, change) in changes.into_iter().enumerate() {
         if tombstones.get(i).copied().unwrap_or(false) {
             if !elided {
-                let sign = "⋮";
+                let count = tombstones[i..].iter().take_while(|t| **t).count();
+                let noun = if count == 1 { "line" } else { "lines" };
 
                 write!(writer, "{:>4} ", " ",)?;
                 write!(writer, "{:>4} ", " ",)?;
-                writeln!(writer, "{}", palette.hint(sign))?;
+                writeln!(
+                    writer,
+                    "{}",
+                    palette.hint(format_args!("⋮ {count} identical {noun}"))
+                )?;
             }
             elided = true;
         } else {
@@ -277,6 +476,7 @@ mod test {
             palette,
             0,
             0,
+            DEFAULT_CONTEXT,
         )
         .unwrap();
         let expected_diff = "
@@ -308,6 +508,7 @@ mod test {
             palette,
             0,
             0,
+            DEFAULT_CONTEXT,
         )
         .unwrap();
         let expected_diff = "
@@ -339,6 +540,7 @@ mod test {
             palette,
             0,
             0,
+            DEFAULT_CONTEXT,
         )
         .unwrap();
         let expected_diff = "
@@ -371,6 +573,7 @@ mod test {
             palette,
             0,
             0,
+            DEFAULT_CONTEXT,
         )
         .unwrap();
         let expected_diff = "
@@ -427,6 +630,7 @@ mod test {
             palette,
             0,
             0,
+            DEFAULT_CONTEXT,
         )
         .unwrap();
         let expected_diff = "
@@ -439,7 +643,7 @@ mod test {
    4    4 | 2
    5    5 | 3
    6    6 | 4
-          ⋮
+          ⋮ 10 identical lines
   17   17 | 15
   18   18 | 16
   19   19 | 17
@@ -452,7 +656,7 @@ mod test {
   25   25 | 2
   26   26 | 3
   27   27 | 4
-          ⋮
+          ⋮ 10 identical lines
   38   38 | 15
   39   39 | 16
   40   40 | 17
@@ -465,6 +669,97 @@ mod test {
         assert_eq!(expected_diff, actual_diff);
     }
 
+    #[cfg(feature = "diff")]
+    #[test]
+    fn diff_eq_elided_custom_context() {
+        let mut expected = String::new();
+        expected.push_str("Hello\n");
+        for i in 0..20 {
+            expected.push_str(&i.to_string());
+            expected.push('\n');
+        }
+        expected.push_str("World\n");
+        let expected_name = "A";
+
+        let mut actual = String::new();
+        actual.push_str("Goodbye\n");
+        for i in 0..20 {
+            actual.push_str(&i.to_string());
+            actual.push('\n');
+        }
+        actual.push_str("Moon\n");
+        let actual_name = "B";
+
+        let palette = crate::report::Palette::plain();
+
+        let mut actual_diff = String::new();
+        write_diff_inner(
+            &mut actual_diff,
+            &expected,
+            &actual,
+            Some(&expected_name),
+            Some(&actual_name),
+            palette,
+            0,
+            0,
+            1,
+        )
+        .unwrap();
+        let expected_diff = "
+---- expected: A
+++++ actual:   B
+   1      - Hello
+        1 + Goodbye
+   2    2 | 0
+          ⋮ 18 identical lines
+  21   21 | 19
+  22      - World
+       22 + Moon
+";
+
+        assert_eq!(expected_diff, actual_diff);
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn context_setting() {
+        assert_eq!(parse_context(None), DEFAULT_CONTEXT);
+        assert_eq!(parse_context(Some(" 2 ")), 2);
+        assert_eq!(parse_context(Some("all")), usize::MAX);
+        assert_eq!(parse_context(Some("lots")), DEFAULT_CONTEXT);
+    }
+
+    #[test]
+    fn diff_binary() {
+        let mut expected = vec![0_u8; 40];
+        expected[1] = 0xff;
+        let mut actual = expected.clone();
+        actual[17] = b'A';
+        actual.push(b'!');
+        let palette = crate::report::Palette::plain();
+
+        let mut actual_diff = String::new();
+        write_diff(
+            &mut actual_diff,
+            &crate::Data::binary(expected),
+            &crate::Data::binary(actual),
+            Some(&"A"),
+            Some(&"B"),
+            palette,
+        )
+        .unwrap();
+        let expected_diff = "
+---- expected: A (40 bytes)
+++++ actual:   B (41 bytes)
+00000010 - 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 |................|
+00000010 + 00 41 00 00 00 00 00 00 00 00 00 00 00 00 00 00 |.A..............|
+00000020 - 00 00 00 00 00 00 00 00                         |........|
+00000020 + 00 00 00 00 00 00 00 00 21                      |........!|
+";
+
+        assert_eq!(expected_diff, actual_diff);
+    }
+
     #[cfg(feature = "diff")]
     #[cfg(feature = "term-svg")]
     #[test]
@@ -33,3 +33,111 @@ fn large_stdout_single() {
         .assert()
         .success();
 }
+
+#[test]
+#[cfg(feature = "cmd")]
+fn max_output_split() {
+    let output = snapbox::cmd::Command::new(snapbox::cmd::cargo_bin!("snap-fixture"))
+        .env("echo_large", "1")
+        .max_output(8)
+        .output()
+        .unwrap();
+    let expected = format!("0\n1\n2\n3\n{}", snapbox::cmd::OUTPUT_LIMIT_MARKER);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+#[cfg(feature = "cmd")]
+fn max_output_single() {
+    let output = snapbox::cmd::Command::new(snapbox::cmd::cargo_bin!("snap-fixture"))
+        .env("echo_large", "1")
+        .stderr_to_stdout()
+        .max_output(8)
+        .output()
+        .unwrap();
+    let expected = format!("0\n1\n2\n3\n{}", snapbox::cmd::OUTPUT_LIMIT_MARKER);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+#[cfg(feature = "cmd")]
+fn max_output_within_limit() {
+    let output = snapbox::cmd::Command::new(snapbox::cmd::cargo_bin!("snap-fixture"))
+        .env("stdout", "hello")
+        .max_output(6)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello\n");
+}
+
+#[test]
+#[cfg(feature = "cmd")]
+fn max_output_unbounded() {
+    let output = snapbox::cmd::Command::new(snapbox::cmd::cargo_bin!("snap-fixture"))
+        .env("stdout", "hello")
+        .max_output(usize::MAX)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello\n");
+}
+
+#[test]
+#[cfg(feature = "cmd")]
+fn chunks_cover_output() {
+    let chunked = snapbox::cmd::Command::new(snapbox::cmd::cargo_bin!("snap-fixture"))
+        .env("echo_large", "1")
+        .env("stderr", "world")
+        .chunked_output()
+        .unwrap();
+    for (stream, content) in [
+        (snapbox::cmd::OutputStream::Stdout, &chunked.output.stdout),
+        (snapbox::cmd::OutputStream::Stderr, &chunked.output.stderr),
+    ] {
+        let mut offset = 0;
+        for chunk in chunked.chunks.iter().filter(|c| c.stream == stream) {
+            assert_eq!(chunk.offset, offset);
+            offset += chunk.len;
+        }
+        assert_eq!(offset, content.len(), "{stream}");
+    }
+    assert!(chunked
+        .chunks
+        .windows(2)
+        .all(|pair| pair[0].elapsed <= pair[1].elapsed));
+}
+
+#[test]
+#[cfg(feature = "cmd")]
+fn chunks_single() {
+    let chunked = snapbox::cmd::Command::new(snapbox::cmd::cargo_bin!("snap-fixture"))
+        .env("stdout", "hello")
+        .env("stderr", "world")
+        .stderr_to_stdout()
+        .chunked_output()
+        .unwrap();
+    assert!(chunked
+        .chunks
+        .iter()
+        .all(|c| c.stream == snapbox::cmd::OutputStream::Stdout));
+    let len: usize = chunked.chunks.iter().map(|c| c.len).sum();
+    assert_eq!(len, chunked.output.stdout.len());
+}
+
+#[test]
+#[cfg(feature = "examples")]
+fn workspace_bins() {
+    let bins = snapbox::cmd::compile_workspace_bins([])
+        .unwrap()
+        .map(|(name, bin)| (name, bin.unwrap()))
+        .collect::<std::collections::BTreeMap<_, _>>();
+    assert!(bins["snap-fixture"].exists());
+    assert!(bins["bin-fixture"].exists());
+}
+
+#[test]
+fn cargo_bin_matches_cargo() {
+    assert_eq!(
+        snapbox::cmd::cargo_bin("snap-fixture"),
+        std::path::Path::new(env!("CARGO_BIN_EXE_snap-fixture"))
+    );
+}
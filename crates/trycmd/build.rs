@@ -0,0 +1,6 @@
+fn main() {
+    // The target the bins under test are built for, to find its `CARGO_TARGET_<TRIPLE>_RUNNER`
+    let target = std::env::var("TARGET").unwrap();
+    println!("cargo:rustc-env=TRYCMD_TARGET={target}");
+    println!("cargo:rerun-if-changed=build.rs");
+}
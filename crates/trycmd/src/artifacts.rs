@@ -0,0 +1,627 @@
+//! Bundles of what failed, for CI to upload and reviewers to accept with `git apply`
+
+use crate::runner::Output;
+
+/// `trycmd/` in Cargo's target directory, when it can be found
+pub(crate) fn default_dir() -> Option<std::path::PathBuf> {
+    if let Some(target) = std::env::var_os("CARGO_TARGET_DIR").filter(|t| !t.is_empty()) {
+        return Some(std::path::PathBuf::from(target).join("trycmd"));
+    }
+    // Test binaries are under the target directory, which Cargo tags
+    let exe = std::env::current_exe().ok()?;
+    exe.ancestors()
+        .find(|dir| dir.join("CACHEDIR.TAG").exists())
+        .map(|target| target.join("trycmd"))
+}
+
+/// Replace the bundle for the case `name`, leaving none when it passed
+///
+/// Each bundle has:
+/// - `actual.<stream>` and, when compared, `expected.<stream>`, prefixed with the step's id for
+///   cases with several steps
+/// - `meta.json`, with how each step ran and how it went, and the binary snapshots that changed
+/// - `diff.patch`, to apply from the directory the tests ran in to accept the output like
+///   `TRYCMD=overwrite` would, for cases loaded from a file
+pub(crate) fn record(
+    dir: &std::path::Path,
+    name: &str,
+    path: Option<&std::path::Path>,
+    sequence: &crate::schema::TryCmd,
+    outputs: &[Result<Output, Output>],
+) -> Result<(), crate::Error> {
+    let bundle = dir.join(bundle_name(name));
+    if bundle.exists() {
+        std::fs::remove_dir_all(&bundle).map_err(|e| {
+            crate::Error::Io(format!("Failed to remove {}: {}", bundle.display(), e))
+        })?;
+    }
+    if outputs.iter().all(|o| o.is_ok()) {
+        return Ok(());
+    }
+    let write = |file: &str, content: &[u8]| {
+        let path = bundle.join(file);
+        std::fs::write(&path, content)
+            .map_err(|e| crate::Error::Io(format!("Failed to write {}: {}", path.display(), e)))
+    };
+    std::fs::create_dir_all(&bundle)
+        .map_err(|e| crate::Error::Io(format!("Failed to create {}: {}", bundle.display(), e)))?;
+
+    let mut steps = Vec::new();
+    for output in outputs {
+        let (output, passed) = match output {
+            Ok(output) => (output, true),
+            Err(output) => (output, false),
+        };
+        let prefix = match &output.id {
+            Some(id) if 1 < sequence.steps.len() => format!("{id}."),
+            _ => String::new(),
+        };
+        for stream in [&output.stdout, &output.stderr, &output.merged]
+            .into_iter()
+            .flatten()
+        {
+            let ext = stream.stream.as_str();
+            if let Ok(actual) = stream.content.to_bytes() {
+                write(&format!("{prefix}actual.{ext}"), &actual)?;
+            }
+            let expected = match &stream.status {
+                crate::runner::StreamStatus::Expected { expected, .. }
+                | crate::runner::StreamStatus::ExpectedJson { expected, .. } => Some(expected),
+                _ => None,
+            };
+            if let Some(Ok(expected)) = expected.map(|e| e.to_bytes()) {
+                write(&format!("{prefix}expected.{ext}"), &expected)?;
+            }
+        }
+        steps.push(serde_json::json!({
+            "id": output.id,
+            "passed": passed,
+            "argv": output.argv,
+            "env": output.env,
+            "repro": output.repro,
+            "exit": output.spawn.exit.map(crate::runner::describe_exit),
+            "duration": output.duration.map(|d| d.as_secs_f64()),
+            "seed": output.seed.as_ref().map(|(_, seed)| seed),
+            "message": (!passed).then(|| output.to_string()),
+        }));
+    }
+    // Binary snapshots that changed, which `diff.patch` can't carry
+    #[cfg(feature = "diff")]
+    let binary = match path {
+        Some(path) => {
+            let (patch, binary) = accept_patch(&bundle, path, sequence, outputs)?;
+            if !patch.is_empty() {
+                write("diff.patch", patch.as_bytes())?;
+            }
+            binary
+        }
+        None => Vec::new(),
+    };
+    #[cfg(not(feature = "diff"))]
+    let binary: Vec<String> = {
+        let _ = path;
+        Vec::new()
+    };
+
+    let meta = serde_json::json!({
+        "name": name,
+        "steps": steps,
+        "binary": binary,
+    });
+    write(
+        "meta.json",
+        serde_json::to_string_pretty(&meta)
+            .expect("always valid JSON")
+            .as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+/// Apply the `diff.patch` of each of the case `name`'s bundles in `dir`, like ones downloaded from
+/// CI, removing the bundles once applied
+///
+/// Returns the files changed.  A patch that doesn't apply, or that changes anything but the case
+/// file `case` and its snapshots, leaves all files and its bundle as they were.
+pub(crate) fn accept(
+    dir: &std::path::Path,
+    name: &str,
+    case: &std::path::Path,
+) -> Result<Vec<std::path::PathBuf>, crate::Error> {
+    let bundle = bundle_name(name);
+    // `[[matrix]]` entries get a bundle each, named after the case and the entry
+    let entry_prefix = format!("{bundle}_");
+    let mut bundles = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name == bundle || name.starts_with(&entry_prefix)
+            })
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    bundles.sort();
+
+    let mut changed = Vec::new();
+    for bundle in bundles {
+        let path = bundle.join("diff.patch");
+        let patch = match std::fs::read_to_string(&path) {
+            Ok(patch) => patch,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(crate::Error::Io(format!(
+                    "Failed to read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let files = apply_patch(&patch, std::path::Path::new(""), &relative(case))
+            .map_err(|e| format!("{}: {}", path.display(), e.message()))?;
+        changed.extend(files);
+        std::fs::remove_dir_all(&bundle).map_err(|e| {
+            crate::Error::Io(format!("Failed to remove {}: {}", bundle.display(), e))
+        })?;
+    }
+    Ok(changed)
+}
+
+/// Apply a unified diff to the case file `case` and its snapshots under `root`, checking every
+/// file and hunk before writing any file
+fn apply_patch(
+    patch: &str,
+    root: &std::path::Path,
+    case: &std::path::Path,
+) -> Result<Vec<std::path::PathBuf>, crate::Error> {
+    let mut writes = Vec::new();
+    for file in parse_patch(patch)? {
+        for side in file.old.iter().chain(file.new.iter()) {
+            if !is_own_file(side, case) {
+                return Err(format!(
+                    "{} isn't `{}` or one of its snapshots",
+                    side.display(),
+                    case.display()
+                )
+                .into());
+            }
+        }
+        let target = file
+            .new
+            .as_ref()
+            .or(file.old.as_ref())
+            .expect("`parse_patch` requires a path");
+        let path = root.join(target);
+        let old = match &file.old {
+            Some(_) => std::fs::read_to_string(&path).map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+            })?,
+            None if path.exists() => {
+                return Err(format!("{} already exists", path.display()).into());
+            }
+            None => String::new(),
+        };
+        let new = apply_hunks(&old, &file.hunks)
+            .map_err(|e| format!("{}: {}", target.display(), e.message()))?;
+        writes.push((path, file.new.is_some().then_some(new)));
+    }
+
+    let mut changed = Vec::new();
+    for (path, content) in writes {
+        match content {
+            Some(content) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| {
+                        crate::Error::Io(format!("Failed to create {}: {}", parent.display(), e))
+                    })?;
+                }
+                std::fs::write(&path, content).map_err(|e| {
+                    crate::Error::Io(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+            }
+            None => std::fs::remove_file(&path).map_err(|e| {
+                crate::Error::Io(format!("Failed to remove {}: {}", path.display(), e))
+            })?,
+        }
+        changed.push(path);
+    }
+    Ok(changed)
+}
+
+/// Whether `path` is the case file `case` or a snapshot next to it
+///
+/// `path` is absolute only for cases outside the directory the tests ran in, and can't go through
+/// `..` or `.` to reach the case's directory.
+fn is_own_file(path: &std::path::Path, case: &std::path::Path) -> bool {
+    if path.components().any(|c| {
+        matches!(
+            c,
+            std::path::Component::ParentDir | std::path::Component::CurDir
+        )
+    }) {
+        return false;
+    }
+    let (Some(name), Some(stem)) = (path.file_name(), case.file_stem()) else {
+        return false;
+    };
+    let (name, stem) = (name.to_string_lossy(), stem.to_string_lossy());
+    path.parent() == case.parent()
+        && (name.starts_with(&format!("{stem}.")) || name.starts_with(&format!("{stem}-")))
+}
+
+/// `path` relative to the current directory, when it's under it, without `./`
+fn relative(path: &std::path::Path) -> std::path::PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .components()
+        .filter(|c| *c != std::path::Component::CurDir)
+        .collect()
+}
+
+/// One file's changes, with `None` for `/dev/null`
+#[derive(Debug, PartialEq, Eq)]
+struct FilePatch {
+    old: Option<std::path::PathBuf>,
+    new: Option<std::path::PathBuf>,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Hunk {
+    /// The line the hunk starts on, counting from 1, or the line it follows when `old` is empty
+    start: usize,
+    /// Lines, with their line endings, to replace
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, crate::Error> {
+    let path = |header: &str| {
+        let path = header.split('\t').next().unwrap_or_default().trim_end();
+        if path == "/dev/null" {
+            None
+        } else {
+            let path = path
+                .strip_prefix("a/")
+                .or_else(|| path.strip_prefix("b/"))
+                .unwrap_or(path);
+            Some(std::path::PathBuf::from(path))
+        }
+    };
+
+    let mut files = Vec::new();
+    let mut lines = patch.lines().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(old) = line.strip_prefix("--- ") {
+            let new = lines
+                .next()
+                .and_then(|line| line.strip_prefix("+++ "))
+                .ok_or("`---` isn't followed by `+++`")?;
+            let (old, new) = (path(old), path(new));
+            if old.is_none() && new.is_none() {
+                return Err("both sides are `/dev/null`".into());
+            }
+            files.push(FilePatch {
+                old,
+                new,
+                hunks: Vec::new(),
+            });
+        } else if let Some(header) = line.strip_prefix("@@ ") {
+            let file = files
+                .last_mut()
+                .ok_or("hunk before the `---` and `+++` naming its file")?;
+            let (start, mut old_count, mut new_count) =
+                parse_hunk_header(header).ok_or_else(|| format!("invalid hunk header `{line}`"))?;
+            let mut hunk = Hunk {
+                start,
+                old: Vec::new(),
+                new: Vec::new(),
+            };
+            let mut last = ' ';
+            while 0 < old_count
+                || 0 < new_count
+                || matches!(lines.peek(), Some(l) if l.starts_with('\\'))
+            {
+                let line = lines.next().ok_or("hunk ends early")?;
+                let (kind, text) = match line.chars().next() {
+                    Some(kind @ (' ' | '-' | '+' | '\\')) => (kind, &line[1..]),
+                    // Some tools strip the space of blank context lines
+                    None => (' ', ""),
+                    Some(_) => return Err(format!("invalid hunk line `{line}`").into()),
+                };
+                let text = format!("{text}\n");
+                match kind {
+                    ' ' if 0 < old_count && 0 < new_count => {
+                        hunk.old.push(text.clone());
+                        hunk.new.push(text);
+                        old_count -= 1;
+                        new_count -= 1;
+                    }
+                    '-' if 0 < old_count => {
+                        hunk.old.push(text);
+                        old_count -= 1;
+                    }
+                    '+' if 0 < new_count => {
+                        hunk.new.push(text);
+                        new_count -= 1;
+                    }
+                    // `\ No newline at end of file`, for the line before
+                    '\\' => {
+                        let sides = match last {
+                            ' ' => vec![&mut hunk.old, &mut hunk.new],
+                            '-' => vec![&mut hunk.old],
+                            '+' => vec![&mut hunk.new],
+                            _ => Vec::new(),
+                        };
+                        for side in sides {
+                            if let Some(line) = side.last_mut() {
+                                line.pop();
+                            }
+                        }
+                    }
+                    _ => return Err(format!("hunk `{header}` has more lines than it says").into()),
+                }
+                last = kind;
+            }
+            file.hunks.push(hunk);
+        }
+    }
+    if files.is_empty() {
+        return Err("no changes in patch".into());
+    }
+    Ok(files)
+}
+
+/// The start and line counts from `-1,5 +1,6 @@`
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let mut ranges = header.split(" @@").next()?.split(' ');
+    let range = |range: Option<&str>, sign: char| -> Option<(usize, usize)> {
+        let range = range?.strip_prefix(sign)?;
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (start, old_count) = range(ranges.next(), '-')?;
+    let (_, new_count) = range(ranges.next(), '+')?;
+    Some((start, old_count, new_count))
+}
+
+/// `old` with `hunks` applied, each where it says or, if the file changed since, where its lines
+/// are found after the hunk before it
+fn apply_hunks(old: &str, hunks: &[Hunk]) -> Result<String, crate::Error> {
+    let lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let mut new = String::new();
+    let mut next = 0;
+    for hunk in hunks {
+        let at = if hunk.old.is_empty() {
+            hunk.start
+        } else {
+            hunk.start.saturating_sub(1)
+        };
+        let matches = |at: usize| {
+            at + hunk.old.len() <= lines.len()
+                && lines[at..at + hunk.old.len()]
+                    .iter()
+                    .zip(&hunk.old)
+                    .all(|(line, old)| line == old)
+        };
+        let at = if next <= at && matches(at) {
+            at
+        } else {
+            (next..=lines.len())
+                .find(|at| matches(*at))
+                .ok_or_else(|| format!("hunk at line {} doesn't match", hunk.start))?
+        };
+        new.extend(lines[next..at].iter().copied());
+        new.extend(hunk.new.iter().map(String::as_str));
+        next = at + hunk.old.len();
+    }
+    new.extend(lines[next..].iter().copied());
+    Ok(new)
+}
+
+/// One directory per case, like `tests_cmd_help.toml` for `tests/cmd/help.toml`
+fn bundle_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// The changes `TRYCMD=overwrite` would make, found by making them to a copy of the case, and the
+/// binary files it would change, which are left out
+#[cfg(feature = "diff")]
+fn accept_patch(
+    bundle: &std::path::Path,
+    path: &std::path::Path,
+    sequence: &crate::schema::TryCmd,
+    outputs: &[Result<Output, Output>],
+) -> Result<(String, Vec<String>), crate::Error> {
+    let scratch = bundle.join("scratch");
+    std::fs::create_dir_all(&scratch)
+        .map_err(|e| crate::Error::Io(format!("Failed to create {}: {}", scratch.display(), e)))?;
+    let dir = match path.parent() {
+        Some(parent) if parent != std::path::Path::new("") => parent,
+        _ => std::path::Path::new("."),
+    };
+    let before = own_files(dir, path)?;
+    for file in &before {
+        std::fs::copy(dir.join(file), scratch.join(file))
+            .map_err(|e| crate::Error::Io(format!("Failed to copy {}: {}", file.display(), e)))?;
+    }
+    let copy = scratch.join(path.file_name().unwrap_or_default());
+    // `rev()` to keep the line numbers of earlier steps in `.trycmd` files valid
+    for output in outputs.iter().rev() {
+        if let Err(output) = output {
+            let _ = crate::runner::overwrite_output(sequence, &copy, output);
+        }
+    }
+
+    let mut files = own_files(&scratch, &copy)?;
+    files.extend(before.iter().cloned());
+    files.sort();
+    files.dedup();
+    let mut patch = String::new();
+    let mut binary = Vec::new();
+    for file in &files {
+        let old = std::fs::read(dir.join(file)).ok();
+        let new = std::fs::read(scratch.join(file)).ok();
+        if old == new {
+            continue;
+        }
+        let name = relative(&dir.join(file))
+            .display()
+            .to_string()
+            .replace('\\', "/");
+        let name = name.strip_prefix("./").unwrap_or(&name);
+        let text = |content: Option<Vec<u8>>| content.map(String::from_utf8).transpose();
+        let (Ok(old), Ok(new)) = (text(old), text(new)) else {
+            binary.push(name.to_owned());
+            continue;
+        };
+        let (old_name, new_name) = match (&old, &new) {
+            (None, _) => ("/dev/null".to_owned(), format!("b/{name}")),
+            (_, None) => (format!("a/{name}"), "/dev/null".to_owned()),
+            _ => (format!("a/{name}"), format!("b/{name}")),
+        };
+        let old = old.unwrap_or_default();
+        let new = new.unwrap_or_default();
+        patch.push_str(
+            &similar::TextDiff::from_lines(&old, &new)
+                .unified_diff()
+                .header(&old_name, &new_name)
+                .to_string(),
+        );
+    }
+
+    std::fs::remove_dir_all(&scratch)
+        .map_err(|e| crate::Error::Io(format!("Failed to remove {}: {}", scratch.display(), e)))?;
+    Ok((patch, binary))
+}
+
+/// The case file at `path` and the snapshots next to it, by name
+#[cfg(feature = "diff")]
+fn own_files(
+    dir: &std::path::Path,
+    path: &std::path::Path,
+) -> Result<Vec<std::path::PathBuf>, crate::Error> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| crate::Error::Io(format!("Failed to read {}: {}", dir.display(), e)))?;
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_file = entry.file_type().map(|t| t.is_file()).unwrap_or(false);
+        if is_file
+            && (name.starts_with(&format!("{stem}.")) || name.starts_with(&format!("{stem}-")))
+        {
+            files.push(name.into());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(bundle_name("tests/cmd/help.toml"), "tests_cmd_help.toml");
+        assert_eq!(
+            bundle_name("tests\\cmd\\greet.toml:de_DE.UTF-8"),
+            "tests_cmd_greet.toml_de_DE.UTF-8"
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let patch = "\
+--- a/tests/cmd/help.stdout
++++ b/tests/cmd/help.stdout
+@@ -1,3 +1,3 @@
+ Usage: bin
+-  --old
++  --new
+ Options:
+@@ -6 +6 @@
+-end
+\\ No newline at end of file
++end
+--- /dev/null
++++ b/tests/cmd/help.stderr
+@@ -0,0 +1 @@
++warning
+";
+        let files = parse_patch(patch).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(
+            files[0].old.as_deref(),
+            Some("tests/cmd/help.stdout".as_ref())
+        );
+        assert_eq!(files[0].hunks[1].old, ["end"]);
+        assert_eq!(files[0].hunks[1].new, ["end\n"]);
+        assert_eq!(files[1].old, None);
+
+        // A line added at the top since the patch was made
+        let old = "Added\nUsage: bin\n  --old\nOptions:\n  -h\n  -V\nend";
+        assert_eq!(
+            apply_hunks(old, &files[0].hunks).unwrap(),
+            "Added\nUsage: bin\n  --new\nOptions:\n  -h\n  -V\nend\n"
+        );
+        assert_eq!(apply_hunks("", &files[1].hunks).unwrap(), "warning\n");
+        assert_eq!(
+            apply_hunks("Usage: bin\n  --other\n", &files[0].hunks)
+                .unwrap_err()
+                .message(),
+            "hunk at line 1 doesn't match"
+        );
+    }
+
+    #[test]
+    fn apply_confined() {
+        let case = std::path::Path::new("tests/cmd/help.toml");
+        assert!(is_own_file("tests/cmd/help.stdout".as_ref(), case));
+        assert!(is_own_file("tests/cmd/help-2.stderr".as_ref(), case));
+        assert!(is_own_file("tests/cmd/help.toml".as_ref(), case));
+        for path in [
+            "tests/cmd/other.stdout",
+            "tests/help.stdout",
+            "tests/cmd/../cmd/help.stdout",
+            "/tests/cmd/help.stdout",
+            "tests/cmd/help.in/file",
+        ] {
+            assert!(!is_own_file(path.as_ref(), case), "{path}");
+        }
+
+        let patch = "\
+--- a/../Cargo.toml
++++ /dev/null
+@@ -1 +0,0 @@
+-[package]
+";
+        assert_eq!(
+            apply_patch(patch, "".as_ref(), case).unwrap_err().message(),
+            "../Cargo.toml isn't `tests/cmd/help.toml` or one of its snapshots"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "diff")]
+    fn apply_diff() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+        let new = "one\n2\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten";
+        let patch = similar::TextDiff::from_lines(old, new)
+            .unified_diff()
+            .header("a/file", "b/file")
+            .to_string();
+        let files = parse_patch(&patch).unwrap();
+        assert_eq!(apply_hunks(old, &files[0].hunks).unwrap(), new);
+    }
+}
@@ -18,6 +18,16 @@ fn run() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    if env::var("echo_stdin").as_deref() == Ok("1") {
+        io::copy(&mut io::stdin(), &mut io::stdout())?;
+    }
+
+    if let Ok(vars) = env::var("echo_env") {
+        for var in vars.split(',') {
+            println!("{}={}", var, env::var(var).unwrap_or_default());
+        }
+    }
+
     if env::var("echo_cwd").as_deref() == Ok("1") {
         if let Ok(cwd) = env::current_dir() {
             eprintln!("{}", cwd.display());
@@ -34,8 +44,32 @@ fn run() -> Result<(), Box<dyn Error>> {
         eprintln!("{}", text);
     }
 
+    if let Ok(url) = env::var("http_get") {
+        print!("{}", http_get(&url)?);
+    }
+
+    let on_term = env::var("on_term").ok();
+    let on_int = env::var("on_int").ok();
+    if on_term.is_some() || on_int.is_some() {
+        trap();
+    }
+
     if let Some(timeout) = env::var("sleep").ok().and_then(|s| s.parse().ok()) {
-        std::thread::sleep(std::time::Duration::from_secs(timeout));
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout);
+        while std::time::Instant::now() < deadline {
+            match SIGNALED.load(std::sync::atomic::Ordering::SeqCst) {
+                0 => {}
+                TERM => {
+                    println!("{}", on_term.unwrap_or_default());
+                    process::exit(0);
+                }
+                _ => {
+                    println!("{}", on_int.unwrap_or_default());
+                    reraise_int();
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
     }
 
     let exit = env::var("exit").ok();
@@ -51,6 +85,66 @@ fn run() -> Result<(), Box<dyn Error>> {
     process::exit(code);
 }
 
+/// The status line and body from a plain `http://` URL
+fn http_get(url: &str) -> Result<String, Box<dyn Error>> {
+    use std::io::Read as _;
+
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or("Only `http://` is supported")?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let mut stream = std::net::TcpStream::connect(host)?;
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        if path.is_empty() { "/" } else { path },
+        host
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head.lines().next().unwrap_or_default();
+    Ok(format!("{}\n{}", status, body))
+}
+
+/// What `SIGNALED` is set to on `SIGTERM`, with `SIGINT` setting it to anything else
+const TERM: i32 = 1;
+
+static SIGNALED: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+#[cfg(unix)]
+fn trap() {
+    extern "C" fn handle(signal: libc::c_int) {
+        let signaled = if signal == libc::SIGTERM { TERM } else { 2 };
+        SIGNALED.store(signaled, std::sync::atomic::Ordering::SeqCst);
+    }
+    let handle = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGTERM, handle);
+        libc::signal(libc::SIGINT, handle);
+    }
+}
+
+#[cfg(not(unix))]
+fn trap() {}
+
+/// Die from `SIGINT` after handling it, like a well-behaved CLI
+#[cfg(unix)]
+fn reraise_int() -> ! {
+    // SAFETY: restoring the default action and raising have no memory safety requirements
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+        libc::raise(libc::SIGINT);
+    }
+    process::exit(130);
+}
+
+#[cfg(not(unix))]
+fn reraise_int() -> ! {
+    process::exit(130);
+}
+
 fn main() {
     let code = match run() {
         Ok(_) => 0,
@@ -1,7 +1,24 @@
+//! Write the JSON Schema for `*.toml` cases
+//!
+//! Usage: `trycmd-schema [OUTPUT]`, writing to `stdout` when no `OUTPUT` is given.
+
 use std::io::prelude::*;
 
 fn main() {
-    let schema = schemars::schema_for!(trycmd::schema::OneShot);
-    let schema = serde_json::to_string_pretty(&schema).unwrap();
-    std::io::stdout().write_all(schema.as_bytes()).unwrap();
+    let schema = trycmd::schema::OneShot::json_schema();
+    match std::env::args_os().nth(1) {
+        Some(path) => {
+            if let Err(err) = std::fs::write(&path, schema) {
+                let path = std::path::Path::new(&path);
+                let _ = writeln!(
+                    std::io::stderr(),
+                    "Failed to write {}: {}",
+                    path.display(),
+                    err
+                );
+                std::process::exit(1);
+            }
+        }
+        None => std::io::stdout().write_all(schema.as_bytes()).unwrap(),
+    }
 }
@@ -0,0 +1,258 @@
+use snapbox::IntoData;
+
+/// A case defined in Rust, rather than loaded from a file
+///
+/// Add it with [`TestCases::add`][crate::TestCases::add] to run it alongside file-based cases, or
+/// [`Case::run`] it on its own.
+///
+/// ```rust,no_run
+/// trycmd::TestCases::new().add(
+///     trycmd::Case::new()
+///         .name("version")
+///         .bin(trycmd::cargo::cargo_bin("my-cmd"))
+///         .args(["--version"])
+///         .expect_stdout("my-cmd 1.0.0\n"),
+/// );
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct Case {
+    name: Option<String>,
+    path: Option<std::path::PathBuf>,
+    trycmd: Option<String>,
+    step: crate::schema::Step,
+    fs: crate::schema::Filesystem,
+}
+
+impl Case {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the case from a `cmd.toml` or `.trycmd` file when run
+    ///
+    /// The file describes the whole case, so the other methods don't apply.
+    pub fn from_path(path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Parse the case from the `.trycmd` format, with or without the surrounding fence
+    ///
+    /// Common indentation is stripped, for raw strings indented with the code around them.  The
+    /// source describes the whole case, so the other methods don't apply.  See also
+    /// [`trycmd!`][crate::trycmd!].
+    pub fn from_trycmd(source: impl Into<String>) -> Self {
+        Self {
+            trycmd: Some(source.into()),
+            ..Default::default()
+        }
+    }
+
+    /// The name to report the case under, defaulting to its command line
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The program to run, defaulting to [`TestCases::default_bin_name`][crate::TestCases::default_bin_name]
+    pub fn bin(mut self, bin: impl Into<crate::schema::Bin>) -> Self {
+        self.step.bin = Some(bin.into());
+        self
+    }
+
+    /// Add an argument to pass to the program
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.step.args.push(arg.into());
+        self
+    }
+
+    /// Add arguments to pass to the program
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.step.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set an environment variable for the program
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.step.env.add.insert(key.into(), value.into());
+        self
+    }
+
+    /// Remove an environment variable for the program
+    pub fn env_remove(mut self, key: impl Into<String>) -> Self {
+        let key = key.into();
+        self.step.env.add.remove(&key);
+        self.step.env.remove.push(key);
+        self
+    }
+
+    /// Don't pass the test's environment on to the program
+    pub fn env_clear(mut self) -> Self {
+        self.step.env.inherit = Some(false);
+        self
+    }
+
+    /// Data to write to the program's `stdin`
+    pub fn stdin(mut self, stdin: impl IntoData) -> Self {
+        self.step.stdin = Some(stdin.into_data());
+        self
+    }
+
+    /// The directory to run the program in
+    pub fn current_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.fs.cwd = Some(dir.into());
+        self
+    }
+
+    /// Kill the program if it runs longer than this
+    pub fn timeout(mut self, time: std::time::Duration) -> Self {
+        self.step.timeout = Some(time);
+        self
+    }
+
+    /// On [`Case::timeout`], ask the program to stop and wait this long before killing it
+    pub fn timeout_grace(mut self, time: std::time::Duration) -> Self {
+        self.step.timeout_grace = Some(time);
+        self
+    }
+
+    /// Send the program `signal` `after` it starts, like [`Signal::Int`][crate::schema::Signal::Int]
+    /// to check its Ctrl-C handling (Unix only)
+    pub fn signal(mut self, after: std::time::Duration, signal: crate::schema::Signal) -> Self {
+        self.step
+            .signals
+            .push(crate::schema::ScheduledSignal { after, signal });
+        self
+    }
+
+    /// How the program is expected to exit, defaulting to success
+    pub fn expect_status(mut self, status: crate::schema::CommandStatus) -> Self {
+        self.step.expected_status = Some(status);
+        self
+    }
+
+    /// What the program is expected to write to `stdout`
+    ///
+    /// Unlike file-based cases, these can't be updated with `TRYCMD=overwrite`.
+    pub fn expect_stdout(mut self, stdout: impl IntoData) -> Self {
+        self.step.expected_stdout = Some(stdout.into_data());
+        self
+    }
+
+    /// What the program is expected to write to `stderr`
+    pub fn expect_stderr(mut self, stderr: impl IntoData) -> Self {
+        self.step.expected_stderr = Some(stderr.into_data());
+        self
+    }
+
+    /// Run the case, returning how it went rather than panicking
+    ///
+    /// This is for other test frameworks and custom harnesses to make their own pass/fail
+    /// decisions.  Snapshots are never overwritten.
+    pub fn run(&self) -> crate::CaseResult {
+        let case = self
+            .clone()
+            .into_runner(None, None, None, Vec::new(), Default::default(), None);
+        let start = std::time::Instant::now();
+        let steps = case.run(
+            &crate::Mode::Fail,
+            &crate::BinRegistry::new(),
+            &Default::default(),
+            &Default::default(),
+        );
+        crate::CaseResult::new(case.name().display().to_string(), steps, start.elapsed())
+    }
+
+    /// Resolve into what the runner runs, with defaults from [`TestCases`][crate::TestCases]
+    pub(crate) fn into_runner(
+        self,
+        expected: Option<crate::schema::CommandStatus>,
+        default_bin: Option<crate::schema::Bin>,
+        timeout: Option<std::time::Duration>,
+        wrapper: Vec<String>,
+        env: crate::schema::Env,
+        sandbox_root: Option<std::path::PathBuf>,
+    ) -> crate::runner::Case {
+        let (path, built) = match (self.path, self.trycmd) {
+            (Some(path), _) => (path, None),
+            (None, Some(source)) => {
+                let path = std::path::PathBuf::from(self.name.unwrap_or_else(|| "trycmd".into()));
+                match parse_inline(&source) {
+                    Ok(sequence) => (path, Some(sequence)),
+                    Err(err) => return crate::runner::Case::with_error(path, err),
+                }
+            }
+            (None, None) => {
+                let name = self.name.unwrap_or_else(|| self.step.command_line());
+                let sequence = crate::schema::TryCmd {
+                    steps: vec![self.step],
+                    fs: self.fs,
+                    ..Default::default()
+                };
+                (std::path::PathBuf::from(name), Some(sequence))
+            }
+        };
+        crate::runner::Case {
+            path,
+            name: None,
+            expected,
+            default_bin,
+            timeout,
+            wrapper,
+            env,
+            sandbox_root,
+            error: None,
+            built,
+            seed: None,
+            artifacts: None,
+        }
+    }
+}
+
+/// Parse `.trycmd` content from Rust source, adding a fence if needed
+fn parse_inline(source: &str) -> Result<crate::schema::TryCmd, crate::Error> {
+    let source = snapbox::filter::normalize_lines(source);
+    let indent = source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let dedented = source
+        .lines()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let dedented = dedented.trim_matches('\n');
+    let fenced = if dedented.starts_with("```") || dedented.starts_with("~~~") {
+        format!("{dedented}\n")
+    } else {
+        format!("```\n{dedented}\n```\n")
+    };
+    fenced.parse()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_inline_fence_optional() {
+        let unfenced = parse_inline(
+            "
+            $ cmd --flag
+              indented
+            ",
+        )
+        .unwrap();
+        let fenced = parse_inline("```console\n$ cmd --flag\n  indented\n```").unwrap();
+        assert_eq!(unfenced.steps.len(), 1);
+        let step = &unfenced.steps[0];
+        assert_eq!(step.args, ["--flag"]);
+        assert_eq!(step.expected_stdout, Some(crate::Data::text("  indented")));
+        assert_eq!(fenced.steps[0].args, step.args);
+        assert_eq!(fenced.steps[0].expected_stdout, step.expected_stdout);
+    }
+}
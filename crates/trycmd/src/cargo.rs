@@ -47,5 +47,34 @@ pub fn compile_example<'a>(
 pub fn compile_examples<'a>(
     args: impl IntoIterator<Item = &'a str>,
 ) -> Result<impl Iterator<Item = (String, crate::schema::Bin)>, crate::Error> {
-    snapbox::cmd::compile_examples(args).map(|i| i.map(|(name, path)| (name, path.into())))
+    snapbox::cmd::compile_examples(args)
+        .map(|i| i.map(|(name, path)| (name, path.into())))
+        .map_err(crate::Error::from)
+}
+
+/// Prepare the binaries of all workspace members for testing
+///
+/// This makes `bin.name` resolve to binaries from other packages in the workspace.
+///
+/// Unlike `cargo_bin!`, this does not inherit all of the current compiler settings.  It
+/// will match the current target and profile but will not get feature flags.  Pass those arguments
+/// to the compiler via `args`.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// #[test]
+/// fn cli_tests() {
+///     trycmd::TestCases::new()
+///         .register_bins(trycmd::cargo::compile_workspace_bins([]).unwrap())
+///         .case("tests/cmd/*.trycmd");
+/// }
+/// ```
+#[cfg(feature = "examples")]
+pub fn compile_workspace_bins<'a>(
+    args: impl IntoIterator<Item = &'a str>,
+) -> Result<impl Iterator<Item = (String, crate::schema::Bin)>, crate::Error> {
+    snapbox::cmd::compile_workspace_bins(args)
+        .map(|i| i.map(|(name, path)| (name, path.into())))
+        .map_err(crate::Error::from)
 }
@@ -1,11 +1,18 @@
 use std::borrow::Cow;
 
+#[cfg(feature = "color")]
+use anstream::panic;
+
 /// Entry point for running tests
 #[derive(Debug, Default)]
 pub struct TestCases {
     runner: std::cell::RefCell<crate::RunnerSpec>,
     bins: std::cell::RefCell<crate::BinRegistry>,
     substitutions: std::cell::RefCell<snapbox::Redactions>,
+    hooks: std::cell::RefCell<crate::hooks::Hooks>,
+    verbose: std::cell::Cell<u8>,
+    quiet: std::cell::Cell<bool>,
+    exit_codes: std::cell::Cell<ExitCodes>,
     has_run: std::cell::Cell<bool>,
 }
 
@@ -24,6 +31,21 @@ impl TestCases {
         self
     }
 
+    /// Load tests from `dir` and its subdirectories, `*.toml` and `*.trycmd`
+    ///
+    /// Cases are named by their path relative to `dir`, so `subcmd/users/add.toml` is reported as
+    /// `subcmd::users::add`.
+    pub fn case_dir(&self, dir: impl AsRef<std::path::Path>) -> &Self {
+        self.runner.borrow_mut().case_dir(dir.as_ref());
+        self
+    }
+
+    /// Add a case defined in Rust
+    pub fn add(&self, case: crate::Case) -> &Self {
+        self.runner.borrow_mut().built(case);
+        self
+    }
+
     /// Overwrite expected status for a test
     pub fn pass(&self, glob: impl AsRef<std::path::Path>) -> &Self {
         self.runner
@@ -57,6 +79,18 @@ impl TestCases {
         self
     }
 
+    /// Only run cases whose file name matches `glob`
+    ///
+    /// If `glob` contains a `/`, it is matched against the whole path instead.  When called more
+    /// than once, cases matching any of the globs are run, along with the cases they
+    /// `depends-on`.
+    ///
+    /// This can also be set with `TRYCMD=<glob>`.
+    pub fn filter(&self, glob: impl AsRef<str>) -> &Self {
+        self.runner.borrow_mut().filter(glob.as_ref());
+        self
+    }
+
     /// Set default bin, by path, for commands
     pub fn default_bin_path(&self, path: impl AsRef<std::path::Path>) -> &Self {
         let bin = Some(crate::schema::Bin::Path(path.as_ref().into()));
@@ -77,12 +111,39 @@ impl TestCases {
         self
     }
 
+    /// Run each program under `wrapper`, like `["valgrind", "--error-exitcode=99"]`, unless a case
+    /// sets `wrapper`
+    ///
+    /// This is for running the suite under tools like `valgrind`, `strace`, or `qemu-aarch64`.
+    pub fn wrapper(&self, wrapper: impl IntoIterator<Item = impl Into<String>>) -> &Self {
+        self.runner
+            .borrow_mut()
+            .wrapper(wrapper.into_iter().map(Into::into).collect());
+        self
+    }
+
     /// Set default environment variable
     pub fn env(&self, key: impl Into<String>, value: impl Into<String>) -> &Self {
         self.runner.borrow_mut().env(key, value);
         self
     }
 
+    /// Set whether commands print colors, unless a case sets `color`
+    pub fn color(&self, color: crate::schema::Color) -> &Self {
+        self.runner.borrow_mut().color(color);
+        self
+    }
+
+    /// Forward `LLVM_PROFILE_FILE` and `CARGO_LLVM_COV*` to commands, even with `env.inherit =
+    /// false` or `env.preset`, so `cargo llvm-cov` measures the coverage of the binaries under test
+    ///
+    /// `%p` in `LLVM_PROFILE_FILE` is prefixed with the case's path, so each case's profiles stand
+    /// apart.
+    pub fn coverage(&self, yes: bool) -> &Self {
+        self.runner.borrow_mut().coverage(yes);
+        self
+    }
+
     /// Add a bin to the "PATH" for cases to use
     pub fn register_bin(
         &self,
@@ -95,6 +156,160 @@ impl TestCases {
         self
     }
 
+    /// Run `main` in-process for `bin.name = <name>`, rather than spawning a bin
+    ///
+    /// This saves the cost of starting a process for each case, like for thousands of cases of a
+    /// CLI whose `main` only parses arguments and calls into a library.  This takes precedence
+    /// over a bin registered or built under the same name.
+    ///
+    /// ```rust,no_run
+    /// #[test]
+    /// fn cli_tests() {
+    ///     trycmd::TestCases::new()
+    ///         .case("tests/cmd/*.toml")
+    ///         .register_fn("echo", |args: &[String], _stdin: &[u8]| {
+    ///             (0, format!("{}\n", args[1..].join(" ")).into_bytes(), Vec::new())
+    ///         });
+    /// }
+    /// ```
+    pub fn register_fn(
+        &self,
+        name: impl Into<String>,
+        main: impl crate::InProcessBin + 'static,
+    ) -> &Self {
+        self.hooks
+            .borrow_mut()
+            .mains
+            .insert(name.into(), std::sync::Arc::new(main));
+        self
+    }
+
+    /// With `TRYCMD=overwrite`, remove snapshots that no case is named after, rather than only
+    /// reporting them
+    ///
+    /// After a full run, `*.stdout`, `*.stderr`, `*.in`, and `*.out` next to the cases are
+    /// reported when they don't belong to one, like `help.stdout` after `help.toml` was renamed.
+    /// `help-<id>.stdout`, as for a `[[matrix]]` entry, belongs to `help.toml`, and paths a case
+    /// reads, like `fs.base`, `fs.cwd`, and `include`, belong to it.  Directories are never
+    /// removed, only reported.
+    pub fn remove_unused_snapshots(&self, yes: bool) -> &Self {
+        self.runner.borrow_mut().remove_unused(yes);
+        self
+    }
+
+    /// Run only the cases with at least one of `tags`, like `tags = ["smoke"]`
+    ///
+    /// The others are skipped, unless a case that runs depends on them.  `TRYCMD_TAGS`, like
+    /// `smoke,!network`, takes precedence over this and [`TestCases::exclude_tags`].
+    pub fn tags(&self, tags: impl IntoIterator<Item = impl Into<String>>) -> &Self {
+        let mut runner = self.runner.borrow_mut();
+        for tag in tags {
+            runner.include_tag(tag);
+        }
+        drop(runner);
+        self
+    }
+
+    /// Skip the cases with any of `tags`, like `["slow", "network"]` for quick checks
+    pub fn exclude_tags(&self, tags: impl IntoIterator<Item = impl Into<String>>) -> &Self {
+        let mut runner = self.runner.borrow_mut();
+        for tag in tags {
+            runner.exclude_tag(tag);
+        }
+        drop(runner);
+        self
+    }
+
+    /// Pass each command a seed in `var`, for CLIs that take one to make their randomness
+    /// reproducible
+    ///
+    /// Each case's seed is derived from its name and the seed from [`TestCases::seed`], so it is
+    /// the same from run to run.  It is reported when the case fails.
+    pub fn seed_env(&self, var: impl Into<String>) -> &Self {
+        self.runner.borrow_mut().seed_var(var);
+        self
+    }
+
+    /// Derive each case's seed from `seed` as well as its name, passing it in `TRYCMD_SEED` unless
+    /// [`TestCases::seed_env`] chose another variable
+    ///
+    /// `TRYCMD_SEED`, when set for the tests, takes precedence, to try other seeds.
+    pub fn seed(&self, seed: u64) -> &Self {
+        self.runner.borrow_mut().seed(seed);
+        self
+    }
+
+    /// Create sandboxes within `root`, rather than the OS's temporary directory
+    ///
+    /// This is for when the temporary directory is small or to keep all of a run's sandboxes in
+    /// one place for inspection.  `root` is created if needed.  `TRYCMD_TMPDIR`, when set, takes
+    /// precedence.
+    pub fn sandbox_root(&self, root: impl Into<std::path::PathBuf>) -> &Self {
+        self.runner.borrow_mut().sandbox_root(Some(root.into()));
+        self
+    }
+
+    /// Write a bundle for each failing case into `dir`, rather than `trycmd/` in Cargo's target
+    /// directory
+    ///
+    /// Each case gets a directory, like `tests_cmd_help.toml/`, with its `actual.stdout`,
+    /// `expected.stdout`, a `meta.json` of how each step ran, and a `diff.patch` to accept the
+    /// output with `git apply`.  Bundles of cases that now pass are removed.  `TRYCMD_ARTIFACTS`,
+    /// when set, takes precedence.
+    pub fn artifacts_dir(&self, dir: impl Into<std::path::PathBuf>) -> &Self {
+        self.runner.borrow_mut().artifacts(Some(dir.into()));
+        self
+    }
+
+    /// Stop starting cases once `max` have failed, skipping the rest, like `1` to fail fast
+    ///
+    /// Cases already running are left to finish.  `TRYCMD_MAX_FAILURES`, when set, takes
+    /// precedence.
+    pub fn max_failures(&self, max: usize) -> &Self {
+        self.runner.borrow_mut().max_failures(Some(max));
+        self
+    }
+
+    /// Stop starting cases once the run has taken `deadline`, reporting the rest as not run
+    ///
+    /// This is for finishing within a CI job's time limit with the results so far and the
+    /// summary, rather than being killed without either.  Cases already running are left to
+    /// finish, and cases left out this way fail the run.  `TRYCMD_DEADLINE`, like `10m`, when set,
+    /// takes precedence.
+    pub fn deadline(&self, deadline: std::time::Duration) -> &Self {
+        self.runner.borrow_mut().deadline(Some(deadline));
+        self
+    }
+
+    /// Only report the cases that fail, and the summary
+    ///
+    /// This is for wrappers that run many suites and only care about what broke.
+    /// `TRYCMD_VERBOSE`, when set, takes precedence.
+    pub fn quiet(&self) -> &Self {
+        self.quiet.set(true);
+        self.verbose.set(0);
+        self
+    }
+
+    /// Report more about each case, like `TRYCMD_VERBOSE=<level>`, which takes precedence
+    ///
+    /// - `1` shows how each command was run and lists the slowest cases
+    /// - `2` also shows each command's output, step by step, even when it passed
+    pub fn verbose(&self, level: u8) -> &Self {
+        self.quiet.set(false);
+        self.verbose.set(level);
+        self
+    }
+
+    /// Run the bins built for the target under `runner`, like `"qemu-aarch64 -L /usr/aarch64-linux-gnu"`
+    ///
+    /// This defaults to cargo's `CARGO_TARGET_<TRIPLE>_RUNNER` for the target being tested, so
+    /// cross-compiled bins run through an emulator.  Pass `None` to run them directly.
+    pub fn runner(&self, runner: Option<&str>) -> &Self {
+        self.bins.borrow_mut().runner(runner.map(ToOwned::to_owned));
+        self
+    }
+
     /// Add a series of bins to the "PATH" for cases to use
     pub fn register_bins<N: Into<String>, B: Into<crate::schema::Bin>>(
         &self,
@@ -121,6 +336,7 @@ impl TestCases {
     /// - `[EXE]`
     /// - `[CWD]`
     /// - `[ROOT]`
+    /// - `[BIN]`
     ///
     /// ## Example
     ///
@@ -169,22 +385,387 @@ impl TestCases {
         Ok(self)
     }
 
+    /// Run `hook` before each case's commands
+    ///
+    /// The hook runs after the sandbox is prepared, so it can add files to it, start a server, or
+    /// seed a database.  Returning an error fails the case without running its commands.
+    ///
+    /// Cases run in parallel, so hooks may be called concurrently.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// #[test]
+    /// fn cli_tests() {
+    ///     trycmd::TestCases::new()
+    ///         .case("tests/cmd/*.toml")
+    ///         .setup(|case| {
+    ///             if let Some(sandbox) = case.sandbox() {
+    ///                 std::fs::write(sandbox.join("config.toml"), "")
+    ///                     .map_err(|e| trycmd::Error::new(e.to_string()))?;
+    ///             }
+    ///             Ok(())
+    ///         });
+    /// }
+    /// ```
+    pub fn setup(
+        &self,
+        hook: impl Fn(&crate::CaseContext<'_>) -> Result<(), crate::Error> + Send + Sync + 'static,
+    ) -> &Self {
+        self.hooks
+            .borrow_mut()
+            .setup
+            .push(std::sync::Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` after each case's commands
+    ///
+    /// The hook runs before the sandbox is checked against `*.out/` and removed.  Returning an
+    /// error fails the case.
+    ///
+    /// See [`TestCases::setup`].
+    pub fn teardown(
+        &self,
+        hook: impl Fn(&crate::CaseContext<'_>) -> Result<(), crate::Error> + Send + Sync + 'static,
+    ) -> &Self {
+        self.hooks
+            .borrow_mut()
+            .teardown
+            .push(std::sync::Arc::new(hook));
+        self
+    }
+
+    /// Rewrite output with `normalizer` before comparing it, for noise that `[substitutions]`
+    /// can't express
+    ///
+    /// ```rust,no_run
+    /// #[test]
+    /// fn cli_tests() {
+    ///     trycmd::TestCases::new()
+    ///         .case("tests/cmd/*.toml")
+    ///         .normalizer(|text: String| {
+    ///             text.lines()
+    ///                 .map(|line| match line.strip_prefix("cores: ") {
+    ///                     Some(_) => "cores: [N]\n".to_owned(),
+    ///                     None => format!("{line}\n"),
+    ///                 })
+    ///                 .collect()
+    ///         });
+    /// }
+    /// ```
+    pub fn normalizer(&self, normalizer: impl crate::Normalizer + 'static) -> &Self {
+        self.hooks
+            .borrow_mut()
+            .normalizers
+            .push(std::sync::Arc::new(normalizer));
+        self
+    }
+
+    /// Check `cmd.stdout.<ext>` and `cmd.stderr.<ext>` snapshots with `validator`
+    ///
+    /// ```rust,no_run
+    /// #[test]
+    /// fn cli_tests() {
+    ///     trycmd::TestCases::new()
+    ///         .case("tests/cmd/*.toml")
+    ///         .validator("csv", |actual: &[u8], expected: &std::path::Path| {
+    ///             let expected = std::fs::read(expected)
+    ///                 .map_err(|e| vec![trycmd::Difference::new(e.to_string())])?;
+    ///             let rows = |csv: &[u8]| csv.split(|b| *b == b'\n').count();
+    ///             match rows(actual) == rows(&expected) {
+    ///                 true => Ok(()),
+    ///                 false => Err(vec![trycmd::Difference::new("the row counts differ")]),
+    ///             }
+    ///         });
+    /// }
+    /// ```
+    pub fn validator(
+        &self,
+        ext: impl Into<String>,
+        validator: impl crate::OutputValidator + 'static,
+    ) -> &Self {
+        self.hooks
+            .borrow_mut()
+            .validators
+            .push((ext.into(), std::sync::Arc::new(validator)));
+        self
+    }
+
     /// Run tests
     ///
     /// This will happen on `drop` if not done explicitly
     pub fn run(&self) {
+        if std::env::var_os("TRYCMD").as_deref() == Some(std::ffi::OsStr::new("list")) {
+            self.list();
+            return;
+        }
+        if std::env::var_os("TRYCMD").as_deref() == Some(std::ffi::OsStr::new("accept")) {
+            self.accept();
+            return;
+        }
+        let (mode, runner) = self.prepare();
+        let format = parse_format(std::env::var_os("TRYCMD_FORMAT").as_deref()).unwrap_or_default();
+        if let Err(err) = self.run_with(&mode, &runner, format).into_result() {
+            panic!("{}", err);
+        }
+    }
+
+    /// Print each case with where and how its commands would run, without running anything
+    ///
+    /// This is for debugging which cases are found and which bins they resolve to, listing each
+    /// command's resolved bin and arguments, its `cwd`, and whether it would run in a sandbox.
+    /// `TRYCMD=list` does this rather than running the cases.
+    pub fn list(&self) {
+        use std::io::Write as _;
+
+        let (_, runner) = self.prepare();
+        let description = runner.describe(&self.bins.borrow(), &self.hooks.borrow());
+        let _ = write!(std::io::stdout(), "{description}");
+    }
+
+    /// Accept the output of the cases' failure bundles, applying their `diff.patch` rather than
+    /// running anything
+    ///
+    /// This is for taking output changes seen in CI, with its bundles downloaded into
+    /// [`TestCases::artifacts_dir`] or `TRYCMD_ARTIFACTS`, without reproducing its environment.
+    /// Each bundle is removed once applied.  `TRYCMD=accept` does this rather than running the
+    /// cases.
+    ///
+    /// # Panics
+    ///
+    /// If a patch doesn't apply, like when the snapshot changed since, leaving its files as they
+    /// were.
+    pub fn accept(&self) {
+        use std::io::Write as _;
+
+        let (_, runner) = self.prepare();
+        let (changed, errors) = runner.accept();
+        let mut stdout = std::io::stdout();
+        for path in changed {
+            let _ = writeln!(stdout, "Accepted {}", path.display());
+        }
+        if !errors.is_empty() {
+            panic!("{}", errors.join("\n"));
+        }
+    }
+
+    /// Run all cases, returning how each went rather than reporting them and panicking
+    ///
+    /// This is for wrappers to aggregate, post-process, or upload the results and make their own
+    /// pass/fail decisions.  `TRYCMD` still selects the mode and filters the cases.
+    pub fn collect(&self) -> Vec<crate::CaseResult> {
+        let (mode, runner) = self.prepare();
+        runner.collect(
+            &mode,
+            &self.bins.borrow(),
+            &self.substitutions.borrow(),
+            &self.hooks.borrow(),
+        )
+    }
+
+    /// Create a case at `path`, a `*.toml`, by running `cmdline`, like `"$ my-bin --help"`
+    ///
+    /// This bootstraps a case rather than writing it by hand: `bin.name`, `args`, and `status` go
+    /// in `path`, and the output in `<name>.stdout` and `<name>.stderr`.  If `<name>.in` exists,
+    /// the command runs in a sandbox copy of it and the files it leaves are written to
+    /// `<name>.out`.  Review what was recorded before committing it.
+    ///
+    /// `path` must not exist yet.
+    pub fn record(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        cmdline: &str,
+    ) -> Result<(), crate::Error> {
+        let case = self
+            .runner
+            .borrow()
+            .runner_case(path.as_ref().to_owned(), None);
+        case.record(
+            cmdline,
+            &self.bins.borrow(),
+            &self.substitutions.borrow(),
+            &self.hooks.borrow(),
+        )
+    }
+
+    /// Run tests as the `main` of a `harness = false` test target
+    ///
+    /// Each case is reported as its own test, accepting libtest's command-line, so
+    /// `cargo test <case>` and IDE test explorers can list and run individual cases:
+    /// - `--list` to list the cases
+    /// - `<filter>` to only run cases whose names contain it, or are it with `--exact`
+    /// - `--skip <filter>` to leave out cases whose names contain it
+    ///
+    /// Other flags, like `--test-threads`, are accepted but ignored.
+    ///
+    /// ```toml
+    /// [[test]]
+    /// name = "cli_tests"
+    /// harness = false
+    /// ```
+    /// Then, from `tests/cli_tests.rs`'s `main`:
+    /// ```rust,no_run
+    /// trycmd::TestCases::new()
+    ///     .case("tests/cmd/*.toml")
+    ///     .run_harness();
+    /// ```
+    pub fn run_harness(&self) {
+        let args = HarnessArgs::parse(std::env::args_os().skip(1));
+        let (mode, mut runner) = self.prepare();
+        runner.retain(|name| args.is_included(name));
+
+        if args.list {
+            use std::io::Write as _;
+
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            let mut count = 0;
+            for name in runner.names() {
+                let _ = writeln!(stdout, "{name}: test");
+                count += 1;
+            }
+            if !args.terse {
+                let _ = writeln!(stdout, "\n{count} tests, 0 benchmarks");
+            }
+            return;
+        }
+
+        let format = parse_format(std::env::var_os("TRYCMD_FORMAT").as_deref())
+            .unwrap_or(crate::report::Format::Libtest);
+        let code = self
+            .exit_codes
+            .get()
+            .code(&self.run_with(&mode, &runner, format));
+        if code != 0 {
+            std::process::exit(code);
+        }
+    }
+
+    /// Exit [`TestCases::run_harness`] with these codes, so wrapper scripts can tell problems
+    /// with the test setup from output that didn't match
+    pub fn exit_codes(&self, codes: ExitCodes) -> &Self {
+        self.exit_codes.set(codes);
+        self
+    }
+
+    fn prepare(&self) -> (crate::Mode, crate::Runner) {
         self.has_run.set(true);
 
-        let mode = parse_mode(std::env::var_os("TRYCMD").as_deref());
+        let mode_var = std::env::var_os("TRYCMD");
+        let mode = parse_mode(mode_var.as_deref());
         mode.initialize().unwrap();
+        if let Some(filter) = parse_filter(mode_var.as_deref()) {
+            self.runner.borrow_mut().filter(filter);
+        }
+        if let Some(root) = std::env::var_os("TRYCMD_TMPDIR").filter(|root| !root.is_empty()) {
+            self.runner.borrow_mut().sandbox_root(Some(root.into()));
+        }
+        if let Some(dir) = std::env::var_os("TRYCMD_ARTIFACTS").filter(|dir| !dir.is_empty()) {
+            self.runner.borrow_mut().artifacts(Some(dir.into()));
+        }
+        if let Some(max) = parse_max_failures(std::env::var_os("TRYCMD_MAX_FAILURES").as_deref()) {
+            self.runner.borrow_mut().max_failures(Some(max));
+        }
+        if let Some(deadline) = parse_deadline(std::env::var_os("TRYCMD_DEADLINE").as_deref()) {
+            self.runner.borrow_mut().deadline(Some(deadline));
+        }
+        if let Some(tags) = std::env::var("TRYCMD_TAGS").ok().filter(|t| !t.is_empty()) {
+            self.runner
+                .borrow_mut()
+                .tags(crate::runner::TagFilter::parse(&tags));
+        }
+        if let Some(seed) = parse_seed(std::env::var_os(crate::spec::DEFAULT_SEED_VAR).as_deref()) {
+            self.runner.borrow_mut().seed(seed);
+        }
 
         let runner = self.runner.borrow_mut().prepare();
-        runner.run(&mode, &self.bins.borrow(), &self.substitutions.borrow());
+        (mode, runner)
+    }
+
+    fn run_with(
+        &self,
+        mode: &crate::Mode,
+        runner: &crate::Runner,
+        format: crate::report::Format,
+    ) -> crate::runner::Outcome {
+        let (verbose, quiet) = match std::env::var_os("TRYCMD_VERBOSE") {
+            Some(var) => (parse_verbose(Some(&var)), false),
+            None => (self.verbose.get(), self.quiet.get()),
+        };
+        runner.run(
+            mode,
+            crate::report::Reporter::new(format, verbose).quiet(quiet),
+            &self.bins.borrow(),
+            &self.substitutions.borrow(),
+            &self.hooks.borrow(),
+        )
     }
 }
 
 impl std::panic::RefUnwindSafe for TestCases {}
 
+/// The exit codes for [`TestCases::run_harness`], by how the run went
+///
+/// By default, like libtest, any failure exits with `101` and a run where everything was skipped
+/// passes.
+/// ```rust,no_run
+/// trycmd::TestCases::new()
+///     .case("tests/cmd/*.toml")
+///     .exit_codes(trycmd::ExitCodes::new().failures(1).errors(2).only_skips(3))
+///     .run_harness();
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExitCodes {
+    failures: i32,
+    errors: i32,
+    only_skips: i32,
+}
+
+impl ExitCodes {
+    pub fn new() -> Self {
+        Self {
+            failures: 101,
+            errors: 101,
+            only_skips: 0,
+        }
+    }
+
+    /// When commands didn't do what was expected, like their output not matching
+    pub fn failures(mut self, code: i32) -> Self {
+        self.failures = code;
+        self
+    }
+
+    /// When cases couldn't be loaded or their commands couldn't be started, like an invalid case
+    /// or a missing bin, taking precedence over [`ExitCodes::failures`]
+    pub fn errors(mut self, code: i32) -> Self {
+        self.errors = code;
+        self
+    }
+
+    /// When nothing failed because no case ran, as all were skipped or filtered out
+    pub fn only_skips(mut self, code: i32) -> Self {
+        self.only_skips = code;
+        self
+    }
+
+    fn code(&self, outcome: &crate::runner::Outcome) -> i32 {
+        match outcome {
+            crate::runner::Outcome::Passed => 0,
+            crate::runner::Outcome::OnlySkipped => self.only_skips,
+            crate::runner::Outcome::Failed(_) => self.failures,
+            crate::runner::Outcome::Errored(_) => self.errors,
+        }
+    }
+}
+
+impl Default for ExitCodes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[doc(hidden)]
 impl Drop for TestCases {
     fn drop(&mut self) {
@@ -238,3 +819,203 @@ fn parse_mode(var: Option<&std::ffi::OsStr>) -> crate::Mode {
         crate::Mode::Fail
     }
 }
+
+/// Any `TRYCMD` value that isn't a mode is a glob of cases to run
+fn parse_filter(var: Option<&std::ffi::OsStr>) -> Option<String> {
+    let var = var?.to_str()?;
+    match var {
+        "" | "overwrite" | "dump" | "list" | "accept" => None,
+        filter => Some(filter.to_owned()),
+    }
+}
+
+fn parse_format(var: Option<&std::ffi::OsStr>) -> Option<crate::report::Format> {
+    if var == Some(std::ffi::OsStr::new("tap")) {
+        Some(crate::report::Format::Tap)
+    } else if var == Some(std::ffi::OsStr::new("json")) {
+        Some(crate::report::Format::Json)
+    } else if var == Some(std::ffi::OsStr::new("human")) {
+        Some(crate::report::Format::Human)
+    } else {
+        None
+    }
+}
+
+/// The parts of libtest's command-line that apply to cases
+#[derive(Debug, Default, PartialEq, Eq)]
+struct HarnessArgs {
+    list: bool,
+    /// `--format terse`, for `--list`
+    terse: bool,
+    exact: bool,
+    filters: Vec<String>,
+    skip: Vec<String>,
+}
+
+impl HarnessArgs {
+    fn parse(args: impl IntoIterator<Item = std::ffi::OsString>) -> Self {
+        let mut parsed = Self::default();
+        let mut args = args
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned());
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_owned())),
+                _ => (arg.as_str(), None),
+            };
+            match flag {
+                "--list" => parsed.list = true,
+                "--exact" => parsed.exact = true,
+                "--skip" => parsed.skip.extend(value.or_else(|| args.next())),
+                "--format" => {
+                    parsed.terse = value.or_else(|| args.next()).as_deref() == Some("terse");
+                }
+                // Flags that take a value
+                "--test-threads" | "--color" | "--logfile" | "--report-time" | "-Z" => {
+                    if value.is_none() {
+                        args.next();
+                    }
+                }
+                // `trycmd=<filter>` is handled by `parse_include`
+                _ if flag.starts_with('-') || flag.starts_with("trycmd=") => {}
+                _ => parsed.filters.push(arg),
+            }
+        }
+        parsed
+    }
+
+    fn is_included(&self, name: &str) -> bool {
+        let matches = |filter: &String| {
+            if self.exact {
+                name == filter
+            } else {
+                name.contains(filter.as_str())
+            }
+        };
+        (self.filters.is_empty() || self.filters.iter().any(matches))
+            && !self.skip.iter().any(matches)
+    }
+}
+
+fn parse_verbose(var: Option<&std::ffi::OsStr>) -> u8 {
+    match var.and_then(|v| v.to_str()) {
+        None | Some("") => 0,
+        Some(level) => level.parse().unwrap_or(1),
+    }
+}
+
+fn parse_seed(var: Option<&std::ffi::OsStr>) -> Option<u64> {
+    var?.to_str()?.parse().ok()
+}
+
+fn parse_max_failures(var: Option<&std::ffi::OsStr>) -> Option<usize> {
+    var?.to_str()?.parse().ok()
+}
+
+fn parse_deadline(var: Option<&std::ffi::OsStr>) -> Option<std::time::Duration> {
+    humantime::parse_duration(var?.to_str()?).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn max_failures_var() {
+        assert_eq!(parse_max_failures(None), None);
+        assert_eq!(parse_max_failures(Some("".as_ref())), None);
+        assert_eq!(parse_max_failures(Some("3".as_ref())), Some(3));
+        assert_eq!(parse_max_failures(Some("many".as_ref())), None);
+    }
+
+    #[test]
+    fn exit_codes() {
+        use crate::runner::Outcome;
+
+        let default = ExitCodes::default();
+        assert_eq!(default.code(&Outcome::Passed), 0);
+        assert_eq!(default.code(&Outcome::OnlySkipped), 0);
+        assert_eq!(
+            default.code(&Outcome::Failed("1 of 2 tests failed".into())),
+            101
+        );
+        assert_eq!(
+            default.code(&Outcome::Errored("1 of 2 tests failed".into())),
+            101
+        );
+
+        let codes = ExitCodes::new().failures(1).errors(2).only_skips(3);
+        assert_eq!(codes.code(&Outcome::Passed), 0);
+        assert_eq!(codes.code(&Outcome::OnlySkipped), 3);
+        assert_eq!(
+            codes.code(&Outcome::Failed("1 of 2 tests failed".into())),
+            1
+        );
+        assert_eq!(
+            codes.code(&Outcome::Errored("1 of 2 tests failed".into())),
+            2
+        );
+    }
+
+    #[test]
+    fn exit_codes_broken_setup() {
+        let cases = TestCases::new();
+        cases.case("tests/cmd/setup/missing-cwd.toml").quiet();
+        let (mode, runner) = cases.prepare();
+        let outcome = cases.run_with(&mode, &runner, Default::default());
+        let codes = ExitCodes::new().failures(1).errors(2);
+        assert_eq!(codes.code(&outcome), 2, "{outcome:?}");
+    }
+
+    #[test]
+    fn deadline_var() {
+        assert_eq!(parse_deadline(None), None);
+        assert_eq!(parse_deadline(Some("".as_ref())), None);
+        assert_eq!(
+            parse_deadline(Some("10m".as_ref())),
+            Some(std::time::Duration::from_secs(600))
+        );
+        assert_eq!(
+            parse_deadline(Some("1h 30m".as_ref())),
+            Some(std::time::Duration::from_secs(5400))
+        );
+        assert_eq!(parse_deadline(Some("soon".as_ref())), None);
+    }
+
+    #[test]
+    fn harness_args() {
+        let args = HarnessArgs::parse(
+            [
+                "--test-threads",
+                "4",
+                "--list",
+                "--format=terse",
+                "basic",
+                "--skip",
+                "code",
+            ]
+            .map(std::ffi::OsString::from),
+        );
+        assert_eq!(
+            args,
+            HarnessArgs {
+                list: true,
+                terse: true,
+                exact: false,
+                filters: vec!["basic".into()],
+                skip: vec!["code".into()],
+            }
+        );
+        assert!(args.is_included("tests/cmd/basic.toml"));
+        assert!(!args.is_included("tests/cmd/basic-code.toml"));
+        assert!(!args.is_included("tests/cmd/help.toml"));
+    }
+
+    #[test]
+    fn harness_args_exact() {
+        let args =
+            HarnessArgs::parse(["--exact", "tests/cmd/basic.toml"].map(std::ffi::OsString::from));
+        assert!(args.is_included("tests/cmd/basic.toml"));
+        assert!(!args.is_included("tests/cmd/basic.toml:short"));
+    }
+}
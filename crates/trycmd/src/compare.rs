@@ -0,0 +1,462 @@
+//! Normalizing and comparing output against what is expected
+
+use snapbox::filter::Filter as _;
+
+/// Convert CRLF line endings to LF
+pub(crate) fn normalize_line_endings(data: crate::Data) -> crate::Data {
+    if data.format() == snapbox::data::DataFormat::Binary {
+        let Ok(bytes) = data.to_bytes() else {
+            return data;
+        };
+        let mut normalized = Vec::with_capacity(bytes.len());
+        let mut bytes = bytes.into_iter().peekable();
+        while let Some(byte) = bytes.next() {
+            if byte == b'\r' && bytes.peek() == Some(&b'\n') {
+                continue;
+            }
+            normalized.push(byte);
+        }
+        crate::Data::binary(normalized)
+    } else {
+        snapbox::filter::FilterNewlines.filter(data)
+    }
+}
+
+/// Remove spaces and tabs from the end of each line
+pub(crate) fn trim_trailing_whitespace(data: crate::Data) -> crate::Data {
+    map_text(data, |text| {
+        let mut trimmed = String::with_capacity(text.len());
+        for line in snapbox::utils::LinesWithTerminator::new(text) {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let terminator = &line[content.len()..];
+            trimmed.push_str(content.trim_end_matches([' ', '\t']));
+            trimmed.push_str(terminator);
+        }
+        trimmed
+    })
+}
+
+/// Add a final newline, if missing, so its absence isn't treated as a difference
+pub(crate) fn ensure_final_newline(data: crate::Data) -> crate::Data {
+    map_text(data, |text| {
+        if text.is_empty() || text.ends_with('\n') {
+            text.to_owned()
+        } else {
+            format!("{text}\n")
+        }
+    })
+}
+
+/// Remove ANSI escape sequences, like colors and hyperlinks
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates up to and including a final byte
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ST (`ESC \\`)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // nF: intermediate bytes up to and including a final byte
+            Some(c) if ('\x20'..='\x2f').contains(&c) => {
+                for c in chars.by_ref() {
+                    if !('\x20'..='\x2f').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Two-character sequences
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// Apply [`strip_ansi`] to text
+pub(crate) fn strip_ansi_data(data: crate::Data) -> crate::Data {
+    map_text(data, strip_ansi)
+}
+
+/// Write Windows paths like Unix ones, so the same snapshot matches on both
+///
+/// `\` becomes `/` and drive letters, like `C:`, and verbatim prefixes, like `\\?\`, are dropped.
+pub(crate) fn normalize_paths(text: &str) -> String {
+    let text = text.replace(r"\\?\", "").replace('\\', "/");
+    let mut normalized = String::with_capacity(text.len());
+    let mut prev = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let starts_word = !prev.map(|p: char| p.is_alphanumeric()).unwrap_or(false);
+        if starts_word && c.is_ascii_alphabetic() && chars.peek() == Some(&':') {
+            let mut rest = chars.clone();
+            rest.next();
+            if rest.peek() == Some(&'/') {
+                chars = rest;
+                prev = Some(':');
+                continue;
+            }
+        }
+        normalized.push(c);
+        prev = Some(c);
+    }
+    normalized
+}
+
+/// Apply [`normalize_paths`] to text
+pub(crate) fn normalize_paths_data(data: crate::Data) -> crate::Data {
+    map_text(data, normalize_paths)
+}
+
+fn map_text(data: crate::Data, op: impl FnOnce(&str) -> String) -> crate::Data {
+    if data.format() != snapbox::data::DataFormat::Text {
+        return data;
+    }
+    match data.render() {
+        Some(text) => crate::Data::text(op(&text)),
+        None => data,
+    }
+}
+
+/// Compare lines in any order, returning the `(missing, extra)` lines
+///
+/// A `...` line in `expected` allows any number of extra lines.  Exact matches are paired first
+/// so `matches`, like for `[..]`, only sees what's left.
+pub(crate) fn unordered_line_mismatches(
+    expected: &str,
+    actual: &str,
+    matches: impl Fn(&str, &str) -> bool,
+) -> (Vec<String>, Vec<String>) {
+    let allow_extra = expected.lines().any(|line| line == "...");
+    let mut missing = expected
+        .lines()
+        .filter(|line| *line != "...")
+        .collect::<Vec<_>>();
+    let mut extra = Vec::new();
+    for line in actual.lines() {
+        match missing.iter().position(|expected| *expected == line) {
+            Some(i) => {
+                missing.remove(i);
+            }
+            None => extra.push(line),
+        }
+    }
+    extra.retain(
+        |line| match missing.iter().position(|expected| matches(expected, line)) {
+            Some(i) => {
+                missing.remove(i);
+                false
+            }
+            None => true,
+        },
+    );
+    if allow_extra {
+        extra.clear();
+    }
+    (
+        missing.into_iter().map(String::from).collect(),
+        extra.into_iter().map(String::from).collect(),
+    )
+}
+
+/// Merge the lines of `first` and `second`, each kept in order, in the order `expected` has them
+///
+/// Returns `None` when no interleaving matches, like when `expected` has lines that neither has,
+/// or the streams are too long to search.
+pub(crate) fn interleave_lines(
+    expected: &str,
+    first: &str,
+    second: &str,
+    matches: impl Fn(&str, &str) -> bool,
+) -> Option<String> {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let first = first.lines().collect::<Vec<_>>();
+    let second = second.lines().collect::<Vec<_>>();
+    if expected.len() != first.len() + second.len()
+        || MAX_INTERLEAVE < (first.len() + 1) * (second.len() + 1)
+    {
+        return None;
+    }
+    let is_match = |expected: &str, actual: &str| expected == actual || matches(expected, actual);
+
+    // `taken[i][j]`: which stream the `i + j`th line came from, when the first `i` lines of
+    // `first` and `j` of `second` can be merged
+    let mut taken = vec![vec![None; second.len() + 1]; first.len() + 1];
+    taken[0][0] = Some(Stream::First);
+    for i in 0..=first.len() {
+        for j in 0..=second.len() {
+            if taken[i][j].is_some() {
+                continue;
+            }
+            let line = expected[i + j - 1];
+            if 0 < i && taken[i - 1][j].is_some() && is_match(line, first[i - 1]) {
+                taken[i][j] = Some(Stream::First);
+            } else if 0 < j && taken[i][j - 1].is_some() && is_match(line, second[j - 1]) {
+                taken[i][j] = Some(Stream::Second);
+            }
+        }
+    }
+
+    let (mut i, mut j) = (first.len(), second.len());
+    taken[i][j]?;
+    let mut merged = Vec::with_capacity(expected.len());
+    while 0 < i + j {
+        match taken[i][j] {
+            Some(Stream::First) => {
+                i -= 1;
+                merged.push(first[i]);
+            }
+            _ => {
+                j -= 1;
+                merged.push(second[j]);
+            }
+        }
+    }
+    merged.reverse();
+    let mut merged = merged.join("\n");
+    if !merged.is_empty() {
+        merged.push('\n');
+    }
+    Some(merged)
+}
+
+/// The most states to consider when interleaving lines
+const MAX_INTERLEAVE: usize = 10_000_000;
+
+#[derive(Copy, Clone)]
+enum Stream {
+    First,
+    Second,
+}
+
+/// Structurally compare JSON values, reporting each difference by its JSON Pointer
+pub(crate) fn json_mismatches(
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    collect_json_mismatches(expected, actual, &mut String::new(), &mut mismatches);
+    mismatches
+}
+
+fn collect_json_mismatches(
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+    pointer: &mut String,
+    mismatches: &mut Vec<String>,
+) {
+    use serde_json::Value;
+
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            let keys = expected
+                .keys()
+                .chain(actual.keys())
+                .collect::<std::collections::BTreeSet<_>>();
+            for key in keys {
+                let len = pointer.len();
+                push_token(pointer, key);
+                match (expected.get(key), actual.get(key)) {
+                    (Some(expected), Some(actual)) => {
+                        collect_json_mismatches(expected, actual, pointer, mismatches);
+                    }
+                    (Some(_), None) => {
+                        mismatches.push(format!("{}: missing key", display_pointer(pointer)));
+                    }
+                    (None, _) => {
+                        mismatches.push(format!("{}: unexpected key", display_pointer(pointer)));
+                    }
+                }
+                pointer.truncate(len);
+            }
+        }
+        (Value::Array(expected_items), Value::Array(actual_items)) => {
+            for (i, (expected, actual)) in expected_items.iter().zip(actual_items).enumerate() {
+                let len = pointer.len();
+                push_token(pointer, &i.to_string());
+                collect_json_mismatches(expected, actual, pointer, mismatches);
+                pointer.truncate(len);
+            }
+            if expected_items.len() != actual_items.len() {
+                mismatches.push(format!(
+                    "{}: expected {} items, got {}",
+                    display_pointer(pointer),
+                    expected_items.len(),
+                    actual_items.len()
+                ));
+            }
+        }
+        (expected, actual) => {
+            if expected != actual {
+                mismatches.push(format!(
+                    "{}: expected {}, got {}",
+                    display_pointer(pointer),
+                    expected,
+                    actual
+                ));
+            }
+        }
+    }
+}
+
+/// Append a reference token, escaped per RFC 6901
+fn push_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+fn display_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "(root)"
+    } else {
+        pointer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trailing_whitespace() {
+        let actual = trim_trailing_whitespace(crate::Data::text("a  \nb\t\r\n  c \n\nd "));
+        assert_eq!(actual, crate::Data::text("a\nb\r\n  c\n\nd"));
+    }
+
+    #[test]
+    fn final_newline() {
+        assert_eq!(
+            ensure_final_newline(crate::Data::text("a\nb")),
+            crate::Data::text("a\nb\n")
+        );
+        assert_eq!(
+            ensure_final_newline(crate::Data::text("a\n")),
+            crate::Data::text("a\n")
+        );
+        assert_eq!(
+            ensure_final_newline(crate::Data::text("")),
+            crate::Data::text("")
+        );
+    }
+
+    #[test]
+    fn ansi() {
+        let styled =
+            "\x1b[1;31merror\x1b[0m: \x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07 \x1b(Bdone";
+        assert_eq!(strip_ansi(styled), "error: link done");
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            normalize_paths(r"Wrote C:\Users\me\out.txt and d:/tmp/a, not a:b or ab:/c"),
+            "Wrote /Users/me/out.txt and /tmp/a, not a:b or ab:/c"
+        );
+        assert_eq!(normalize_paths(r"\\?\C:\src\lib.rs"), "/src/lib.rs");
+    }
+
+    #[test]
+    fn line_endings_binary() {
+        let actual = normalize_line_endings(crate::Data::binary(b"a\r\nb\rc\r\n".to_vec()));
+        assert_eq!(actual, crate::Data::binary(b"a\nb\rc\n".to_vec()));
+    }
+
+    #[test]
+    fn unordered_lines() {
+        let wildcard = |expected: &str, actual: &str| {
+            expected
+                .strip_suffix("[..]")
+                .map(|prefix| actual.starts_with(prefix))
+                .unwrap_or(false)
+        };
+        let (missing, extra) =
+            unordered_line_mismatches("a\nb\nb\nc[..]\n", "b\nc3\na\nb\n", wildcard);
+        assert!(missing.is_empty(), "{missing:?}");
+        assert!(extra.is_empty(), "{extra:?}");
+
+        let (missing, extra) = unordered_line_mismatches("a\nb\nb\n", "b\nd\na\n", wildcard);
+        assert_eq!(missing, ["b"]);
+        assert_eq!(extra, ["d"]);
+
+        let (missing, extra) = unordered_line_mismatches("b\n...\n", "a\nb\nc\n", wildcard);
+        assert!(missing.is_empty(), "{missing:?}");
+        assert!(extra.is_empty(), "{extra:?}");
+    }
+
+    #[test]
+    fn interleave() {
+        let wildcard = |expected: &str, actual: &str| {
+            expected
+                .strip_suffix("[..]")
+                .map(|prefix| actual.starts_with(prefix))
+                .unwrap_or(false)
+        };
+        let actual = interleave_lines(
+            "warning: a\nstep 1\nwarning: b[..]\nstep 2\n",
+            "step 1\nstep 2\n",
+            "warning: a\nwarning: b 42\n",
+            wildcard,
+        );
+        assert_eq!(
+            actual.as_deref(),
+            Some("warning: a\nstep 1\nwarning: b 42\nstep 2\n")
+        );
+
+        // Each stream stays in order
+        let actual = interleave_lines("step 2\nstep 1\n", "step 1\nstep 2\n", "", wildcard);
+        assert_eq!(actual, None);
+        let actual = interleave_lines("step 1\n", "step 1\n", "extra\n", wildcard);
+        assert_eq!(actual, None);
+    }
+
+    #[test]
+    fn json_ignores_key_order() {
+        let expected = serde_json::json!({"a": 1, "b": [1, 2]});
+        let actual = serde_json::from_str(r#"{ "b": [1, 2], "a": 1 }"#).unwrap();
+        assert_eq!(json_mismatches(&expected, &actual), Vec::<String>::new());
+    }
+
+    #[test]
+    fn json_reports_pointers() {
+        let expected = serde_json::json!({"a": {"b/c": 1}, "list": [1, 2], "gone": null});
+        let actual = serde_json::json!({"a": {"b/c": 2}, "list": [1], "new": true});
+        assert_eq!(
+            json_mismatches(&expected, &actual),
+            [
+                "/a/b~1c: expected 1, got 2",
+                "/gone: missing key",
+                "/list: expected 2 items, got 1",
+                "/new: unexpected key",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_root_mismatch() {
+        let expected = serde_json::json!([]);
+        let actual = serde_json::json!("text");
+        assert_eq!(
+            json_mismatches(&expected, &actual),
+            [r#"(root): expected [], got "text""#]
+        );
+    }
+}
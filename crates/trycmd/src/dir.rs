@@ -0,0 +1,225 @@
+//! Comparing directory trees outside of cases, like a build script's or code generator's output
+
+use crate::schema::FileCompare;
+
+/// Check that each file in `expected_root` matches the one at the same path in `actual_root`
+///
+/// Files are compared like a case's `*.out/`; see [`DirAssert`].
+///
+/// ```rust,no_run
+/// trycmd::assert_subset_eq("tests/snapshots/codegen", "target/codegen");
+/// ```
+#[track_caller]
+pub fn assert_subset_eq(
+    expected_root: impl AsRef<std::path::Path>,
+    actual_root: impl AsRef<std::path::Path>,
+) {
+    DirAssert::new().subset_eq(expected_root, actual_root);
+}
+
+/// Check that `expected_root` and `actual_root` hold the same files with matching content
+///
+/// Files are compared like a case's `*.out/`; see [`DirAssert`].
+///
+/// ```rust,no_run
+/// trycmd::assert_eq_dirs("tests/snapshots/codegen", "target/codegen");
+/// ```
+#[track_caller]
+pub fn assert_eq_dirs(
+    expected_root: impl AsRef<std::path::Path>,
+    actual_root: impl AsRef<std::path::Path>,
+) {
+    DirAssert::new().eq(expected_root, actual_root);
+}
+
+/// Compare directory trees the way a case's `*.out/` is compared
+///
+/// Each file is read as text or binary based on its extension and content, unless a
+/// [`DirAssert::compare`] pattern says otherwise.  Text supports the same wildcards and
+/// placeholders as cases, and `TRYCMD=overwrite` updates `expected_root` to match.
+///
+/// ```rust,no_run
+/// trycmd::DirAssert::new()
+///     .ignore("*.log")
+///     .compare("*.png", trycmd::schema::FileCompare::Binary)
+///     .compare("build-id.txt", trycmd::schema::FileCompare::Exists)
+///     .eq("tests/snapshots/site", "target/site");
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct DirAssert {
+    ignore: Vec<String>,
+    compare: Vec<(String, FileCompare)>,
+}
+
+impl DirAssert {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave out paths matching a `.gitignore`-style glob, like `fs.ignore`
+    pub fn ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Compare paths matching a `.gitignore`-style glob, like `*.png`, this way, like `fs.files`
+    ///
+    /// The first pattern that matches a path is used.
+    pub fn compare(mut self, pattern: impl Into<String>, compare: FileCompare) -> Self {
+        self.compare.push((pattern.into(), compare));
+        self
+    }
+
+    /// Check that each file in `expected_root` matches the one at the same path in `actual_root`
+    #[track_caller]
+    pub fn subset_eq(
+        &self,
+        expected_root: impl AsRef<std::path::Path>,
+        actual_root: impl AsRef<std::path::Path>,
+    ) {
+        if let Err(err) = self.try_check(expected_root.as_ref(), actual_root.as_ref(), false) {
+            panic!("{err}");
+        }
+    }
+
+    /// Check that `expected_root` and `actual_root` hold the same files with matching content
+    #[track_caller]
+    pub fn eq(
+        &self,
+        expected_root: impl AsRef<std::path::Path>,
+        actual_root: impl AsRef<std::path::Path>,
+    ) {
+        if let Err(err) = self.try_check(expected_root.as_ref(), actual_root.as_ref(), true) {
+            panic!("{err}");
+        }
+    }
+
+    fn try_check(
+        &self,
+        expected_root: &std::path::Path,
+        actual_root: &std::path::Path,
+        exact: bool,
+    ) -> Result<(), crate::Error> {
+        let overwrite =
+            std::env::var_os("TRYCMD").as_deref() == Some(std::ffi::OsStr::new("overwrite"));
+        let ignore = crate::ignore::Ignore::new(&self.ignore)?;
+        let compare = self
+            .compare
+            .iter()
+            .map(|(pattern, compare)| {
+                crate::ignore::Ignore::new(std::slice::from_ref(pattern))
+                    .map(|pattern| (pattern, *compare))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let substitutions = crate::output::redactions();
+        let palette = snapbox::report::Palette::color();
+
+        let mut failures = String::new();
+        let mut record = |diff: snapbox::dir::PathDiff| -> Result<(), crate::Error> {
+            if overwrite {
+                diff.overwrite()?;
+            } else {
+                diff.write(&mut failures, palette)
+                    .map_err(|e| crate::Error::new(e.to_string()))?;
+            }
+            Ok(())
+        };
+
+        for status in
+            snapbox::dir::PathDiff::subset_matches_iter(expected_root, actual_root, &substitutions)
+        {
+            let expected_path = match &status {
+                Ok((expected_path, _)) => Some(expected_path.as_path()),
+                Err(diff) => diff.expected_path(),
+            };
+            let rel = expected_path.and_then(|p| p.strip_prefix(expected_root).ok());
+            if rel.map(|rel| ignore.is_ignored(rel)).unwrap_or(false) {
+                continue;
+            }
+            let file_compare = rel
+                .and_then(|rel| {
+                    compare
+                        .iter()
+                        .find(|(pattern, _)| pattern.is_ignored(rel))
+                        .map(|(_, compare)| *compare)
+                })
+                .unwrap_or_default();
+            let mut status = status;
+            crate::runner::compare_file(&mut status, file_compare, &substitutions);
+            match status {
+                Ok(_) => {}
+                Err(snapbox::dir::PathDiff::TypeMismatch {
+                    expected_path,
+                    actual_type: snapbox::dir::FileType::Missing,
+                    ..
+                }) if exact && overwrite => remove(&expected_path)?,
+                Err(diff) => record(diff)?,
+            }
+        }
+
+        if exact {
+            for actual_path in snapbox::dir::Walk::new(actual_root) {
+                let actual_path = actual_path.map_err(|e| crate::Error::Io(e.to_string()))?;
+                let rel = actual_path.strip_prefix(actual_root).unwrap();
+                let expected_path = expected_root.join(rel);
+                if rel.as_os_str().is_empty()
+                    || ignore.is_ignored(rel)
+                    || snapbox::dir::FileType::from_path(&expected_path)
+                        != snapbox::dir::FileType::Missing
+                {
+                    continue;
+                }
+                if overwrite {
+                    copy(&actual_path, &expected_path)?;
+                } else if !actual_path.is_dir() {
+                    use std::fmt::Write as _;
+                    let _ = writeln!(
+                        failures,
+                        "{}: Unexpected {}",
+                        actual_path.display(),
+                        palette.error("file")
+                    );
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::new(failures))
+        }
+    }
+}
+
+fn remove(path: &std::path::Path) -> Result<(), crate::Error> {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    result.map_err(|e| crate::Error::Io(format!("Failed to remove {}: {}", path.display(), e)))
+}
+
+/// Record a file only in the actual tree, creating the directories leading to it
+fn copy(
+    actual_path: &std::path::Path,
+    expected_path: &std::path::Path,
+) -> Result<(), crate::Error> {
+    if actual_path.is_dir() {
+        std::fs::create_dir_all(expected_path)
+    } else {
+        if let Some(parent) = expected_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                crate::Error::Io(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+        std::fs::copy(actual_path, expected_path).map(|_| ())
+    }
+    .map_err(|e| {
+        crate::Error::Io(format!(
+            "Failed to write {}: {}",
+            expected_path.display(),
+            e
+        ))
+    })
+}
@@ -0,0 +1,96 @@
+/// Why a case couldn't be loaded or run
+///
+/// Match on the kind to handle failures programmatically; [`Display`][std::fmt::Display] gives
+/// the full message, including the file involved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io(String),
+    /// A `*.toml` case is invalid
+    ParseToml(String),
+    /// A `*.trycmd` or `*.md` case is invalid
+    ParseTryCmd(String),
+    /// `bin.name` isn't registered or built, or the binary doesn't exist
+    UnknownBin(String),
+    /// The command couldn't be started
+    SpawnFailed(String),
+    /// The command didn't get where it was expected to in time
+    Timeout(String),
+    /// Anything else, including errors from hooks
+    Other(String),
+}
+
+impl Error {
+    /// An [`Error::Other`] with `message`
+    pub fn new(message: impl std::fmt::Display) -> Self {
+        Self::Other(message.to_string())
+    }
+
+    /// The full message, like [`Display`][std::fmt::Display] without the trailing newline
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Io(message)
+            | Self::ParseToml(message)
+            | Self::ParseTryCmd(message)
+            | Self::UnknownBin(message)
+            | Self::SpawnFailed(message)
+            | Self::Timeout(message)
+            | Self::Other(message) => message,
+        }
+    }
+
+    /// Prefix the message with where the error happened, keeping the kind
+    pub(crate) fn context(self, context: impl std::fmt::Display) -> Self {
+        let message = format!("{}: {}", context, self.message());
+        match self {
+            Self::Io(_) => Self::Io(message),
+            Self::ParseToml(_) => Self::ParseToml(message),
+            Self::ParseTryCmd(_) => Self::ParseTryCmd(message),
+            Self::UnknownBin(_) => Self::UnknownBin(message),
+            Self::SpawnFailed(_) => Self::SpawnFailed(message),
+            Self::Timeout(_) => Self::Timeout(message),
+            Self::Other(_) => Self::Other(message),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl<'s> From<&'s str> for Error {
+    fn from(message: &'s str) -> Self {
+        Self::Other(message.to_owned())
+    }
+}
+
+impl From<snapbox::assert::Error> for Error {
+    fn from(error: snapbox::assert::Error) -> Self {
+        Self::Other(error.to_string().trim_end().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn context_keeps_kind() {
+        let error = Error::ParseToml("unknown field `sdtout`".into()).context("case.toml");
+        assert_eq!(
+            error,
+            Error::ParseToml("case.toml: unknown field `sdtout`".into())
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! A stable clock for `fake-time`, through `SOURCE_DATE_EPOCH` and libfaketime
+
+/// The variables for a command's clock to read `time`, keeping any others `env` sets
+pub(crate) fn env(time: std::time::SystemTime, env: &crate::schema::Env) -> Vec<(String, String)> {
+    let preloaded = env
+        .add
+        .get(PRELOAD)
+        .cloned()
+        .or_else(|| env.inherit().then(|| std::env::var(PRELOAD).ok()).flatten());
+    vars(time, find_shim().as_deref(), preloaded)
+        .into_iter()
+        .filter(|(var, _)| var == PRELOAD || !env.add.contains_key(var))
+        .collect()
+}
+
+fn vars(
+    time: std::time::SystemTime,
+    shim: Option<&std::path::Path>,
+    preloaded: Option<String>,
+) -> Vec<(String, String)> {
+    let epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut vars = vec![
+        ("SOURCE_DATE_EPOCH".to_owned(), epoch.to_string()),
+        ("TZ".to_owned(), "UTC".to_owned()),
+    ];
+    if let Some(shim) = shim {
+        // Starting from `time` rather than stopped at it, so the command's timeouts still work
+        let start = humantime::format_rfc3339_seconds(time)
+            .to_string()
+            .replace('T', " ")
+            .replace('Z', "");
+        vars.push(("FAKETIME".to_owned(), format!("@{start}")));
+        let shim = shim.display().to_string();
+        let preload = match preloaded.filter(|p| !p.is_empty()) {
+            Some(preloaded) => format!("{shim}:{preloaded}"),
+            None => shim,
+        };
+        vars.push((PRELOAD.to_owned(), preload));
+        if cfg!(target_os = "macos") {
+            vars.push(("DYLD_FORCE_FLAT_NAMESPACE".to_owned(), "1".to_owned()));
+        }
+    }
+    vars
+}
+
+/// The variable to load libfaketime through
+const PRELOAD: &str = if cfg!(target_os = "macos") {
+    "DYLD_INSERT_LIBRARIES"
+} else {
+    "LD_PRELOAD"
+};
+
+/// libfaketime, from `TRYCMD_FAKETIME_LIB` or where packages install it
+fn find_shim() -> Option<std::path::PathBuf> {
+    if let Some(path) = std::env::var_os("TRYCMD_FAKETIME_LIB") {
+        return Some(path.into());
+    }
+    let candidates = if cfg!(target_os = "linux") {
+        vec![
+            format!(
+                "/usr/lib/{}-linux-gnu/faketime/libfaketime.so.1",
+                std::env::consts::ARCH
+            ),
+            "/usr/lib64/faketime/libfaketime.so.1".to_owned(),
+            "/usr/lib/faketime/libfaketime.so.1".to_owned(),
+            "/usr/local/lib/faketime/libfaketime.so.1".to_owned(),
+        ]
+    } else if cfg!(target_os = "macos") {
+        vec![
+            "/opt/homebrew/lib/faketime/libfaketime.1.dylib".to_owned(),
+            "/usr/local/lib/faketime/libfaketime.1.dylib".to_owned(),
+        ]
+    } else {
+        Vec::new()
+    };
+    candidates
+        .into_iter()
+        .map(std::path::PathBuf::from)
+        .find(|path| path.exists())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vars_with_shim() {
+        let time = humantime::parse_rfc3339("2021-01-01T00:00:00Z").unwrap();
+        assert_eq!(
+            vars(time, None, None),
+            [
+                ("SOURCE_DATE_EPOCH".to_owned(), "1609459200".to_owned()),
+                ("TZ".to_owned(), "UTC".to_owned()),
+            ]
+        );
+
+        let actual = vars(
+            time,
+            Some(std::path::Path::new("/lib/libfaketime.so.1")),
+            Some("/lib/libasan.so".to_owned()),
+        );
+        assert_eq!(
+            actual[2],
+            ("FAKETIME".to_owned(), "@2021-01-01 00:00:00".to_owned())
+        );
+        assert_eq!(
+            actual[3],
+            (
+                PRELOAD.to_owned(),
+                "/lib/libfaketime.so.1:/lib/libasan.so".to_owned()
+            )
+        );
+    }
+}
@@ -0,0 +1,81 @@
+/// What a [`TestCases::setup`][crate::TestCases::setup] or
+/// [`TestCases::teardown`][crate::TestCases::teardown] hook knows about the case
+#[derive(Debug)]
+pub struct CaseContext<'a> {
+    pub(crate) path: &'a std::path::Path,
+    pub(crate) name: &'a str,
+    pub(crate) sandbox: Option<&'a std::path::Path>,
+    pub(crate) cwd: Option<&'a std::path::Path>,
+}
+
+impl CaseContext<'_> {
+    /// The test case file
+    pub fn path(&self) -> &std::path::Path {
+        self.path
+    }
+
+    /// The name the case is reported under, like `tests/cmd/help.toml` or
+    /// `tests/cmd/help.toml:short` for a `[[matrix]]` entry
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// The temporary directory the case runs in, when `*.out/` or `fs.sandbox = true`
+    pub fn sandbox(&self) -> Option<&std::path::Path> {
+        self.sandbox
+    }
+
+    /// The directory commands run from
+    pub fn cwd(&self) -> Option<&std::path::Path> {
+        self.cwd
+    }
+}
+
+pub(crate) type Hook =
+    std::sync::Arc<dyn Fn(&CaseContext<'_>) -> Result<(), crate::Error> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) setup: Vec<Hook>,
+    pub(crate) teardown: Vec<Hook>,
+    /// Snapshot extensions and what checks them
+    pub(crate) validators: Vec<(String, crate::validator::Validator)>,
+    pub(crate) normalizers: Vec<crate::normalizer::Normalize>,
+    /// Bin names and the functions to run in-process for them
+    pub(crate) mains: std::collections::BTreeMap<String, crate::in_process::Main>,
+}
+
+impl Hooks {
+    pub(crate) fn setup(&self, context: &CaseContext<'_>) -> Result<(), crate::Error> {
+        for hook in &self.setup {
+            hook(context)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn teardown(&self, context: &CaseContext<'_>) -> Result<(), crate::Error> {
+        for hook in &self.teardown {
+            hook(context)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("setup", &self.setup.len())
+            .field("teardown", &self.teardown.len())
+            .field(
+                "validators",
+                &self
+                    .validators
+                    .iter()
+                    .map(|(ext, _)| ext)
+                    .collect::<Vec<_>>(),
+            )
+            .field("normalizers", &self.normalizers.len())
+            .field("mains", &self.mains.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
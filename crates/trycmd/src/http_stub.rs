@@ -0,0 +1,279 @@
+//! A local HTTP server for a case's `[[http-stub]]`, so HTTP clients can be tested hermetically
+
+/// Serves the case's `[[http-stub]]` until dropped
+pub(crate) struct Server {
+    url: String,
+    stopped: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Server {
+    pub(crate) fn start(stubs: &[crate::schema::HttpStub]) -> Result<Self, crate::Error> {
+        let routes = stubs
+            .iter()
+            .map(Route::load)
+            .collect::<Result<Vec<_>, _>>()?;
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .map_err(|e| {
+                crate::Error::Io(format!("Failed to start `[[http-stub]]` server: {}", e))
+            })?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| {
+                crate::Error::Io(format!("Failed to start `[[http-stub]]` server: {}", e))
+            })?
+            .port();
+
+        let stopped = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let stop = stopped.clone();
+        let thread = std::thread::spawn(move || {
+            while !stop.load(std::sync::atomic::Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let _ = respond(stream, &routes);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(POLL);
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(Self {
+            url: format!("http://127.0.0.1:{}", port),
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Like `http://127.0.0.1:8080`, for `[HTTP_STUB]`
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stopped
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// How often to check for connections
+const POLL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// A `[[http-stub]]` with its body loaded
+struct Route {
+    method: Option<String>,
+    path: String,
+    status: u16,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl Route {
+    fn load(stub: &crate::schema::HttpStub) -> Result<Self, crate::Error> {
+        let body = match (&stub.body, &stub.body_file) {
+            (_, Some(path)) => std::fs::read(path).map_err(|e| {
+                crate::Error::Io(format!(
+                    "Failed to read `http-stub.body-file` {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?,
+            (Some(body), None) => body.clone().into_bytes(),
+            (None, None) => Vec::new(),
+        };
+        let content_type = stub.content_type.clone().unwrap_or_else(|| {
+            let is_json = stub.body_file.as_deref().and_then(|path| path.extension())
+                == Some(std::ffi::OsStr::new("json"));
+            if is_json {
+                "application/json".to_owned()
+            } else {
+                "text/plain".to_owned()
+            }
+        });
+        Ok(Self {
+            method: stub.method.as_ref().map(|m| m.to_ascii_uppercase()),
+            path: stub.path.clone(),
+            status: stub.status,
+            content_type,
+            body,
+        })
+    }
+
+    fn matches_path(&self, target: &str) -> bool {
+        if self.path.contains('?') {
+            self.path == target
+        } else {
+            self.path == target.split('?').next().unwrap_or_default()
+        }
+    }
+}
+
+/// Answer one request, closing the connection after
+fn respond(stream: std::net::TcpStream, routes: &[Route]) -> std::io::Result<()> {
+    use std::io::BufRead as _;
+    use std::io::Read as _;
+    use std::io::Write as _;
+
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut reader = std::io::BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let target = parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    // Read the body, unused, so the client isn't cut off mid-request
+    std::io::copy(
+        &mut reader.by_ref().take(content_length),
+        &mut std::io::sink(),
+    )?;
+
+    // A stub for the method takes precedence over one for any method
+    let for_path = routes
+        .iter()
+        .filter(|r| r.matches_path(&target))
+        .collect::<Vec<_>>();
+    let route = for_path
+        .iter()
+        .find(|r| r.method.as_deref() == Some(method.as_str()))
+        .or_else(|| for_path.iter().find(|r| r.method.is_none()));
+    let (status, content_type, body) = match route {
+        Some(route) => (
+            route.status,
+            route.content_type.as_str(),
+            route.body.clone(),
+        ),
+        None if !for_path.is_empty() => (
+            405,
+            "text/plain",
+            format!("No `[[http-stub]]` for {} {}\n", method, target).into_bytes(),
+        ),
+        None => (
+            404,
+            "text/plain",
+            format!("No `[[http-stub]]` for {} {}\n", method, target).into_bytes(),
+        ),
+    };
+
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    if method != "HEAD" {
+        response.extend(body);
+    }
+    let mut stream = &stream;
+    stream.write_all(&response)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get(url: &str, request: &str) -> String {
+        use std::io::Read as _;
+        use std::io::Write as _;
+
+        let address = url.strip_prefix("http://").unwrap();
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serve() {
+        let stubs = [
+            crate::schema::HttpStub {
+                path: "/v1/items".into(),
+                method: None,
+                status: 200,
+                body: Some("[]".into()),
+                body_file: None,
+                content_type: Some("application/json".into()),
+            },
+            crate::schema::HttpStub {
+                path: "/v1/items".into(),
+                method: Some("post".into()),
+                status: 201,
+                body: None,
+                body_file: None,
+                content_type: None,
+            },
+        ];
+        let server = Server::start(&stubs).unwrap();
+
+        let response = get(
+            server.url(),
+            "POST /v1/items HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+        );
+        assert_eq!(
+            response,
+            "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+        let response = get(server.url(), "GET /v1/items?page=2 HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with("\r\n\r\n[]"), "{response}");
+        let response = get(server.url(), "GET /v2/items HTTP/1.1\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{response}"
+        );
+        assert!(
+            response.ends_with("No `[[http-stub]]` for GET /v2/items\n"),
+            "{response}"
+        );
+    }
+}
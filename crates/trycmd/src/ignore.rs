@@ -0,0 +1,113 @@
+//! Fixture paths to leave out of the sandbox and out of `*.out/` comparisons
+
+/// File in `fs.base` listing more patterns, one per line
+pub(crate) const IGNORE_FILE: &str = ".trycmdignore";
+
+/// `.gitignore`-style glob patterns, without negation
+#[derive(Clone, Default, Debug)]
+pub(crate) struct Ignore {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl Ignore {
+    /// Patterns from `fs.ignore` and the [`IGNORE_FILE`] in `fs.base`, if any
+    pub(crate) fn load(fs: &crate::schema::Filesystem) -> Result<Self, crate::Error> {
+        let mut lines = fs.ignore.clone();
+        if let Some(base) = fs.base.as_deref() {
+            let path = base.join(IGNORE_FILE);
+            if path.exists() {
+                let raw = std::fs::read_to_string(&path).map_err(|e| {
+                    crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+                })?;
+                lines.extend(
+                    raw.lines()
+                        .map(str::trim)
+                        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                        .map(ToOwned::to_owned),
+                );
+                lines.push(IGNORE_FILE.to_owned());
+            }
+        }
+        Self::new(&lines)
+    }
+
+    pub(crate) fn new(lines: &[String]) -> Result<Self, crate::Error> {
+        let patterns = lines
+            .iter()
+            .map(|line| {
+                let line = line.trim_end_matches('/');
+                // Like `.gitignore`, patterns without a `/` match at any depth
+                let pattern = match line.strip_prefix('/') {
+                    Some(anchored) => anchored.to_owned(),
+                    None if line.contains('/') => line.to_owned(),
+                    None => format!("**/{line}"),
+                };
+                glob::Pattern::new(&pattern)
+                    .map_err(|e| format!("Invalid ignore pattern `{line}`: {e}").into())
+            })
+            .collect::<Result<_, crate::Error>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Whether `rel`, relative to the fixture root, or any of its parents is ignored
+    pub(crate) fn is_ignored(&self, rel: &std::path::Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        rel.ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .any(|p| {
+                self.patterns
+                    .iter()
+                    .any(|g| g.matches_path_with(p, options))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ignore(lines: &[&str]) -> Ignore {
+        Ignore::new(&lines.iter().map(|l| (*l).to_owned()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn empty() {
+        let ignore = ignore(&[]);
+        assert!(!ignore.is_ignored(std::path::Path::new("file.txt")));
+    }
+
+    #[test]
+    fn name_at_any_depth() {
+        let ignore = ignore(&[".git", "*.log"]);
+        assert!(ignore.is_ignored(std::path::Path::new(".git")));
+        assert!(ignore.is_ignored(std::path::Path::new(".git/HEAD")));
+        assert!(ignore.is_ignored(std::path::Path::new("nested/.git/HEAD")));
+        assert!(ignore.is_ignored(std::path::Path::new("out.log")));
+        assert!(ignore.is_ignored(std::path::Path::new("nested/out.log")));
+        assert!(!ignore.is_ignored(std::path::Path::new("out.txt")));
+        assert!(!ignore.is_ignored(std::path::Path::new(".github/workflow.yml")));
+    }
+
+    #[test]
+    fn anchored() {
+        let ignore = ignore(&["/target/", "docs/*.html", "**/cache/*.bin"]);
+        assert!(ignore.is_ignored(std::path::Path::new("target/debug/bin")));
+        assert!(!ignore.is_ignored(std::path::Path::new("nested/target/debug/bin")));
+        assert!(ignore.is_ignored(std::path::Path::new("docs/index.html")));
+        assert!(!ignore.is_ignored(std::path::Path::new("docs/api/index.html")));
+        assert!(ignore.is_ignored(std::path::Path::new("a/b/cache/blob.bin")));
+        assert!(ignore.is_ignored(std::path::Path::new("cache/blob.bin")));
+    }
+
+    #[test]
+    fn invalid() {
+        let err = Ignore::new(&["[".to_owned()]).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid ignore pattern `[`"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Bins registered with `register_fn`, run on a thread of the test process
+
+/// A CLI run inside the test process rather than spawned, like the body of a thin `main`
+///
+/// Register it under a bin name with
+/// [`TestCases::register_fn`][crate::TestCases::register_fn].  It is given the arguments, like
+/// [`std::env::args`] starting with the bin's name, and `stdin`, and returns the exit code,
+/// `stdout`, and `stderr`.
+///
+/// It runs on its own thread, with a panic reported as exit code 101.  As it shares the test
+/// process, cases can't set `env`, `fs.cwd`, a sandbox, `[limits]`, `net`, `priority`, `cpus`,
+/// `wrapper`, or `shell` for it.  A run past the case's `timeout` is reported, but its thread
+/// can't be stopped, so it is left running in the background until it returns or the test
+/// process exits.
+///
+/// Closures taking the same arguments as [`InProcessBin::run`] implement this.
+pub trait InProcessBin: Send + Sync {
+    fn run(&self, args: &[String], stdin: &[u8]) -> (i32, Vec<u8>, Vec<u8>);
+}
+
+impl<F> InProcessBin for F
+where
+    F: Fn(&[String], &[u8]) -> (i32, Vec<u8>, Vec<u8>) + Send + Sync,
+{
+    fn run(&self, args: &[String], stdin: &[u8]) -> (i32, Vec<u8>, Vec<u8>) {
+        self(args, stdin)
+    }
+}
+
+pub(crate) type Main = std::sync::Arc<dyn InProcessBin>;
+
+/// Check the `env` the case set, before the suite's is added, as the bin can't be given one
+pub(crate) fn check_env(name: &str, env: &crate::schema::Env) -> Result<(), crate::Error> {
+    // Set by `[[http-stub]]` rather than the case; `[HTTP_STUB]` still reaches the bin
+    let added = env.add.keys().any(|key| key != "TRYCMD_HTTP_STUB");
+    if env.inherit.is_some() || env.preset.is_some() || !env.remove.is_empty() || added {
+        return Err(format!("`env` is unsupported with in-process bin `{name}`").into());
+    }
+    Ok(())
+}
+
+/// Run `step` with `main` as its bin, giving up on it after the step's `timeout`
+///
+/// On timeout, the thread running `main` is left behind.
+pub(crate) fn run(
+    main: &Main,
+    name: &str,
+    step: &crate::schema::Step,
+    cwd: Option<&std::path::Path>,
+) -> Result<std::process::Output, crate::Error> {
+    let process_cwd = std::env::current_dir().ok();
+    if cwd.is_some() && cwd != process_cwd.as_deref() {
+        return Err(format!(
+            "`fs.cwd` and `fs.sandbox` are unsupported with in-process bin `{name}`"
+        )
+        .into());
+    }
+    if !step.limits.is_empty() {
+        return Err(format!("`[limits]` is unsupported with in-process bin `{name}`").into());
+    }
+    if !step.pipe.is_empty() {
+        return Err(format!("`pipe` is unsupported with in-process bin `{name}`").into());
+    }
+    if !step.interaction.is_empty() {
+        return Err(
+            format!("`[[interaction]]` is unsupported with in-process bin `{name}`").into(),
+        );
+    }
+    if step.stdin_close != crate::schema::StdinClose::default() {
+        return Err(format!("`stdin.close` is unsupported with in-process bin `{name}`").into());
+    }
+    if !step.expect_lines.is_empty() {
+        return Err(
+            format!("`[[expect-line]]` is unsupported with in-process bin `{name}`").into(),
+        );
+    }
+    if step.background.is_some() {
+        return Err(format!("`background` is unsupported with in-process bin `{name}`").into());
+    }
+    if !step.signals.is_empty() {
+        return Err(format!("`[[signals]]` is unsupported with in-process bin `{name}`").into());
+    }
+    if step.tolerate_interleave {
+        return Err(
+            format!("`tolerate-interleave` is unsupported with in-process bin `{name}`").into(),
+        );
+    }
+    if step.fake_time.is_some() {
+        return Err(format!("`fake-time` is unsupported with in-process bin `{name}`").into());
+    }
+    if step.net != crate::schema::Net::Host {
+        return Err(format!("`net` is unsupported with in-process bin `{name}`").into());
+    }
+    if step.scheduling != crate::sched::Scheduling::default() {
+        return Err(
+            format!("`priority` and `cpus` are unsupported with in-process bin `{name}`").into(),
+        );
+    }
+    if !step.wrapper.is_empty() {
+        return Err(format!("`wrapper` is unsupported with in-process bin `{name}`").into());
+    }
+    if step.shell {
+        return Err(format!("`shell` is unsupported with in-process bin `{name}`").into());
+    }
+
+    let args: Vec<String> = std::iter::once(name.to_owned())
+        .chain(step.args.iter().cloned())
+        .collect();
+    let stdin = step
+        .stdin
+        .as_ref()
+        .map(|stdin| stdin.to_bytes())
+        .transpose()?
+        .unwrap_or_default();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let main = main.clone();
+    std::thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || {
+            let result =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| main.run(&args, &stdin)));
+            let _ = sender.send(result.unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| (*s).to_owned())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "Box<dyn Any>".to_owned());
+                (
+                    101,
+                    Vec::new(),
+                    format!("panicked: {message}\n").into_bytes(),
+                )
+            }));
+        })
+        .map_err(|e| crate::Error::SpawnFailed(e.to_string()))?;
+
+    let (code, mut stdout, stderr) = match step.timeout {
+        Some(timeout) => receiver.recv_timeout(timeout).map_err(|_| {
+            crate::Error::Timeout(format!(
+                "In-process bin `{name}` didn't finish within {timeout:?}"
+            ))
+        })?,
+        None => receiver
+            .recv()
+            .map_err(|e| crate::Error::SpawnFailed(e.to_string()))?,
+    };
+    let stderr = if step.stderr_to_stdout {
+        stdout.extend(stderr);
+        Vec::new()
+    } else {
+        stderr
+    };
+    Ok(std::process::Output {
+        status: crate::platform::exit_status(code),
+        stdout,
+        stderr,
+    })
+}
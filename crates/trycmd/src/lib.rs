@@ -35,6 +35,10 @@
 //! Run this with `cargo test` like normal.  [`TestCases`] will enumerate all test case files and
 //! run the contained commands, verifying they run as expected.
 //!
+//! To organize a large CLI's cases into subdirectories, load them with
+//! [`TestCases::case_dir`], like `.case_dir("tests/cmd")`.  Cases are named by their relative
+//! path, so `tests/cmd/subcmd/users/add.toml` is `subcmd::users::add`.
+//!
 //! To temporarily override the results, you can do:
 //! ```rust,no_run
 //! #[test]
@@ -47,27 +51,203 @@
 //! }
 //! ```
 //!
+//! Cases can also be defined in Rust, like when generating them from a table, with [`Case`]:
+//! ```rust,no_run
+//! #[test]
+//! fn cli_tests() {
+//!     let t = trycmd::TestCases::new();
+//!     t.default_bin_name("my-cmd");
+//!     for (flag, expected) in [("-V", "my-cmd 1.0.0\n"), ("--version", "my-cmd 1.0.0\n")] {
+//!         t.add(
+//!             trycmd::Case::new()
+//!                 .name(format!("version{flag}"))
+//!                 .args([flag])
+//!                 .expect_stdout(expected),
+//!         );
+//!     }
+//! }
+//! ```
+//!
+//! For one-off checks that don't warrant their own file, [`trycmd!`] runs a case written in the
+//! `.trycmd` format from within a Rust test:
+//! ```rust,no_run
+//! #[test]
+//! fn version() {
+//!     trycmd::trycmd!(
+//!         r#"
+//!         $ my-cmd --version
+//!         my-cmd [..]
+//!         "#
+//!     );
+//! }
+//! ```
+//!
+//! To make your own pass/fail decisions, like from another test framework, [`Case::run`] runs a
+//! single case, including one loaded with [`Case::from_path`], and returns a [`CaseResult`]
+//! instead of panicking.  [`TestCases::collect`] does the same for all of its cases, to aggregate
+//! or upload the results.
+//!
+//! For a command you run yourself, like with [`std::process::Command`] in an ordinary `#[test]`,
+//! [`assert`] checks its output the same way, wildcards and `TRYCMD=overwrite` included:
+//! ```rust,no_run
+//! let output = std::process::Command::new("my-bin").output().unwrap();
+//! trycmd::assert(output)
+//!     .success()
+//!     .stdout_matches_path("tests/snapshots/my-bin.stdout");
+//! ```
+//! [`assert_matches_path`] does the same for other artifacts, like generated configs or rendered
+//! templates, and [`assert_subset_eq`] and [`assert_eq_dirs`] for whole directories, like a
+//! build script's output.
+//!
+//! To measure the coverage of your binaries under `cargo llvm-cov`, call
+//! [`TestCases::coverage`] so the commands get its variables, even in a hermetic environment.
+//!
+//! For CLIs that take a seed for their randomness, [`TestCases::seed_env`] passes each command
+//! one derived from its case's name, so snapshots are reproducible.  Failures report the seed, and
+//! [`TestCases::seed`] or `TRYCMD_SEED` change what the seeds are derived from.
+//!
+//! To check how a CLI shuts down, give a case `timeout-grace = "2s"` along with its `timeout`, and
+//! it is sent `SIGTERM` (`CTRL_BREAK` on Windows) and given that long to exit before being killed.
+//!
+//! For a server or other command that doesn't exit on its own, `[[expect-line]]` waits for lines
+//! like `pattern = "Listening on"` `within = "2s"` of it starting, then stops it like on `timeout`.
+//!
+//! To run a command alongside the ones after it, like a server for a client to talk to, start it
+//! with `$ bg: ` in a `.trycmd` file, like `$ bg: my-server --port 8080`.  It is stopped once the
+//! case's other steps are done, with its output checked then.  A trailing `&` is passed to the
+//! command as an argument, like any other word, so cases written with one need `bg:` instead.
+//!
+//! To test an HTTP client hermetically, give the case `[[http-stub]]` responses, like
+//! `path = "/v1/items"` with `body-file = "items.json"`, and a local server answers them while it
+//! runs.  Its URL is in `TRYCMD_HTTP_STUB` and the `[HTTP_STUB]` placeholder, like
+//! `env.add.API_URL = "[HTTP_STUB]/v1"`.
+//!
+//! For a CLI that acts differently when `stdin` is an open but empty pipe, `stdin.close` sets when
+//! it is closed: `"immediately"`, `"after-write"` (the default), or `"never"`.
+//!
+//! For a CLI that prints the current date, `fake-time = "2021-01-01T00:00:00Z"` passes it
+//! `SOURCE_DATE_EPOCH` and `TZ=UTC`, and on Linux and macOS preloads libfaketime if it is installed
+//! (or at `TRYCMD_FAKETIME_LIB`), so its snapshots stay stable.
+//!
+//! To check how it handles Ctrl-C, `[[signals]]` sends it signals while it runs, like
+//! `after = "500ms"` and `signal = "INT"`, on Unix.
+//!
+//! To split a suite, like keeping slow cases out of every push, give cases `tags = ["slow"]` and
+//! select them with [`TestCases::tags`] and [`TestCases::exclude_tags`], or with `TRYCMD_TAGS`,
+//! like `TRYCMD_TAGS=slow` or `TRYCMD_TAGS=!slow,!network`.
+//!
+//! So a failing case tells the reader what it guards and where that was discussed, give it
+//! `description = "..."` and `links = ["https://github.com/org/repo/issues/42"]`.  Both are shown
+//! with its failures and included in `TRYCMD_FORMAT=json`'s `finished` events.
+//!
 //! ## Workflow
 //!
 //! To generate snapshots, run
 //! ```console
 //! $ TRYCMD=dump cargo test --test cli_tests
 //! ```
-//! This will write all of the `.stdout` and `.stderr` files in a `dump/` directory.
+//! This will write all of the `.stdout` and `.stderr` files in a `dump/` directory.  Next to
+//! them, `.chunks` files list each read of the output, like `stdout 0..12 +1.2ms`, to debug
+//! how `stdout` and `stderr` interleaved.
 //!
 //! You can then copy over to `tests/cmd` the cases you want to test
 //!
+//! To start a new case from a command, rather than writing it by hand, call
+//! [`TestCases::record`], like `record("tests/cmd/help.toml", "$ my-bin --help")`.  It writes the
+//! `.toml` along with the `.stdout` and `.stderr` it produced.
+//!
 //! To update snapshots, run
 //! ```console
 //! $ TRYCMD=overwrite cargo test --test cli_tests
 //! ```
 //! This will overwrite any existing `.stdout` and `.stderr` file in `tests/cmd`
 //!
+//! Snapshots next to the cases that no case file is named after, like `help.stdout` left behind
+//! by renaming `help.toml`, are reported at the end of an unfiltered run, unless a case reads
+//! them, like a shared `fs.base`.  With [`TestCases::remove_unused_snapshots`],
+//! `TRYCMD=overwrite` removes unused files too; unused directories are only reported.
+//!
 //! To filter the tests to those with `name1`, `name2`, etc in their file names, you can run:
 //! ```console
 //! cargo test --test cli_tests -- cli_tests trycmd=name1 trycmd=name2...
 //! ```
 //!
+//! Or, to run only the cases whose file names match a glob (or whose paths match, if it contains a
+//! `/`), run:
+//! ```console
+//! $ TRYCMD='help*' cargo test --test cli_tests
+//! ```
+//! See also [`TestCases::filter`].
+//!
+//! To check which cases are found and how their commands would run, without running them, run:
+//! ```console
+//! $ TRYCMD=list cargo test --test cli_tests -- --nocapture
+//! ```
+//! This prints each case with its commands' resolved bins and arguments, their `cwd`, and whether
+//! they would run in a sandbox.  See also [`TestCases::list`].
+//!
+//! To stop early on a catastrophic regression, `TRYCMD_MAX_FAILURES=1` (or
+//! [`TestCases::max_failures`]) skips the cases that haven't started once that many have failed,
+//! letting those already running finish.
+//!
+//! To finish within a CI job's time limit, with the results so far and the summary rather than
+//! being killed without either, `TRYCMD_DEADLINE=10m` (or [`TestCases::deadline`]) stops starting
+//! cases once the run has taken that long.  The rest are reported as not run, which fails the
+//! run.
+//!
+//! Each failing case also leaves a bundle in `target/trycmd/<case>/` for CI to upload: its
+//! `actual.stdout` and `expected.stdout` (likewise for `stderr`), a `meta.json` with each step's
+//! arguments, environment, exit status, and duration, and a `diff.patch` that accepts the new
+//! output like `TRYCMD=overwrite` would, with `git apply` from the package's directory.  Bundles
+//! are removed once their case passes.  Set `TRYCMD_ARTIFACTS` (or call
+//! [`TestCases::artifacts_dir`]) to put them elsewhere.
+//!
+//! To accept output changes seen in CI without reproducing its environment, download its bundles
+//! into `target/trycmd/` (or `TRYCMD_ARTIFACTS`) and run
+//! ```console
+//! $ TRYCMD=accept cargo test --test cli_tests
+//! ```
+//! This applies each case's `diff.patch`, removing the bundles once applied, rather than running
+//! the cases.  A patch that no longer applies, like to a snapshot that changed since, is an error
+//! that leaves its files alone.
+//!
+//! Failures are reported as a diff with 5 lines of context around each change, and a count of
+//! the identical lines left out between them; set `SNAPBOX_DIFF_CONTEXT` to show more or less, or
+//! to `all` for the full output.  Colors are used when writing to a terminal and can be disabled
+//! with `NO_COLOR=1`.  When the output matches another case's `.stdout` or `.stderr` in the same
+//! directory better than its own, that is pointed out, as after snapshots were swapped or renamed.
+//!
+//! Each case is reported with how long it took as it finishes, followed by a summary of how many
+//! passed, failed, or were ignored.  `TRYCMD_VERBOSE=1` also lists the slowest cases.
+//!
+//! A failing command is reported with a shell command line to run it by hand, with the resolved
+//! bin, `cwd`, changes to the environment, and `stdin`.  `TRYCMD_VERBOSE=1` shows it for passing
+//! commands too, and `TRYCMD_VERBOSE=2` also shows their output.  Wrappers can set this with
+//! [`TestCases::verbose`], or call [`TestCases::quiet`] to only report failures and the summary.
+//!
+//! To report results in the [Test Anything Protocol](https://testanything.org/) on `stdout`, for
+//! consumption by `prove` and other TAP harnesses, with one test point per case and the status of
+//! each of a failed case's steps in its YAML diagnostics, run:
+//! ```console
+//! $ TRYCMD_FORMAT=tap cargo test --test cli_tests
+//! ```
+//!
+//! For other tooling, `TRYCMD_FORMAT=json` writes newline-delimited JSON events to `stdout`:
+//! - `started`: a case is about to run
+//! - `sandbox`: the temporary directory a case ran in and whether it was `kept`
+//! - `diff`: expected and actual content for a mismatched stream or file, and the `similar`
+//!   snapshot of another case, if any
+//! - `finished`: the status of a case or step
+//! - `summary`: the total number of steps run and how many failed
+//!
+//! To run individual cases with `cargo test <case>` or from an IDE's test explorer, make the test
+//! target `harness = false` and call [`TestCases::run_harness`] from its `main`.  Each case is
+//! then reported as its own test and libtest's `--list`, `--exact`, and `--skip` are supported.
+//! It exits with `101` when anything failed, like libtest; [`TestCases::exit_codes`] gives cases
+//! that couldn't be loaded or started, like for a missing bin, and runs where every case was
+//! skipped codes of their own, so wrapper scripts can tell those apart from output that didn't
+//! match.
+//!
 //! To debug what `trycmd` is doing, run `cargo test -F trycmd/debug`.
 //!
 //! ## File Formats
@@ -92,20 +272,35 @@
 //! - Running multiple commands within the same temp dir (if a `*.out/` directory is present)
 //!
 //! The syntax is:
-//! - Test cases live inside of ` ``` ` fenced code blocks
+//! - Test cases live inside of ` ``` ` (or `~~~`) fenced code blocks
 //!   - Everything out of them is ignored
+//!   - Like Markdown, fences may be indented up to 3 spaces, like within a list, with the
+//!     indentation stripped from the block and kept when overwriting it
 //!   - Blocks with info strings with an unsupported language (not `trycmd`, `console`) or the
 //!     `ignore` attribute are ignored
 //! - "`$ `" line prefix starts a new command
 //! - "`> `" line prefix appends to the prior command
+//! - "`$ pipe: `" splits the command on each unquoted "` | `", feeding the `stdout` of one command
+//!   into the `stdin` of the next, without a shell.  Without it, `|` is passed as an argument, so
+//!   pipelines written without `pipe:` need it added.
+//! - "`$ sh: `" runs the command through the platform shell (`sh -c` or `cmd /C`) for redirection,
+//!   globs, and `&&` chains.  Everything after the bin is passed to the shell as written.  This
+//!   is not portable across platforms.
+//! - "`$ bg: `" keeps the command running alongside the commands after it, until they are done
 //! - "`? <status>`" line indicates the exit code (like `echo "? $?"`) and `<status>` can be
 //!   - An exit code
 //!   - `success` *(default)*, `failed`, `interrupted`, `skipped`
+//!   - `core-dumped`, on Unix
 //!  - All following lines are treated as stdout + stderr
+//! - The `capture-env` attribute in the info string (like `console,capture-env`) carries what the
+//!   block's commands print as `export NAME=value`, `NAME=value`, or `unset NAME` lines over to
+//!   the commands after them, like `eval $(my-cmd env)`.  For a dotenv file the command writes,
+//!   use `capture-env=<path>`, relative to the `cwd`.
 //!
 //! The command is then split with [shlex](https://crates.io/crates/shlex), allowing quoted content
 //! to allow spaces.  The first argument is the program to run which maps to `bin.name` in the
-//! `.toml` file.
+//! `.toml` file.  Each command in a pipeline is split separately and any leading `NAME=value`
+//! environment variables apply to the whole pipeline.
 //!
 //! Example:
 //!
@@ -135,22 +330,200 @@
 //! - 1-to-1 with dumped results
 //! - `TRYCMD=overwrite` support
 //!
-//! [See full schema](https://github.com/assert-rs/trycmd/blob/main/schema.json):
+//! [See full schema](https://github.com/assert-rs/trycmd/blob/main/schema.json), also available
+//! from `schema::OneShot::json_schema()` or `cargo run --features schema --bin trycmd-schema
+//! schema.json` for editor completion and validation.
+//!
+//! `schema-version = 1` declares the layout a case is written for, like in `_default.toml` for a
+//! whole directory, and is `1` when unset.  When a later version renames keys, cases declaring an
+//! older one are still read, with a warning for each renamed key they use, rather than being
+//! misread; a version newer than this `trycmd` reads is an error.
+//!
 //! Basic parameters:
-//! - `bin.name`: The name of the binary target from `Cargo.toml` to be used to find the file path
+//! - `bin.name`: The name of the binary target from `Cargo.toml` to be used to find the file path,
+//!   or of a function registered with [`TestCases::register_fn`] to run in-process instead
+//! - `bin.script`: A command line to run instead, like `python3 tools/stub.py`, for targets
+//!   written in scripting languages
 //! - `args`: the arguments (including flags and option) passed to the binary
+//! - `status`: how the binary is expected to exit, like `"failed"` or `status.code = 2`.  On Unix,
+//!   `status.signal = 9` and `"core-dumped"` check how it was killed.  Failures report the exit
+//!   code or signal and whether core was dumped.
+//!
+//! To feed the output into other commands, like `my-cmd | my-filter --sort`, list them in `pipe`.
+//! They all run at once, like in a shell, and `timeout` covers them together.  `stdout`,
+//! `stderr`, and `status` are checked against the last command, with how the others exit only
+//! checked with `pipefail = true`, where one killed by `SIGPIPE` for writing after the next one
+//! stopped reading, like `yes` in `yes | head -1`, still passes:
+//! ```toml
+//! bin.name = "my-cmd"
+//! pipe = [{ bin.name = "my-filter", args = ["--sort"] }]
+//! ```
+//!
+//! Set `shell = true` to run `bin` through the platform shell with `args` (preferably a string)
+//! as written, like `args = "--verbose > log.txt && cat log.txt"`.  This is not portable across
+//! platforms, so pair it with `skip-on` as needed.
+//!
+//! For commands that are flaky, like those using the network, `retries = { count = 3, backoff =
+//! "100ms" }` runs a failing command again, waiting `backoff` before the first retry and twice as
+//! long before each one after.  If every attempt fails, all of them are reported.
+//!
+//! To keep a runaway command from exhausting memory, `max-output = "10MB"` stops capturing
+//! `stdout` or `stderr` past that size (`KB`, `MB`, `GB` or `KiB`, `MiB`, `GiB`), closing the
+//! stream and failing the case with "output limit exceeded".
+//!
+//! For a coarse performance budget, `max-duration = "2s"` fails the case when the command takes
+//! longer, even though it completed.  Unlike `timeout`, the command is not killed.
+//!
+//! On Unix, `[limits]` constrain the command's resources to check that it behaves sanely under
+//! pressure: `memory = "256MB"` (address space), `nofile = 64`, `nproc`, `cpu = "10s"`, `fsize`,
+//! and `core`.  These are set with `setrlimit` just before the command runs.
+//!
+//! To run the commands under another program, like `valgrind`, `strace`, or a QEMU user-mode
+//! emulator for cross targets, set `wrapper = ["valgrind", "--error-exitcode=99"]`, or
+//! [`TestCases::wrapper`] for the whole suite.  With `shell = true`, it goes in front of `bin` in
+//! the command line.
+//!
+//! When cross-compiling, bins built for the target, whether found by name or given by path, run
+//! through cargo's `CARGO_TARGET_<TRIPLE>_RUNNER`, if set, so cases don't need a `wrapper`.  [`TestCases::runner`] overrides it.
+//!
+//! So snapshots don't differ between developer machines and CI, `env.preset = "hermetic"` removes
+//! `TERM`, `COLUMNS`, locale variables, and `CARGO_*` variables other than `CARGO_HOME`,
+//! `CARGO_MANIFEST_DIR`, and `CARGO_TARGET_DIR`, and sets `TZ=UTC` and `LC_ALL=C`.  `env.add`
+//! and `env.remove` still apply on top of it.
+//!
+//! To keep timing-sensitive cases steady on busy machines, `priority = "low"`, `"normal"`, or
+//! `"high"` sets the command's niceness on Unix and `cpus = [0]` pins it to those CPUs on Linux.
+//!
+//! To check that a command works offline, `net = "none"` runs it in its own network namespace
+//! on Linux, with only a loopback interface.  Without the privileges for that, a user namespace
+//! is used, and where neither is permitted the case fails, or is skipped with
+//! `net = "none-or-skip"`.
+//!
+//! To test prompts, like confirmations or wizards, run the command on a terminal (Unix only) and
+//! answer each `expect` with its `send`, in order.  Everything printed to the terminal, including
+//! what it echoes of what was sent, is checked as `stdout`:
+//! ```toml
+//! bin.name = "my-cmd"
+//! args = ["login"]
+//!
+//! [[interaction]]
+//! expect = "Password:"
+//! send = "hunter2\n"
+//! ```
+//! Each `expect` is waited on for the case's `timeout`, or 10 seconds.
+//!
+//! To keep colors out of, or in, the output, `color = "never"`, `"always"`, or `"auto"` sets
+//! `NO_COLOR`, `CLICOLOR_FORCE`, `CLICOLOR`, and `TERM` to match, before `env`.  With `"auto"`,
+//! commands color only with `[[interaction]]`, where they run on a terminal.  See also
+//! [`TestCases::color`].
+//!
+//! For CLIs that wrap text to the terminal, `term = { width = 100, height = 40 }` sets `COLUMNS`
+//! and `LINES` and, with `[[interaction]]`, the terminal's size, so the output is the same
+//! everywhere.
+//!
+//! To run the same command with several variations, add `[[matrix]]` entries.  Each entry's
+//! `args` are appended to the top-level `args` and its `env` is layered on top of the top-level
+//! `env`.  Every entry runs as its own case, named `<case>:<name>`, with snapshots in
+//! `<case>-<name>.stdout` and `<case>-<name>.stderr`:
+//! ```toml
+//! bin.name = "my-cmd"
+//!
+//! [[matrix]]
+//! name = "short"
+//! args = ["-h"]
+//!
+//! [[matrix]]
+//! name = "long"
+//! args = ["--help"]
+//! ```
+//!
+//! Similarly, `locales = ["C", "de_DE.UTF-8"]` runs the command once per locale, with `LC_ALL`
+//! and `LANG` set to it, as `<case>:<locale>` with snapshots in `<case>-<locale>.stdout`.
+//!
+//! Settings shared by the cases in a directory, like `bin.name`, `env`, `timeout`, or `fs`, can go
+//! in a `_default.toml` there, which is not run as a case.  Editor backups, like `help.toml~` and
+//! `.#help.toml`, are skipped, and two cases that share a name, like `help.toml` and
+//! `help.trycmd`, are an error since they'd share snapshots.  A case can also build on another file
+//! with `include = "common.toml"`, relative to the case.  Tables are merged, with the case's own
+//! settings taking precedence, while other values, including arrays like `args`, are replaced.
+//! Paths like `fs.base` stay relative to the case.  Extra placeholders for a case or directory
+//! go in `[substitutions]`:
+//! ```toml
+//! [substitutions]
+//! "[HOST]" = "example.com"
+//! ```
+//!
+//! To temporarily leave out cases:
+//! - `skip = true` skips the case, or `skip = "reason"` to report why it was skipped
+//! - `only = true` focuses on the case: when any case sets it, the other cases are skipped
+//! - `expected-failure = "reason"` marks a known bug: the case runs and is reported as `xfail`
+//!   when it fails, while passing is reported as `xpass` and fails the run
+//!
+//! For platform differences:
+//! - `skip-on = ["windows"]` skips the case on an operating system (like `macos`) or family (like
+//!   `unix`)
+//! - `[target.'cfg(windows)']` overrides `status`, `stdout`, or `stderr` on platforms matching a
+//!   `cfg` expression using `unix`, `windows`, `target_os`, `target_family`, `target_arch`,
+//!   `all`, `any`, and `not`
+//! - Snapshot files may be suffixed with an operating system or family, like
+//!   `help.stdout.windows`, to be used instead of `help.stdout` on that platform
 //!
 //! #### `*.stdin`
 //!
 //! Data to pass to `stdin`.
 //! - If not present, nothing will be written to `stdin`
 //! - If `binary = false` in `*.toml` (the default), newlines and path separators will be normalized.
+//! - If `binary = false` in `*.toml` (the default), `[EXE]`, `[ROOT]`, `[CWD]`, `[BIN]`, and
+//!   `[PORT]` will be replaced with their values (see below)
+//!
+//! Instead of committing large fixtures, generate deterministic content in `*.toml` with
+//! `stdin.generate = { bytes = "1MiB", pattern = "random", seed = 42 }`, where `pattern` is
+//! `random` (the default), `zeros`, or `lines` (`1\n2\n3\n...`).
 //!
 //! #### `*.stdout` and `*.stderr`
 //!
 //! Expected results for `stdout` or `stderr`.
 //! - If not present, we'll not verify the output
+//! - Short expectations can be kept in `*.toml` instead, like `stdout = """..."""`.  Setting
+//!   them both inline and in a file is an error.
+//! - Instead of content, `stdout` and `stderr` in `*.toml` can be set to `"empty"`, `"non-empty"`,
+//!   or `"any"` to only check whether something was printed
+//! - `expected-merged` in `*.toml`, or a `*.merged` file, checks `stdout` and `stderr` together,
+//!   in the order they were read, while `stdout` and `stderr` still check each on its own.  The
+//!   streams are read separately, so the order is best-effort; for exact interleaving, set
+//!   `stderr-to-stdout = true` instead.
+//! - With `stderr-to-stdout = true`, also setting `tolerate-interleave = true` accepts `stdout`
+//!   as long as the lines of each stream are in order, however they are interleaved
+//! - If `stdout-compare = "unordered-lines"` (or `stderr-compare`) in `*.toml`, lines may come in
+//!   any order, like from parallel workers, with missing and extra lines reported.  A `...` line
+//!   allows extra lines.
+//! - `stdout-must-not-contain = ["panicked at"]` (or `stderr-must-not-contain`) in `*.toml` fails
+//!   the case when any of those are printed, even if the rest of the output isn't checked.  They
+//!   are literal substrings, without wildcards or regular expressions.
 //! - If `binary = false` in `*.toml` (the default), newlines and path separators will be normalized before comparing
+//!   - Set `normalize-line-endings = false` to compare CRLF line endings as-is, or `true` to
+//!     convert them even with `binary = true`
+//! - If `normalize-paths = true` in `*.toml`, Windows paths in the output are written like Unix
+//!   ones, with `/` separators and without drive letters, like `C:`, so the same snapshot matches
+//!   on both
+//! - If `trim-trailing-whitespace = true` in `*.toml`, spaces and tabs at the end of lines are
+//!   ignored
+//! - If `require-final-newline = false` in `*.toml`, a missing newline at the end is ignored
+//! - If `strip-ansi = true` in `*.toml`, ANSI escape sequences, like colors, are removed from the
+//!   output.  Otherwise they are compared as-is.
+//! - If `binary = true` in `*.toml`, mismatches are reported as a hex dump of the differing rows
+//! - Text output is passed through each [`Normalizer`] registered with
+//!   [`TestCases::normalizer`], for machine-specific noise, like GPU names or core counts, that
+//!   placeholders can't express
+//! - If `expected-output-format = "json"` in `*.toml`, `stdout` is parsed as JSON and compared
+//!   structurally, ignoring key order and whitespace, with each mismatch reported by its JSON
+//!   Pointer
+//! - A `*.stdout.regex` or `*.stderr.regex` file, or `expected-output-format = "regex"` in
+//!   `*.toml` for `stdout`, is a regular expression that must match the whole output, with `^`
+//!   and `$` also matching at line boundaries.  These are never overwritten.
+//! - A `*.stdout.<ext>` or `*.stderr.<ext>` file, for an extension registered with
+//!   [`TestCases::validator`], is checked by that [`OutputValidator`], like for CSV or protobuf
+//!   text format.  These are never overwritten either.
 //!
 //! **Eliding Content**
 //!
@@ -165,6 +538,12 @@
 //! - `[EXE]` as part of the line: On Windows, matches `.exe`, ignored otherwise
 //! - `[ROOT]` as part of the line: The root directory for where the test is running
 //! - `[CWD]` as part of the line: The current working directory within the root
+//! - `[BIN]` as part of the line: The path to the binary under test
+//! - `[PORT]` and `[PORT:<label>]` as part of the line: A free TCP port reserved for the case, one
+//!   per label.  These are also replaced in `args`, `env.add`, and `stdin`, so a server and its
+//!   client can agree on a port, like `args = ["--listen", "127.0.0.1:[PORT:api]"]`
+//! - `[HTTP_STUB]` as part of the line: The URL of the case's `[[http-stub]]` server, also
+//!   replaced in `args`, `env.add`, and `stdin`
 //! - `[YOUR_NAME_HERE]` as part of the line: See [`TestCases::insert_var`]
 //!
 //! We will preserve these with `TRYCMD=dump` and will make a best-effort at preserving them with
@@ -176,9 +555,43 @@
 //!
 //! `.keep` files will be ignored but their parent directories will be created.
 //!
+//! To leave large or volatile files out of the sandbox and out of `*.out/` comparisons, list
+//! `.gitignore`-style glob patterns in `fs.ignore`, like `fs.ignore = ["*.log", "/target/"]`, or in
+//! a `.trycmdignore` file in `*.in/`.  Patterns without a `/` match at any depth.
+//!
 //! Tests are assumed to not modify files in `*.in/` unless an `*.out/` is provided or
 //! `fs.sandbox = true` is set in the `.toml` file.
 //!
+//! A `fs.cwd` that is missing from `fs.base` is created when sandboxed and is otherwise an error.
+//!
+//! When many cases share a large `fs.base`, `fs.base-readonly = true` hard-links its files into
+//! each sandbox rather than copying them, falling back to copying where linking isn't possible,
+//! like across filesystems.  The commands must then not write to those files in place, as that
+//! would change the fixture, though they may create, rename, and remove files.  The case fails
+//! when a file in `fs.base` changed during the run.  `fs.files` entries replace a linked file
+//! rather than write through it.
+//!
+//! Sandboxes are created in the OS's temporary directory.  To put them elsewhere, like when it is
+//! small, or to keep all of a run's sandboxes in one place, call [`TestCases::sandbox_root`] or set
+//! `TRYCMD_TMPDIR`.  Putting them on the same filesystem as the fixtures also lets
+//! `fs.base-readonly` link files rather than copy them.
+//!
+//! When cases build on each other, like one initializing a database that others query,
+//! `depends-on = ["setup-db"]` runs `setup-db.toml` from the same directory (or the case reported
+//! as `setup-db`) first.  The dependent starts from a copy of the sandbox its last dependency left
+//! behind, in place of `fs.base`, and is skipped if any dependency failed or was skipped.
+//! Filtering the cases by name or tag keeps what the remaining cases depend on.
+//!
+//! To hear about quick smoke tests first on a big suite, give them `order = -1`: cases with a
+//! lower `order` run and are reported before those with a higher one, which default to `0`.
+//!
+//! For a few small inputs, skip `*.in/` and declare them in the `.toml` file, relative to the
+//! sandbox, which requires `fs.sandbox = true`:
+//! ```toml
+//! [fs.files."config.json"]
+//! content = '{"a":1}'
+//! ```
+//!
 //! ### `*.out/`
 //!
 //! When present, each file in this directory will be compared to generated or modified files.
@@ -187,8 +600,35 @@
 //!
 //! `.keep` files will be ignored.
 //!
+//! To change how a file is compared, set `compare` for its `/`-separated path relative to
+//! `*.out/` in the `.toml` file:
+//! - `auto` *(default)*: detect binary files, matching text with wildcards and redactions
+//! - `text`: always match as text
+//! - `binary`: match byte-for-byte
+//! - `exists`: only check that the file exists, for nondeterministic content
+//! ```toml
+//! [fs.files."generated.lock"]
+//! compare = "exists"
+//! ```
+//!
 //! Note: This implies `fs.sandbox = true`.
 //!
+//! To check only a file or two the commands leave in the sandbox, instead of a whole `*.out/`,
+//! add `[[fs.expect]]` entries with the file's `content` (with wildcards and redactions, like
+//! `.stdout`), text it `contains`, or a regular expression it `matches`:
+//! ```toml
+//! [[fs.expect]]
+//! path = "out/report.txt"
+//! contains = "total: 3"
+//! ```
+//!
+//! Without exact `*.out/` fixtures, a sandboxed case can still catch a command writing far more
+//! than it should: `fs.max-new-files = 10` fails when the commands leave more new files behind and
+//! `fs.max-size = "1MB"` fails when the sandbox ends up larger.
+//!
+//! When a sandboxed case fails, its temporary directory is kept for inspection and its path is
+//! reported.  Set `TRYCMD_CLEAN_SANDBOX=1` to always remove it.
+//!
 //! ## Examples
 //!
 //! - Simple cargo binary: [trycmd's integration tests](https://github.com/assert-rs/trycmd/blob/main/tests/cli_tests.rs)
@@ -232,16 +672,53 @@
 pub mod cargo;
 pub mod schema;
 
+mod macros;
+
+mod artifacts;
+mod builder;
 mod cases;
+mod compare;
+#[cfg(feature = "filesystem")]
+mod dir;
+mod error;
+mod fake_time;
+mod hooks;
+mod http_stub;
+mod ignore;
+mod in_process;
+mod limits;
+mod migrate;
+mod net;
+mod normalizer;
+mod output;
+mod pipe;
+mod platform;
+mod port;
+mod pty;
 mod registry;
+mod report;
+mod result;
 mod runner;
+mod sched;
 mod spec;
+mod suggest;
+mod validator;
+mod watch;
 
-pub use cases::TestCases;
-pub use snapbox::assert::Error;
+pub use builder::Case;
+pub use cases::{ExitCodes, TestCases};
+#[cfg(feature = "filesystem")]
+pub use dir::{assert_eq_dirs, assert_subset_eq, DirAssert};
+pub use error::Error;
+pub use hooks::CaseContext;
+pub use in_process::InProcessBin;
+pub use normalizer::Normalizer;
+pub use output::{assert, assert_matches_path, Actual, OutputAssert};
+pub use result::{CaseResult, StepResult};
+pub use validator::{Difference, OutputValidator};
 
 pub(crate) use registry::BinRegistry;
-pub(crate) use runner::{Case, Mode, Runner};
+pub(crate) use runner::{Mode, Runner};
 pub(crate) use spec::RunnerSpec;
 
 pub(crate) use snapbox::Data;
@@ -0,0 +1,128 @@
+//! Resource limits for spawned commands, like `[limits] memory = "256MB"`
+
+/// Limits to set with `setrlimit` in the child, just before it runs the command
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub(crate) struct Rlimits {
+    limits: Vec<(Resource, u64)>,
+}
+
+impl Rlimits {
+    pub(crate) fn new(limits: &crate::schema::Limits) -> Result<Self, crate::Error> {
+        let crate::schema::Limits {
+            memory,
+            nofile,
+            nproc,
+            cpu,
+            fsize,
+            core,
+        } = limits;
+        let mut resolved = Vec::new();
+        if let Some(memory) = memory {
+            resolved.push((Resource::Memory, memory.to_bytes()? as u64));
+        }
+        if let Some(nofile) = nofile {
+            resolved.push((Resource::Nofile, *nofile));
+        }
+        if let Some(nproc) = nproc {
+            resolved.push((Resource::Nproc, *nproc));
+        }
+        if let Some(cpu) = cpu {
+            // Rounded up, so a sub-second limit doesn't mean no limit
+            let secs = cpu.as_secs() + u64::from(cpu.subsec_nanos() != 0);
+            resolved.push((Resource::Cpu, secs));
+        }
+        if let Some(fsize) = fsize {
+            resolved.push((Resource::Fsize, fsize.to_bytes()? as u64));
+        }
+        if let Some(core) = core {
+            resolved.push((Resource::Core, core.to_bytes()? as u64));
+        }
+        if !cfg!(unix) && !resolved.is_empty() {
+            return Err("`[limits]` is only supported on Unix".into());
+        }
+        Ok(Self { limits: resolved })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Set the limits in `cmd`'s process before it runs
+    #[cfg(unix)]
+    pub(crate) fn apply(&self, cmd: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt as _;
+
+        if self.limits.is_empty() {
+            return;
+        }
+        let limits = self.limits.clone();
+        // SAFETY: between `fork` and `exec`, this only calls `setrlimit`, which is
+        // async-signal-safe, and doesn't allocate
+        unsafe {
+            cmd.pre_exec(move || {
+                for (resource, value) in &limits {
+                    let raw = match resource {
+                        Resource::Memory => libc::RLIMIT_AS,
+                        Resource::Nofile => libc::RLIMIT_NOFILE,
+                        Resource::Nproc => libc::RLIMIT_NPROC,
+                        Resource::Cpu => libc::RLIMIT_CPU,
+                        Resource::Fsize => libc::RLIMIT_FSIZE,
+                        Resource::Core => libc::RLIMIT_CORE,
+                    };
+                    let limit = libc::rlimit {
+                        rlim_cur: *value as libc::rlim_t,
+                        rlim_max: *value as libc::rlim_t,
+                    };
+                    if libc::setrlimit(raw, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn apply(&self, _cmd: &mut std::process::Command) {}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Resource {
+    /// `RLIMIT_AS`
+    Memory,
+    /// `RLIMIT_NOFILE`
+    Nofile,
+    /// `RLIMIT_NPROC`
+    Nproc,
+    /// `RLIMIT_CPU`
+    Cpu,
+    /// `RLIMIT_FSIZE`
+    Fsize,
+    /// `RLIMIT_CORE`
+    Core,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        let limits = crate::schema::Limits {
+            memory: Some(crate::schema::ByteSize::Text("256MB".into())),
+            nofile: Some(64),
+            cpu: Some(std::time::Duration::from_millis(1500)),
+            ..Default::default()
+        };
+        let actual = Rlimits::new(&limits).unwrap();
+        assert_eq!(
+            actual.limits,
+            [
+                (Resource::Memory, 256_000_000),
+                (Resource::Nofile, 64),
+                (Resource::Cpu, 2),
+            ]
+        );
+        assert!(Rlimits::new(&Default::default()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,22 @@
+/// Run a case written in the `.trycmd` format, panicking if it fails
+///
+/// For one-off checks that don't warrant their own file.  The fence is optional and common
+/// indentation is stripped.  Bins are looked up like `bin.name`.
+///
+/// ```rust,no_run
+/// trycmd::trycmd!(
+///     r#"
+///     $ my-cmd --version
+///     my-cmd [..]
+///     "#
+/// );
+/// ```
+#[macro_export]
+macro_rules! trycmd {
+    ($source: expr $(,)?) => {{
+        $crate::Case::from_trycmd($source)
+            .name(::std::concat!(::std::file!(), ":", ::std::line!()))
+            .run()
+            .assert_ok();
+    }};
+}
@@ -0,0 +1,133 @@
+//! Reading `*.toml` cases written for older `schema-version`s, with a warning for what changed
+
+/// The `schema-version` of the current layout of `*.toml` cases
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+/// A key a `schema-version` renamed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Rename {
+    /// The version that renamed it
+    pub(crate) version: u32,
+    /// The dotted key, as of the version before
+    pub(crate) old: &'static str,
+    pub(crate) new: &'static str,
+}
+
+/// Keys renamed since `schema-version = 1`, to accept from cases declaring an older version
+pub(crate) const RENAMES: &[Rename] = &[];
+
+/// `toml` with the keys `renames` renamed since its `schema-version`, and a warning for each
+///
+/// A case without a `schema-version` is taken to be `1`.  Invalid TOML is left for the parser to
+/// report.
+pub(crate) fn migrate<'s>(
+    toml: &'s str,
+    renames: &[Rename],
+) -> (std::borrow::Cow<'s, str>, Vec<String>) {
+    if renames.is_empty() {
+        return (toml.into(), Vec::new());
+    }
+    let Ok(mut doc) = toml.parse::<toml_edit::DocumentMut>() else {
+        return (toml.into(), Vec::new());
+    };
+    let version = doc
+        .get("schema-version")
+        .and_then(|v| v.as_integer())
+        .unwrap_or(1);
+
+    let mut warnings = Vec::new();
+    for rename in renames.iter().filter(|r| version < i64::from(r.version)) {
+        let Some(item) = take(doc.as_table_mut(), rename.old) else {
+            continue;
+        };
+        put(doc.as_table_mut(), rename.new, item);
+        warnings.push(format!(
+            "`{}` is `{}` since `schema-version = {}`; rename it and set `schema-version = {}`",
+            rename.old, rename.new, rename.version, rename.version
+        ));
+    }
+    if warnings.is_empty() {
+        (toml.into(), warnings)
+    } else {
+        (doc.to_string().into(), warnings)
+    }
+}
+
+/// Remove the dotted `key` from `table`
+fn take(table: &mut toml_edit::Table, key: &str) -> Option<toml_edit::Item> {
+    let (parents, leaf) = match key.rsplit_once('.') {
+        Some((parents, leaf)) => (Some(parents), leaf),
+        None => (None, key),
+    };
+    let mut parent: &mut dyn toml_edit::TableLike = table;
+    for segment in parents.into_iter().flat_map(|p| p.split('.')) {
+        parent = parent.get_mut(segment)?.as_table_like_mut()?;
+    }
+    parent.remove(leaf)
+}
+
+/// Insert `item` at the dotted `key` in `table`, creating the tables on the way, unless the case
+/// sets it already
+fn put(table: &mut toml_edit::Table, key: &str, item: toml_edit::Item) {
+    let (parents, leaf) = match key.rsplit_once('.') {
+        Some((parents, leaf)) => (Some(parents), leaf),
+        None => (None, key),
+    };
+    let mut parent: &mut dyn toml_edit::TableLike = table;
+    for segment in parents.into_iter().flat_map(|p| p.split('.')) {
+        let entry = parent.entry(segment).or_insert(toml_edit::table());
+        let Some(next) = entry.as_table_like_mut() else {
+            return;
+        };
+        parent = next;
+    }
+    if !parent.contains_key(leaf) {
+        parent.insert(leaf, item);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_RENAMES: &[Rename] = &[
+        Rename {
+            version: 2,
+            old: "fs.base",
+            new: "fs.in",
+        },
+        Rename {
+            version: 2,
+            old: "order",
+            new: "priority",
+        },
+    ];
+
+    #[test]
+    fn renames() {
+        let (toml, warnings) = migrate(
+            "order = 1\n[fs]\nbase = \"help.in\"\nsandbox = true\n",
+            TEST_RENAMES,
+        );
+        let doc = toml.parse::<toml_edit::DocumentMut>().unwrap();
+        assert_eq!(doc["fs"]["in"].as_str(), Some("help.in"));
+        assert!(doc["fs"].get("base").is_none());
+        assert_eq!(doc["priority"].as_integer(), Some(1));
+        assert_eq!(
+            warnings,
+            [
+                "`fs.base` is `fs.in` since `schema-version = 2`; rename it and set `schema-version = 2`",
+                "`order` is `priority` since `schema-version = 2`; rename it and set `schema-version = 2`",
+            ]
+        );
+
+        let inline = migrate("fs = { base = \"help.in\" }", TEST_RENAMES).0;
+        let doc = inline.parse::<toml_edit::DocumentMut>().unwrap();
+        assert_eq!(doc["fs"]["in"].as_str(), Some("help.in"));
+
+        let current = "schema-version = 2\norder = 1\n";
+        assert_eq!(migrate(current, TEST_RENAMES), (current.into(), Vec::new()));
+        let invalid = "order = ";
+        assert_eq!(migrate(invalid, TEST_RENAMES), (invalid.into(), Vec::new()));
+    }
+}
@@ -0,0 +1,160 @@
+//! Network isolation for spawned commands, like `net = "none"`
+
+/// Whether commands can be cut off from the network here
+///
+/// This is only checked once per process, when the first isolated command is about to be spawned.
+pub(crate) fn check() -> Result<(), crate::Error> {
+    static PROBED: std::sync::Mutex<Option<Result<(), crate::Error>>> = std::sync::Mutex::new(None);
+    let mut probed = PROBED.lock().unwrap_or_else(|e| e.into_inner());
+    probed.get_or_insert_with(probe).clone()
+}
+
+fn probe() -> Result<(), crate::Error> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::probe()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("`net = \"none\"` is only supported on Linux".into())
+    }
+}
+
+/// Run `cmd` in its own network namespace, with only a loopback interface
+#[cfg(target_os = "linux")]
+pub(crate) fn isolate(cmd: &mut std::process::Command) {
+    use std::os::unix::process::CommandExt as _;
+
+    let ids = linux::Ids::current();
+    // SAFETY: between `fork` and `exec`, `Ids::unshare` only makes async-signal-safe calls and
+    // doesn't allocate
+    unsafe {
+        cmd.pre_exec(move || ids.unshare());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn isolate(_cmd: &mut std::process::Command) {}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    /// The user and group to keep when isolating through a user namespace, formatted ahead of
+    /// time as no allocating is allowed after `fork`
+    pub(super) struct Ids {
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+    }
+
+    impl Ids {
+        pub(super) fn current() -> Self {
+            // SAFETY: these can't fail
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Self {
+                uid_map: format!("{uid} {uid} 1").into_bytes(),
+                gid_map: format!("{gid} {gid} 1").into_bytes(),
+            }
+        }
+
+        /// Move the current process into a new network namespace and bring up its loopback
+        ///
+        /// Without the privilege to do that directly, this goes through a user namespace.
+        pub(super) fn unshare(&self) -> std::io::Result<()> {
+            // SAFETY: `unshare` has no memory safety requirements
+            if unsafe { libc::unshare(libc::CLONE_NEWNET) } != 0 {
+                // SAFETY: as above
+                if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                write(b"/proc/self/setgroups\0", b"deny")?;
+                write(b"/proc/self/uid_map\0", &self.uid_map)?;
+                write(b"/proc/self/gid_map\0", &self.gid_map)?;
+            }
+            loopback_up()
+        }
+    }
+
+    /// Check for network isolation in a child process, as it can't be undone
+    pub(super) fn probe() -> Result<(), crate::Error> {
+        use std::os::unix::process::CommandExt as _;
+
+        // Never run, as the child exits once isolated
+        let program = std::env::current_exe().map_err(|e| {
+            crate::Error::new(format!("Failed to check for network isolation: {e}"))
+        })?;
+        let ids = Ids::current();
+        let mut cmd = std::process::Command::new(program);
+        cmd.stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        // SAFETY: as with `isolate`, and `_exit` is async-signal-safe
+        unsafe {
+            cmd.pre_exec(move || {
+                ids.unshare()?;
+                libc::_exit(0)
+            });
+        }
+        let status = cmd
+            .spawn()
+            .map_err(|e| crate::Error::new(format!("Network isolation isn't permitted: {e}")))?
+            .wait()
+            .map_err(|e| {
+                crate::Error::new(format!("Failed to check for network isolation: {e}"))
+            })?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(crate::Error::new(format!(
+                "Network isolation isn't permitted: the check {status}"
+            )))
+        }
+    }
+
+    /// Write all of `content` to the nul-terminated `path`
+    fn write(path: &[u8], content: &[u8]) -> std::io::Result<()> {
+        // SAFETY: `path` is nul-terminated
+        let fd = unsafe { libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: `content` is valid for reads of its length
+        let written = unsafe { libc::write(fd, content.as_ptr().cast(), content.len()) };
+        let result = if written == content.len() as isize {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        };
+        // SAFETY: `fd` was opened above
+        unsafe { libc::close(fd) };
+        result
+    }
+
+    /// A new network namespace's loopback interface starts out down
+    fn loopback_up() -> std::io::Result<()> {
+        // SAFETY: `socket` has no memory safety requirements
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: an all-zero `ifreq` is valid
+        let mut request: libc::ifreq = unsafe { std::mem::zeroed() };
+        for (dest, src) in request.ifr_name.iter_mut().zip(b"lo") {
+            *dest = *src as libc::c_char;
+        }
+        // SAFETY: `request` is an `ifreq`, as these requests expect
+        let result = unsafe {
+            if libc::ioctl(fd, libc::SIOCGIFFLAGS as _, &mut request) == -1 {
+                Err(std::io::Error::last_os_error())
+            } else {
+                request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+                if libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &request) == -1 {
+                    Err(std::io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        // SAFETY: `fd` was opened above
+        unsafe { libc::close(fd) };
+        result
+    }
+}
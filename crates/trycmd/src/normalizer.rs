@@ -0,0 +1,54 @@
+/// A rewrite of text output before it is compared, like to strip GPU names or core counts
+///
+/// Register it with [`TestCases::normalizer`][crate::TestCases::normalizer].  It runs on
+/// `stdout`, `stderr`, and merged output after the built-in normalizations and before
+/// redactions, so what it leaves is also what `TRYCMD=overwrite` records.
+///
+/// Closures taking and returning a [`String`] implement this.
+pub trait Normalizer: Send + Sync {
+    fn normalize(&self, text: String) -> String;
+}
+
+impl<F> Normalizer for F
+where
+    F: Fn(String) -> String + Send + Sync,
+{
+    fn normalize(&self, text: String) -> String {
+        self(text)
+    }
+}
+
+pub(crate) type Normalize = std::sync::Arc<dyn Normalizer>;
+
+/// Run `normalizers` on `data`, in the order they were registered, unless it is binary
+pub(crate) fn apply(data: crate::Data, normalizers: &[Normalize]) -> crate::Data {
+    if normalizers.is_empty() {
+        return data;
+    }
+    match data.render() {
+        Some(text) => crate::Data::text(
+            normalizers
+                .iter()
+                .fold(text, |text, normalizer| normalizer.normalize(text)),
+        ),
+        None => data,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn in_order() {
+        let normalizers: Vec<Normalize> = vec![
+            std::sync::Arc::new(|text: String| text.replace("NVIDIA RTX 4090", "[GPU]")),
+            std::sync::Arc::new(|text: String| text.replace("[GPU]", "[DEVICE]")),
+        ];
+        let actual = apply(
+            crate::Data::text("Running on NVIDIA RTX 4090\n"),
+            &normalizers,
+        );
+        assert_eq!(actual, crate::Data::text("Running on [DEVICE]\n"));
+    }
+}
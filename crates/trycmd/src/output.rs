@@ -0,0 +1,200 @@
+/// Check the [`Output`][std::process::Output] of a command run by hand, like in an ordinary
+/// `#[test]`, the way cases are checked
+///
+/// Expected output supports the same wildcards and placeholders as cases, including `[CWD]`, and
+/// `TRYCMD=overwrite` updates snapshot files.
+///
+/// ```rust,no_run
+/// let output = std::process::Command::new("my-bin")
+///     .arg("--help")
+///     .output()
+///     .unwrap();
+/// trycmd::assert(output)
+///     .success()
+///     .stdout_matches_path("tests/snapshots/help.stdout");
+/// ```
+pub fn assert(output: std::process::Output) -> OutputAssert {
+    OutputAssert::new(output)
+}
+
+/// Assertions on a command's [`Output`][std::process::Output], created with [`assert`]
+///
+/// Each assertion panics with a diff on failure.
+pub struct OutputAssert {
+    inner: snapbox::cmd::OutputAssert,
+}
+
+impl OutputAssert {
+    pub fn new(output: std::process::Output) -> Self {
+        Self {
+            inner: snapbox::cmd::OutputAssert::new(output).with_assert(config()),
+        }
+    }
+
+    pub fn get_output(&self) -> &std::process::Output {
+        self.inner.get_output()
+    }
+
+    #[track_caller]
+    pub fn success(self) -> Self {
+        Self {
+            inner: self.inner.success(),
+        }
+    }
+
+    #[track_caller]
+    pub fn failure(self) -> Self {
+        Self {
+            inner: self.inner.failure(),
+        }
+    }
+
+    /// The command was killed, like by a signal, rather than exiting with a code
+    #[track_caller]
+    pub fn interrupted(self) -> Self {
+        Self {
+            inner: self.inner.interrupted(),
+        }
+    }
+
+    #[track_caller]
+    pub fn code(self, expected: i32) -> Self {
+        Self {
+            inner: self.inner.code(expected),
+        }
+    }
+
+    #[track_caller]
+    pub fn stdout_eq(self, expected: impl snapbox::IntoData) -> Self {
+        Self {
+            inner: self.inner.stdout_eq(expected.into_data()),
+        }
+    }
+
+    #[track_caller]
+    pub fn stderr_eq(self, expected: impl snapbox::IntoData) -> Self {
+        Self {
+            inner: self.inner.stderr_eq(expected.into_data()),
+        }
+    }
+
+    /// `stdout` matches the snapshot at `path`, which `TRYCMD=overwrite` creates or updates
+    #[track_caller]
+    pub fn stdout_matches_path(self, path: impl AsRef<std::path::Path>) -> Self {
+        self.stdout_eq(snapshot(path.as_ref()))
+    }
+
+    /// `stderr` matches the snapshot at `path`, which `TRYCMD=overwrite` creates or updates
+    #[track_caller]
+    pub fn stderr_matches_path(self, path: impl AsRef<std::path::Path>) -> Self {
+        self.stderr_eq(snapshot(path.as_ref()))
+    }
+}
+
+impl std::fmt::Debug for OutputAssert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputAssert")
+            .field("output", self.get_output())
+            .finish()
+    }
+}
+
+/// Check a file or content that isn't a command's output, like a generated config or rendered
+/// template, against the snapshot at `expected_path`
+///
+/// This compares the way cases do, with wildcards and placeholders, and `TRYCMD=overwrite` creates
+/// or updates the snapshot.
+///
+/// ```rust,no_run
+/// let rendered = "name = \"demo\"\n";
+/// trycmd::assert_matches_path(rendered, "tests/snapshots/config.toml");
+/// trycmd::assert_matches_path(
+///     std::path::Path::new("target/generated/schema.json"),
+///     "tests/snapshots/schema.json",
+/// );
+/// ```
+#[track_caller]
+pub fn assert_matches_path(actual: impl Into<Actual>, expected_path: impl AsRef<std::path::Path>) {
+    let actual = actual.into();
+    let expected = snapshot(expected_path.as_ref());
+    if let Err(err) = config().try_eq(Some(&actual.name), actual.content, expected) {
+        panic!("{err}");
+    }
+}
+
+/// What [`assert_matches_path`] checks: content, or a file to read it from
+#[derive(Clone, Debug)]
+pub struct Actual {
+    name: String,
+    content: crate::Data,
+}
+
+impl From<&'_ [u8]> for Actual {
+    fn from(content: &[u8]) -> Self {
+        Self::from(content.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Actual {
+    fn from(content: Vec<u8>) -> Self {
+        Self {
+            name: "actual".to_owned(),
+            content: crate::Data::binary(content),
+        }
+    }
+}
+
+impl From<&'_ str> for Actual {
+    fn from(content: &str) -> Self {
+        Self::from(content.to_owned())
+    }
+}
+
+impl From<String> for Actual {
+    fn from(content: String) -> Self {
+        Self {
+            name: "actual".to_owned(),
+            content: crate::Data::text(content),
+        }
+    }
+}
+
+impl From<&'_ std::path::Path> for Actual {
+    fn from(path: &std::path::Path) -> Self {
+        Self {
+            name: path.display().to_string(),
+            content: crate::Data::read_from(path, None),
+        }
+    }
+}
+
+impl From<std::path::PathBuf> for Actual {
+    fn from(path: std::path::PathBuf) -> Self {
+        Self::from(path.as_path())
+    }
+}
+
+/// How to compare, like a case: with its placeholders and `TRYCMD=overwrite`
+fn config() -> snapbox::Assert {
+    snapbox::Assert::new()
+        .action_env("TRYCMD")
+        .redact_with(redactions())
+}
+
+/// The placeholders available outside of cases
+pub(crate) fn redactions() -> snapbox::Redactions {
+    let mut redactions = snapbox::Redactions::new();
+    redactions
+        .insert("[EXE]", std::env::consts::EXE_SUFFIX)
+        .unwrap();
+    if let Ok(cwd) = std::env::current_dir() {
+        redactions.insert("[CWD]", cwd).unwrap();
+    }
+    redactions
+}
+
+/// Read a snapshot like a case's, using the platform-specific one if there is one
+fn snapshot(path: &std::path::Path) -> crate::Data {
+    let path = crate::platform::snapshot_path(path.to_owned());
+    crate::Data::read_from(&path, None)
+}
@@ -0,0 +1,143 @@
+//! The commands of a step's `pipe` before the last one, running alongside it like in a shell
+
+/// The commands feeding the checked command, each reading the `stdout` of the one before
+///
+/// Any left running when dropped are killed.
+pub(crate) struct Upstream {
+    stages: Vec<Stage>,
+    writing: Option<std::thread::JoinHandle<()>>,
+}
+
+struct Stage {
+    child: std::process::Child,
+    stderr: Option<std::thread::JoinHandle<Vec<u8>>>,
+}
+
+/// Start `cmds` at once, the first reading `stdin`, returning them with the last one's `stdout`
+/// for the checked command to read
+pub(crate) fn start(
+    cmds: Vec<std::process::Command>,
+    stdin: Option<Vec<u8>>,
+) -> Result<(Upstream, std::process::ChildStdout), crate::Error> {
+    use std::io::Write as _;
+
+    let mut upstream = Upstream {
+        stages: Vec::new(),
+        writing: None,
+    };
+    let mut stdin = stdin;
+    let mut input = None;
+    for mut cmd in cmds {
+        cmd.stdin(match input.take() {
+            Some(stdout) => std::process::Stdio::from(stdout),
+            None => std::process::Stdio::piped(),
+        })
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| crate::Error::SpawnFailed(e.to_string()))?;
+        if let Some(mut writer) = child.stdin.take() {
+            let stdin = stdin.take().unwrap_or_default();
+            upstream.writing = Some(std::thread::spawn(move || {
+                let _ = writer.write_all(&stdin);
+            }));
+        }
+        input = child.stdout.take();
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut buffer = Vec::new();
+                let _ = std::io::Read::read_to_end(&mut stderr, &mut buffer);
+                buffer
+            })
+        });
+        upstream.stages.push(Stage { child, stderr });
+    }
+    let stdout = input.ok_or("`pipe` has no commands before the last")?;
+    Ok((upstream, stdout))
+}
+
+impl Upstream {
+    /// Wait for the commands to exit, killing any still running at `deadline`
+    ///
+    /// Like in a shell, how they exit doesn't matter unless `pipefail`, and even then not being
+    /// killed by `SIGPIPE` for writing after the command they feed stopped reading.
+    pub(crate) fn finish(
+        mut self,
+        deadline: Option<std::time::Instant>,
+        pipefail: bool,
+    ) -> Result<(), crate::Error> {
+        for (i, stage) in self.stages.iter_mut().enumerate() {
+            let status = wait(&mut stage.child, deadline).map_err(|e| {
+                crate::Error::Io(format!("Failed to wait for pipe stage {}: {}", i + 1, e))
+            })?;
+            let Some(status) = status else {
+                return Err(crate::Error::Timeout(format!(
+                    "Pipe stage {} was still running at the step's `timeout`",
+                    i + 1
+                )));
+            };
+            if pipefail && !status.success() && !killed_by_sigpipe(status) {
+                let stderr = stage
+                    .stderr
+                    .take()
+                    .and_then(|reading| reading.join().ok())
+                    .unwrap_or_default();
+                return Err(format!(
+                    "Pipe stage {} exited with {}:\n{}",
+                    i + 1,
+                    crate::runner::describe_exit(status),
+                    String::from_utf8_lossy(&stderr),
+                )
+                .into());
+            }
+        }
+        if let Some(writing) = self.writing.take() {
+            let _ = writing.join();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Upstream {
+    fn drop(&mut self) {
+        for stage in &mut self.stages {
+            let _ = stage.child.kill();
+            let _ = stage.child.wait();
+        }
+    }
+}
+
+/// Wait for `child` to exit, or kill it at `deadline`, returning `None` then
+fn wait(
+    child: &mut std::process::Child,
+    deadline: Option<std::time::Instant>,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let Some(deadline) = deadline else {
+        return child.wait().map(Some);
+    };
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if deadline <= std::time::Instant::now() {
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+#[cfg(unix)]
+fn killed_by_sigpipe(status: std::process::ExitStatus) -> bool {
+    crate::platform::exit_signal(status) == Some(libc::SIGPIPE)
+}
+
+#[cfg(not(unix))]
+fn killed_by_sigpipe(_status: std::process::ExitStatus) -> bool {
+    false
+}
+
+/// How often to check on commands left running once the checked one exited
+const POLL: std::time::Duration = std::time::Duration::from_millis(10);
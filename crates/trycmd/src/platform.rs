@@ -0,0 +1,209 @@
+//! Match cases against the platform they run on
+
+/// Whether `name` describes the current platform
+///
+/// `name` is either an operating system (`linux`, `macos`, `windows`, ...) or a family (`unix`,
+/// `windows`).
+pub(crate) fn is_current(name: &str) -> bool {
+    name == std::env::consts::OS || name == std::env::consts::FAMILY
+}
+
+/// Evaluate a `cfg(...)` expression, like `cfg(windows)` or `cfg(not(target_os = "macos"))`,
+/// against the current platform
+///
+/// Bare platform names, like `windows`, are accepted as a shorthand, see [`is_current`].
+pub(crate) fn eval(expr: &str) -> Result<bool, crate::Error> {
+    let expr = expr.trim();
+    let Some(inner) = expr
+        .strip_prefix("cfg(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    else {
+        return Ok(is_current(expr));
+    };
+
+    let mut parser = Parser { rest: inner };
+    let value = parser
+        .predicate()
+        .map_err(|e| format!("Invalid `{expr}`: {e}"))?;
+    if !parser.rest.trim().is_empty() {
+        return Err(format!("Invalid `{expr}`: unexpected `{}`", parser.rest.trim()).into());
+    }
+    Ok(value)
+}
+
+struct Parser<'s> {
+    rest: &'s str,
+}
+
+impl<'s> Parser<'s> {
+    fn predicate(&mut self) -> Result<bool, String> {
+        let ident = self.ident()?;
+        match ident {
+            "all" | "any" | "not" => {
+                self.eat('(')?;
+                let mut values = Vec::new();
+                if !self.peek(')') {
+                    loop {
+                        values.push(self.predicate()?);
+                        if !self.peek(',') {
+                            break;
+                        }
+                        self.eat(',')?;
+                        if self.peek(')') {
+                            break;
+                        }
+                    }
+                }
+                self.eat(')')?;
+                match ident {
+                    "all" => Ok(values.into_iter().all(|v| v)),
+                    "any" => Ok(values.into_iter().any(|v| v)),
+                    _ => match values[..] {
+                        [value] => Ok(!value),
+                        _ => Err("`not` takes exactly one predicate".to_owned()),
+                    },
+                }
+            }
+            "unix" | "windows" => Ok(ident == std::env::consts::FAMILY),
+            "target_os" | "target_family" | "target_arch" => {
+                self.eat('=')?;
+                let value = self.string()?;
+                let current = match ident {
+                    "target_os" => std::env::consts::OS,
+                    "target_family" => std::env::consts::FAMILY,
+                    _ => std::env::consts::ARCH,
+                };
+                Ok(value == current)
+            }
+            _ => Err(format!("unsupported predicate `{ident}`")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'s str, String> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err("expected a predicate".to_owned());
+        }
+        let (ident, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(ident)
+    }
+
+    fn string(&mut self) -> Result<&'s str, String> {
+        self.eat('"')?;
+        let end = self
+            .rest
+            .find('"')
+            .ok_or_else(|| "unterminated string".to_owned())?;
+        let (value, rest) = self.rest.split_at(end);
+        self.rest = &rest[1..];
+        Ok(value)
+    }
+
+    fn peek(&mut self, c: char) -> bool {
+        self.rest = self.rest.trim_start();
+        self.rest.starts_with(c)
+    }
+
+    fn eat(&mut self, c: char) -> Result<(), String> {
+        if self.peek(c) {
+            self.rest = &self.rest[1..];
+            Ok(())
+        } else {
+            Err(format!("expected `{c}`"))
+        }
+    }
+}
+
+/// Prefer a platform-specific variant of `path`, like `case.stdout.windows`, when it exists
+pub(crate) fn snapshot_path(path: std::path::PathBuf) -> std::path::PathBuf {
+    for platform in [std::env::consts::OS, std::env::consts::FAMILY] {
+        let mut candidate = path.clone().into_os_string();
+        candidate.push(".");
+        candidate.push(platform);
+        let candidate = std::path::PathBuf::from(candidate);
+        if candidate.exists() {
+            return candidate;
+        }
+    }
+    path
+}
+
+/// The status of a process that exited with `code`
+#[cfg(unix)]
+pub(crate) fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::unix::process::ExitStatusExt as _;
+    std::process::ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+pub(crate) fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::windows::process::ExitStatusExt as _;
+    std::process::ExitStatus::from_raw(code as u32)
+}
+
+/// The signal that killed the process, if any
+#[cfg(unix)]
+pub(crate) fn exit_signal(status: std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt as _;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn exit_signal(_status: std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Whether the signal that killed the process dumped core
+#[cfg(unix)]
+pub(crate) fn core_dumped(status: std::process::ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt as _;
+    status.core_dumped()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn core_dumped(_status: std::process::ExitStatus) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn eval_family() {
+        assert_eq!(eval("cfg(windows)").unwrap(), cfg!(windows));
+        assert_eq!(eval("cfg(unix)").unwrap(), cfg!(unix));
+        assert_eq!(eval("windows").unwrap(), cfg!(windows));
+    }
+
+    #[test]
+    fn eval_combinators() {
+        assert_eq!(
+            eval(r#"cfg(all(unix, not(target_os = "macos")))"#).unwrap(),
+            cfg!(all(unix, not(target_os = "macos")))
+        );
+        assert_eq!(
+            eval(r#"cfg(any(windows, target_arch = "x86_64"))"#).unwrap(),
+            cfg!(any(windows, target_arch = "x86_64"))
+        );
+        assert!(eval("cfg(all())").unwrap());
+        assert!(!eval("cfg(any())").unwrap());
+    }
+
+    #[test]
+    fn eval_errors() {
+        assert_eq!(
+            eval("cfg(feature = \"foo\")").unwrap_err().to_string(),
+            "Invalid `cfg(feature = \"foo\")`: unsupported predicate `feature`\n"
+        );
+        assert_eq!(
+            eval("cfg(not(unix, windows))").unwrap_err().to_string(),
+            "Invalid `cfg(not(unix, windows))`: `not` takes exactly one predicate\n"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Free TCP ports for a case, through `[PORT]` and `[PORT:<label>]`
+
+/// The `[PORT]` placeholders `steps` use, in their args, env, stdin, or expected output
+pub(crate) fn placeholders(steps: &[crate::schema::Step]) -> std::collections::BTreeSet<String> {
+    let pattern = regex::Regex::new(r"\[PORT(?::[A-Za-z0-9_-]+)?\]").unwrap();
+    let mut found = std::collections::BTreeSet::new();
+    for step in steps {
+        let mut texts: Vec<String> = step.args.clone();
+        texts.extend(step.env.add.values().cloned());
+        for stage in &step.pipe {
+            texts.extend(stage.args.iter().cloned());
+            texts.extend(stage.env.add.values().cloned());
+        }
+        let data = [&step.stdin, &step.expected_stdout, &step.expected_stderr];
+        texts.extend(data.into_iter().flatten().filter_map(|data| data.render()));
+        for text in &texts {
+            found.extend(pattern.find_iter(text).map(|m| m.as_str().to_owned()));
+        }
+    }
+    found
+}
+
+/// Find a TCP port nothing is listening on, not handed out to another case
+///
+/// The port is released before the case runs so its commands can bind it, leaving a small
+/// window for another process to take it.
+pub(crate) fn reserve() -> Result<u16, crate::Error> {
+    static RESERVED: std::sync::Mutex<Vec<u16>> = std::sync::Mutex::new(Vec::new());
+
+    let mut reserved = RESERVED.lock().unwrap_or_else(|e| e.into_inner());
+    // Bound while looking so the OS doesn't offer the same port twice
+    let mut listeners = Vec::new();
+    loop {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .map_err(|e| crate::Error::Io(format!("Failed to reserve a port: {}", e)))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| crate::Error::Io(format!("Failed to reserve a port: {}", e)))?
+            .port();
+        if !reserved.contains(&port) {
+            reserved.push(port);
+            return Ok(port);
+        }
+        if 100 <= listeners.len() {
+            return Err(crate::Error::Io(
+                "Failed to reserve a port: all offered ports are in use by other cases".to_owned(),
+            ));
+        }
+        listeners.push(listener);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placeholders() {
+        let mut step = crate::schema::Step {
+            args: vec![
+                "--listen=[PORT:api]".into(),
+                "--admin".into(),
+                "[PORT]".into(),
+            ],
+            expected_stdout: Some(crate::Data::text("on [PORT:api] and [PORT:db]\n")),
+            ..Default::default()
+        };
+        step.env
+            .add
+            .insert("API".into(), "http://localhost:[PORT:api]".into());
+        let found = super::placeholders(std::slice::from_ref(&step));
+        assert_eq!(
+            found.into_iter().collect::<Vec<_>>(),
+            ["[PORT:api]", "[PORT:db]", "[PORT]"]
+        );
+    }
+
+    #[test]
+    fn reserve_distinct() {
+        let first = reserve().unwrap();
+        let second = reserve().unwrap();
+        assert_ne!(first, second);
+    }
+}
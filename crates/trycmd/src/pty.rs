@@ -0,0 +1,209 @@
+//! Drive a command through a pseudo-terminal for `[[interaction]]`
+
+/// How long to wait for an `expect` when the case has no `timeout`
+pub(crate) const DEFAULT_EXPECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Run `cmd` on a terminal, answering each `expect` with its `send`
+///
+/// All output is read from the terminal into `stdout`, including what the terminal echoes of
+/// what was sent.
+#[cfg(unix)]
+pub(crate) fn run(
+    mut cmd: std::process::Command,
+    interactions: &[crate::schema::Interaction],
+    timeout: Option<std::time::Duration>,
+    mut grace: Option<std::time::Duration>,
+    term: crate::schema::Term,
+) -> Result<std::process::Output, crate::Error> {
+    use std::io::Read as _;
+    use std::io::Write as _;
+    use std::os::unix::process::CommandExt as _;
+
+    let (master, slave) = open(term)?;
+    let stdio = || {
+        slave
+            .try_clone()
+            .map(std::process::Stdio::from)
+            .map_err(|e| e.to_string())
+    };
+    cmd.stdin(stdio()?).stdout(stdio()?).stderr(stdio()?);
+    // SAFETY: between `fork` and `exec`, this only calls `setsid` and `ioctl`, which are
+    // async-signal-safe, and doesn't allocate
+    unsafe {
+        cmd.pre_exec(|| {
+            // Make the terminal the controlling one, like a shell would
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| crate::Error::SpawnFailed(e.to_string()))?;
+    // Otherwise the terminal stays open after the command exits
+    drop(cmd);
+    drop(slave);
+
+    let master = std::fs::File::from(master);
+    let mut reader = master.try_clone().map_err(|e| e.to_string())?;
+    let (sender, receiver) = std::sync::mpsc::channel::<Vec<u8>>();
+    let reading = std::thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            match reader.read(&mut buffer) {
+                // Linux reports `EIO` once the command closes the terminal
+                Ok(0) | Err(_) => break,
+                Ok(len) => {
+                    if sender.send(buffer[..len].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut writer = master;
+    let mut stdout = Vec::new();
+    let mut unmatched = 0;
+    let wait = timeout.unwrap_or(DEFAULT_EXPECT_TIMEOUT);
+    for interaction in interactions {
+        let expect = interaction.expect.as_bytes();
+        let deadline = std::time::Instant::now() + wait;
+        loop {
+            if let Some(pos) = find(&stdout[unmatched..], expect) {
+                unmatched += pos + expect.len();
+                break;
+            }
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let (kind, problem): (fn(String) -> crate::Error, _) =
+                match receiver.recv_timeout(remaining) {
+                    Ok(chunk) => {
+                        stdout.extend(chunk);
+                        continue;
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        let _ = child.kill();
+                        (crate::Error::Timeout, "Timed out waiting for")
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                        (crate::Error::Other, "Exited before printing")
+                    }
+                };
+            let _ = child.wait();
+            return Err(kind(format!(
+                "{} `{}`, after:\n{}",
+                problem,
+                interaction.expect,
+                String::from_utf8_lossy(&stdout)
+            )));
+        }
+        writer
+            .write_all(interaction.send.as_bytes())
+            .map_err(|e| format!("Failed to send `{}`: {}", interaction.send, e))?;
+    }
+
+    let mut deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+    loop {
+        let chunk = match deadline {
+            Some(at) => {
+                let remaining = at.saturating_duration_since(std::time::Instant::now());
+                match receiver.recv_timeout(remaining) {
+                    Ok(chunk) => chunk,
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        // Give the command `grace` to shut down cleanly before killing it
+                        if let Some(grace) = grace.take() {
+                            // SAFETY: `kill` has no memory safety requirements
+                            unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+                            deadline = Some(std::time::Instant::now() + grace);
+                            continue;
+                        }
+                        let _ = child.kill();
+                        break;
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match receiver.recv() {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            },
+        };
+        stdout.extend(chunk);
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    drop(writer);
+    // Collect anything read before the command was killed
+    let _ = reading.join();
+    stdout.extend(receiver.try_iter().flatten());
+
+    Ok(std::process::Output {
+        status,
+        stdout,
+        stderr: Default::default(),
+    })
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run(
+    _cmd: std::process::Command,
+    _interactions: &[crate::schema::Interaction],
+    _timeout: Option<std::time::Duration>,
+    _grace: Option<std::time::Duration>,
+    _term: crate::schema::Term,
+) -> Result<std::process::Output, crate::Error> {
+    Err("`[[interaction]]` is only supported on Unix".into())
+}
+
+/// Open a terminal of size `term`, returning the `(master, slave)` ends
+#[cfg(unix)]
+fn open(
+    term: crate::schema::Term,
+) -> Result<(std::os::unix::io::OwnedFd, std::os::unix::io::OwnedFd), crate::Error> {
+    use std::os::unix::io::FromRawFd as _;
+
+    let mut master = -1;
+    let mut slave = -1;
+    let mut size = libc::winsize {
+        ws_row: term.height.unwrap_or(0),
+        ws_col: term.width.unwrap_or(0),
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: `openpty` only writes the two file descriptors, which we take ownership of, and
+    // only reads `size`
+    let res = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::addr_of_mut!(size),
+        )
+    };
+    if res != 0 {
+        return Err(format!(
+            "Failed to open a terminal: {}",
+            std::io::Error::last_os_error()
+        )
+        .into());
+    }
+    // SAFETY: `openpty` succeeded, so both are open and unowned
+    let fds = unsafe {
+        (
+            std::os::unix::io::OwnedFd::from_raw_fd(master),
+            std::os::unix::io::OwnedFd::from_raw_fd(slave),
+        )
+    };
+    Ok(fds)
+}
+
+#[cfg(unix)]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -2,6 +2,10 @@
 pub(crate) struct BinRegistry {
     bins: std::collections::BTreeMap<String, crate::schema::Bin>,
     fallback: bool,
+    /// Command line to run bins under, like cargo's `target.<triple>.runner`
+    runner: Option<String>,
+    /// Where bins for the target being tested are built, so only those go through `runner`
+    target_dir: Option<std::path::PathBuf>,
 }
 
 impl BinRegistry {
@@ -9,9 +13,15 @@ impl BinRegistry {
         Self {
             bins: Default::default(),
             fallback: true,
+            runner: default_runner(),
+            target_dir: default_target_dir(),
         }
     }
 
+    pub(crate) fn runner(&mut self, runner: Option<String>) {
+        self.runner = runner;
+    }
+
     pub(crate) fn register_bin(&mut self, name: String, bin: crate::schema::Bin) {
         self.bins.insert(name, bin);
     }
@@ -29,13 +39,14 @@ impl BinRegistry {
     ) -> Result<crate::schema::Bin, crate::Error> {
         match bin {
             crate::schema::Bin::Path(path) => {
-                let bin = crate::schema::Bin::Path(path);
+                let bin = self.with_runner(crate::schema::Bin::Path(path));
                 Ok(bin)
             }
             crate::schema::Bin::Name(name) => {
                 let bin = self.resolve_name(&name);
                 Ok(bin)
             }
+            crate::schema::Bin::Script(script) => Ok(crate::schema::Bin::Script(script)),
             crate::schema::Bin::Ignore => Ok(crate::schema::Bin::Ignore),
             crate::schema::Bin::Error(err) => Err(err),
         }
@@ -43,18 +54,57 @@ impl BinRegistry {
 
     pub(crate) fn resolve_name(&self, name: &str) -> crate::schema::Bin {
         if let Some(path) = self.bins.get(name) {
-            return path.clone();
+            return self.with_runner(path.clone());
         }
 
         if self.fallback {
             let path = crate::cargo::cargo_bin(name);
             if path.exists() {
-                return crate::schema::Bin::Path(path);
+                return self.with_runner(crate::schema::Bin::Path(path));
             }
         }
 
         crate::schema::Bin::Name(name.to_owned())
     }
+
+    /// Run a bin built for the target through `runner`, if any, however it was looked up
+    fn with_runner(&self, bin: crate::schema::Bin) -> crate::schema::Bin {
+        match (&self.runner, &self.target_dir, bin) {
+            (Some(runner), Some(target_dir), crate::schema::Bin::Path(path))
+                if path.starts_with(target_dir) =>
+            {
+                let path = path.display().to_string();
+                let path = shlex::try_quote(&path)
+                    .map(|p| p.into_owned())
+                    .unwrap_or(path);
+                crate::schema::Bin::Script(format!("{runner} {path}"))
+            }
+            (_, _, bin) => bin,
+        }
+    }
+}
+
+/// Cargo's `CARGO_TARGET_<TRIPLE>_RUNNER` for the target being tested
+fn default_runner() -> Option<String> {
+    let target = env!("TRYCMD_TARGET")
+        .to_ascii_uppercase()
+        .replace(['-', '.'], "_");
+    std::env::var(format!("CARGO_TARGET_{target}_RUNNER"))
+        .ok()
+        .filter(|runner| !runner.trim().is_empty())
+}
+
+/// Where cargo puts bins for the target being tested, next to the test itself
+///
+/// When cross-compiling, that is `target/<triple>/<profile>`, leaving out host tools like build
+/// scripts.
+fn default_target_dir() -> Option<std::path::PathBuf> {
+    let mut path = std::env::current_exe().ok()?;
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    Some(path)
 }
 
 impl Default for BinRegistry {
@@ -62,3 +112,58 @@ impl Default for BinRegistry {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn runner() {
+        let mut bins = BinRegistry::new();
+        bins.target_dir = Some(std::path::PathBuf::from("/target/aarch64/debug"));
+        bins.register_bin(
+            "foo".to_owned(),
+            crate::schema::Bin::Path(std::path::PathBuf::from("/target/aarch64/debug/foo")),
+        );
+        bins.register_bin(
+            "python".to_owned(),
+            crate::schema::Bin::Path(std::path::PathBuf::from("/usr/bin/python")),
+        );
+        bins.runner(Some("qemu-aarch64 -L /sysroot".to_owned()));
+        assert_eq!(
+            bins.resolve_name("foo"),
+            crate::schema::Bin::Script(
+                "qemu-aarch64 -L /sysroot /target/aarch64/debug/foo".to_owned()
+            )
+        );
+        // Host tools run directly
+        assert_eq!(
+            bins.resolve_name("python"),
+            crate::schema::Bin::Path(std::path::PathBuf::from("/usr/bin/python"))
+        );
+
+        bins.runner(None);
+        assert_eq!(
+            bins.resolve_name("foo"),
+            crate::schema::Bin::Path(std::path::PathBuf::from("/target/aarch64/debug/foo"))
+        );
+    }
+
+    #[test]
+    fn runner_by_path() {
+        let mut bins = BinRegistry::new();
+        bins.target_dir = Some(std::path::PathBuf::from("/target/aarch64/debug"));
+        bins.runner(Some("qemu-aarch64".to_owned()));
+        let bin = crate::schema::Bin::Path(std::path::PathBuf::from(
+            "/target/aarch64/debug/examples/demo",
+        ));
+        assert_eq!(
+            bins.resolve_bin(bin).unwrap(),
+            crate::schema::Bin::Script(
+                "qemu-aarch64 /target/aarch64/debug/examples/demo".to_owned()
+            )
+        );
+        let bin = crate::schema::Bin::Path(std::path::PathBuf::from("/usr/bin/env"));
+        assert_eq!(bins.resolve_bin(bin.clone()).unwrap(), bin);
+    }
+}
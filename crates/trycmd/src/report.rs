@@ -0,0 +1,989 @@
+use std::io::prelude::*;
+
+#[cfg(feature = "color")]
+use anstream::{stderr, stdout};
+#[cfg(not(feature = "color"))]
+use std::io::{stderr, stdout};
+
+use crate::runner::{ExpectedFailure, FileStatus, Output, SpawnStatus, StreamStatus};
+
+/// How results are reported to the user
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// Human-oriented terminal output
+    #[default]
+    Human,
+    /// [Test Anything Protocol](https://testanything.org/tap-version-13-specification.html)
+    Tap,
+    /// Newline-delimited JSON events
+    Json,
+    /// Like libtest's default output, for `harness = false` test targets
+    Libtest,
+}
+
+/// How many of the slowest cases to list with `TRYCMD_VERBOSE=1`
+const SLOWEST_CASES: usize = 10;
+
+#[derive(Debug)]
+pub(crate) struct Reporter {
+    format: Format,
+    verbose: u8,
+    /// Leave out cases that pass, for the human format
+    quiet: bool,
+    palette: snapbox::report::Palette,
+    count: std::sync::atomic::AtomicUsize,
+    started: std::time::Instant,
+    tally: std::sync::Mutex<Tally>,
+    filtered_out: usize,
+    /// Names and reports of failures, for libtest to list at the end
+    failures: std::sync::Mutex<Vec<(String, String)>>,
+}
+
+impl Reporter {
+    pub(crate) fn new(format: Format, verbose: u8) -> Self {
+        Self {
+            format,
+            verbose,
+            quiet: false,
+            palette: snapbox::report::Palette::color(),
+            count: Default::default(),
+            started: std::time::Instant::now(),
+            tally: Default::default(),
+            filtered_out: 0,
+            failures: Default::default(),
+        }
+    }
+
+    /// How many cases were left out, for libtest's summary
+    pub(crate) fn filtered_out(mut self, filtered_out: usize) -> Self {
+        self.filtered_out = filtered_out;
+        self
+    }
+
+    /// Only report failures and the summary
+    pub(crate) fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    pub(crate) fn empty(&self) {
+        match self.format {
+            Format::Human => {
+                let _ = writeln!(
+                    stderr(),
+                    "{}",
+                    self.palette.warn("There are no trycmd tests enabled yet")
+                );
+            }
+            Format::Tap => {
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                let _ = writeln!(stdout, "TAP version 13");
+                let _ = writeln!(stdout, "1..0 # SKIP There are no trycmd tests enabled yet");
+            }
+            Format::Json => {
+                write_event(serde_json::json!({"type": "summary", "total": 0, "failed": 0}));
+            }
+            Format::Libtest => {
+                self.start(0);
+                self.finish(0);
+            }
+        }
+    }
+
+    pub(crate) fn start(&self, total: usize) {
+        match self.format {
+            Format::Human => {}
+            Format::Tap => {
+                let _ = writeln!(std::io::stdout(), "TAP version 13");
+            }
+            Format::Json => {}
+            Format::Libtest => {
+                let tests = if total == 1 { "test" } else { "tests" };
+                let _ = writeln!(stdout(), "\nrunning {total} {tests}");
+            }
+        }
+    }
+
+    /// A case is about to run
+    pub(crate) fn started(&self, path: &std::path::Path) {
+        match self.format {
+            Format::Human | Format::Tap | Format::Libtest => {}
+            Format::Json => {
+                write_event(serde_json::json!({
+                    "type": "started",
+                    "name": path.display().to_string(),
+                }));
+            }
+        }
+    }
+
+    /// Report the results of a single case
+    ///
+    /// All results are written together so output from parallel cases doesn't interleave.
+    pub(crate) fn case(
+        &self,
+        path: &std::path::Path,
+        results: &[Result<Output, Output>],
+        duration: std::time::Duration,
+    ) {
+        self.tally
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(path, results, duration);
+        match self.format {
+            Format::Human => {
+                let stderr = stderr();
+                let mut stderr = stderr.lock();
+                for result in results {
+                    snapbox::debug!("Case: {:#?}", result);
+                    if self.quiet && result.is_ok() {
+                        continue;
+                    }
+                    let _ = write_human(&mut stderr, result, self.verbose, self.palette);
+                }
+            }
+            Format::Tap => {
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                snapbox::debug!("Case: {:#?}", results);
+                let number = self.next_number();
+                let _ = write_tap(&mut stdout, number, path, results);
+            }
+            Format::Json => {
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                for result in results {
+                    snapbox::debug!("Case: {:#?}", result);
+                    self.next_number();
+                    for event in json_events(result) {
+                        let _ = writeln!(stdout, "{event}");
+                    }
+                }
+            }
+            Format::Libtest => {
+                let stdout = stdout();
+                let mut stdout = stdout.lock();
+                for result in results {
+                    snapbox::debug!("Case: {:#?}", result);
+                    let _ = write_libtest(&mut stdout, result, self.palette);
+                    if let Err(status) = result {
+                        self.failures
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .push((status.name(), status.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// A snapshot that no case is named after, like one left behind by a rename
+    pub(crate) fn unused(&self, path: &std::path::Path, removed: bool) {
+        match self.format {
+            Format::Human | Format::Libtest => {
+                let message = if removed {
+                    format!("Removed unused snapshot {}", path.display())
+                } else {
+                    format!("Unused snapshot {}", path.display())
+                };
+                let _ = writeln!(stderr(), "{}", self.palette.warn(message));
+            }
+            Format::Tap => {
+                let _ = writeln!(
+                    std::io::stdout(),
+                    "# {}unused snapshot {}",
+                    if removed { "removed " } else { "" },
+                    path.display()
+                );
+            }
+            Format::Json => {
+                write_event(serde_json::json!({
+                    "type": "unused",
+                    "path": path.display().to_string(),
+                    "removed": removed,
+                }));
+            }
+        }
+    }
+
+    pub(crate) fn finish(&self, failures: usize) {
+        match self.format {
+            Format::Human => {
+                let stderr = stderr();
+                let mut stderr = stderr.lock();
+                let tally = self.tally.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = write_summary(
+                    &mut stderr,
+                    &tally,
+                    self.started.elapsed(),
+                    self.verbose,
+                    self.palette,
+                );
+                if failures != 0 {
+                    let _ = writeln!(
+                        stderr,
+                        "{}",
+                        self.palette
+                            .hint("Update snapshots with `TRYCMD=overwrite`"),
+                    );
+                    let _ = writeln!(
+                        stderr,
+                        "{}",
+                        self.palette.hint("Debug output with `TRYCMD=dump`"),
+                    );
+                }
+            }
+            Format::Tap => {
+                let count = self.count.load(std::sync::atomic::Ordering::SeqCst);
+                let _ = writeln!(std::io::stdout(), "1..{count}");
+            }
+            Format::Json => {
+                let count = self.count.load(std::sync::atomic::Ordering::SeqCst);
+                write_event(serde_json::json!({
+                    "type": "summary",
+                    "total": count,
+                    "failed": failures,
+                }));
+            }
+            Format::Libtest => {
+                let stdout = stdout();
+                let mut stdout = stdout.lock();
+                let tally = self.tally.lock().unwrap_or_else(|e| e.into_inner());
+                let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+                failures.sort();
+                let _ = write_libtest_summary(
+                    &mut stdout,
+                    &tally,
+                    &failures,
+                    self.filtered_out,
+                    self.started.elapsed(),
+                    self.palette,
+                );
+            }
+        }
+    }
+
+    fn next_number(&self) -> usize {
+        self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+    }
+}
+
+/// Counts and timings for the cases run so far
+#[derive(Default, Debug)]
+struct Tally {
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    xfailed: usize,
+    durations: Vec<(std::path::PathBuf, std::time::Duration)>,
+}
+
+impl Tally {
+    fn record(
+        &mut self,
+        path: &std::path::Path,
+        results: &[Result<Output, Output>],
+        duration: std::time::Duration,
+    ) {
+        for result in results {
+            match result {
+                Ok(output) if *output.spawn_status() == SpawnStatus::Skipped => self.ignored += 1,
+                Ok(output) if output.expected_failure.is_some() => self.xfailed += 1,
+                Ok(_) => self.passed += 1,
+                Err(_) => self.failed += 1,
+            }
+        }
+        self.durations.push((path.to_owned(), duration));
+    }
+}
+
+fn write_summary(
+    writer: &mut dyn Write,
+    tally: &Tally,
+    elapsed: std::time::Duration,
+    verbose: u8,
+    palette: snapbox::report::Palette,
+) -> std::io::Result<()> {
+    if 1 <= verbose && !tally.durations.is_empty() {
+        let mut durations = tally.durations.iter().collect::<Vec<_>>();
+        durations.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        writeln!(writer, "{}", palette.hint("Slowest cases:"))?;
+        for (path, duration) in durations.into_iter().take(SLOWEST_CASES) {
+            writeln!(
+                writer,
+                "  {:>8} {}",
+                format_duration(*duration),
+                path.display()
+            )?;
+        }
+    }
+    let xfailed = if tally.xfailed == 0 {
+        String::new()
+    } else {
+        format!("{} xfailed; ", palette.warn(tally.xfailed))
+    };
+    writeln!(
+        writer,
+        "{} passed; {} failed; {} ignored; {}finished in {}",
+        palette.info(tally.passed),
+        palette.error(tally.failed),
+        palette.warn(tally.ignored),
+        xfailed,
+        format_duration(elapsed),
+    )
+}
+
+fn format_duration(duration: std::time::Duration) -> String {
+    format!("{:.2}s", duration.as_secs_f64())
+}
+
+fn write_human(
+    writer: &mut dyn Write,
+    result: &Result<Output, Output>,
+    verbose: u8,
+    palette: snapbox::report::Palette,
+) -> std::io::Result<()> {
+    match result {
+        Ok(status) => {
+            let (summary, reason) = match &status.expected_failure {
+                Some(ExpectedFailure::Failed(reason)) => {
+                    (palette.warn("xfail").to_string(), Some(reason.as_str()))
+                }
+                _ => (
+                    status.spawn_status().summary().to_string(),
+                    status.skip_reason.as_deref(),
+                ),
+            };
+            let reason = reason
+                .map(|reason| format!(" {}", palette.warn(format_args!("({reason})"))))
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                "{} {} ... {}{}{}",
+                palette.hint("Testing"),
+                status.name(),
+                summary,
+                reason,
+                human_duration(status, palette),
+            )?;
+            if (!status.is_ok() && status.expected_failure.is_none())
+                || (2 <= verbose && status.spawn.exit.is_some())
+            {
+                // Assuming `status` will print the newline
+                write!(writer, "{}", &status)?;
+            } else {
+                for warning in &status.warnings {
+                    writeln!(
+                        writer,
+                        "{}",
+                        palette.warn(format_args!("warning: {warning}"))
+                    )?;
+                }
+                if let (1.., Some(repro)) = (verbose, &status.repro) {
+                    writeln!(writer, "{} {}", palette.hint("Ran"), repro)?;
+                }
+            }
+        }
+        Err(status) => {
+            writeln!(
+                writer,
+                "{} {} ... {}{}",
+                palette.hint("Testing"),
+                status.name(),
+                palette.error(failure_label(status)),
+                human_duration(status, palette),
+            )?;
+            // Assuming `status` will print the newline
+            write!(writer, "{}", &status)?;
+        }
+    }
+    Ok(())
+}
+
+fn human_duration(output: &Output, palette: snapbox::report::Palette) -> String {
+    output
+        .duration
+        .map(|d| {
+            format!(
+                " {}",
+                palette.hint(format_args!("({})", format_duration(d)))
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// One line for the whole case, with what each of its steps did in the YAML diagnostics
+fn write_tap(
+    writer: &mut dyn Write,
+    number: usize,
+    path: &std::path::Path,
+    results: &[Result<Output, Output>],
+) -> std::io::Result<()> {
+    let name = path.display();
+    if results.is_empty() {
+        // Nothing to tell a skipped case from one that never ran
+        writeln!(writer, "not ok {number} - {name}")?;
+        writeln!(writer, "  ---")?;
+        writeln!(writer, "  message: no steps were run")?;
+        writeln!(writer, "  ...")?;
+        return Ok(());
+    }
+    let failures: Vec<_> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+    if let Some(first) = failures.first() {
+        writeln!(writer, "not ok {number} - {name}")?;
+        writeln!(writer, "  ---")?;
+        writeln!(writer, "  message: {}", failure_label(first))?;
+        writeln!(writer, "  steps:")?;
+        for result in results {
+            let (output, status) = match result {
+                Ok(output) if *output.spawn_status() == SpawnStatus::Skipped => (output, "skipped"),
+                Ok(output) if output.expected_failure.is_some() => (output, "xfail"),
+                Ok(output) => (output, "ok"),
+                Err(output) => (output, failure_label(output)),
+            };
+            writeln!(writer, "    - {}: {status}", output.name())?;
+        }
+        writeln!(writer, "  diagnostics: |")?;
+        for failure in failures {
+            for line in strip_styling(failure.to_string()).trim_end().lines() {
+                writeln!(writer, "    {line}")?;
+            }
+        }
+        writeln!(writer, "  ...")?;
+        return Ok(());
+    }
+
+    let outputs = results.iter().filter_map(|r| r.as_ref().ok());
+    let skipped = results
+        .iter()
+        .all(|r| matches!(r, Ok(o) if *o.spawn_status() == SpawnStatus::Skipped));
+    let todo = outputs.clone().find_map(|o| match &o.expected_failure {
+        Some(ExpectedFailure::Failed(reason)) => Some(reason),
+        _ => None,
+    });
+    if skipped {
+        match outputs.clone().find_map(|o| o.skip_reason.as_deref()) {
+            Some(reason) => writeln!(writer, "ok {number} - {name} # SKIP {reason}")?,
+            None => writeln!(writer, "ok {number} - {name} # SKIP")?,
+        }
+    } else if let Some(reason) = todo {
+        writeln!(writer, "not ok {number} - {name} # TODO {reason}")?;
+    } else {
+        writeln!(writer, "ok {number} - {name}")?;
+    }
+    Ok(())
+}
+
+fn write_libtest(
+    writer: &mut dyn Write,
+    result: &Result<Output, Output>,
+    palette: snapbox::report::Palette,
+) -> std::io::Result<()> {
+    match result {
+        Ok(status) if *status.spawn_status() == SpawnStatus::Skipped => {
+            match status.skip_reason.as_deref() {
+                Some(reason) => writeln!(
+                    writer,
+                    "test {} ... {}",
+                    status.name(),
+                    palette.warn(format_args!("ignored, {reason}"))
+                ),
+                None => writeln!(
+                    writer,
+                    "test {} ... {}",
+                    status.name(),
+                    palette.warn("ignored")
+                ),
+            }
+        }
+        Ok(status) => writeln!(writer, "test {} ... {}", status.name(), palette.info("ok")),
+        Err(status) => writeln!(
+            writer,
+            "test {} ... {}",
+            status.name(),
+            palette.error("FAILED")
+        ),
+    }
+}
+
+fn write_libtest_summary(
+    writer: &mut dyn Write,
+    tally: &Tally,
+    failures: &[(String, String)],
+    filtered_out: usize,
+    elapsed: std::time::Duration,
+    palette: snapbox::report::Palette,
+) -> std::io::Result<()> {
+    if !failures.is_empty() {
+        writeln!(writer, "\nfailures:\n")?;
+        for (name, report) in failures {
+            writeln!(writer, "---- {name} stdout ----")?;
+            // Assuming `report` ends with a newline
+            writeln!(writer, "{report}")?;
+        }
+        writeln!(writer, "\nfailures:")?;
+        for (name, _) in failures {
+            writeln!(writer, "    {name}")?;
+        }
+    }
+    let result = if tally.failed == 0 {
+        palette.info("ok").to_string()
+    } else {
+        palette.error("FAILED").to_string()
+    };
+    writeln!(
+        writer,
+        "\ntest result: {}. {} passed; {} failed; {} ignored; 0 measured; {} filtered out; finished in {}\n",
+        result,
+        tally.passed + tally.xfailed,
+        tally.failed,
+        tally.ignored,
+        filtered_out,
+        format_duration(elapsed),
+    )
+}
+
+fn failure_label(output: &Output) -> &'static str {
+    match output.expected_failure {
+        Some(ExpectedFailure::Passed(_)) => "xpass",
+        _ => "failed",
+    }
+}
+
+fn write_event(event: serde_json::Value) {
+    let _ = writeln!(std::io::stdout(), "{event}");
+}
+
+fn json_events(result: &Result<Output, Output>) -> Vec<serde_json::Value> {
+    let (output, status) = match result {
+        Ok(output) if *output.spawn_status() == SpawnStatus::Skipped => (output, "ignored"),
+        Ok(output) if output.expected_failure.is_some() => (output, "xfail"),
+        Ok(output) if output.is_ok() => (output, "ok"),
+        Ok(output) | Err(output) => (output, failure_label(output)),
+    };
+    let reason = match &output.expected_failure {
+        Some(ExpectedFailure::Failed(reason) | ExpectedFailure::Passed(reason)) => Some(reason),
+        None => output.skip_reason.as_ref(),
+    };
+    let name = output.name();
+
+    let mut events = Vec::new();
+    if let Some(sandbox) = &output.sandbox {
+        events.push(serde_json::json!({
+            "type": "sandbox",
+            "name": name,
+            "path": sandbox.display().to_string(),
+            "kept": output.sandbox_kept,
+        }));
+    }
+    for stream in [&output.stdout, &output.stderr, &output.merged]
+        .into_iter()
+        .flatten()
+    {
+        if let StreamStatus::Expected { expected, similar } = &stream.status {
+            events.push(serde_json::json!({
+                "type": "diff",
+                "name": name,
+                "stream": stream.stream.as_str(),
+                "expected": expected.render(),
+                "actual": stream.content.render(),
+                "diff": render_diff(expected, &stream.content, &stream.stream, &stream.stream),
+                "similar": similar.as_ref().map(|p| p.display().to_string()),
+            }));
+        }
+        if let StreamStatus::ExpectedJson {
+            expected,
+            actual,
+            mismatches,
+        } = &stream.status
+        {
+            events.push(serde_json::json!({
+                "type": "diff",
+                "name": name,
+                "stream": stream.stream.as_str(),
+                "expected": expected.render(),
+                "actual": actual.render(),
+                "mismatches": mismatches,
+                "diff": render_diff(expected, actual, &stream.stream, &stream.stream),
+            }));
+        }
+        if let StreamStatus::Differences {
+            snapshot,
+            differences,
+        } = &stream.status
+        {
+            events.push(serde_json::json!({
+                "type": "diff",
+                "name": name,
+                "stream": stream.stream.as_str(),
+                "path": snapshot.display().to_string(),
+                "actual": stream.content.render(),
+                "differences": differences
+                    .iter()
+                    .map(|d| serde_json::json!({"location": d.location(), "message": d.message()}))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+    }
+    for file in &output.fs.context {
+        if let FileStatus::ContentMismatch {
+            expected_path,
+            actual_path,
+            expected_content,
+            actual_content,
+        } = file
+        {
+            events.push(serde_json::json!({
+                "type": "diff",
+                "name": name,
+                "path": expected_path.display().to_string(),
+                "expected": expected_content.render(),
+                "actual": actual_content.render(),
+                "diff": render_diff(
+                    expected_content,
+                    actual_content,
+                    &expected_path.display(),
+                    &actual_path.display(),
+                ),
+            }));
+        }
+    }
+
+    let message = (!output.is_ok()).then(|| strip_styling(output.to_string()));
+    events.push(serde_json::json!({
+        "type": "finished",
+        "name": name,
+        "status": status,
+        "exit": output.spawn.exit.map(crate::runner::describe_exit),
+        "duration": output.duration.map(|d| d.as_secs_f64()),
+        "reason": reason,
+        "description": output.description,
+        "links": output.links,
+        "warnings": output.warnings,
+        "message": message,
+    }));
+    events
+}
+
+fn render_diff(
+    expected: &crate::Data,
+    actual: &crate::Data,
+    expected_name: &dyn std::fmt::Display,
+    actual_name: &dyn std::fmt::Display,
+) -> String {
+    let mut diff = String::new();
+    let _ = snapbox::report::write_diff(
+        &mut diff,
+        expected,
+        actual,
+        Some(expected_name),
+        Some(actual_name),
+        snapbox::report::Palette::plain(),
+    );
+    diff
+}
+
+/// Machine-readable formats shouldn't carry terminal styling
+fn strip_styling(text: String) -> String {
+    if text.contains('\x1b') {
+        crate::compare::strip_ansi(&text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tap_skipped() {
+        let output = Output::sequence("cmd/case.toml".into());
+        let mut actual = Vec::new();
+        write_tap(&mut actual, 3, "cmd/case.toml".as_ref(), &[Ok(output)]).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "ok 3 - cmd/case.toml # SKIP\n"
+        );
+    }
+
+    #[test]
+    fn tap_no_steps() {
+        let mut actual = Vec::new();
+        write_tap(&mut actual, 3, "cmd/case.toml".as_ref(), &[]).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "not ok 3 - cmd/case.toml\n  ---\n  message: no steps were run\n  ...\n"
+        );
+    }
+
+    #[test]
+    fn tap_skipped_reason() {
+        let mut output = Output::sequence("cmd/case.toml".into());
+        output.skip_reason = Some("flaky on CI".into());
+        let mut actual = Vec::new();
+        write_tap(&mut actual, 3, "cmd/case.toml".as_ref(), &[Ok(output)]).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "ok 3 - cmd/case.toml # SKIP flaky on CI\n"
+        );
+    }
+
+    #[test]
+    fn json_failure() {
+        let mut output =
+            Output::step("cmd/case.toml".into(), "setup".into()).error("No bin specified".into());
+        output.sandbox = Some("/tmp/sandbox".into());
+        let actual = json_events(&Err(output))
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            [
+                r#"{"kept":false,"name":"cmd/case.toml:setup","path":"/tmp/sandbox","type":"sandbox"}"#,
+                r#"{"description":null,"duration":null,"exit":null,"links":[],"message":"Failed: No bin specified\n\n","name":"cmd/case.toml:setup","reason":null,"status":"failed","type":"finished","warnings":[]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn human_verbose() {
+        use std::os::unix::process::ExitStatusExt as _;
+
+        let mut output = Output::step("cmd/case.toml".into(), "1".into());
+        output.spawn.status = SpawnStatus::Ok;
+        output.spawn.exit = Some(std::process::ExitStatus::from_raw(0));
+        output.repro = Some("bin-fixture".into());
+        let human = |verbose| {
+            let mut actual = Vec::new();
+            write_human(
+                &mut actual,
+                &Ok(output.clone()),
+                verbose,
+                snapbox::report::Palette::plain(),
+            )
+            .unwrap();
+            strip_styling(String::from_utf8(actual).unwrap())
+        };
+        assert_eq!(human(0), "Testing cmd/case.toml:1 ... ok\n");
+        assert_eq!(
+            human(1),
+            "Testing cmd/case.toml:1 ... ok\nRan bin-fixture\n"
+        );
+        assert_eq!(
+            human(2),
+            "\
+Testing cmd/case.toml:1 ... ok
+Exit: success
+Reproduce with:
+  bin-fixture
+"
+        );
+    }
+
+    #[test]
+    fn tap_failure() {
+        let output =
+            Output::step("cmd/case.toml".into(), "setup".into()).error("No bin specified".into());
+        let mut actual = Vec::new();
+        write_tap(&mut actual, 1, "cmd/case.toml".as_ref(), &[Err(output)]).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "\
+not ok 1 - cmd/case.toml
+  ---
+  message: failed
+  steps:
+    - cmd/case.toml:setup: failed
+  diagnostics: |
+    Failed: No bin specified
+  ...
+"
+        );
+    }
+
+    #[test]
+    fn tap_multi_step() {
+        let mut first = Output::step("cmd/case.trycmd".into(), "3".into());
+        first.spawn.status = SpawnStatus::Ok;
+        let mut second = Output::step("cmd/case.trycmd".into(), "7".into());
+        second.spawn.status = SpawnStatus::Ok;
+        let mut actual = Vec::new();
+        write_tap(
+            &mut actual,
+            2,
+            "cmd/case.trycmd".as_ref(),
+            &[Ok(first.clone()), Ok(second.clone())],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "ok 2 - cmd/case.trycmd\n"
+        );
+
+        let second = second.error("No bin specified".into());
+        let mut actual = Vec::new();
+        write_tap(
+            &mut actual,
+            2,
+            "cmd/case.trycmd".as_ref(),
+            &[Ok(first), Err(second)],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "\
+not ok 2 - cmd/case.trycmd
+  ---
+  message: failed
+  steps:
+    - cmd/case.trycmd:3: ok
+    - cmd/case.trycmd:7: failed
+  diagnostics: |
+    Failed: No bin specified
+  ...
+"
+        );
+    }
+
+    #[test]
+    fn tap_expected_failure() {
+        let mut output =
+            Output::step("cmd/case.toml".into(), "setup".into()).error("No bin specified".into());
+        output.expected_failure = Some(ExpectedFailure::Failed("issue 42".into()));
+        let mut actual = Vec::new();
+        write_tap(&mut actual, 1, "cmd/case.toml".as_ref(), &[Ok(output)]).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "not ok 1 - cmd/case.toml # TODO issue 42\n"
+        );
+
+        let mut output = Output::step("cmd/case.toml".into(), "setup".into());
+        output.spawn.status = SpawnStatus::Ok;
+        output.expected_failure = Some(ExpectedFailure::Passed("issue 42".into()));
+        let mut actual = Vec::new();
+        write_tap(&mut actual, 1, "cmd/case.toml".as_ref(), &[Err(output)]).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "\
+not ok 1 - cmd/case.toml
+  ---
+  message: xpass
+  steps:
+    - cmd/case.toml:setup: xpass
+  diagnostics: |
+    Expected failure (issue 42) but passed; remove `expected-failure` if it is fixed
+  ...
+"
+        );
+    }
+
+    #[test]
+    fn libtest_summary() {
+        let mut tally = Tally::default();
+        let ok = Ok(Output::sequence("cmd/skipped.toml".into()));
+        let failed =
+            Err(Output::step("cmd/broken.toml".into(), "setup".into())
+                .error("No bin specified".into()));
+        tally.record(
+            std::path::Path::new("cmd/skipped.toml"),
+            std::slice::from_ref(&ok),
+            std::time::Duration::from_millis(10),
+        );
+        tally.record(
+            std::path::Path::new("cmd/broken.toml"),
+            std::slice::from_ref(&failed),
+            std::time::Duration::from_millis(10),
+        );
+
+        let mut actual = Vec::new();
+        for result in [&ok, &failed] {
+            write_libtest(&mut actual, result, snapbox::report::Palette::plain()).unwrap();
+        }
+        let failures = vec![(
+            "cmd/broken.toml:setup".to_owned(),
+            "Failed: No bin specified\n".to_owned(),
+        )];
+        write_libtest_summary(
+            &mut actual,
+            &tally,
+            &failures,
+            3,
+            std::time::Duration::from_secs(2),
+            snapbox::report::Palette::plain(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "\
+test cmd/skipped.toml ... ignored
+test cmd/broken.toml:setup ... FAILED
+
+failures:
+
+---- cmd/broken.toml:setup stdout ----
+Failed: No bin specified
+
+
+failures:
+    cmd/broken.toml:setup
+
+test result: FAILED. 0 passed; 1 failed; 1 ignored; 0 measured; 3 filtered out; finished in 2.00s
+
+"
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let mut tally = Tally::default();
+        tally.record(
+            std::path::Path::new("cmd/fast.toml"),
+            &[Ok(Output::sequence("cmd/fast.toml".into()))],
+            std::time::Duration::from_millis(10),
+        );
+        tally.record(
+            std::path::Path::new("cmd/slow.toml"),
+            &[Err(Output::step("cmd/slow.toml".into(), "setup".into())
+                .error("No bin specified".into()))],
+            std::time::Duration::from_millis(1500),
+        );
+        let elapsed = std::time::Duration::from_secs(2);
+
+        let mut actual = Vec::new();
+        write_summary(
+            &mut actual,
+            &tally,
+            elapsed,
+            0,
+            snapbox::report::Palette::plain(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "0 passed; 1 failed; 1 ignored; finished in 2.00s\n"
+        );
+
+        let mut actual = Vec::new();
+        write_summary(
+            &mut actual,
+            &tally,
+            elapsed,
+            1,
+            snapbox::report::Palette::plain(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "\
+Slowest cases:
+     1.50s cmd/slow.toml
+     0.01s cmd/fast.toml
+0 passed; 1 failed; 1 ignored; finished in 2.00s
+"
+        );
+    }
+}
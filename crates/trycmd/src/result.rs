@@ -0,0 +1,128 @@
+#[cfg(feature = "color")]
+use anstream::panic;
+
+/// How a [`Case::run`][crate::Case::run] went
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    name: String,
+    steps: Vec<StepResult>,
+    duration: std::time::Duration,
+}
+
+impl CaseResult {
+    pub(crate) fn new(
+        name: String,
+        steps: Vec<Result<crate::runner::Output, crate::runner::Output>>,
+        duration: std::time::Duration,
+    ) -> Self {
+        Self {
+            name,
+            steps: steps.into_iter().map(StepResult).collect(),
+            duration,
+        }
+    }
+
+    /// The name the case is reported under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether every step passed, was skipped, or failed as expected
+    pub fn is_ok(&self) -> bool {
+        self.steps.iter().all(StepResult::is_ok)
+    }
+
+    /// The commands run, in order, followed by the sandbox's teardown, if any
+    pub fn steps(&self) -> &[StepResult] {
+        &self.steps
+    }
+
+    /// How long the whole case took, including setup and teardown
+    pub fn duration(&self) -> std::time::Duration {
+        self.duration
+    }
+
+    /// The temporary directory the case ran in, when kept for inspection after a failure
+    pub fn sandbox(&self) -> Option<&std::path::Path> {
+        self.steps.iter().find_map(StepResult::sandbox)
+    }
+
+    /// Panic with the report of each failed step, if any
+    #[track_caller]
+    pub fn assert_ok(&self) {
+        if !self.is_ok() {
+            panic!("{}: case failed\n{}", self.name, self);
+        }
+    }
+}
+
+/// Reports each failed step, like the test runner would
+impl std::fmt::Display for CaseResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for step in self.steps.iter().filter(|s| !s.is_ok()) {
+            writeln!(f, "{}:", step.name())?;
+            step.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// How one command of a [`CaseResult`] went
+#[derive(Clone, Debug)]
+pub struct StepResult(Result<crate::runner::Output, crate::runner::Output>);
+
+impl StepResult {
+    /// The name the step is reported under, like `tests/cmd/multistep.trycmd:3`
+    pub fn name(&self) -> String {
+        self.output().name()
+    }
+
+    /// Whether the step passed, was skipped, or failed as expected
+    pub fn is_ok(&self) -> bool {
+        self.0.is_ok()
+    }
+
+    /// Whether the command wasn't run
+    pub fn is_skipped(&self) -> bool {
+        *self.output().spawn_status() == crate::runner::SpawnStatus::Skipped
+    }
+
+    /// How the command exited, if it was run
+    pub fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        self.output().spawn.exit
+    }
+
+    /// What the command wrote to `stdout`, normalized for comparison
+    pub fn stdout(&self) -> Option<&snapbox::Data> {
+        self.output().stdout.as_ref().map(|s| &s.content)
+    }
+
+    /// What the command wrote to `stderr`, normalized for comparison
+    pub fn stderr(&self) -> Option<&snapbox::Data> {
+        self.output().stderr.as_ref().map(|s| &s.content)
+    }
+
+    /// How long the command ran for
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        self.output().duration
+    }
+
+    /// The temporary directory the step ran in, when kept for inspection after a failure
+    pub fn sandbox(&self) -> Option<&std::path::Path> {
+        let output = self.output();
+        output.sandbox.as_deref().filter(|_| output.sandbox_kept)
+    }
+
+    fn output(&self) -> &crate::runner::Output {
+        match &self.0 {
+            Ok(output) | Err(output) => output,
+        }
+    }
+}
+
+/// Reports the exit status along with diffs for any streams or files that didn't match
+impl std::fmt::Display for StepResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.output().fmt(f)
+    }
+}
@@ -1,31 +1,35 @@
-use std::io::prelude::*;
-
-#[cfg(feature = "color")]
-use anstream::eprintln;
-#[cfg(feature = "color")]
-use anstream::panic;
-#[cfg(feature = "color")]
-use anstream::stderr;
-#[cfg(not(feature = "color"))]
-use std::eprintln;
-#[cfg(not(feature = "color"))]
-use std::io::stderr;
-
 use rayon::prelude::*;
 use snapbox::data::DataFormat;
 use snapbox::dir::FileType;
-use snapbox::filter::{Filter as _, FilterNewlines, FilterPaths, NormalizeToExpected};
+use snapbox::filter::{Filter as _, FilterPaths, NormalizeToExpected};
 use snapbox::IntoData;
 
 #[derive(Debug)]
 pub(crate) struct Runner {
     cases: Vec<Case>,
+    /// Cases left out by [`Runner::retain`]
+    filtered_out: usize,
+    /// Stop starting cases once this many have failed
+    max_failures: Option<usize>,
+    /// Stop starting cases once the run has taken this long
+    deadline: Option<std::time::Duration>,
+    /// Snapshots no case is named after
+    unused: Vec<std::path::PathBuf>,
+    /// Remove [`Runner::unused`] snapshots with `TRYCMD=overwrite`
+    remove_unused: bool,
+    tags: TagFilter,
 }
 
 impl Runner {
     pub(crate) fn new() -> Self {
         Self {
             cases: Default::default(),
+            filtered_out: 0,
+            max_failures: None,
+            deadline: None,
+            unused: Vec::new(),
+            remove_unused: false,
+            tags: TagFilter::default(),
         }
     }
 
@@ -33,107 +37,786 @@ impl Runner {
         self.cases.push(case);
     }
 
+    pub(crate) fn max_failures(&mut self, max: Option<usize>) {
+        self.max_failures = max;
+    }
+
+    pub(crate) fn deadline(&mut self, deadline: Option<std::time::Duration>) {
+        self.deadline = deadline;
+    }
+
+    pub(crate) fn tags(&mut self, tags: TagFilter) {
+        self.tags = tags;
+    }
+
+    pub(crate) fn unused(&mut self, unused: Vec<std::path::PathBuf>) {
+        self.unused = unused;
+    }
+
+    pub(crate) fn remove_unused(&mut self, yes: bool) {
+        self.remove_unused = yes;
+    }
+
+    /// Only keep the cases whose names match, and the cases they depend on
+    pub(crate) fn retain(&mut self, mut matches: impl FnMut(&str) -> bool) {
+        let before = self.cases.len();
+        self.retain_cases(|case| matches(&case.name().display().to_string()));
+        self.filtered_out += before - self.cases.len();
+    }
+
+    /// Only keep the cases `keep` accepts, and the cases they depend on, as with tags
+    pub(crate) fn retain_cases(&mut self, keep: impl FnMut(&Case) -> bool) {
+        let mut kept: Vec<_> = self.cases.iter().map(keep).collect();
+        if kept.iter().all(|k| *k) {
+            return;
+        }
+        let graph = Graph::new(&self.cases);
+        let mut pending: Vec<_> = (0..self.cases.len()).filter(|i| kept[*i]).collect();
+        while let Some(i) = pending.pop() {
+            for dep in &graph.deps[i] {
+                if !kept[*dep] {
+                    kept[*dep] = true;
+                    pending.push(*dep);
+                }
+            }
+        }
+        let mut kept = kept.into_iter();
+        self.cases.retain(|_| kept.next().unwrap_or(false));
+    }
+
+    /// How each case would run, for `TRYCMD=list`
+    pub(crate) fn describe(
+        &self,
+        bins: &crate::BinRegistry,
+        hooks: &crate::hooks::Hooks,
+    ) -> String {
+        self.cases
+            .iter()
+            .map(|case| case.describe(bins, hooks))
+            .collect()
+    }
+
+    /// Apply the `diff.patch` of each case's failure bundles, for `TRYCMD=accept`, returning the
+    /// files changed and the patches that didn't apply
+    pub(crate) fn accept(&self) -> (Vec<std::path::PathBuf>, Vec<String>) {
+        let mut changed = Vec::new();
+        let mut errors = Vec::new();
+        for case in &self.cases {
+            let Some(dir) = &case.artifacts else {
+                continue;
+            };
+            if case.built.is_some() {
+                continue;
+            }
+            match crate::artifacts::accept(dir, &case.name().display().to_string(), &case.path) {
+                Ok(files) => changed.extend(files),
+                Err(err) => errors.push(err.message().to_owned()),
+            }
+        }
+        (changed, errors)
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.cases
+            .iter()
+            .map(|case| case.name().display().to_string())
+    }
+
+    /// Run the cases, reporting the results, and how the run went
     pub(crate) fn run(
         &self,
         mode: &Mode,
+        reporter: crate::report::Reporter,
         bins: &crate::BinRegistry,
         substitutions: &snapbox::Redactions,
-    ) {
-        let palette = snapbox::report::Palette::color();
+        hooks: &crate::hooks::Hooks,
+    ) -> Outcome {
+        let reporter = reporter.filtered_out(self.filtered_out);
 
         if self.cases.is_empty() {
-            eprintln!("{}", palette.warn("There are no trycmd tests enabled yet"));
+            reporter.empty();
+            Outcome::OnlySkipped
         } else {
-            let failures: Vec<_> = self
-                .cases
-                .par_iter()
-                .flat_map(|c| {
-                    let results = c.run(mode, bins, substitutions);
-
-                    let stderr = stderr();
-                    let mut stderr = stderr.lock();
-
-                    results
-                        .into_iter()
-                        .filter_map(|s| {
-                            snapbox::debug!("Case: {:#?}", s);
-                            match s {
-                                Ok(status) => {
-                                    let _ = writeln!(
-                                        stderr,
-                                        "{} {} ... {}",
-                                        palette.hint("Testing"),
-                                        status.name(),
-                                        status.spawn.status.summary()
-                                    );
-                                    if !status.is_ok() {
-                                        // Assuming `status` will print the newline
-                                        let _ = write!(stderr, "{}", &status);
-                                    }
-                                    None
-                                }
-                                Err(status) => {
-                                    let _ = writeln!(
-                                        stderr,
-                                        "{} {} ... {}",
-                                        palette.hint("Testing"),
-                                        status.name(),
-                                        palette.error("failed"),
-                                    );
-                                    // Assuming `status` will print the newline
-                                    let _ = write!(stderr, "{}", &status);
-                                    Some(status)
-                                }
-                            }
+            reporter.start(self.cases.len());
+            let results = self.run_cases(mode, bins, substitutions, hooks, Some(&reporter));
+            let failures = results
+                .iter()
+                .flat_map(|(results, _)| results.iter().filter(|s| s.is_err()))
+                .count();
+            let deadline = self.deadline.map(deadline_reason);
+            let not_run = results
+                .iter()
+                .filter(|(results, _)| {
+                    deadline.is_some()
+                        && matches!(results.as_slice(), [Ok(o)] if o.skip_reason == deadline)
+                })
+                .count();
+            self.report_unused(mode, &reporter);
+            reporter.finish(failures);
+
+            let mut errors = Vec::new();
+            if failures != 0 {
+                errors.push(format!("{} of {} tests failed", failures, self.cases.len()));
+            }
+            if not_run != 0 {
+                errors.push(format!(
+                    "{} of {} tests {}",
+                    not_run,
+                    self.cases.len(),
+                    deadline.unwrap_or_default()
+                ));
+            }
+            let errored = results
+                .iter()
+                .flat_map(|(results, _)| results)
+                .any(|r| matches!(r, Err(o) if o.is_error()));
+            let skipped = results
+                .iter()
+                .flat_map(|(results, _)| results)
+                .all(|r| matches!(r, Ok(o) if o.spawn.status == SpawnStatus::Skipped));
+            match (errors.is_empty(), errored) {
+                (false, true) => Outcome::Errored(errors.join("; ")),
+                (false, false) => Outcome::Failed(errors.join("; ")),
+                (true, _) if skipped => Outcome::OnlySkipped,
+                (true, _) => Outcome::Passed,
+            }
+        }
+    }
+
+    /// Report snapshots no case uses, removing unused files if asked to
+    ///
+    /// Directories are only reported, as they may hold fixtures kept by hand.
+    fn report_unused(&self, mode: &Mode, reporter: &crate::report::Reporter) {
+        let remove = self.remove_unused && *mode == Mode::Overwrite;
+        for path in &self.unused {
+            let removed = remove && path.is_file() && std::fs::remove_file(path).is_ok();
+            reporter.unused(path, removed);
+        }
+    }
+
+    /// Run the cases without reporting, returning how each went, in order
+    pub(crate) fn collect(
+        &self,
+        mode: &Mode,
+        bins: &crate::BinRegistry,
+        substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
+    ) -> Vec<crate::CaseResult> {
+        self.run_cases(mode, bins, substitutions, hooks, None)
+            .into_iter()
+            .zip(&self.cases)
+            .map(|((results, elapsed), c)| {
+                crate::CaseResult::new(c.name().display().to_string(), results, elapsed)
+            })
+            .collect()
+    }
+
+    /// Run the cases, each after the cases it `depends-on`, returning how each went, in order
+    fn run_cases(
+        &self,
+        mode: &Mode,
+        bins: &crate::BinRegistry,
+        substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
+        reporter: Option<&crate::report::Reporter>,
+    ) -> Vec<(Vec<Result<Output, Output>>, std::time::Duration)> {
+        let graph = Graph::new(&self.cases);
+        let mut focused: Vec<_> = self.cases.par_iter().map(Case::is_focused).collect();
+        let any_focused = focused.contains(&true);
+        let mut deselected: Vec<_> = self
+            .cases
+            .par_iter()
+            .map(|c| c.deselected(&self.tags))
+            .collect();
+        // A focused or selected case still needs what it depends on
+        let mut pending: Vec<_> = (0..self.cases.len())
+            .filter(|i| focused[*i] || deselected[*i].is_none())
+            .collect();
+        while let Some(i) = pending.pop() {
+            for dep in &graph.deps[i] {
+                if focused[i] && !focused[*dep] {
+                    focused[*dep] = true;
+                    pending.push(*dep);
+                }
+                if deselected[i].is_none() && deselected[*dep].is_some() {
+                    deselected[*dep] = None;
+                    pending.push(*dep);
+                }
+            }
+        }
+        let exports = match graph.exported.contains(&true) {
+            true => match export_root(&self.cases) {
+                Ok(root) => Some(root),
+                Err(err) => {
+                    return self
+                        .cases
+                        .iter()
+                        .map(|c| {
+                            let output = Output::step(c.name(), "setup".into());
+                            (vec![Err(output.error(err.clone()))], Default::default())
                         })
-                        .collect::<Vec<_>>()
+                        .collect();
+                }
+            },
+            false => None,
+        };
+        let export_dir = |i: usize| {
+            exports
+                .as_ref()
+                .and_then(|root| root.path())
+                .map(|root| root.join(i.to_string()))
+        };
+
+        let started = std::time::Instant::now();
+        let failed = std::sync::atomic::AtomicUsize::new(0);
+        let mut results: Vec<_> = self.cases.iter().map(|_| None).collect();
+        for level in &graph.levels {
+            let level_results: Vec<_> = level
+                .par_iter()
+                .map(|&i| {
+                    let c = &self.cases[i];
+                    if let Some(reporter) = reporter {
+                        reporter.started(&c.name());
+                    }
+                    let start = std::time::Instant::now();
+                    let blocked = graph.deps[i].iter().find_map(|dep| {
+                        let (dep_results, _): &(Vec<Result<Output, Output>>, _) =
+                            results[*dep].as_ref().expect("dependencies run first");
+                        let dep_name = self.cases[*dep].name();
+                        if dep_results.iter().any(|r| r.is_err()) {
+                            Some(format!("`{}` failed", dep_name.display()))
+                        } else if dep_results
+                            .iter()
+                            .all(|r| matches!(r, Ok(o) if o.spawn.status == SpawnStatus::Skipped))
+                        {
+                            Some(format!("`{}` was skipped", dep_name.display()))
+                        } else {
+                            None
+                        }
+                    });
+                    let stopped = self
+                        .max_failures
+                        .filter(|max| failed.load(std::sync::atomic::Ordering::SeqCst) >= *max);
+                    let past_deadline = self.deadline.filter(|d| started.elapsed() >= *d);
+                    let case_results = if let Some(deadline) = past_deadline {
+                        let mut output = Output::sequence(c.name());
+                        output.skip_reason = Some(deadline_reason(deadline));
+                        vec![Ok(output)]
+                    } else if let Some(max) = stopped {
+                        let mut output = Output::sequence(c.name());
+                        output.skip_reason = Some(format!("stopped after {max} failed"));
+                        vec![Ok(output)]
+                    } else if let Some(err) = &graph.errors[i] {
+                        let output = Output::step(c.name(), "setup".into());
+                        vec![Err(output.error(err.clone()))]
+                    } else if let Some(reason) = blocked {
+                        let mut output = Output::sequence(c.name());
+                        output.skip_reason = Some(reason);
+                        vec![Ok(output)]
+                    } else {
+                        let links = Links {
+                            base: graph.deps[i]
+                                .iter()
+                                .rev()
+                                .filter_map(|dep| export_dir(*dep))
+                                .find(|dir| dir.is_dir()),
+                            export: graph.exported[i].then(|| export_dir(i)).flatten(),
+                        };
+                        let skip_reason = if any_focused && !focused[i] {
+                            Some("other cases are marked `only`".to_owned())
+                        } else {
+                            deselected[i].clone()
+                        };
+                        c.run_unless_skipped(skip_reason, &links, mode, bins, substitutions, hooks)
+                    };
+                    if case_results.iter().any(|r| r.is_err()) {
+                        failed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    }
+                    let elapsed = start.elapsed();
+                    if let Some(reporter) = reporter {
+                        reporter.case(&c.name(), &case_results, elapsed);
+                    }
+                    (i, case_results, elapsed)
                 })
                 .collect();
-
-            if !failures.is_empty() {
-                let stderr = stderr();
-                let mut stderr = stderr.lock();
-                let _ = writeln!(
-                    stderr,
-                    "{}",
-                    palette.hint("Update snapshots with `TRYCMD=overwrite`"),
-                );
-                let _ = writeln!(
-                    stderr,
-                    "{}",
-                    palette.hint("Debug output with `TRYCMD=dump`"),
-                );
-                panic!("{} of {} tests failed", failures.len(), self.cases.len());
+            for (i, case_results, elapsed) in level_results {
+                results[i] = Some((case_results, elapsed));
             }
         }
+        results
+            .into_iter()
+            .map(|result| result.expect("every case is in a level"))
+            .collect()
     }
 }
 
+/// How a run went, for the harness's exit code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Passed,
+    /// Nothing failed, as nothing ran
+    OnlySkipped,
+    /// Commands didn't do what was expected, with the summary
+    Failed(String),
+    /// Cases couldn't be loaded or their commands started, with the summary
+    Errored(String),
+}
+
+impl Outcome {
+    pub(crate) fn into_result(self) -> Result<(), String> {
+        match self {
+            Self::Passed | Self::OnlySkipped => Ok(()),
+            Self::Failed(summary) | Self::Errored(summary) => Err(summary),
+        }
+    }
+}
+
+/// Why cases weren't started, once the run took longer than `deadline`
+fn deadline_reason(deadline: std::time::Duration) -> String {
+    format!(
+        "not run before the {} deadline",
+        humantime::format_duration(deadline)
+    )
+}
+
 impl Default for Runner {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Which cases to run by their `tags`
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub(crate) struct TagFilter {
+    /// Run only the cases with at least one of these, if any
+    pub(crate) include: Vec<String>,
+    /// Skip the cases with any of these
+    pub(crate) exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Parse `TRYCMD_TAGS`, like `fast,!network`, where `!` excludes a tag
+    pub(crate) fn parse(var: &str) -> Self {
+        let mut filter = Self::default();
+        for tag in var.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match tag.strip_prefix('!') {
+                Some(tag) => filter.exclude.push(tag.to_owned()),
+                None => filter.include.push(tag.to_owned()),
+            }
+        }
+        filter
+    }
+
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Why a case with `tags` is left out, if it is
+    fn deselects(&self, tags: &[String]) -> Option<String> {
+        if let Some(tag) = tags.iter().find(|t| self.exclude.contains(t)) {
+            return Some(format!("tagged `{tag}`"));
+        }
+        if !self.include.is_empty() && !tags.iter().any(|t| self.include.contains(t)) {
+            let wanted = self
+                .include
+                .iter()
+                .map(|t| format!("`{t}`"))
+                .collect::<Vec<_>>()
+                .join(" or ");
+            return Some(format!("not tagged {wanted}"));
+        }
+        None
+    }
+}
+
+/// Which cases run after which, from `depends-on` and `order`
+#[derive(Debug)]
+struct Graph {
+    /// The cases each case depends on, by index
+    deps: Vec<Vec<usize>>,
+    /// Whether other cases depend on each case, so its sandbox needs to be kept for them
+    exported: Vec<bool>,
+    /// Why each case's dependencies can't be run
+    errors: Vec<Option<crate::Error>>,
+    /// Cases that can run in parallel, each level after the ones before it
+    levels: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    fn new(cases: &[Case]) -> Self {
+        let mut order = Vec::with_capacity(cases.len());
+        let mut deps = Vec::with_capacity(cases.len());
+        let mut errors = Vec::with_capacity(cases.len());
+        for (i, case) in cases.iter().enumerate() {
+            let (case_order, depends_on) = case.schedule();
+            order.push(case_order);
+            let mut case_deps = Vec::new();
+            let mut error = None;
+            for name in depends_on {
+                match (0..cases.len()).find(|j| *j != i && cases[*j].is_named(&name, &case.path)) {
+                    Some(dep) => case_deps.push(dep),
+                    None => {
+                        error = Some(crate::Error::new(format!(
+                            "`depends-on` names `{name}`, which isn't among the cases being run"
+                        )));
+                        case_deps.clear();
+                        break;
+                    }
+                }
+            }
+            deps.push(case_deps);
+            errors.push(error);
+        }
+
+        let mut placed = vec![false; cases.len()];
+        let mut levels = Vec::new();
+        loop {
+            let ready: Vec<_> = (0..cases.len())
+                .filter(|i| !placed[*i] && deps[*i].iter().all(|dep| placed[*dep]))
+                .collect();
+            let Some(first) = ready.iter().map(|i| order[*i]).min() else {
+                // Cases left depending on a cycle are then blocked by it, like by a failure
+                let circular: Vec<_> = (0..cases.len())
+                    .filter(|i| !placed[*i] && is_circular(&deps, &placed, *i))
+                    .collect();
+                if circular.is_empty() {
+                    break;
+                }
+                for i in &circular {
+                    deps[*i].clear();
+                    errors[*i] = Some(crate::Error::new("`depends-on` is circular"));
+                    placed[*i] = true;
+                }
+                levels.push(circular);
+                continue;
+            };
+            let level: Vec<_> = ready.into_iter().filter(|i| order[*i] == first).collect();
+            for i in &level {
+                placed[*i] = true;
+            }
+            levels.push(level);
+        }
+
+        let mut exported = vec![false; cases.len()];
+        for dep in deps.iter().flatten() {
+            exported[*dep] = true;
+        }
+
+        Self {
+            deps,
+            exported,
+            errors,
+            levels,
+        }
+    }
+}
+
+/// Whether case `i` depends on itself through cases not yet `placed`
+fn is_circular(deps: &[Vec<usize>], placed: &[bool], i: usize) -> bool {
+    let mut seen = vec![false; deps.len()];
+    let mut pending = deps[i].clone();
+    while let Some(dep) = pending.pop() {
+        if dep == i {
+            return true;
+        }
+        if !placed[dep] && !seen[dep] {
+            seen[dep] = true;
+            pending.extend(deps[dep].iter().copied());
+        }
+    }
+    false
+}
+
+/// Where to share a sandbox between a case and the cases that `depends-on` it
+#[derive(Clone, Default, Debug)]
+struct Links {
+    /// Start from this copy of a dependency's sandbox, rather than `fs.base`
+    base: Option<std::path::PathBuf>,
+    /// Copy the sandbox here once the steps have run
+    export: Option<std::path::PathBuf>,
+}
+
+/// Where to keep the sandboxes that dependents start from
+fn export_root(cases: &[Case]) -> Result<snapbox::dir::DirRoot, crate::Error> {
+    #[cfg(feature = "filesystem")]
+    {
+        let root = match cases.iter().find_map(|c| c.sandbox_root.as_deref()) {
+            Some(sandbox_root) => snapbox::dir::DirRoot::mutable_temp_in(sandbox_root)?,
+            None => snapbox::dir::DirRoot::mutable_temp()?,
+        };
+        Ok(root)
+    }
+    #[cfg(not(feature = "filesystem"))]
+    {
+        let _ = cases;
+        Ok(snapbox::dir::DirRoot::none())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Case {
     pub(crate) path: std::path::PathBuf,
+    /// What the case is reported as, rather than `path`, like `subcmd::users::add`
+    pub(crate) name: Option<String>,
     pub(crate) expected: Option<crate::schema::CommandStatus>,
     pub(crate) timeout: Option<std::time::Duration>,
+    /// Command to run each program under, unless the case sets `wrapper`
+    pub(crate) wrapper: Vec<String>,
     pub(crate) default_bin: Option<crate::schema::Bin>,
     pub(crate) env: crate::schema::Env,
+    /// Where to create sandboxes, rather than the OS's temporary directory
+    pub(crate) sandbox_root: Option<std::path::PathBuf>,
     pub(crate) error: Option<SpawnStatus>,
+    /// Defined with [`crate::Case`], rather than loaded from `path`
+    pub(crate) built: Option<crate::schema::TryCmd>,
+    /// The variable to pass the case's seed in, and the seed
+    pub(crate) seed: Option<(String, u64)>,
+    /// Where to write a bundle of what failed, for CI to upload
+    pub(crate) artifacts: Option<std::path::PathBuf>,
 }
 
 impl Case {
     pub(crate) fn with_error(path: std::path::PathBuf, error: crate::Error) -> Self {
         Self {
             path,
+            name: None,
             expected: None,
             timeout: None,
+            wrapper: Vec::new(),
             default_bin: None,
             env: Default::default(),
+            sandbox_root: None,
+            seed: None,
+            artifacts: None,
             error: Some(SpawnStatus::Failure(error)),
+            built: None,
+        }
+    }
+
+    /// What the case is reported as
+    pub(crate) fn name(&self) -> std::path::PathBuf {
+        match &self.name {
+            Some(name) => name.into(),
+            None => self.path.clone(),
+        }
+    }
+
+    /// The case's name, then where and how each of its commands would run, without running them
+    pub(crate) fn describe(
+        &self,
+        bins: &crate::BinRegistry,
+        hooks: &crate::hooks::Hooks,
+    ) -> String {
+        let mut description = format!("{}\n", self.name().display());
+        if self.expected == Some(crate::schema::CommandStatus::Skipped) {
+            description.push_str("  skipped\n");
+            return description;
+        }
+        if let Some(err) = &self.error {
+            let message = match err {
+                SpawnStatus::Failure(err) => err.message().to_owned(),
+                _ => "invalid case".to_owned(),
+            };
+            description.push_str(&format!("  error: {message}\n"));
+            return description;
+        }
+        let sequence = match self.load() {
+            Ok(sequence) => sequence,
+            Err(err) => {
+                description.push_str(&format!("  error: {}\n", err.message()));
+                return description;
+            }
+        };
+
+        let cwd = sequence
+            .fs
+            .cwd
+            .clone()
+            .or_else(|| std::env::current_dir().ok());
+        if let Some(cwd) = cwd {
+            description.push_str(&format!("  cwd: {}\n", cwd.display()));
+        }
+        let sandbox = if sequence.fs.sandbox() { "yes" } else { "no" };
+        description.push_str(&format!("  sandbox: {sandbox}\n"));
+        for step in &sequence.steps {
+            let mut stages = vec![(step.bin.as_ref(), step.args.as_slice())];
+            stages.extend(
+                step.pipe
+                    .iter()
+                    .map(|stage| (stage.bin.as_ref(), &stage.args[..])),
+            );
+            let pipeline = stages
+                .into_iter()
+                .map(|(bin, args)| {
+                    let bin = describe_bin(bin.or(self.default_bin.as_ref()), bins, hooks);
+                    std::iter::once(bin)
+                        .chain(args.iter().map(|arg| {
+                            shlex::try_quote(arg)
+                                .map(|a| a.into_owned())
+                                .unwrap_or_else(|_| arg.clone())
+                        }))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join(" | ");
+            match &step.id {
+                Some(id) => description.push_str(&format!("  {id}: $ {pipeline}\n")),
+                None => description.push_str(&format!("  $ {pipeline}\n")),
+            }
+        }
+        description
+    }
+
+    fn load(&self) -> Result<crate::schema::TryCmd, crate::Error> {
+        match &self.built {
+            Some(sequence) => Ok(sequence.clone()),
+            None => crate::schema::TryCmd::load(&self.path),
+        }
+    }
+
+    /// Paths the case reads beyond the snapshots named after it, like a shared `fs.base`
+    pub(crate) fn referenced_paths(&self) -> Vec<std::path::PathBuf> {
+        let mut paths = Vec::new();
+        if self.error.is_some() {
+            return paths;
+        }
+        if self.built.is_none() {
+            paths.extend(crate::schema::includes(&self.path));
+        }
+        if let Ok(sequence) = self.load() {
+            paths.extend(sequence.fs.base);
+            paths.extend(sequence.fs.cwd);
+            paths.extend(
+                sequence
+                    .http_stubs
+                    .into_iter()
+                    .filter_map(|stub| stub.body_file),
+            );
+        }
+        paths
+    }
+
+    /// Write `self.path` to run `cmdline`, then run it, recording its output next to it
+    pub(crate) fn record(
+        &self,
+        cmdline: &str,
+        bins: &crate::BinRegistry,
+        substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
+    ) -> Result<(), crate::Error> {
+        let cmdline = cmdline.trim();
+        let cmdline = cmdline.strip_prefix("$ ").unwrap_or(cmdline);
+        let words = shlex::split(cmdline)
+            .ok_or_else(|| crate::Error::ParseTryCmd(format!("Invalid quoting in `{cmdline}`")))?;
+        let (bin, args) = words
+            .split_first()
+            .ok_or_else(|| crate::Error::ParseTryCmd("No command to record".to_owned()))?;
+        if self.path.exists() {
+            return Err(crate::Error::Io(format!(
+                "{} already exists",
+                self.path.display()
+            )));
+        }
+
+        let mut doc = toml_edit::DocumentMut::new();
+        let mut bin_table = toml_edit::Table::new();
+        bin_table.set_dotted(true);
+        bin_table["name"] = toml_edit::value(bin);
+        doc["bin"] = toml_edit::Item::Table(bin_table);
+        doc["args"] = toml_edit::value(args.iter().collect::<toml_edit::Array>());
+        if self.path.with_extension("in").is_dir() {
+            let mut fs_table = toml_edit::Table::new();
+            fs_table.set_dotted(true);
+            fs_table["sandbox"] = toml_edit::value(true);
+            doc["fs"] = toml_edit::Item::Table(fs_table);
+        }
+        std::fs::write(&self.path, doc.to_string()).map_err(|e| {
+            crate::Error::Io(format!("Failed to write {}: {}", self.path.display(), e))
+        })?;
+
+        // Dumping alongside the case writes the `.stdout`, `.stderr`, and `.out` it uses
+        let root = match self.path.parent() {
+            Some(parent) if parent != std::path::Path::new("") => parent.to_owned(),
+            _ => std::path::PathBuf::from("."),
+        };
+        let outputs = self.run(&Mode::Dump(root), bins, substitutions, hooks);
+        let outputs: Vec<_> = outputs
+            .into_iter()
+            .map(|output| output.unwrap_or_else(|output| output))
+            .collect();
+        let Some(exit) = outputs.iter().find_map(|output| output.spawn.exit) else {
+            let _ = std::fs::remove_file(&self.path);
+            let err = outputs
+                .into_iter()
+                .find_map(|output| match output.spawn.status {
+                    SpawnStatus::Failure(err) => Some(err),
+                    _ => None,
+                });
+            return Err(err.unwrap_or_else(|| {
+                crate::Error::SpawnFailed(format!("Failed to run `{cmdline}`"))
+            }));
+        };
+        let sequence = crate::schema::TryCmd::load(&self.path)?;
+        sequence.overwrite(&self.path, None, None, None, None, Some(exit))
+    }
+
+    /// Whether the case is marked `only = true`
+    fn is_focused(&self) -> bool {
+        if self.expected == Some(crate::schema::CommandStatus::Skipped) || self.error.is_some() {
+            return false;
+        }
+        self.load().map(|sequence| sequence.only).unwrap_or(false)
+    }
+
+    /// The case's `order` and the names of the cases it `depends-on`
+    fn schedule(&self) -> (i32, Vec<String>) {
+        if self.expected == Some(crate::schema::CommandStatus::Skipped) || self.error.is_some() {
+            return Default::default();
+        }
+        self.load()
+            .map(|sequence| (sequence.order, sequence.depends_on))
+            .unwrap_or_default()
+    }
+
+    /// Whether this case is what `name` refers to in `depends-on` from a case at `from`
+    fn is_named(&self, name: &str, from: &std::path::Path) -> bool {
+        self.name().to_str() == Some(name)
+            || (self.path.parent() == from.parent()
+                && self.path.file_stem().and_then(|s| s.to_str()) == Some(name))
+    }
+
+    /// Why the case's `tags` leave it out, if they do
+    fn deselected(&self, tags: &TagFilter) -> Option<String> {
+        if tags.is_empty()
+            || self.expected == Some(crate::schema::CommandStatus::Skipped)
+            || self.error.is_some()
+        {
+            return None;
+        }
+        let case_tags = self
+            .load()
+            .map(|sequence| sequence.tags)
+            .unwrap_or_default();
+        tags.deselects(&case_tags)
+    }
+
+    /// Run the case, or skip it for `skip_reason`, like other cases being marked `only = true`
+    fn run_unless_skipped(
+        &self,
+        skip_reason: Option<String>,
+        links: &Links,
+        mode: &Mode,
+        bins: &crate::BinRegistry,
+        substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
+    ) -> Vec<Result<Output, Output>> {
+        if let Some(reason) = skip_reason {
+            let mut output = Output::sequence(self.name());
+            output.skip_reason = Some(reason);
+            vec![Ok(output)]
+        } else {
+            self.run_linked(links, mode, bins, substitutions, hooks)
         }
     }
 
@@ -142,47 +825,151 @@ impl Case {
         mode: &Mode,
         bins: &crate::BinRegistry,
         substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
+    ) -> Vec<Result<Output, Output>> {
+        self.run_linked(&Links::default(), mode, bins, substitutions, hooks)
+    }
+
+    fn run_linked(
+        &self,
+        links: &Links,
+        mode: &Mode,
+        bins: &crate::BinRegistry,
+        substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
     ) -> Vec<Result<Output, Output>> {
         if self.expected == Some(crate::schema::CommandStatus::Skipped) {
-            let output = Output::sequence(self.path.clone());
+            let output = Output::sequence(self.name());
             assert_eq!(output.spawn.status, SpawnStatus::Skipped);
             return vec![Ok(output)];
         }
 
         if let Some(err) = self.error.clone() {
-            let mut output = Output::step(self.path.clone(), "setup".into());
+            let mut output = Output::step(self.name(), "setup".into());
             output.spawn.status = err;
             return vec![Err(output)];
         }
 
-        let mut sequence = match crate::schema::TryCmd::load(&self.path) {
+        let sequence = match self.load() {
             Ok(sequence) => sequence,
             Err(e) => {
-                let output = Output::step(self.path.clone(), "setup".into());
+                let output = Output::step(self.name(), "setup".into());
                 return vec![Err(output.error(e))];
             }
         };
 
         if sequence.steps.is_empty() {
-            let output = Output::sequence(self.path.clone());
+            let output = Output::sequence(self.name());
             assert_eq!(output.spawn.status, SpawnStatus::Skipped);
             return vec![Ok(output)];
         }
 
+        if sequence.matrix {
+            let crate::schema::TryCmd {
+                steps,
+                fs,
+                matrix,
+                only,
+                expected_failure,
+                substitutions: case_substitutions,
+                depends_on,
+                order,
+                tags,
+                description,
+                links: case_links,
+                warnings,
+                http_stubs,
+            } = sequence;
+            return steps
+                .into_iter()
+                .flat_map(|step| {
+                    let sequence = crate::schema::TryCmd {
+                        steps: vec![step],
+                        fs: fs.clone(),
+                        matrix,
+                        only,
+                        expected_failure: expected_failure.clone(),
+                        substitutions: case_substitutions.clone(),
+                        depends_on: depends_on.clone(),
+                        order,
+                        tags: tags.clone(),
+                        description: description.clone(),
+                        links: case_links.clone(),
+                        warnings: warnings.clone(),
+                        http_stubs: http_stubs.clone(),
+                    };
+                    self.run_sequence(sequence, links, mode, bins, substitutions, hooks)
+                })
+                .collect();
+        }
+
+        self.run_sequence(sequence, links, mode, bins, substitutions, hooks)
+    }
+
+    fn run_sequence(
+        &self,
+        mut sequence: crate::schema::TryCmd,
+        links: &Links,
+        mode: &Mode,
+        bins: &crate::BinRegistry,
+        substitutions: &snapbox::Redactions,
+        hooks: &crate::hooks::Hooks,
+    ) -> Vec<Result<Output, Output>> {
+        if let Some(base) = &links.base {
+            // Start from where the dependency left off, keeping the same relative `fs.cwd`
+            let rel_cwd = match sequence.fs.rel_cwd() {
+                Ok(rel_cwd) => rel_cwd.to_owned(),
+                Err(e) => {
+                    let output = Output::step(self.name(), "setup".into());
+                    return vec![Err(output.error(e))];
+                }
+            };
+            sequence.fs.cwd = Some(base.join(rel_cwd));
+            sequence.fs.base = Some(base.clone());
+            sequence.fs.sandbox = Some(true);
+        }
+        let ignore = match crate::ignore::Ignore::load(&sequence.fs) {
+            Ok(ignore) => ignore,
+            Err(e) => {
+                let output = Output::step(self.name(), "setup".into());
+                return vec![Err(output.error(e))];
+            }
+        };
+        // Linked files share the fixture's inode, so writing one in place changes `fs.base`
+        let base_stamps = match sequence.fs.base.as_deref() {
+            Some(base) if sequence.fs.base_readonly && sequence.fs.sandbox() => {
+                match file_stamps(base) {
+                    Ok(stamps) => Some((base.to_owned(), stamps)),
+                    Err(e) => {
+                        let output = Output::step(self.name(), "setup".into());
+                        return vec![Err(output.error(e))];
+                    }
+                }
+            }
+            _ => None,
+        };
         let fs_context = match fs_context(
             &self.path,
             sequence.fs.base.as_deref(),
             sequence.fs.sandbox(),
+            sequence.fs.base_readonly,
+            self.sandbox_root.as_deref(),
             mode,
+            &ignore,
         ) {
             Ok(fs_context) => fs_context,
             Err(e) => {
-                let output = Output::step(self.path.clone(), "setup".into());
-                return vec![Err(
-                    output.error(format!("Failed to initialize sandbox: {}", e).into())
-                )];
+                let output = Output::step(self.name(), "setup".into());
+                return vec![Err(output.error(crate::Error::Io(format!(
+                    "Failed to initialize sandbox: {}",
+                    e.message()
+                ))))];
             }
         };
+        let sandbox = fs_context
+            .is_mutable()
+            .then(|| fs_context.path().map(|p| p.to_owned()))
+            .flatten();
         let cwd = match fs_context
             .path()
             .map(|p| {
@@ -195,10 +982,40 @@ impl Case {
         {
             Ok(cwd) => cwd.or_else(|| std::env::current_dir().ok()),
             Err(e) => {
-                let output = Output::step(self.path.clone(), "setup".into());
+                let output = Output::step(self.name(), "setup".into());
                 return vec![Err(output.error(e))];
             }
         };
+        if !sequence.fs.expect.is_empty() && !fs_context.is_mutable() {
+            let output = Output::step(self.name(), "setup".into());
+            return vec![Err(output.error(crate::Error::ParseToml(
+                "`[[fs.expect]]` needs `fs.sandbox = true`".to_owned(),
+            )))];
+        }
+        if let Err(e) = write_files(
+            fs_context.path(),
+            &sequence.fs.files,
+            fs_context.is_mutable(),
+        ) {
+            let output = Output::step(self.name(), "setup".into());
+            return vec![Err(output.error(e))];
+        }
+        if let Some(cwd) = cwd.as_deref() {
+            if let Err(e) = ensure_cwd(cwd, &sequence.fs, fs_context.is_mutable()) {
+                let output = Output::step(self.name(), "setup".into());
+                return vec![Err(output.error(e))];
+            }
+        }
+        let files_before = match (sandbox.as_deref(), sequence.fs.max_new_files) {
+            (Some(root), Some(_)) => match sandbox_usage(root) {
+                Ok((files, _)) => files,
+                Err(e) => {
+                    let output = Output::step(self.name(), "setup".into());
+                    return vec![Err(output.error(e))];
+                }
+            },
+            _ => Default::default(),
+        };
         let mut substitutions = substitutions.clone();
         if let Some(root) = fs_context.path() {
             substitutions.insert("[ROOT]", root.to_owned()).unwrap();
@@ -209,20 +1026,167 @@ impl Case {
         substitutions
             .insert("[EXE]", std::env::consts::EXE_SUFFIX)
             .unwrap();
+        for (placeholder, value) in &sequence.substitutions {
+            if let Err(e) = substitutions.insert(intern_placeholder(placeholder), value.clone()) {
+                let output = Output::step(self.name(), "setup".into());
+                return vec![Err(output.error(e.into()))];
+            }
+        }
+        let mut case_expansions = Vec::new();
+        for placeholder in crate::port::placeholders(&sequence.steps) {
+            let placeholder = intern_placeholder(&placeholder);
+            let port = match crate::port::reserve() {
+                Ok(port) => port.to_string(),
+                Err(e) => {
+                    let output = Output::step(self.name(), "setup".into());
+                    return vec![Err(output.error(e))];
+                }
+            };
+            substitutions.insert(placeholder, port.clone()).unwrap();
+            case_expansions.push((placeholder, port));
+        }
+        // Stopped once the case's steps, including `background` ones, are done
+        let _http_stub = if sequence.http_stubs.is_empty() {
+            None
+        } else {
+            let server = match crate::http_stub::Server::start(&sequence.http_stubs) {
+                Ok(server) => server,
+                Err(e) => {
+                    let output = Output::step(self.name(), "setup".into());
+                    return vec![Err(output.error(e))];
+                }
+            };
+            substitutions
+                .insert("[HTTP_STUB]", server.url().to_owned())
+                .unwrap();
+            case_expansions.push(("[HTTP_STUB]", server.url().to_owned()));
+            for step in &mut sequence.steps {
+                step.env
+                    .add
+                    .entry("TRYCMD_HTTP_STUB".to_owned())
+                    .or_insert_with(|| server.url().to_owned());
+            }
+            Some(server)
+        };
+        for step in &mut sequence.steps {
+            expand_args_and_env(step, &case_expansions);
+        }
         snapbox::debug!("{:?}", substitutions);
+        let mut expansions = vec![("[EXE]", std::env::consts::EXE_SUFFIX.to_owned())];
+        expansions.extend(case_expansions);
+        if let Some(root) = fs_context.path() {
+            expansions.push(("[ROOT]", root.display().to_string()));
+        }
+        if let Some(cwd) = cwd.clone().or_else(|| std::env::current_dir().ok()) {
+            expansions.push(("[CWD]", cwd.display().to_string()));
+        }
+
+        let name = match (sequence.matrix, &sequence.steps[0].id) {
+            (true, Some(id)) => format!("{}:{}", self.name().display(), id),
+            _ => self.name().display().to_string(),
+        };
+        let context = crate::CaseContext {
+            path: &self.path,
+            name: &name,
+            sandbox: sandbox.as_deref(),
+            cwd: cwd.as_deref(),
+        };
 
         let mut outputs = Vec::with_capacity(sequence.steps.len());
         let mut prior_step_failed = false;
-        for step in &mut sequence.steps {
-            if prior_step_failed {
-                step.expected_status = Some(crate::schema::CommandStatus::Skipped);
-            }
+        let setup = hooks.setup(&context);
+        if let Err(err) = &setup {
+            let output = Output::step(self.name(), "setup".into());
+            outputs.push(Err(output.error(err.clone())));
+            prior_step_failed = true;
+        }
+        let mut captured_env = crate::schema::Env::default();
+        std::thread::scope(|scope| {
+            let mut running = Vec::new();
+            for step in &mut sequence.steps {
+                if prior_step_failed {
+                    step.expected_status = Some(crate::schema::CommandStatus::Skipped);
+                }
 
-            let step_status = self.run_step(step, cwd.as_deref(), bins, &substitutions);
-            if fs_context.is_mutable() && step_status.is_err() && *mode == Mode::Fail {
-                prior_step_failed = true;
+                let mut env = captured_env.clone();
+                env.update(&step.env);
+                step.env = env;
+                if let Some(background) = step.background.clone() {
+                    let mut step = step.clone();
+                    let (cwd, substitutions, expansions) =
+                        (cwd.as_deref(), &substitutions, &expansions);
+                    // In case it never gets to starting the command
+                    let started = background.ready_on_drop();
+                    #[allow(clippy::result_large_err)]
+                    let handle = scope.spawn(move || {
+                        let _started = started;
+                        self.run_step(&mut step, cwd, bins, substitutions, expansions, hooks)
+                    });
+                    background.wait_ready();
+                    // Filled in once the step is stopped, to keep the steps in order
+                    running.push((outputs.len(), background, handle));
+                    outputs.push(Ok(Output::sequence(self.name())));
+                    continue;
+                }
+                let step_status = self.run_step(
+                    step,
+                    cwd.as_deref(),
+                    bins,
+                    &substitutions,
+                    &expansions,
+                    hooks,
+                );
+                let step_status = match (step.capture_env.as_ref(), step_status) {
+                    (Some(capture), Ok(output)) => {
+                        match capture_env(capture, &output, cwd.as_deref()) {
+                            Ok(env) => {
+                                captured_env.update(&env);
+                                Ok(output)
+                            }
+                            Err(err) => Err(output.error(err)),
+                        }
+                    }
+                    (_, step_status) => step_status,
+                };
+                if fs_context.is_mutable() && step_status.is_err() && *mode == Mode::Fail {
+                    prior_step_failed = true;
+                }
+                outputs.push(step_status);
+            }
+            for (i, background, handle) in running {
+                background.stop();
+                outputs[i] = match handle.join() {
+                    Ok(step_status) => step_status,
+                    Err(panic) => {
+                        let message = panic
+                            .downcast_ref::<&str>()
+                            .map(|s| (*s).to_owned())
+                            .or_else(|| panic.downcast_ref::<String>().cloned())
+                            .unwrap_or_else(|| "Box<dyn Any>".to_owned());
+                        let output = Output::sequence(self.name());
+                        Err(output.error(format!("`background` step panicked: {message}").into()))
+                    }
+                };
+            }
+        });
+        if setup.is_ok() {
+            if let Err(err) = hooks.teardown(&context) {
+                let output = Output::step(self.name(), "teardown".into());
+                outputs.push(Err(output.error(err)));
+            }
+        }
+        #[cfg(feature = "filesystem")]
+        if let (Some(export), Some(sandbox)) = (&links.export, sandbox.as_deref()) {
+            if let Err(err) = snapbox::dir::copy_template(sandbox, export) {
+                let output = Output::step(self.name(), "teardown".into());
+                outputs.push(Err(output.error(
+                    format!(
+                        "Failed to share sandbox with dependents: {}",
+                        err.to_string().trim_end()
+                    )
+                    .into(),
+                )));
             }
-            outputs.push(step_status);
         }
         match mode {
             Mode::Dump(root) => {
@@ -241,21 +1205,32 @@ impl Case {
                             Ok(stream) => stream,
                             Err(stream) => stream,
                         };
+                    output.merged =
+                        match self.dump_stream(root, output.id.as_deref(), output.merged.take()) {
+                            Ok(stream) => stream,
+                            Err(stream) => stream,
+                        };
+                    if let Err(err) = self.dump_chunks(root, output) {
+                        output.spawn.status = SpawnStatus::Failure(err);
+                    }
                 }
             }
+            Mode::Overwrite if sequence.expected_failure.is_some() => {
+                // Known failures are not the output to record
+            }
+            Mode::Overwrite if self.built.is_some() => {
+                // There is no file to record the output in
+            }
             Mode::Overwrite => {
                 // `rev()` to ensure we don't mess up our line number info
                 for step_status in outputs.iter_mut().rev() {
                     if let Err(output) = step_status {
-                        let res = sequence.overwrite(
-                            &self.path,
-                            output.id.as_deref(),
-                            output.stdout.as_ref().map(|s| &s.content),
-                            output.stderr.as_ref().map(|s| &s.content),
-                            output.spawn.exit,
-                        );
-
-                        if res.is_ok() {
+                        if output.spawn.exit.is_none() {
+                            // Nothing was run to record
+                            continue;
+                        }
+                        let res = overwrite_output(&sequence, &self.path, output);
+                        if res == Ok(true) {
                             *step_status = Ok(output.clone());
                         }
                     }
@@ -264,15 +1239,22 @@ impl Case {
             Mode::Fail => {}
         }
 
+        let mut sandbox_kept = false;
         if sequence.fs.sandbox() {
             let mut ok = true;
-            let mut output = Output::step(self.path.clone(), "teardown".into());
+            let teardown = match (sequence.matrix, &sequence.steps[0].id) {
+                (true, Some(id)) => format!("{id}-teardown"),
+                _ => "teardown".to_owned(),
+            };
+            let mut output = Output::step(self.name(), teardown);
 
             output.fs = match self.validate_fs(
                 fs_context.path().expect("sandbox must be filled"),
                 output.fs,
                 mode,
                 &substitutions,
+                &ignore,
+                &sequence.fs.files,
             ) {
                 Ok(fs) => fs,
                 Err(fs) => {
@@ -280,7 +1262,38 @@ impl Case {
                     fs
                 }
             };
-            if let Err(err) = fs_context.close() {
+            for expect in &sequence.fs.expect {
+                let status = check_file_expect(
+                    fs_context.path().expect("sandbox must be filled"),
+                    expect,
+                    &substitutions,
+                );
+                if !status.is_ok() {
+                    ok = false;
+                }
+                output.fs.context.push(status);
+            }
+            if let Err(err) = check_usage(
+                fs_context.path().expect("sandbox must be filled"),
+                &sequence.fs,
+                &files_before,
+            ) {
+                ok = false;
+                output.fs.context.push(FileStatus::Failure(err));
+            }
+            if let Some((base, stamps)) = &base_stamps {
+                if let Err(err) = check_base_unchanged(base, stamps) {
+                    ok = false;
+                    output.fs.context.push(FileStatus::Failure(err));
+                }
+            }
+            let failed = !ok || outputs.iter().any(|o| o.is_err());
+            if failed
+                && sequence.expected_failure.is_none()
+                && std::env::var_os("TRYCMD_CLEAN_SANDBOX").is_none()
+            {
+                sandbox_kept = fs_context.keep().is_some();
+            } else if let Err(err) = fs_context.close() {
                 ok = false;
                 output.fs.context.push(FileStatus::Failure(
                     format!("Failed to cleanup sandbox: {}", err).into(),
@@ -297,7 +1310,28 @@ impl Case {
             outputs.push(output);
         }
 
-        outputs
+        for output in &mut outputs {
+            let output = match output {
+                Ok(output) => output,
+                Err(output) => output,
+            };
+            output.sandbox.clone_from(&sandbox);
+            output.sandbox_kept = sandbox_kept;
+            output.description.clone_from(&sequence.description);
+            output.links.clone_from(&sequence.links);
+            output.warnings.clone_from(&sequence.warnings);
+        }
+
+        if let (Mode::Fail, Some(dir), None) = (mode, &self.artifacts, &sequence.expected_failure) {
+            let path = self.built.is_none().then_some(self.path.as_path());
+            // Best effort, as the case's result stands either way
+            let _ = crate::artifacts::record(dir, &name, path, &sequence, &outputs);
+        }
+
+        match &sequence.expected_failure {
+            Some(reason) => expect_failure(outputs, reason),
+            None => outputs,
+        }
     }
 
     #[allow(clippy::result_large_err)]
@@ -307,62 +1341,290 @@ impl Case {
         cwd: Option<&std::path::Path>,
         bins: &crate::BinRegistry,
         substitutions: &snapbox::Redactions,
+        expansions: &[(&'static str, String)],
+        hooks: &crate::hooks::Hooks,
     ) -> Result<Output, Output> {
-        let output = if let Some(id) = step.id.clone() {
-            Output::step(self.path.clone(), id)
+        let mut output = if let Some(id) = step.id.clone() {
+            Output::step(self.name(), id)
         } else {
-            Output::sequence(self.path.clone())
+            Output::sequence(self.name())
         };
 
         let mut bin = step.bin.take();
         if bin.is_none() {
             bin.clone_from(&self.default_bin);
         }
-        bin = bin
-            .map(|name| bins.resolve_bin(name))
-            .transpose()
-            .map_err(|e| output.clone().error(e))?;
+        let in_process = match &bin {
+            Some(crate::schema::Bin::Name(name)) => hooks
+                .mains
+                .get(name)
+                .map(|main| (name.clone(), main.clone())),
+            _ => None,
+        };
+        if in_process.is_none() {
+            bin = bin
+                .map(|name| bins.resolve_bin(name))
+                .transpose()
+                .map_err(|e| output.clone().error(e))?;
+        }
         step.bin = bin;
+        for stage in &mut step.pipe {
+            let mut bin = stage.bin.take();
+            if bin.is_none() {
+                bin.clone_from(&self.default_bin);
+            }
+            stage.bin = bin
+                .map(|name| bins.resolve_bin(name))
+                .transpose()
+                .map_err(|e| output.clone().error(e))?;
+        }
         if step.timeout.is_none() {
             step.timeout = self.timeout;
         }
+        // The suite's `wrapper` is for spawned programs, unlike one the case sets
+        if step.wrapper.is_empty() && in_process.is_none() {
+            step.wrapper.clone_from(&self.wrapper);
+        }
         if self.expected.is_some() {
             step.expected_status = self.expected;
         }
+        if let Some((name, _)) = &in_process {
+            crate::in_process::check_env(name, &step.env).map_err(|e| output.clone().error(e))?;
+        }
         step.env.update(&self.env);
+        if let Some((var, seed)) = &self.seed {
+            step.env.add.insert(var.clone(), seed.to_string());
+            output.seed = self.seed.clone();
+        }
+        if step.env.coverage {
+            step.env
+                .add
+                .extend(coverage_env(&self.path, std::env::vars()));
+        }
+        if let Some(time) = step.fake_time {
+            let vars = crate::fake_time::env(time, &step.env);
+            step.env.add.extend(vars);
+        }
+
+        // Only checked for steps that will be spawned, as checking forks
+        if in_process.is_none() && step.expected_status() != crate::schema::CommandStatus::Skipped {
+            match step.net {
+                crate::schema::Net::Host => {}
+                crate::schema::Net::None => {
+                    crate::net::check().map_err(|e| output.clone().error(e))?;
+                }
+                crate::schema::Net::NoneOrSkip => {
+                    if let Err(err) = crate::net::check() {
+                        step.expected_status = Some(crate::schema::CommandStatus::Skipped);
+                        step.skip_reason = Some(err.message().to_owned());
+                    }
+                }
+            }
+        }
 
         if step.expected_status() == crate::schema::CommandStatus::Skipped {
             assert_eq!(output.spawn.status, SpawnStatus::Skipped);
+            let mut output = output;
+            output.skip_reason.clone_from(&step.skip_reason);
             return Ok(output);
         }
 
-        match &step.bin {
-            Some(crate::schema::Bin::Path(_)) => {}
-            Some(crate::schema::Bin::Name(_name)) => {
-                // Unhandled by resolve
-                snapbox::debug!("bin={:?} not found", _name);
-                assert_eq!(output.spawn.status, SpawnStatus::Skipped);
-                return Ok(output);
+        let stage_bins = std::iter::once(&step.bin)
+            .chain(step.pipe.iter().map(|s| &s.bin))
+            .skip(usize::from(in_process.is_some()));
+        for bin in stage_bins {
+            match bin {
+                Some(crate::schema::Bin::Path(_)) | Some(crate::schema::Bin::Script(_)) => {}
+                Some(crate::schema::Bin::Name(_name)) => {
+                    // Unhandled by resolve
+                    snapbox::debug!("bin={:?} not found", _name);
+                    assert_eq!(output.spawn.status, SpawnStatus::Skipped);
+                    return Ok(output);
+                }
+                Some(crate::schema::Bin::Error(_)) => {}
+                // Unlike `Name`, this always represents a bug
+                None => {}
+                Some(crate::schema::Bin::Ignore) => {
+                    // Unhandled by resolve
+                    assert_eq!(output.spawn.status, SpawnStatus::Skipped);
+                    return Ok(output);
+                }
             }
-            Some(crate::schema::Bin::Error(_)) => {}
-            // Unlike `Name`, this always represents a bug
-            None => {}
-            Some(crate::schema::Bin::Ignore) => {
-                // Unhandled by resolve
-                assert_eq!(output.spawn.status, SpawnStatus::Skipped);
-                return Ok(output);
+        }
+
+        let mut substitutions = substitutions.clone();
+        let mut expansions = expansions.to_vec();
+        if let Some(crate::schema::Bin::Path(bin)) = &step.bin {
+            substitutions.insert("[BIN]", bin.clone()).unwrap();
+            expansions.push(("[BIN]", bin.display().to_string()));
+        }
+        if !step.binary {
+            step.stdin = step.stdin.take().map(|stdin| match stdin.render() {
+                Some(text) => crate::Data::text(expand_placeholders(&text, &expansions)),
+                None => stdin,
+            });
+        }
+
+        output.repro = step.repro(cwd).ok();
+        if let Ok((argv, env)) = step.invocation(cwd) {
+            output.argv = argv;
+            output.env = env;
+        }
+
+        let find_snapshot = |stream| match &self.built {
+            Some(_) => None,
+            None => crate::validator::find_snapshot(
+                &hooks.validators,
+                &self.path,
+                step.id.as_deref(),
+                stream,
+            ),
+        };
+        let custom = StreamHooks {
+            stdout: find_snapshot("stdout"),
+            stderr: find_snapshot("stderr"),
+            normalizers: hooks.normalizers.clone(),
+        };
+
+        let mut attempts = Vec::new();
+        loop {
+            let mut attempt = step.clone();
+            match self.run_attempt(
+                &mut attempt,
+                output.clone(),
+                cwd,
+                &substitutions,
+                &custom,
+                in_process.as_ref(),
+            ) {
+                Ok(output) => return Ok(output),
+                Err(failed) if attempts.len() < step.retries.count => {
+                    std::thread::sleep(step.retries.delay(attempts.len()));
+                    // Reported once, after the last attempt
+                    attempts.push(Output {
+                        repro: None,
+                        ..failed
+                    });
+                }
+                Err(mut failed) => {
+                    failed.attempts = attempts;
+                    return Err(failed);
+                }
             }
         }
+    }
 
-        let cmd = step.to_command(cwd).map_err(|e| output.clone().error(e))?;
-        let cmd_output = cmd
-            .output()
-            .map_err(|e| output.clone().error(e.to_string().into()))?;
-        let output = output.output(cmd_output);
+    /// Run the command once, validating the result
+    #[allow(clippy::result_large_err)]
+    fn run_attempt(
+        &self,
+        step: &mut crate::schema::Step,
+        output: Output,
+        cwd: Option<&std::path::Path>,
+        substitutions: &snapbox::Redactions,
+        custom: &StreamHooks,
+        in_process: Option<&(String, crate::in_process::Main)>,
+    ) -> Result<Output, Output> {
+        let start = std::time::Instant::now();
+        let (cmd_output, chunks) = if let Some((name, main)) = in_process {
+            let cmd_output = crate::in_process::run(main, name, step, cwd)
+                .map_err(|e| output.clone().error(e))?;
+            (cmd_output, Vec::new())
+        } else if !step.expect_lines.is_empty() || step.background.is_some() {
+            let cmd = step
+                .to_watched_command(cwd)
+                .map_err(|e| output.clone().error(e))?;
+            let cmd_output = crate::watch::run(cmd, step).map_err(|e| output.clone().error(e))?;
+            (cmd_output, Vec::new())
+        } else if step.interaction.is_empty() {
+            let mut cmd = step.to_command(cwd).map_err(|e| output.clone().error(e))?;
+            let pipe_cmds = step
+                .to_pipe_commands(cwd)
+                .map_err(|e| output.clone().error(e))?;
+            let upstream = if pipe_cmds.is_empty() {
+                None
+            } else {
+                let stdin = step
+                    .stdin
+                    .as_ref()
+                    .map(|stdin| stdin.to_bytes())
+                    .transpose()
+                    .map_err(|e| output.clone().error(e.into()))?;
+                let (upstream, stdout) =
+                    crate::pipe::start(pipe_cmds, stdin).map_err(|e| output.clone().error(e))?;
+                cmd = cmd.stdin_from(stdout);
+                Some(upstream)
+            };
+            let chunked = cmd.chunked_output().map_err(|e| {
+                output
+                    .clone()
+                    .error(crate::Error::SpawnFailed(e.to_string()))
+            })?;
+            if let Some(upstream) = upstream {
+                let deadline = step.timeout.map(|timeout| start + timeout);
+                upstream
+                    .finish(deadline, step.pipefail)
+                    .map_err(|e| output.clone().error(e))?;
+            }
+            let mut cmd_output = chunked.output;
+            if step.tolerate_interleave {
+                cmd_output.stdout = interleave(
+                    step.expected_stdout.as_ref(),
+                    &cmd_output,
+                    &chunked.chunks,
+                    substitutions,
+                );
+                cmd_output.stderr = Vec::new();
+            }
+            (cmd_output, chunked.chunks)
+        } else {
+            let cmd = step
+                .to_interactive_command(cwd)
+                .map_err(|e| output.clone().error(e))?;
+            let cmd_output = crate::pty::run(
+                cmd,
+                &step.interaction,
+                step.timeout,
+                step.timeout_grace,
+                step.term,
+            )
+            .map_err(|e| output.clone().error(e))?;
+            (cmd_output, Vec::new())
+        };
+        let merged = (step.expected_merged.is_some() && !step.stderr_to_stdout)
+            .then(|| merge_chunks(&cmd_output.stdout, &cmd_output.stderr, &chunks));
+        let mut output = output.output(cmd_output);
+        output.merged = merged.map(|merged| Stream {
+            stream: Stdio::Merged,
+            content: merged.into_data(),
+            status: StreamStatus::Ok,
+        });
+        output.chunks = chunks;
+        output.duration = Some(start.elapsed());
 
         // For Mode::Dump's sake, allow running all
-        let output = self.validate_spawn(output, step.expected_status());
-        let output = self.validate_streams(output, step, substitutions);
+        let mut output = self.validate_spawn(output, step.expected_status());
+        if let Some(max_output) = step.max_output {
+            let marker = snapbox::cmd::OUTPUT_LIMIT_MARKER.as_bytes();
+            let truncated = [&output.stdout, &output.stderr]
+                .into_iter()
+                .flatten()
+                .any(|s| {
+                    s.content
+                        .to_bytes()
+                        .map(|b| b.ends_with(marker))
+                        .unwrap_or(false)
+                });
+            if truncated {
+                output.spawn.status = SpawnStatus::OutputLimitExceeded(max_output);
+            }
+        }
+        if let (Some(max), Some(actual)) = (step.max_duration, output.duration) {
+            if max < actual && output.spawn.status == SpawnStatus::Ok {
+                output.spawn.status = SpawnStatus::TooSlow { max, actual };
+            }
+        }
+        let output = self.validate_streams(output, step, substitutions, custom);
 
         if output.is_ok() {
             Ok(output)
@@ -395,6 +1657,17 @@ impl Case {
                     output.spawn.status = SpawnStatus::Expected(expected_code.to_string());
                 }
             }
+            crate::schema::CommandStatus::Signal(expected_signal) => {
+                if Some(expected_signal) != crate::platform::exit_signal(status) {
+                    output.spawn.status =
+                        SpawnStatus::Expected(format!("signal: {expected_signal}"));
+                }
+            }
+            crate::schema::CommandStatus::CoreDumped => {
+                if !crate::platform::core_dumped(status) {
+                    output.spawn.status = SpawnStatus::Expected("core dumped".into());
+                }
+            }
         }
 
         output
@@ -405,19 +1678,35 @@ impl Case {
         mut output: Output,
         step: &crate::schema::Step,
         substitutions: &snapbox::Redactions,
+        custom: &StreamHooks,
     ) -> Output {
         output.stdout = self.validate_stream(
             output.stdout,
             step.expected_stdout.as_ref(),
-            step.binary,
+            step,
             substitutions,
+            &custom.normalizers,
         );
         output.stderr = self.validate_stream(
             output.stderr,
             step.expected_stderr.as_ref(),
-            step.binary,
+            step,
+            substitutions,
+            &custom.normalizers,
+        );
+        output.merged = self.validate_stream(
+            output.merged,
+            step.expected_merged.as_ref(),
+            step,
             substitutions,
+            &custom.normalizers,
         );
+        output.stdout = output
+            .stdout
+            .map(|stream| validate_custom(stream, custom.stdout.as_ref()));
+        output.stderr = output
+            .stderr
+            .map(|stream| validate_custom(stream, custom.stderr.as_ref()));
 
         output
     }
@@ -426,32 +1715,162 @@ impl Case {
         &self,
         stream: Option<Stream>,
         expected_content: Option<&crate::Data>,
-        binary: bool,
+        step: &crate::schema::Step,
         substitutions: &snapbox::Redactions,
+        normalizers: &[crate::normalizer::Normalize],
     ) -> Option<Stream> {
         let mut stream = stream?;
+        let (check, format) = match stream.stream {
+            Stdio::Stdout => (step.stdout_check, step.expected_output_format),
+            Stdio::Stderr => (step.stderr_check, step.expected_stderr_format),
+            Stdio::Merged => (None, crate::schema::OutputFormat::Text),
+        };
 
-        if !binary {
+        if !step.binary {
             stream = stream.make_text();
             if !stream.is_ok() {
                 return Some(stream);
             }
         }
+        if step.strip_ansi {
+            stream.content = crate::compare::strip_ansi_data(stream.content);
+        }
+        if step.normalize_line_endings() {
+            stream.content = crate::compare::normalize_line_endings(stream.content);
+        }
+        if step.normalize_paths {
+            stream.content = crate::compare::normalize_paths_data(stream.content);
+        }
+        if step.trim_trailing_whitespace {
+            stream.content = crate::compare::trim_trailing_whitespace(stream.content);
+        }
+        if !step.require_final_newline() {
+            stream.content = crate::compare::ensure_final_newline(stream.content);
+        }
+        if !step.binary {
+            stream.content = crate::normalizer::apply(stream.content, normalizers);
+        }
+
+        let is_empty = stream
+            .content
+            .to_bytes()
+            .map(|b| b.is_empty())
+            .unwrap_or(false);
+        match check {
+            Some(crate::schema::StreamCheck::Empty) if !is_empty => {
+                stream.status = StreamStatus::Failure("expected nothing".into());
+                return Some(stream);
+            }
+            Some(crate::schema::StreamCheck::NonEmpty) if is_empty => {
+                stream.status = StreamStatus::Failure("expected something".into());
+                return Some(stream);
+            }
+            _ => {}
+        }
+        let (forbidden, compare) = match stream.stream {
+            Stdio::Stdout => (&step.stdout_must_not_contain[..], step.stdout_compare),
+            Stdio::Stderr => (&step.stderr_must_not_contain[..], step.stderr_compare),
+            Stdio::Merged => (&[][..], crate::schema::CompareMode::Lines),
+        };
+        if let Some(found) = stream
+            .content
+            .to_bytes()
+            .ok()
+            .and_then(|b| find_forbidden(&b, forbidden))
+        {
+            stream.status = StreamStatus::Failure(format!("must not contain `{found}`").into());
+            return Some(stream);
+        }
 
         if let Some(expected_content) = expected_content {
+            if format == crate::schema::OutputFormat::Regex {
+                stream.status = regex_status(expected_content, &stream.content);
+                return Some(stream);
+            }
+            if compare == crate::schema::CompareMode::UnorderedLines {
+                // Matched line by line, as normalizing the whole stream relies on the order
+                stream.status =
+                    unordered_lines_status(expected_content, &stream.content, substitutions);
+                return Some(stream);
+            }
+            let actual = stream.content.clone();
             stream.content = NormalizeToExpected::new()
                 .redact_with(substitutions)
                 .normalize(stream.content, expected_content);
 
-            if stream.content != *expected_content {
-                stream.status = StreamStatus::Expected(expected_content.clone());
-                return Some(stream);
+            match format {
+                crate::schema::OutputFormat::Text => {
+                    if stream.content != *expected_content {
+                        stream.status = StreamStatus::Expected {
+                            expected: expected_content.clone(),
+                            similar: self.similar_snapshot(
+                                stream.stream,
+                                &actual,
+                                expected_content,
+                                substitutions,
+                            ),
+                        };
+                        return Some(stream);
+                    }
+                }
+                crate::schema::OutputFormat::Json => {
+                    stream.status = json_status(expected_content, &stream.content);
+                }
+                crate::schema::OutputFormat::Regex => unreachable!("handled earlier"),
             }
         }
 
         Some(stream)
     }
 
+    /// Another case's snapshot that `actual` matches better than its own, like after snapshots
+    /// were swapped or renamed
+    fn similar_snapshot(
+        &self,
+        stream: Stdio,
+        actual: &crate::Data,
+        expected: &crate::Data,
+        substitutions: &snapbox::Redactions,
+    ) -> Option<std::path::PathBuf> {
+        if self.built.is_some() {
+            return None;
+        }
+        let similarity = |candidate: &crate::Data| {
+            let actual = NormalizeToExpected::new()
+                .redact_with(substitutions)
+                .normalize(actual.clone(), candidate);
+            match (actual.render(), candidate.render()) {
+                (Some(actual), Some(candidate)) => {
+                    crate::suggest::line_similarity(&actual, &candidate)
+                }
+                _ => 0.0,
+            }
+        };
+
+        let stem = self.path.file_stem()?.to_string_lossy();
+        let dir = match self.path.parent() {
+            Some(parent) if parent != std::path::Path::new("") => parent,
+            _ => std::path::Path::new("."),
+        };
+        let mut best = (similarity(expected).max(SIMILAR_SNAPSHOT), None);
+        for entry in std::fs::read_dir(dir).ok()?.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Skip this case's own snapshots, including `[[matrix]]` and platform ones
+            if path.extension() != Some(std::ffi::OsStr::new(stream.as_str()))
+                || name.starts_with(&format!("{stem}."))
+                || name.starts_with(&format!("{stem}-"))
+            {
+                continue;
+            }
+            let score = similarity(&crate::Data::read_from(&path, None));
+            if best.0 < score {
+                best = (score, Some(path));
+            }
+        }
+        best.1
+    }
+
     fn dump_stream(
         &self,
         root: &std::path::Path,
@@ -480,7 +1899,7 @@ impl Case {
             stream.content.write_to_path(&stream_path).map_err(|e| {
                 let mut stream = stream.clone();
                 if stream.is_ok() {
-                    stream.status = StreamStatus::Failure(e);
+                    stream.status = StreamStatus::Failure(crate::Error::Io(e.to_string()));
                 }
                 stream
             })?;
@@ -490,12 +1909,42 @@ impl Case {
         }
     }
 
+    /// Write where each read of the output started and ended, for debugging interleaving
+    fn dump_chunks(&self, root: &std::path::Path, output: &Output) -> Result<(), crate::Error> {
+        if output.chunks.is_empty() {
+            return Ok(());
+        }
+        let stem = self.path.file_stem().unwrap().to_string_lossy();
+        let file_name = match &output.id {
+            Some(id) => format!("{stem}-{id}.chunks"),
+            None => format!("{stem}.chunks"),
+        };
+        let chunks_path = root.join(file_name);
+        let mut chunks = String::new();
+        for chunk in &output.chunks {
+            use std::fmt::Write as _;
+            let _ = writeln!(
+                chunks,
+                "{} {}..{} +{:?}",
+                chunk.stream,
+                chunk.offset,
+                chunk.offset + chunk.len,
+                chunk.elapsed
+            );
+        }
+        std::fs::write(&chunks_path, chunks).map_err(|e| {
+            crate::Error::Io(format!("Failed to write {}: {}", chunks_path.display(), e))
+        })
+    }
+
     fn validate_fs(
         &self,
         actual_root: &std::path::Path,
         mut fs: Filesystem,
         mode: &Mode,
         substitutions: &snapbox::Redactions,
+        ignore: &crate::ignore::Ignore,
+        files: &std::collections::BTreeMap<String, crate::schema::FileOverride>,
     ) -> Result<Filesystem, Filesystem> {
         let mut ok = true;
 
@@ -506,10 +1955,24 @@ impl Case {
             let fixture_root = self.path.with_extension("out");
             if fixture_root.exists() {
                 for status in snapbox::dir::PathDiff::subset_matches_iter(
-                    fixture_root,
+                    fixture_root.clone(),
                     actual_root,
                     substitutions,
                 ) {
+                    let expected_path = match &status {
+                        Ok((expected_path, _)) => Some(expected_path.as_path()),
+                        Err(diff) => diff.expected_path(),
+                    };
+                    let rel = expected_path.and_then(|p| p.strip_prefix(&fixture_root).ok());
+                    if rel.map(|rel| ignore.is_ignored(rel)).unwrap_or(false) {
+                        continue;
+                    }
+                    let compare = rel
+                        .and_then(|rel| files.get(&rel.to_string_lossy().replace('\\', "/")))
+                        .map(|o| o.compare)
+                        .unwrap_or_default();
+                    let mut status = status;
+                    compare_file(&mut status, compare, substitutions);
                     match status {
                         Ok((expected_path, actual_path)) => {
                             fs.context.push(FileStatus::Ok {
@@ -540,18 +2003,341 @@ impl Case {
     }
 }
 
+/// Apply a `fs.files` override to how a file was compared
+#[cfg(feature = "filesystem")]
+pub(crate) fn compare_file(
+    status: &mut Result<(std::path::PathBuf, std::path::PathBuf), snapbox::dir::PathDiff>,
+    compare: crate::schema::FileCompare,
+    substitutions: &snapbox::Redactions,
+) {
+    let format = match compare {
+        crate::schema::FileCompare::Auto => return,
+        crate::schema::FileCompare::Text => Some(DataFormat::Text),
+        crate::schema::FileCompare::Binary => Some(DataFormat::Binary),
+        crate::schema::FileCompare::Exists => None,
+    };
+    let (expected_path, actual_path) = match status {
+        Ok((expected_path, actual_path))
+        | Err(snapbox::dir::PathDiff::ContentMismatch {
+            expected_path,
+            actual_path,
+            ..
+        }) => (expected_path.clone(), actual_path.clone()),
+        Err(_) => return,
+    };
+    let Some(format) = format else {
+        *status = Ok((expected_path, actual_path));
+        return;
+    };
+    if FileType::from_path(&expected_path) != FileType::File {
+        return;
+    }
+
+    let read = |path: &std::path::Path| crate::Data::try_read_from(path, Some(format));
+    let (expected, mut actual) = match (read(&expected_path), read(&actual_path)) {
+        (Ok(expected), Ok(actual)) => (expected, actual),
+        (Err(err), _) | (_, Err(err)) => {
+            *status = Err(snapbox::dir::PathDiff::Failure(err));
+            return;
+        }
+    };
+    let expected = snapbox::filter::FilterNewlines.filter(expected);
+    if format == DataFormat::Text {
+        actual = NormalizeToExpected::new()
+            .redact_with(substitutions)
+            .normalize(
+                snapbox::filter::FilterNewlines.filter(FilterPaths.filter(actual)),
+                &expected,
+            );
+    }
+    *status = if expected == actual {
+        Ok((expected_path, actual_path))
+    } else {
+        Err(snapbox::dir::PathDiff::ContentMismatch {
+            expected_path,
+            actual_path,
+            expected_content: expected,
+            actual_content: actual,
+        })
+    };
+}
+
+/// What registered hooks do to a step's output
+#[derive(Clone, Default)]
+struct StreamHooks {
+    /// `cmd.stdout.<ext>`, checked by an [`OutputValidator`][crate::OutputValidator]
+    stdout: Option<crate::validator::CustomSnapshot>,
+    /// `cmd.stderr.<ext>`, checked by an [`OutputValidator`][crate::OutputValidator]
+    stderr: Option<crate::validator::CustomSnapshot>,
+    normalizers: Vec<crate::normalizer::Normalize>,
+}
+
+/// The content to overwrite a snapshot with, unless an [`OutputValidator`][crate::OutputValidator]
+/// checks it
+fn recordable(stream: Option<&Stream>) -> Option<&crate::Data> {
+    stream
+        .filter(|s| !matches!(s.status, StreamStatus::Differences { .. }))
+        .map(|s| &s.content)
+}
+
+fn validate_custom(
+    mut stream: Stream,
+    custom: Option<&crate::validator::CustomSnapshot>,
+) -> Stream {
+    let Some(custom) = custom else {
+        return stream;
+    };
+    if !stream.is_ok() {
+        return stream;
+    }
+    let actual = match stream.content.to_bytes() {
+        Ok(actual) => actual,
+        Err(err) => {
+            stream.status = StreamStatus::Failure(err.to_string().into());
+            return stream;
+        }
+    };
+    if let Err(differences) = custom.validator.validate(&actual, &custom.path) {
+        stream.status = StreamStatus::Differences {
+            snapshot: custom.path.clone(),
+            differences,
+        };
+    }
+    stream
+}
+
+/// Replace placeholders with their values, the inverse of redacting them
+fn expand_placeholders(text: &str, expansions: &[(&'static str, String)]) -> String {
+    let mut text = text.to_owned();
+    for (placeholder, value) in expansions {
+        text = text.replace(placeholder, value);
+    }
+    text
+}
+
+/// Fill in placeholders in `step`'s args and env, including those of its `pipe`
+fn expand_args_and_env(step: &mut crate::schema::Step, expansions: &[(&'static str, String)]) {
+    let expand = |text: &mut String| {
+        for (placeholder, value) in expansions {
+            if text.contains(placeholder) {
+                *text = text.replace(placeholder, value);
+            }
+        }
+    };
+    for text in step.args.iter_mut().chain(step.env.add.values_mut()) {
+        expand(text);
+    }
+    for stage in &mut step.pipe {
+        stage.args.for_each_mut(expand);
+        for value in stage.env.add.values_mut() {
+            expand(value);
+        }
+    }
+}
+
+/// Merge `stdout` and `stderr` in the order `expected` has their lines, when it can be
+///
+/// Otherwise, they are merged in the order `chunks` were read, for the comparison to report.
+fn interleave(
+    expected: Option<&crate::Data>,
+    output: &std::process::Output,
+    chunks: &[snapbox::cmd::OutputChunk],
+    substitutions: &snapbox::Redactions,
+) -> Vec<u8> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    expected
+        .and_then(|expected| expected.render())
+        .and_then(|expected| {
+            crate::compare::interleave_lines(&expected, &stdout, &stderr, |e, a| {
+                let e = crate::Data::text(e);
+                NormalizeToExpected::new()
+                    .redact_with(substitutions)
+                    .normalize(crate::Data::text(a), &e)
+                    == e
+            })
+        })
+        .map(String::into_bytes)
+        .unwrap_or_else(|| merge_chunks(&output.stdout, &output.stderr, chunks))
+}
+
+/// Record `output` as what its step in `path` is expected to print
+///
+/// Returns whether all of it was recorded, as snapshots checked by an
+/// [`OutputValidator`][crate::OutputValidator] aren't ours to rewrite.
+pub(crate) fn overwrite_output(
+    sequence: &crate::schema::TryCmd,
+    path: &std::path::Path,
+    output: &Output,
+) -> Result<bool, crate::Error> {
+    let stdout = recordable(output.stdout.as_ref());
+    let stderr = recordable(output.stderr.as_ref());
+    let custom =
+        stdout.is_none() && output.stdout.is_some() || stderr.is_none() && output.stderr.is_some();
+    sequence.overwrite(
+        path,
+        output.id.as_deref(),
+        stdout,
+        stderr,
+        output.merged.as_ref().map(|s| &s.content),
+        output.spawn.exit,
+    )?;
+    Ok(!custom)
+}
+
+/// Interleave `stdout` and `stderr` in the order `chunks` were read
+fn merge_chunks(stdout: &[u8], stderr: &[u8], chunks: &[snapbox::cmd::OutputChunk]) -> Vec<u8> {
+    let mut merged = Vec::with_capacity(stdout.len() + stderr.len());
+    for chunk in chunks {
+        let stream = match chunk.stream {
+            snapbox::cmd::OutputStream::Stdout => stdout,
+            snapbox::cmd::OutputStream::Stderr => stderr,
+        };
+        if let Some(bytes) = stream.get(chunk.offset..chunk.offset + chunk.len) {
+            merged.extend_from_slice(bytes);
+        }
+    }
+    merged
+}
+
+/// The exit code or signal, including whether core was dumped
+pub(crate) fn describe_exit(status: std::process::ExitStatus) -> String {
+    let mut description = snapbox::cmd::display_exit_status(status);
+    if crate::platform::core_dumped(status) {
+        description.push_str(" (core dumped)");
+    }
+    description
+}
+
+/// The first of `forbidden` found in `content`
+fn find_forbidden<'f>(content: &[u8], forbidden: &'f [String]) -> Option<&'f str> {
+    forbidden
+        .iter()
+        .find(|needle| {
+            let needle = needle.as_bytes();
+            content.windows(needle.len()).any(|window| window == needle)
+        })
+        .map(String::as_str)
+}
+
+fn unordered_lines_status(
+    expected: &crate::Data,
+    actual: &crate::Data,
+    substitutions: &snapbox::Redactions,
+) -> StreamStatus {
+    let (Some(expected_text), Some(actual_text)) = (expected.render(), actual.render()) else {
+        return StreamStatus::Failure("Unordered lines can only be compared as text".into());
+    };
+    let (missing, extra) =
+        crate::compare::unordered_line_mismatches(&expected_text, &actual_text, |e, a| {
+            let e = crate::Data::text(e);
+            NormalizeToExpected::new()
+                .redact_with(substitutions)
+                .normalize(crate::Data::text(a), &e)
+                == e
+        });
+    if missing.is_empty() && extra.is_empty() {
+        StreamStatus::Ok
+    } else {
+        StreamStatus::ExpectedLines { missing, extra }
+    }
+}
+
+fn regex_status(expected: &crate::Data, actual: &crate::Data) -> StreamStatus {
+    let (Some(pattern), Some(actual)) = (expected.render(), actual.render()) else {
+        return StreamStatus::Failure("Regular expressions can only match text".into());
+    };
+    // Files end in a newline that isn't part of the pattern
+    let pattern = pattern.strip_suffix('\n').unwrap_or(&pattern);
+    let actual = actual.strip_suffix('\n').unwrap_or(&actual);
+    let regex = match regex::RegexBuilder::new(&format!(r"\A(?:{pattern})\z"))
+        .multi_line(true)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(err) => return StreamStatus::Failure(format!("Invalid regex: {err}").into()),
+    };
+    if regex.is_match(actual) {
+        StreamStatus::Ok
+    } else {
+        StreamStatus::Failure("doesn't match the expected regex".into())
+    }
+}
+
+fn json_status(expected: &crate::Data, actual: &crate::Data) -> StreamStatus {
+    let parse = |data: &crate::Data| -> Result<serde_json::Value, String> {
+        let text = data.render().ok_or_else(|| "not text".to_owned())?;
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    };
+    let expected_value = match parse(expected) {
+        Ok(value) => value,
+        Err(err) => return StreamStatus::Failure(format!("Invalid expected JSON: {err}").into()),
+    };
+    let actual_value = match parse(actual) {
+        Ok(value) => value,
+        Err(err) => return StreamStatus::Failure(format!("Invalid JSON: {err}").into()),
+    };
+
+    let mismatches = crate::compare::json_mismatches(&expected_value, &actual_value);
+    if mismatches.is_empty() {
+        StreamStatus::Ok
+    } else {
+        // Pretty-print both sides so the diff lines up with the structure
+        let pretty = |value: &serde_json::Value| {
+            crate::Data::text(format!(
+                "{}\n",
+                serde_json::to_string_pretty(value).unwrap()
+            ))
+        };
+        StreamStatus::ExpectedJson {
+            expected: pretty(&expected_value),
+            actual: pretty(&actual_value),
+            mismatches,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct Output {
-    path: std::path::PathBuf,
-    id: Option<String>,
-    spawn: Spawn,
-    stdout: Option<Stream>,
-    stderr: Option<Stream>,
-    fs: Filesystem,
+    pub(crate) path: std::path::PathBuf,
+    pub(crate) id: Option<String>,
+    pub(crate) spawn: Spawn,
+    pub(crate) stdout: Option<Stream>,
+    pub(crate) stderr: Option<Stream>,
+    /// `stdout` and `stderr` in the order they were read, for `expected-merged`
+    pub(crate) merged: Option<Stream>,
+    pub(crate) fs: Filesystem,
+    pub(crate) sandbox: Option<std::path::PathBuf>,
+    /// `sandbox` was left in place for inspection
+    pub(crate) sandbox_kept: bool,
+    /// How long the command ran for
+    pub(crate) duration: Option<std::time::Duration>,
+    /// Why the command was skipped
+    pub(crate) skip_reason: Option<String>,
+    /// How a case marked `expected-failure` turned out
+    pub(crate) expected_failure: Option<ExpectedFailure>,
+    /// Earlier failed attempts, when the command was retried
+    pub(crate) attempts: Vec<Output>,
+    /// Each read of `stdout` and `stderr`, in order
+    pub(crate) chunks: Vec<snapbox::cmd::OutputChunk>,
+    /// A shell command line to run the command by hand
+    pub(crate) repro: Option<String>,
+    /// The program and its arguments
+    pub(crate) argv: Vec<String>,
+    /// Variables set, or removed when `None`, for the command
+    pub(crate) env: std::collections::BTreeMap<String, Option<String>>,
+    /// The variable the command was passed its seed in, and the seed
+    pub(crate) seed: Option<(String, u64)>,
+    /// What behavior the case guards
+    pub(crate) description: Option<String>,
+    /// Where the case's behavior was discussed
+    pub(crate) links: Vec<String>,
+    /// About how the case is written, like for an older `schema-version`
+    pub(crate) warnings: Vec<String>,
 }
 
 impl Output {
-    fn sequence(path: std::path::PathBuf) -> Self {
+    pub(crate) fn sequence(path: std::path::PathBuf) -> Self {
         Self {
             path,
             id: None,
@@ -561,18 +2347,48 @@ impl Output {
             },
             stdout: None,
             stderr: None,
+            merged: None,
             fs: Default::default(),
+            sandbox: None,
+            sandbox_kept: false,
+            duration: None,
+            skip_reason: None,
+            expected_failure: None,
+            attempts: Vec::new(),
+            chunks: Vec::new(),
+            repro: None,
+            argv: Vec::new(),
+            env: std::collections::BTreeMap::new(),
+            seed: None,
+            description: None,
+            links: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
-    fn step(path: std::path::PathBuf, step: String) -> Self {
+    pub(crate) fn step(path: std::path::PathBuf, step: String) -> Self {
         Self {
             path,
             id: Some(step),
             spawn: Default::default(),
             stdout: None,
             stderr: None,
+            merged: None,
             fs: Default::default(),
+            sandbox: None,
+            sandbox_kept: false,
+            duration: None,
+            skip_reason: None,
+            expected_failure: None,
+            attempts: Vec::new(),
+            chunks: Vec::new(),
+            repro: None,
+            argv: Vec::new(),
+            env: std::collections::BTreeMap::new(),
+            seed: None,
+            description: None,
+            links: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -593,19 +2409,39 @@ impl Output {
         self
     }
 
-    fn error(mut self, msg: crate::Error) -> Self {
+    pub(crate) fn error(mut self, msg: crate::Error) -> Self {
         self.spawn.status = SpawnStatus::Failure(msg);
         self
     }
 
-    fn is_ok(&self) -> bool {
+    /// Whether the step failed for reasons other than the command, like a missing bin or an
+    /// invalid case, rather than its output not matching
+    pub(crate) fn is_error(&self) -> bool {
+        matches!(
+            self.spawn.status,
+            SpawnStatus::Failure(
+                crate::Error::Io(_)
+                    | crate::Error::ParseToml(_)
+                    | crate::Error::ParseTryCmd(_)
+                    | crate::Error::UnknownBin(_)
+                    | crate::Error::SpawnFailed(_)
+            )
+        )
+    }
+
+    pub(crate) fn is_ok(&self) -> bool {
         self.spawn.is_ok()
             && self.stdout.as_ref().map(|s| s.is_ok()).unwrap_or(true)
             && self.stderr.as_ref().map(|s| s.is_ok()).unwrap_or(true)
+            && self.merged.as_ref().map(|s| s.is_ok()).unwrap_or(true)
             && self.fs.is_ok()
     }
 
-    fn name(&self) -> String {
+    pub(crate) fn spawn_status(&self) -> &SpawnStatus {
+        &self.spawn.status
+    }
+
+    pub(crate) fn name(&self) -> String {
         self.id
             .as_deref()
             .map(|id| format!("{}:{}", self.path.display(), id))
@@ -615,6 +2451,36 @@ impl Output {
 
 impl std::fmt::Display for Output {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(description) = &self.description {
+            let palette = snapbox::report::Palette::color();
+            writeln!(f, "{}", palette.hint(description.trim_end()))?;
+        }
+        for link in &self.links {
+            let palette = snapbox::report::Palette::color();
+            writeln!(f, "{}", palette.hint(format_args!("See {link}")))?;
+        }
+        for warning in &self.warnings {
+            let palette = snapbox::report::Palette::color();
+            writeln!(f, "{}", palette.warn(format_args!("warning: {warning}")))?;
+        }
+        let total = self.attempts.len() + 1;
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            let palette = snapbox::report::Palette::color();
+            writeln!(
+                f,
+                "{}",
+                palette.hint(format_args!("Attempt {} of {total}:", i + 1))
+            )?;
+            attempt.fmt(f)?;
+        }
+        if !self.attempts.is_empty() {
+            let palette = snapbox::report::Palette::color();
+            writeln!(
+                f,
+                "{}",
+                palette.hint(format_args!("Attempt {total} of {total}:"))
+            )?;
+        }
         self.spawn.fmt(f)?;
         if let Some(stdout) = &self.stdout {
             stdout.fmt(f)?;
@@ -622,16 +2488,58 @@ impl std::fmt::Display for Output {
         if let Some(stderr) = &self.stderr {
             stderr.fmt(f)?;
         }
+        if let Some(merged) = &self.merged {
+            merged.fmt(f)?;
+        }
         self.fs.fmt(f)?;
+        if let (true, Some(sandbox)) = (self.sandbox_kept, &self.sandbox) {
+            let palette = snapbox::report::Palette::color();
+            writeln!(
+                f,
+                "{}",
+                palette.hint(format_args!(
+                    "Sandbox kept at {} (set `TRYCMD_CLEAN_SANDBOX=1` to remove it)",
+                    sandbox.display()
+                ))
+            )?;
+        }
+        if let Some((var, seed)) = &self.seed {
+            let palette = snapbox::report::Palette::color();
+            writeln!(
+                f,
+                "{}",
+                palette.hint(format_args!("Seeded with {var}={seed}"))
+            )?;
+        }
+        if let Some(repro) = &self.repro {
+            let palette = snapbox::report::Palette::color();
+            writeln!(f, "{}", palette.hint("Reproduce with:"))?;
+            writeln!(f, "  {repro}")?;
+        }
+        if let Some(ExpectedFailure::Passed(reason)) = &self.expected_failure {
+            writeln!(
+                f,
+                "Expected failure ({}) but passed; remove `expected-failure` if it is fixed",
+                snapbox::report::Palette::color().error(reason)
+            )?;
+        }
 
         Ok(())
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct Spawn {
-    exit: Option<std::process::ExitStatus>,
-    status: SpawnStatus,
+pub(crate) enum ExpectedFailure {
+    /// Failed, as expected
+    Failed(String),
+    /// Unexpectedly passed
+    Passed(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Spawn {
+    pub(crate) exit: Option<std::process::ExitStatus>,
+    pub(crate) status: SpawnStatus,
 }
 
 impl Spawn {
@@ -661,7 +2569,7 @@ impl std::fmt::Display for Spawn {
                     } else if let Some(code) = exit.code() {
                         writeln!(f, "Exit: {}", palette.error(code))?;
                     } else {
-                        writeln!(f, "Exit: {}", palette.error("interrupted"))?;
+                        writeln!(f, "Exit: {}", palette.error(describe_exit(exit)))?;
                     }
                 }
             }
@@ -671,6 +2579,25 @@ impl std::fmt::Display for Spawn {
             SpawnStatus::Failure(msg) => {
                 writeln!(f, "Failed: {}", palette.error(msg))?;
             }
+            SpawnStatus::OutputLimitExceeded(max) => {
+                writeln!(
+                    f,
+                    "Failed: {} (`max-output = {}`)",
+                    palette.error("output limit exceeded"),
+                    max
+                )?;
+            }
+            SpawnStatus::TooSlow { max, actual } => {
+                writeln!(
+                    f,
+                    "Failed: took {}, over `max-duration = \"{}\"`",
+                    palette.error(humantime::format_duration(
+                        // Sub-millisecond precision is noise
+                        std::time::Duration::from_millis(actual.as_millis() as u64)
+                    )),
+                    humantime::format_duration(*max)
+                )?;
+            }
             SpawnStatus::Expected(expected) => {
                 if let Some(exit) = self.exit {
                     if exit.success() {
@@ -685,7 +2612,7 @@ impl std::fmt::Display for Spawn {
                             f,
                             "Expected {}, was {}",
                             palette.info(expected),
-                            palette.error(snapbox::cmd::display_exit_status(exit))
+                            palette.error(describe_exit(exit))
                         )?;
                     }
                 }
@@ -702,40 +2629,56 @@ pub(crate) enum SpawnStatus {
     Skipped,
     Failure(crate::Error),
     Expected(String),
+    /// `stdout` or `stderr` was cut short at this many bytes
+    OutputLimitExceeded(usize),
+    /// Completed, but took longer than `max-duration`
+    TooSlow {
+        max: std::time::Duration,
+        actual: std::time::Duration,
+    },
 }
 
 impl SpawnStatus {
     fn is_ok(&self) -> bool {
         match self {
             Self::Ok | Self::Skipped => true,
-            Self::Failure(_) | Self::Expected(_) => false,
+            Self::Failure(_)
+            | Self::Expected(_)
+            | Self::OutputLimitExceeded(_)
+            | Self::TooSlow { .. } => false,
         }
     }
 
-    fn summary(&self) -> impl std::fmt::Display {
+    pub(crate) fn summary(&self) -> impl std::fmt::Display {
         let palette = snapbox::report::Palette::color();
         match self {
             Self::Ok => palette.info("ok"),
             Self::Skipped => palette.warn("ignored"),
-            Self::Failure(_) | Self::Expected(_) => palette.error("failed"),
+            Self::Failure(_)
+            | Self::Expected(_)
+            | Self::OutputLimitExceeded(_)
+            | Self::TooSlow { .. } => palette.error("failed"),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct Stream {
-    stream: Stdio,
-    content: crate::Data,
-    status: StreamStatus,
+pub(crate) struct Stream {
+    pub(crate) stream: Stdio,
+    pub(crate) content: crate::Data,
+    pub(crate) status: StreamStatus,
 }
 
+/// How alike output must be to another case's snapshot to suggest it was meant for that case
+const SIMILAR_SNAPSHOT: f64 = 0.9;
+
 impl Stream {
     fn make_text(mut self) -> Self {
         let content = self.content.coerce_to(DataFormat::Text);
         if content.format() != DataFormat::Text {
             self.status = StreamStatus::Failure("Unable to convert underlying Data to Text".into());
         }
-        self.content = FilterNewlines.filter(FilterPaths.filter(content));
+        self.content = FilterPaths.filter(content);
         self
     }
 
@@ -758,11 +2701,11 @@ impl std::fmt::Display for Stream {
                     f,
                     "{} {}:",
                     self.stream,
-                    palette.error(format_args!("({})", msg))
+                    palette.error(format_args!("({})", msg.to_string().trim_end()))
                 )?;
                 writeln!(f, "{}", palette.info(&self.content))?;
             }
-            StreamStatus::Expected(expected) => {
+            StreamStatus::Expected { expected, similar } => {
                 snapbox::report::write_diff(
                     f,
                     expected,
@@ -771,6 +2714,65 @@ impl std::fmt::Display for Stream {
                     Some(&self.stream),
                     palette,
                 )?;
+                if let Some(similar) = similar {
+                    writeln!(
+                        f,
+                        "{}",
+                        palette.hint(format_args!(
+                            "{} matches `{}`; were snapshots swapped or renamed?",
+                            self.stream,
+                            similar.display()
+                        ))
+                    )?;
+                }
+            }
+            StreamStatus::ExpectedJson {
+                expected,
+                actual,
+                mismatches,
+            } => {
+                writeln!(f, "{} {}:", self.stream, palette.error("(JSON mismatch)"))?;
+                for mismatch in mismatches {
+                    writeln!(f, "  {}", palette.error(mismatch))?;
+                }
+                snapbox::report::write_diff(
+                    f,
+                    expected,
+                    actual,
+                    Some(&self.stream),
+                    Some(&self.stream),
+                    palette,
+                )?;
+            }
+            StreamStatus::ExpectedLines { missing, extra } => {
+                writeln!(
+                    f,
+                    "{} {}:",
+                    self.stream,
+                    palette.error("(lines differ, in any order)")
+                )?;
+                for line in missing {
+                    writeln!(f, "{}", palette.expected(format_args!("- {line}")))?;
+                }
+                for line in extra {
+                    writeln!(f, "{}", palette.actual(format_args!("+ {line}")))?;
+                }
+                writeln!(f, "{}", palette.info(&self.content))?;
+            }
+            StreamStatus::Differences {
+                snapshot,
+                differences,
+            } => {
+                writeln!(
+                    f,
+                    "{} {}:",
+                    self.stream,
+                    palette.error(format_args!("(differs from {})", snapshot.display()))
+                )?;
+                for difference in differences {
+                    writeln!(f, "  {}", palette.error(difference))?;
+                }
+                writeln!(f, "{}", palette.info(&self.content))?;
             }
         }
 
@@ -779,32 +2781,58 @@ impl std::fmt::Display for Stream {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-enum StreamStatus {
+pub(crate) enum StreamStatus {
     Ok,
     Failure(crate::Error),
-    Expected(crate::Data),
+    Expected {
+        expected: crate::Data,
+        /// Another case's snapshot the output matches better
+        similar: Option<std::path::PathBuf>,
+    },
+    ExpectedJson {
+        expected: crate::Data,
+        actual: crate::Data,
+        mismatches: Vec<String>,
+    },
+    /// Lines compared in any order
+    ExpectedLines {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// Found by an [`OutputValidator`][crate::OutputValidator]
+    Differences {
+        snapshot: std::path::PathBuf,
+        differences: Vec<crate::Difference>,
+    },
 }
 
 impl StreamStatus {
     fn is_ok(&self) -> bool {
         match self {
             Self::Ok => true,
-            Self::Failure(_) | Self::Expected(_) => false,
+            Self::Failure(_)
+            | Self::Expected { .. }
+            | Self::ExpectedJson { .. }
+            | Self::ExpectedLines { .. }
+            | Self::Differences { .. } => false,
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum Stdio {
+pub(crate) enum Stdio {
     Stdout,
     Stderr,
+    /// `stdout` and `stderr` together, in the order they were read
+    Merged,
 }
 
 impl Stdio {
-    fn as_str(&self) -> &str {
+    pub(crate) fn as_str(&self) -> &str {
         match self {
             Self::Stdout => "stdout",
             Self::Stderr => "stderr",
+            Self::Merged => "merged",
         }
     }
 }
@@ -816,8 +2844,8 @@ impl std::fmt::Display for Stdio {
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
-struct Filesystem {
-    context: Vec<FileStatus>,
+pub(crate) struct Filesystem {
+    pub(crate) context: Vec<FileStatus>,
 }
 
 impl Filesystem {
@@ -841,7 +2869,7 @@ impl std::fmt::Display for Filesystem {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-enum FileStatus {
+pub(crate) enum FileStatus {
     Ok {
         expected_path: std::path::PathBuf,
         actual_path: std::path::PathBuf,
@@ -882,7 +2910,7 @@ impl FileStatus {
 impl From<snapbox::dir::PathDiff> for FileStatus {
     fn from(other: snapbox::dir::PathDiff) -> Self {
         match other {
-            snapbox::dir::PathDiff::Failure(err) => FileStatus::Failure(err),
+            snapbox::dir::PathDiff::Failure(err) => FileStatus::Failure(err.into()),
             snapbox::dir::PathDiff::TypeMismatch {
                 expected_path,
                 actual_path,
@@ -988,6 +3016,29 @@ impl std::fmt::Display for FileStatus {
     }
 }
 
+/// What `bin` resolves to, or why it wouldn't run
+fn describe_bin(
+    bin: Option<&crate::schema::Bin>,
+    bins: &crate::BinRegistry,
+    hooks: &crate::hooks::Hooks,
+) -> String {
+    let Some(bin) = bin else {
+        return "<no bin>".to_owned();
+    };
+    if let crate::schema::Bin::Name(name) = bin {
+        if hooks.mains.contains_key(name) {
+            return format!("<{name}, in-process>");
+        }
+    }
+    match bins.resolve_bin(bin.clone()) {
+        Ok(crate::schema::Bin::Path(path)) => path.display().to_string(),
+        Ok(crate::schema::Bin::Script(script)) => script,
+        Ok(crate::schema::Bin::Name(name)) => format!("<{name}, not found>"),
+        Ok(crate::schema::Bin::Ignore) => "<ignored>".to_owned(),
+        Ok(crate::schema::Bin::Error(err)) | Err(err) => format!("<{}>", err.message()),
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum Mode {
     Fail,
@@ -1011,37 +3062,583 @@ impl Mode {
     }
 }
 
+/// The `env` changes a step reported, for the steps after it
+fn capture_env(
+    capture: &crate::schema::CaptureEnv,
+    output: &Output,
+    cwd: Option<&std::path::Path>,
+) -> Result<crate::schema::Env, crate::Error> {
+    let text = match capture {
+        crate::schema::CaptureEnv::Stdout => output
+            .stdout
+            .as_ref()
+            .and_then(|s| s.content.render())
+            .unwrap_or_default(),
+        crate::schema::CaptureEnv::File(path) => {
+            let path = cwd
+                .map(|cwd| cwd.join(path))
+                .unwrap_or_else(|| path.clone());
+            std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to capture env from {}: {}", path.display(), e))?
+        }
+    };
+    Ok(crate::schema::Env::parse_exports(&text))
+}
+
+/// Flip the outcome of a case marked `expected-failure`
+///
+/// Failures become expected and passing becomes the error.
+#[allow(clippy::result_large_err)]
+fn expect_failure(
+    outputs: Vec<Result<Output, Output>>,
+    reason: &str,
+) -> Vec<Result<Output, Output>> {
+    let failed = outputs.iter().any(|o| o.is_err());
+    let last = outputs.len().saturating_sub(1);
+    outputs
+        .into_iter()
+        .enumerate()
+        .map(|(i, output)| match output {
+            Err(mut output) => {
+                output.expected_failure = Some(ExpectedFailure::Failed(reason.to_owned()));
+                Ok(output)
+            }
+            Ok(mut output) if !failed && i == last => {
+                output.expected_failure = Some(ExpectedFailure::Passed(reason.to_owned()));
+                Err(output)
+            }
+            output => output,
+        })
+        .collect()
+}
+
+/// Give a case's placeholder the `'static` lifetime [`snapbox::Redactions`] needs
+///
+/// Each distinct placeholder is leaked once, so memory is bounded by the placeholders in use.
+fn intern_placeholder(placeholder: &str) -> &'static str {
+    static INTERNED: std::sync::Mutex<Vec<&'static str>> = std::sync::Mutex::new(Vec::new());
+
+    let mut interned = INTERNED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = interned.iter().find(|p| **p == placeholder) {
+        return existing;
+    }
+    let leaked: &'static str = Box::leak(placeholder.to_owned().into_boxed_str());
+    interned.push(leaked);
+    leaked
+}
+
+/// The variables `cargo llvm-cov` and similar tools need to collect coverage from a case's commands
+///
+/// `%p` in `LLVM_PROFILE_FILE` is prefixed with the case, so each profile can be traced back to it.
+fn coverage_env(
+    case: &std::path::Path,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Vec<(String, String)> {
+    let case: String = case
+        .display()
+        .to_string()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    vars.filter_map(|(name, value)| {
+        if name == "LLVM_PROFILE_FILE" {
+            let value = value.replace("%p", &format!("{case}-%p"));
+            Some((name, value))
+        } else if name.starts_with("CARGO_LLVM_COV") {
+            Some((name, value))
+        } else {
+            None
+        }
+    })
+    .collect()
+}
+
+/// Check one `[[fs.expect]]` file in the sandbox at `root`
+fn check_file_expect(
+    root: &std::path::Path,
+    expect: &crate::schema::FileExpect,
+    substitutions: &snapbox::Redactions,
+) -> FileStatus {
+    let expected_path = std::path::PathBuf::from(&expect.path);
+    if !expected_path
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return FileStatus::Failure(
+            format!(
+                "`fs.expect` path `{}` must be a relative path within the sandbox",
+                expect.path
+            )
+            .into(),
+        );
+    }
+    let actual_path = root.join(&expect.path);
+    let actual = match std::fs::read(&actual_path) {
+        Ok(actual) => actual,
+        Err(e) => {
+            return FileStatus::Failure(crate::Error::Io(format!(
+                "Failed to read {}: {}",
+                expect.path, e
+            )))
+        }
+    };
+    let actual = crate::Data::binary(actual).coerce_to(DataFormat::Text);
+    let Some(text) = actual.render() else {
+        return FileStatus::Failure(format!("{} is not text", expect.path).into());
+    };
+    if let Some(contains) = &expect.contains {
+        if !text.contains(contains.as_str()) {
+            return FileStatus::Failure(
+                format!("{} doesn't contain `{}`", expect.path, contains).into(),
+            );
+        }
+    }
+    if let Some(pattern) = &expect.matches {
+        let pattern = crate::Data::text(pattern.clone());
+        if let StreamStatus::Failure(err) = regex_status(&pattern, &actual) {
+            return FileStatus::Failure(format!("{}: {}", expect.path, err.message()).into());
+        }
+    }
+    if let Some(content) = &expect.content {
+        let expected_content = crate::Data::text(snapbox::filter::normalize_lines(content));
+        let actual_content = NormalizeToExpected::new()
+            .redact_with(substitutions)
+            .normalize(
+                crate::Data::text(snapbox::filter::normalize_lines(&text)),
+                &expected_content,
+            );
+        if actual_content != expected_content {
+            return FileStatus::ContentMismatch {
+                expected_path,
+                actual_path,
+                expected_content,
+                actual_content,
+            };
+        }
+    }
+    FileStatus::Ok {
+        expected_path,
+        actual_path,
+    }
+}
+
+/// The files in `root` and their total size in bytes
+fn sandbox_usage(
+    root: &std::path::Path,
+) -> Result<(std::collections::BTreeSet<std::path::PathBuf>, u64), crate::Error> {
+    let mut files = std::collections::BTreeSet::new();
+    let mut size = 0;
+    let mut pending = vec![root.to_owned()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| crate::Error::Io(format!("Failed to read {}: {}", dir.display(), e)))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", dir.display(), e))
+            })?;
+            let path = entry.path();
+            let metadata = std::fs::symlink_metadata(&path).map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            if metadata.is_dir() {
+                pending.push(path);
+            } else {
+                size += metadata.len();
+                files.insert(path.strip_prefix(root).unwrap_or(&path).to_owned());
+            }
+        }
+    }
+    Ok((files, size))
+}
+
+/// Check `fs.max-new-files` and `fs.max-size` against what the commands left in the sandbox
+fn check_usage(
+    root: &std::path::Path,
+    fs: &crate::schema::Filesystem,
+    files_before: &std::collections::BTreeSet<std::path::PathBuf>,
+) -> Result<(), crate::Error> {
+    if fs.max_new_files.is_none() && fs.max_size.is_none() {
+        return Ok(());
+    }
+    let (files, size) = sandbox_usage(root)?;
+    if let Some(max_new_files) = fs.max_new_files {
+        let new_files: Vec<_> = files.difference(files_before).collect();
+        if new_files.len() > max_new_files {
+            let mut names = new_files
+                .iter()
+                .take(5)
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if new_files.len() > 5 {
+                names.push_str(", ...");
+            }
+            return Err(format!(
+                "{} new files, over `fs.max-new-files = {}`: {}",
+                new_files.len(),
+                max_new_files,
+                names
+            )
+            .into());
+        }
+    }
+    if let Some(max_size) = &fs.max_size {
+        let max_bytes = max_size.to_bytes()?;
+        if size > max_bytes as u64 {
+            return Err(format!(
+                "Sandbox holds {} bytes, over `fs.max-size` ({} bytes)",
+                size, max_bytes
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Size and modification time of each file under `root`
+fn file_stamps(
+    root: &std::path::Path,
+) -> Result<
+    std::collections::BTreeMap<std::path::PathBuf, (u64, Option<std::time::SystemTime>)>,
+    crate::Error,
+> {
+    let mut stamps = std::collections::BTreeMap::new();
+    let mut pending = vec![root.to_owned()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| crate::Error::Io(format!("Failed to read {}: {}", dir.display(), e)))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", dir.display(), e))
+            })?;
+            let path = entry.path();
+            let metadata = std::fs::symlink_metadata(&path).map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_file() {
+                let rel = path.strip_prefix(root).unwrap_or(&path).to_owned();
+                stamps.insert(rel, (metadata.len(), metadata.modified().ok()));
+            }
+        }
+    }
+    Ok(stamps)
+}
+
+/// Check that no `fs.base-readonly` file was written to through its link in the sandbox
+fn check_base_unchanged(
+    base: &std::path::Path,
+    before: &std::collections::BTreeMap<std::path::PathBuf, (u64, Option<std::time::SystemTime>)>,
+) -> Result<(), crate::Error> {
+    let after = file_stamps(base)?;
+    let changed: Vec<_> = before
+        .iter()
+        .filter(|(rel, stamp)| matches!(after.get(*rel), Some(after) if after != *stamp))
+        .map(|(rel, _)| format!("  {}", rel.display()))
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "Files in `fs.base` were written to in place through `fs.base-readonly` links, \
+         changing the fixture; remove or replace them instead:\n{}",
+        changed.join("\n")
+    )
+    .into())
+}
+
+/// Create the `fs.files` that have `content` within the sandbox
+fn write_files(
+    root: Option<&std::path::Path>,
+    files: &std::collections::BTreeMap<String, crate::schema::FileOverride>,
+    sandbox: bool,
+) -> Result<(), crate::Error> {
+    for (rel, file) in files {
+        let Some(content) = file.content.as_deref() else {
+            continue;
+        };
+        let (Some(root), true) = (root, sandbox) else {
+            return Err(crate::Error::ParseToml(format!(
+                "`fs.files.\"{}\".content` needs `fs.sandbox = true`",
+                rel
+            )));
+        };
+        let rel_path = std::path::Path::new(rel);
+        if !rel_path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(crate::Error::ParseToml(format!(
+                "`fs.files.\"{}\"` must be a relative path within the sandbox",
+                rel
+            )));
+        }
+        let path = root.join(rel_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                crate::Error::Io(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+        // Rather than writing in place, which would change the fixture through a
+        // `fs.base-readonly` link
+        if path.is_file() {
+            std::fs::remove_file(&path).map_err(|e| {
+                crate::Error::Io(format!("Failed to remove {}: {}", path.display(), e))
+            })?;
+        }
+        std::fs::write(&path, content)
+            .map_err(|e| crate::Error::Io(format!("Failed to write {}: {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
 #[cfg_attr(not(feature = "filesystem"), allow(unused_variables))]
+/// Create a missing `fs.cwd` within the sandbox, otherwise explain why it can't be used
+fn ensure_cwd(
+    cwd: &std::path::Path,
+    fs: &crate::schema::Filesystem,
+    sandbox: bool,
+) -> Result<(), crate::Error> {
+    if cwd.is_dir() {
+        return Ok(());
+    }
+
+    let orig_cwd = fs.cwd.as_deref().unwrap_or(cwd);
+    let orig_base = fs
+        .base
+        .as_deref()
+        .map(|base| base.display().to_string())
+        .unwrap_or_else(|| "unset".to_owned());
+    if cwd.exists() {
+        Err(crate::Error::Io(format!(
+            "fs.cwd ({}) is not a directory, within fs.base ({})",
+            orig_cwd.display(),
+            orig_base
+        )))
+    } else if sandbox {
+        std::fs::create_dir_all(cwd).map_err(|e| {
+            crate::Error::Io(format!(
+                "Failed to create fs.cwd ({}) in the sandbox for fs.base ({}): {}",
+                orig_cwd.display(),
+                orig_base,
+                e
+            ))
+        })
+    } else {
+        Err(crate::Error::Io(format!(
+            "fs.cwd ({}) doesn't exist within fs.base ({}); create it or set `fs.sandbox = true` to have it created",
+            orig_cwd.display(),
+            orig_base
+        )))
+    }
+}
+
 fn fs_context(
     path: &std::path::Path,
     cwd: Option<&std::path::Path>,
     sandbox: bool,
+    link: bool,
+    sandbox_root: Option<&std::path::Path>,
     mode: &Mode,
+    ignore: &crate::ignore::Ignore,
 ) -> Result<snapbox::dir::DirRoot, crate::Error> {
     if sandbox {
         #[cfg(feature = "filesystem")]
-        match mode {
-            Mode::Dump(root) => {
-                let target = root.join(path.with_extension("out").file_name().unwrap());
-                let mut context = snapbox::dir::DirRoot::mutable_at(&target)?;
-                if let Some(cwd) = cwd {
-                    context = context.with_template(cwd)?;
-                }
-                Ok(context)
-            }
-            Mode::Fail | Mode::Overwrite => {
-                let mut context = snapbox::dir::DirRoot::mutable_temp()?;
-                if let Some(cwd) = cwd {
-                    context = context.with_template(cwd)?;
+        {
+            let context = match mode {
+                Mode::Dump(root) => {
+                    let target = root.join(path.with_extension("out").file_name().unwrap());
+                    snapbox::dir::DirRoot::mutable_at(&target)?
                 }
-                Ok(context)
+                Mode::Fail | Mode::Overwrite => match sandbox_root {
+                    Some(sandbox_root) => snapbox::dir::DirRoot::mutable_temp_in(sandbox_root)?,
+                    None => snapbox::dir::DirRoot::mutable_temp()?,
+                },
+            };
+            let include = |rel: &std::path::Path| !ignore.is_ignored(rel);
+            match cwd {
+                Some(cwd) if link => Ok(context.with_linked_template_filtered(cwd, include)?),
+                Some(cwd) => Ok(context.with_template_filtered(cwd, include)?),
+                None => Ok(context),
             }
         }
         #[cfg(not(feature = "filesystem"))]
         Err("Sandboxing is disabled".into())
+    } else if link {
+        Err("`fs.base-readonly` needs `fs.sandbox = true`".into())
     } else {
         Ok(cwd
             .map(snapbox::dir::DirRoot::immutable)
             .unwrap_or_else(snapbox::dir::DirRoot::none))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn depending(path: &str, depends_on: &[&str]) -> Case {
+        let mut case = Case::with_error(path.into(), crate::Error::new("unused"));
+        case.error = None;
+        case.built = Some(crate::schema::TryCmd {
+            depends_on: depends_on.iter().map(|name| (*name).to_owned()).collect(),
+            ..Default::default()
+        });
+        case
+    }
+
+    #[test]
+    fn depends_on() {
+        let cases = [
+            depending("cmd/query.toml", &["setup-db"]),
+            depending("cmd/setup-db.toml", &[]),
+            depending("cmd/report.toml", &["query", "setup-db"]),
+            depending("other/query.toml", &["missing"]),
+            depending("cmd/a.toml", &["b"]),
+            depending("cmd/b.toml", &["a"]),
+            depending("cmd/c.toml", &["a"]),
+        ];
+        let graph = Graph::new(&cases);
+        assert_eq!(
+            graph.levels,
+            [vec![1, 3], vec![0], vec![2], vec![4, 5], vec![6]]
+        );
+        assert_eq!(
+            graph.exported,
+            [true, true, false, false, true, false, false]
+        );
+        assert_eq!(
+            graph.errors[3].as_ref().map(crate::Error::message),
+            Some("`depends-on` names `missing`, which isn't among the cases being run")
+        );
+        assert_eq!(
+            graph.errors[4].as_ref().map(crate::Error::message),
+            Some("`depends-on` is circular")
+        );
+        // Blocked by `a` when it runs, rather than part of the cycle
+        assert_eq!(graph.errors[6], None);
+        assert_eq!(graph.deps[6], [4]);
+    }
+
+    #[test]
+    fn retain_keeps_dependencies() {
+        let mut runner = Runner::new();
+        runner.case(depending("cmd/query.toml", &["setup-db"]));
+        runner.case(depending("cmd/setup-db.toml", &[]));
+        runner.case(depending("cmd/report.toml", &["query"]));
+        runner.case(depending("cmd/help.toml", &[]));
+        runner.retain(|name| name.contains("report"));
+        assert_eq!(
+            runner.names().collect::<Vec<_>>(),
+            ["cmd/query.toml", "cmd/setup-db.toml", "cmd/report.toml"]
+        );
+        assert_eq!(runner.filtered_out, 1);
+    }
+
+    #[test]
+    fn describe() {
+        let mut case = depending("cmd/help.toml", &[]);
+        case.built.as_mut().unwrap().steps = vec![crate::schema::Step {
+            bin: Some(crate::schema::Bin::Name("my-bin".into())),
+            args: vec!["--help".into(), "two words".into()],
+            ..Default::default()
+        }];
+        let mut hooks = crate::hooks::Hooks::default();
+        let actual = case.describe(&crate::BinRegistry::new(), &hooks);
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            actual,
+            format!(
+                "cmd/help.toml\n  cwd: {}\n  sandbox: no\n  $ <my-bin, not found> --help 'two words'\n",
+                cwd.display()
+            )
+        );
+
+        hooks.mains.insert(
+            "my-bin".into(),
+            std::sync::Arc::new(|_: &[String], _: &[u8]| (0, Vec::new(), Vec::new())),
+        );
+        let actual = case.describe(&crate::BinRegistry::new(), &hooks);
+        assert!(actual.ends_with("  $ <my-bin, in-process> --help 'two words'\n"));
+    }
+
+    #[test]
+    fn tags() {
+        let filter = TagFilter::parse("fast, smoke,!network,");
+        assert_eq!(filter.include, ["fast", "smoke"]);
+        assert_eq!(filter.exclude, ["network"]);
+        let tags = |tags: &[&str]| tags.iter().map(|t| (*t).to_owned()).collect::<Vec<_>>();
+        assert_eq!(filter.deselects(&tags(&["fast"])), None);
+        assert_eq!(
+            filter.deselects(&tags(&["fast", "network"])).as_deref(),
+            Some("tagged `network`")
+        );
+        assert_eq!(
+            filter.deselects(&tags(&["slow"])).as_deref(),
+            Some("not tagged `fast` or `smoke`")
+        );
+        assert_eq!(TagFilter::default().deselects(&tags(&["slow"])), None);
+    }
+
+    #[test]
+    fn order() {
+        let ordered = |path: &str, order, depends_on: &[&str]| {
+            let mut case = depending(path, depends_on);
+            case.built.as_mut().unwrap().order = order;
+            case
+        };
+        let cases = [
+            ordered("cmd/a.toml", 0, &[]),
+            ordered("cmd/smoke.toml", -1, &[]),
+            ordered("cmd/slow.toml", 1, &[]),
+            ordered("cmd/late.toml", -5, &["a"]),
+            ordered("cmd/b.toml", 0, &[]),
+        ];
+        let graph = Graph::new(&cases);
+        assert_eq!(graph.levels, [vec![1], vec![0, 4], vec![3], vec![2]]);
+    }
+
+    #[test]
+    fn coverage_vars() {
+        let vars = [
+            ("LLVM_PROFILE_FILE", "/target/trycmd-%p-%m.profraw"),
+            ("CARGO_LLVM_COV", "1"),
+            ("HOME", "/home/me"),
+        ]
+        .map(|(name, value)| (name.to_owned(), value.to_owned()));
+        let actual = coverage_env(
+            std::path::Path::new("tests/cmd/help.toml"),
+            vars.into_iter(),
+        );
+        assert_eq!(
+            actual,
+            [
+                (
+                    "LLVM_PROFILE_FILE".to_owned(),
+                    "/target/trycmd-tests_cmd_help_toml-%p-%m.profraw".to_owned()
+                ),
+                ("CARGO_LLVM_COV".to_owned(), "1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_step() {
+        let mut step = crate::schema::Step {
+            args: vec!["--listen=[PORT:api]".into(), "[PORT]".into()],
+            ..Default::default()
+        };
+        step.env.add.insert("API".into(), "[HTTP_STUB]/v1".into());
+        expand_args_and_env(
+            &mut step,
+            &[
+                ("[PORT:api]", "4000".into()),
+                ("[PORT]", "4001".into()),
+                ("[HTTP_STUB]", "http://127.0.0.1:4002".into()),
+            ],
+        );
+        assert_eq!(step.args, ["--listen=4000", "4001"]);
+        assert_eq!(step.env.add["API"], "http://127.0.0.1:4002/v1");
+    }
+}
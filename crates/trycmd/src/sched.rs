@@ -0,0 +1,103 @@
+//! Scheduling for spawned commands, like `priority = "low"` and `cpus = [0]`
+
+/// Scheduling to set in the child, just before it runs the command
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub(crate) struct Scheduling {
+    nice: Option<i32>,
+    cpus: Vec<usize>,
+}
+
+impl Scheduling {
+    pub(crate) fn new(
+        priority: Option<crate::schema::Priority>,
+        cpus: &[usize],
+    ) -> Result<Self, crate::Error> {
+        if !cfg!(unix) && priority.is_some() {
+            return Err("`priority` is only supported on Unix".into());
+        }
+        if !cfg!(target_os = "linux") && !cpus.is_empty() {
+            return Err("`cpus` is only supported on Linux".into());
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(cpu) = cpus.iter().find(|cpu| **cpu >= libc::CPU_SETSIZE as usize) {
+            return Err(format!(
+                "`cpus` entry {} is past the last supported CPU, {}",
+                cpu,
+                libc::CPU_SETSIZE - 1
+            )
+            .into());
+        }
+        Ok(Self {
+            nice: priority.map(crate::schema::Priority::nice),
+            cpus: cpus.to_vec(),
+        })
+    }
+
+    /// Set the scheduling of `cmd`'s process before it runs
+    #[cfg(unix)]
+    pub(crate) fn apply(&self, cmd: &mut std::process::Command) {
+        #[cfg(target_os = "linux")]
+        use std::mem::size_of_val;
+        use std::os::unix::process::CommandExt as _;
+
+        if self.nice.is_none() && self.cpus.is_empty() {
+            return;
+        }
+        let nice = self.nice;
+        #[cfg(target_os = "linux")]
+        let cpus = {
+            // SAFETY: an all-zero `cpu_set_t` is an empty set
+            let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            for cpu in &self.cpus {
+                // SAFETY: `cpu` was checked against `CPU_SETSIZE` in `new`
+                unsafe { libc::CPU_SET(*cpu, &mut set) };
+            }
+            (!self.cpus.is_empty()).then_some(set)
+        };
+        // SAFETY: between `fork` and `exec`, this only calls `setpriority` and
+        // `sched_setaffinity`, which are async-signal-safe, and doesn't allocate
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(nice) = nice {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                #[cfg(target_os = "linux")]
+                if let Some(set) = &cpus {
+                    let size = size_of_val(set);
+                    if libc::sched_setaffinity(0, size, set) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn apply(&self, _cmd: &mut std::process::Command) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        let actual = Scheduling::new(Some(crate::schema::Priority::Low), &[]).unwrap();
+        assert_eq!(actual.nice, Some(10));
+        assert!(actual.cpus.is_empty());
+        assert_eq!(Scheduling::new(None, &[]).unwrap(), Scheduling::default());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn cpus_out_of_range() {
+        let err = Scheduling::new(None, &[libc::CPU_SETSIZE as usize]).unwrap_err();
+        assert!(
+            err.to_string().contains("past the last supported CPU"),
+            "{err}"
+        );
+    }
+}
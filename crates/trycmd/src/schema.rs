@@ -2,7 +2,7 @@
 //!
 //! [`OneShot`] is the top-level item in the `cmd.toml` files.
 
-use snapbox::filter::{Filter as _, FilterNewlines, FilterPaths};
+use snapbox::filter::{Filter as _, FilterPaths};
 use std::collections::BTreeMap;
 use std::collections::VecDeque;
 
@@ -10,68 +10,51 @@ use std::collections::VecDeque;
 pub(crate) struct TryCmd {
     pub(crate) steps: Vec<Step>,
     pub(crate) fs: Filesystem,
+    /// Steps are independent cases, each with their own sandbox
+    pub(crate) matrix: bool,
+    /// Run only the cases with this set
+    pub(crate) only: bool,
+    /// The case is known to fail, for this reason
+    pub(crate) expected_failure: Option<String>,
+    /// Extra placeholders for this case, like `"[HOST]" = "example.com"`
+    pub(crate) substitutions: BTreeMap<String, String>,
+    /// Cases to run before this one, by name
+    pub(crate) depends_on: Vec<String>,
+    /// When to run this case relative to others, lowest first
+    pub(crate) order: i32,
+    /// Labels to select cases by, like `slow`
+    pub(crate) tags: Vec<String>,
+    /// What behavior the case guards, for failure reports
+    pub(crate) description: Option<String>,
+    /// Where the case's behavior was discussed, like issues, for failure reports
+    pub(crate) links: Vec<String>,
+    /// From reading a case written for an older `schema-version`
+    pub(crate) warnings: Vec<String>,
+    /// Canned responses for a local HTTP server that runs alongside the case
+    pub(crate) http_stubs: Vec<HttpStub>,
 }
 
 impl TryCmd {
     pub(crate) fn load(path: &std::path::Path) -> Result<Self, crate::Error> {
         let mut sequence = if let Some(ext) = path.extension() {
             if ext == std::ffi::OsStr::new("toml") {
-                let raw = std::fs::read_to_string(path)
-                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
-                let one_shot = OneShot::parse_toml(&raw)?;
-                let mut sequence: Self = one_shot.into();
-                let is_binary = match sequence.steps[0].binary {
-                    true => snapbox::data::DataFormat::Binary,
-                    false => snapbox::data::DataFormat::Text,
-                };
-
-                if sequence.steps[0].stdin.is_none() {
-                    let stdin_path = path.with_extension("stdin");
-                    let stdin = if stdin_path.exists() {
-                        // No `map_text` as we will trust what the user inputted
-                        Some(crate::Data::try_read_from(&stdin_path, Some(is_binary))?)
-                    } else {
-                        None
-                    };
-                    sequence.steps[0].stdin = stdin;
-                }
-
-                if sequence.steps[0].expected_stdout.is_none() {
-                    let stdout_path = path.with_extension("stdout");
-                    let stdout = if stdout_path.exists() {
-                        Some(
-                            FilterNewlines.filter(
-                                FilterPaths
-                                    .filter(crate::Data::read_from(&stdout_path, Some(is_binary))),
-                            ),
-                        )
-                    } else {
-                        None
-                    };
-                    sequence.steps[0].expected_stdout = stdout;
-                }
-
-                if sequence.steps[0].expected_stderr.is_none() {
-                    let stderr_path = path.with_extension("stderr");
-                    let stderr = if stderr_path.exists() {
-                        Some(
-                            FilterNewlines.filter(
-                                FilterPaths
-                                    .filter(crate::Data::read_from(&stderr_path, Some(is_binary))),
-                            ),
-                        )
-                    } else {
-                        None
-                    };
-                    sequence.steps[0].expected_stderr = stderr;
+                let one_shot = OneShot::load(path)?;
+                let mut sequence: Self = one_shot.try_into().map_err(|e: crate::Error| {
+                    crate::Error::ParseToml(format!("Invalid {}: {}", path.display(), e))
+                })?;
+                for step in &mut sequence.steps {
+                    step.load_snapshots(path)?;
                 }
 
                 sequence
             } else if ext == std::ffi::OsStr::new("trycmd") || ext == std::ffi::OsStr::new("md") {
-                let raw = std::fs::read_to_string(path)
-                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let raw = std::fs::read_to_string(path).map_err(|e| {
+                    crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+                })?;
                 let normalized = snapbox::filter::normalize_lines(&raw);
-                Self::parse_trycmd(&normalized)?
+                Self::parse_trycmd(&normalized).map_err(|e| {
+                    crate::Error::ParseTryCmd(format!("Failed to parse {}: {}", path.display(), e))
+                })?
             } else {
                 return Err(format!("Unsupported extension: {}", ext.to_string_lossy()).into());
             }
@@ -89,6 +72,13 @@ impl TryCmd {
                 .unwrap_or_else(|| std::path::Path::new("."))
                 .join(cwd)
         });
+        for stub in &mut sequence.http_stubs {
+            stub.body_file = stub.body_file.take().map(|body_file| {
+                path.parent()
+                    .unwrap_or_else(|| std::path::Path::new("."))
+                    .join(body_file)
+            });
+        }
 
         if sequence.fs.base.is_none() {
             let base_path = path.with_extension("in");
@@ -111,12 +101,7 @@ impl TryCmd {
             .take()
             .map(|p| snapbox::dir::resolve_dir(p).map_err(|e| e.to_string()))
             .transpose()?;
-        sequence.fs.cwd = sequence
-            .fs
-            .cwd
-            .take()
-            .map(|p| snapbox::dir::resolve_dir(p).map_err(|e| e.to_string()))
-            .transpose()?;
+        sequence.fs.cwd = sequence.fs.cwd.take().map(resolve_cwd).transpose()?;
 
         Ok(sequence)
     }
@@ -127,22 +112,45 @@ impl TryCmd {
         id: Option<&str>,
         stdout: Option<&crate::Data>,
         stderr: Option<&crate::Data>,
+        merged: Option<&crate::Data>,
         exit: Option<std::process::ExitStatus>,
     ) -> Result<(), crate::Error> {
         if let Some(ext) = path.extension() {
             if ext == std::ffi::OsStr::new("toml") {
-                assert_eq!(id, None);
+                assert_eq!(id.is_some(), self.matrix);
+                let step = self
+                    .steps
+                    .iter()
+                    .find(|s| s.id.as_deref() == id)
+                    .expect("id is valid");
+                // Keep `stdout = "any"`, regular expressions, etc
+                let stdout = stdout.filter(|_| {
+                    step.stdout_check.is_none()
+                        && step.expected_output_format != OutputFormat::Regex
+                });
+                let stderr = stderr.filter(|_| {
+                    step.stderr_check.is_none()
+                        && step.expected_stderr_format != OutputFormat::Regex
+                });
 
                 overwrite_toml_output(path, id, stdout, "stdout", "stdout")?;
                 overwrite_toml_output(path, id, stderr, "stderr", "stderr")?;
+                overwrite_toml_output(path, id, merged, "merged", "expected-merged")?;
 
                 if let Some(status) = exit {
-                    let raw = std::fs::read_to_string(path)
-                        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
-                    let overwritten = overwrite_toml_status(status, raw)
-                        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
-                    std::fs::write(path, overwritten)
-                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    let raw = std::fs::read_to_string(path).map_err(|e| {
+                        crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+                    })?;
+                    let overwritten = overwrite_toml_status(status, id, raw).map_err(|e| {
+                        crate::Error::ParseToml(format!(
+                            "Failed to parse {}: {}",
+                            path.display(),
+                            e
+                        ))
+                    })?;
+                    std::fs::write(path, overwritten).map_err(|e| {
+                        crate::Error::Io(format!("Failed to write {}: {}", path.display(), e))
+                    })?;
                 }
             } else if ext == std::ffi::OsStr::new("trycmd") || ext == std::ffi::OsStr::new("md") {
                 if stderr.is_some() && stderr != Some(&crate::Data::new()) {
@@ -159,19 +167,31 @@ impl TryCmd {
                         .clone()
                         .expect("always present for .trycmd");
 
-                    let raw = std::fs::read_to_string(path)
-                        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                    let raw = std::fs::read_to_string(path).map_err(|e| {
+                        crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+                    })?;
                     let mut normalized = snapbox::filter::normalize_lines(&raw);
-
-                    overwrite_trycmd_status(exit, step, &mut line_nums, &mut normalized)?;
+                    // Match the indentation of the command, for fences within lists
+                    let indent = id
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|line_num| normalized.lines().nth(line_num - 1))
+                        .map(|line| {
+                            let content = line.trim_start_matches(' ');
+                            line[..line.len() - content.len()].to_owned()
+                        })
+                        .unwrap_or_default();
+
+                    overwrite_trycmd_status(exit, step, &mut line_nums, &mut normalized, &indent)?;
 
                     let mut stdout = stdout.render().expect("at least Text");
                     // Add back trailing newline removed when parsing
                     stdout.push('\n');
-                    replace_lines(&mut normalized, line_nums, &stdout)?;
+                    replace_lines(&mut normalized, line_nums, &indent_lines(&stdout, &indent))?;
 
-                    std::fs::write(path, normalized.into_bytes())
-                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    std::fs::write(path, normalized.into_bytes()).map_err(|e| {
+                        crate::Error::Io(format!("Failed to write {}: {}", path.display(), e))
+                    })?;
                 }
             } else {
                 return Err(format!("Unsupported extension: {}", ext.to_string_lossy()).into());
@@ -192,25 +212,42 @@ impl TryCmd {
             .collect();
         'outer: loop {
             let mut fence_pattern = "```".to_owned();
+            let mut capture_env = None;
             while let Some((_, line)) = lines.pop_front() {
-                let tick_end = line
-                    .char_indices()
-                    .find_map(|(i, c)| (c != '`').then_some(i))
-                    .unwrap_or(line.len());
+                // Like Markdown, fences may be indented, as within lists
+                let fence = line.trim_start_matches(' ');
+                let indent = line.len() - fence.len();
+                let tick_end = match fence.chars().next() {
+                    Some(c @ ('`' | '~')) if indent <= MAX_FENCE_INDENT => {
+                        fence.find(|f| f != c).unwrap_or(fence.len())
+                    }
+                    _ => 0,
+                };
                 if 3 <= tick_end {
-                    line[..tick_end].clone_into(&mut fence_pattern);
-                    let raw = line[tick_end..].trim();
+                    fence[..tick_end].clone_into(&mut fence_pattern);
+                    let raw = fence[tick_end..].trim();
                     if raw.is_empty() {
                         // Assuming a trycmd block
+                        dedent_block(&mut lines, indent, &fence_pattern);
                         break;
                     } else {
                         let mut info = raw.split(',');
                         let lang = info.next().unwrap();
                         match lang {
                             "trycmd" | "console" => {
-                                if info.any(|i| i == "ignore") {
-                                    snapbox::debug!("ignore from infostring: {:?}", info);
+                                let attrs = info.map(str::trim).collect::<Vec<_>>();
+                                if attrs.contains(&"ignore") {
+                                    snapbox::debug!("ignore from infostring: {:?}", attrs);
                                 } else {
+                                    capture_env =
+                                        attrs.iter().find_map(|a| match a.split_once('=') {
+                                            Some(("capture-env", path)) => {
+                                                Some(CaptureEnv::File(path.into()))
+                                            }
+                                            None if *a == "capture-env" => Some(CaptureEnv::Stdout),
+                                            _ => None,
+                                        });
+                                    dedent_block(&mut lines, indent, &fence_pattern);
                                     break;
                                 }
                             }
@@ -222,7 +259,7 @@ impl TryCmd {
 
                     // Irrelevant block, consume to end
                     while let Some((_, line)) = lines.pop_front() {
-                        if line.starts_with(&fence_pattern) {
+                        if line.trim_start_matches(' ').starts_with(&fence_pattern) {
                             continue 'outer;
                         }
                     }
@@ -230,7 +267,7 @@ impl TryCmd {
             }
 
             'code: loop {
-                let mut cmdline = Vec::new();
+                let mut cmdline = String::new();
                 let mut expected_status_source = None;
                 let mut expected_status = Some(CommandStatus::Success);
                 let mut stdout = String::new();
@@ -241,7 +278,7 @@ impl TryCmd {
                     if line.starts_with(&fence_pattern) {
                         break;
                     } else if let Some(raw) = line.strip_prefix("$ ") {
-                        cmdline.extend(shlex::Shlex::new(raw.trim()));
+                        cmdline.push_str(raw.trim());
                         cmd_start = line_num;
                         stdout_start = line_num + 1;
                     } else {
@@ -254,7 +291,8 @@ impl TryCmd {
                 }
                 while let Some((line_num, line)) = lines.pop_front() {
                     if let Some(raw) = line.strip_prefix("> ") {
-                        cmdline.extend(shlex::Shlex::new(raw.trim()));
+                        cmdline.push(' ');
+                        cmdline.push_str(raw.trim());
                         stdout_start = line_num + 1;
                     } else {
                         lines.push_front((line_num, line));
@@ -292,34 +330,116 @@ impl TryCmd {
                     stdout.pop();
                 }
 
-                let mut env = Env::default();
+                // `bg:` leaves the command running for the next ones, like a shell's trailing `&`
+                let (background, cmdline) = match cmdline.strip_prefix("bg:") {
+                    Some(rest) => (true, rest.trim_start()),
+                    None => (false, cmdline.as_str()),
+                };
 
-                let bin = loop {
-                    if cmdline.is_empty() {
-                        return Err(format!("No bin specified on line {}", cmd_start).into());
-                    }
-                    let next = cmdline.remove(0);
-                    if let Some((key, value)) = next.split_once('=') {
-                        env.add.insert(key.to_owned(), value.to_owned());
-                    } else {
-                        break next;
-                    }
+                let shell_line = cmdline.strip_prefix("sh:").map(str::trim_start);
+                // Only split with `pipe:`, as `|` used to be an argument like any other
+                let stage_lines = match (shell_line, cmdline.strip_prefix("pipe:")) {
+                    (Some(line), _) => vec![line],
+                    (None, Some(pipeline)) => split_pipeline(pipeline.trim_start()),
+                    (None, None) => vec![cmdline],
                 };
+                // Each stage keeps its own `KEY=value` prefix, like in a shell
+                let mut stages = stage_lines.into_iter().map(|stage| {
+                    let mut env = Env::default();
+                    if shell_line.is_some() {
+                        // Leave everything after the bin for the shell to interpret
+                        let mut rest = stage;
+                        let bin = loop {
+                            let (word, remainder) =
+                                rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                            if word.is_empty() {
+                                return Err(format!("No bin specified on line {}", cmd_start));
+                            }
+                            rest = remainder.trim_start();
+                            if let Some((key, value)) = word.split_once('=') {
+                                env.add.insert(key.to_owned(), value.to_owned());
+                            } else {
+                                break word;
+                            }
+                        };
+                        return Ok(PipeStage {
+                            bin: Some(Bin::Name(bin.to_owned())),
+                            args: Args::Split(vec![rest.to_owned()]),
+                            env,
+                        });
+                    }
+
+                    let mut args = shlex::Shlex::new(stage).collect::<Vec<_>>();
+                    let bin = loop {
+                        if args.is_empty() {
+                            return Err(format!("No bin specified on line {}", cmd_start));
+                        }
+                        let next = args.remove(0);
+                        if let Some((key, value)) = next.split_once('=') {
+                            env.add.insert(key.to_owned(), value.to_owned());
+                        } else {
+                            break next;
+                        }
+                    };
+                    Ok(PipeStage {
+                        bin: Some(Bin::Name(bin)),
+                        args: Args::Split(args),
+                        env,
+                    })
+                });
+                let first = stages.next().expect("always at least one stage")?;
+                let pipe = stages.collect::<Result<Vec<_>, _>>()?;
                 let step = Step {
                     id: Some(cmd_start.to_string()),
-                    bin: Some(Bin::Name(bin)),
-                    args: cmdline,
-                    env,
+                    bin: first.bin,
+                    args: first.args.into_vec(),
+                    // The first stage is the step itself, so its prefix is the step's `env`
+                    env: first.env,
+                    pipe,
+                    pipefail: false,
+                    shell: shell_line.is_some(),
                     stdin: None,
+                    stdin_close: Default::default(),
                     stderr_to_stdout: true,
+                    tolerate_interleave: false,
                     expected_status_source,
                     expected_status,
                     expected_stdout_source: Some(stdout_start..post_stdout_start),
                     expected_stdout: Some(crate::Data::text(stdout)),
                     expected_stderr_source: None,
                     expected_stderr: None,
+                    stdout_check: None,
+                    stderr_check: None,
+                    stdout_must_not_contain: Vec::new(),
+                    stderr_must_not_contain: Vec::new(),
+                    stdout_compare: Default::default(),
+                    stderr_compare: Default::default(),
+                    expected_merged: None,
                     binary: false,
+                    normalize_line_endings: None,
+                    strip_ansi: false,
+                    normalize_paths: false,
+                    trim_trailing_whitespace: false,
+                    require_final_newline: None,
+                    expected_output_format: Default::default(),
+                    expected_stderr_format: Default::default(),
                     timeout: None,
+                    timeout_grace: None,
+                    retries: Default::default(),
+                    max_output: None,
+                    max_duration: None,
+                    limits: Default::default(),
+                    scheduling: Default::default(),
+                    wrapper: Vec::new(),
+                    term: Default::default(),
+                    net: Net::Host,
+                    fake_time: None,
+                    interaction: Vec::new(),
+                    signals: Vec::new(),
+                    expect_lines: Vec::new(),
+                    background: background.then(Default::default),
+                    capture_env: capture_env.clone(),
+                    skip_reason: None,
                 };
                 steps.push(step);
                 if block_done {
@@ -335,39 +455,182 @@ impl TryCmd {
     }
 }
 
+/// How far a fence may be indented, like in Markdown
+const MAX_FENCE_INDENT: usize = 3;
+
+/// Strip the fence's indentation from the lines of its block, up to the closing fence
+fn dedent_block(lines: &mut VecDeque<(usize, &str)>, indent: usize, fence_pattern: &str) {
+    if indent == 0 {
+        return;
+    }
+    for (_, line) in lines.iter_mut() {
+        let content = line.trim_start_matches(' ');
+        let stripped = indent.min(line.len() - content.len());
+        *line = &line[stripped..];
+        if content.starts_with(fence_pattern) {
+            break;
+        }
+    }
+}
+
+/// Indent each non-empty line of `text`, like the fence it is written within
+fn indent_lines(text: &str, indent: &str) -> String {
+    snapbox::utils::LinesWithTerminator::new(text)
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_owned()
+            } else {
+                format!("{indent}{line}")
+            }
+        })
+        .collect()
+}
+
+/// Resolve as much of `fs.cwd` as exists as the rest is created within the sandbox
+fn resolve_cwd(cwd: std::path::PathBuf) -> Result<std::path::PathBuf, crate::Error> {
+    let Some(existing) = cwd.ancestors().find(|p| p.exists()) else {
+        return snapbox::dir::resolve_dir(cwd).map_err(|e| e.to_string().into());
+    };
+    let missing = cwd.strip_prefix(existing).expect("ancestor is a prefix");
+    let resolved = snapbox::dir::resolve_dir(existing).map_err(|e| e.to_string())?;
+    Ok(resolved.join(missing))
+}
+
+/// Run `line` through the platform shell
+fn shell_command(line: String) -> std::process::Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut cmd = std::process::Command::new(shell);
+    cmd.arg(flag).arg(line);
+    cmd
+}
+
+fn quote_for_shell(word: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{word}\"")
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// A command's program and arguments, and its changes to the environment
+pub(crate) type Invocation = (Vec<String>, BTreeMap<String, Option<String>>);
+
+/// Write `cmd` as a shell command line, with `env` for its changes to the environment
+fn repro_command(cmd: &std::process::Command, inherit: bool) -> String {
+    let quote = |word: &std::ffi::OsStr| quote_for_shell(&word.to_string_lossy());
+    let mut words = Vec::new();
+    let mut unset = Vec::new();
+    let mut set = Vec::new();
+    for (name, value) in cmd.get_envs() {
+        match value {
+            Some(value) => set.push(format!("{}={}", name.to_string_lossy(), quote(value))),
+            None if inherit => unset.push(format!("-u {}", quote(name))),
+            None => {}
+        }
+    }
+    if !inherit || !unset.is_empty() || !set.is_empty() {
+        words.push("env".to_owned());
+        if !inherit {
+            words.push("-i".to_owned());
+        }
+        words.extend(unset);
+        words.extend(set);
+    }
+    words.push(quote(cmd.get_program()));
+    words.extend(cmd.get_args().map(quote));
+    words.join(" ")
+}
+
+/// Split a command line on each `|` that isn't quoted or escaped
+fn split_pipeline(cmdline: &str) -> Vec<&str> {
+    let mut stages = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in cmdline.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(_), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|') => {
+                stages.push(&cmdline[start..i]);
+                start = i + 1;
+            }
+            (None, _) => {}
+        }
+    }
+    stages.push(&cmdline[start..]);
+    stages
+}
+
+/// The snapshot file next to a `cmd.toml`, like `cmd.stdout` or `cmd-<id>.stdout` for a step
+pub(crate) fn snapshot_file(
+    path: &std::path::Path,
+    id: Option<&str>,
+    ext: &str,
+) -> std::path::PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    crate::platform::snapshot_path(match id {
+        Some(id) => path.with_file_name(format!("{stem}-{id}.{ext}")),
+        None => path.with_extension(ext),
+    })
+}
+
 fn overwrite_toml_output(
     path: &std::path::Path,
-    _id: Option<&str>,
+    id: Option<&str>,
     output: Option<&crate::Data>,
     output_ext: &str,
     output_field: &str,
 ) -> Result<(), crate::Error> {
-    if let Some(output) = output {
-        let output_path = path.with_extension(output_ext);
+    if let (Some(id), Some(output)) = (id, output) {
+        // `[[matrix]]` entries only support snapshot files
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let output_path = crate::platform::snapshot_path(
+            path.with_file_name(format!("{stem}-{id}.{output_ext}")),
+        );
+        output.write_to_path(&output_path)?;
+    } else if let Some(output) = output {
+        let output_path = crate::platform::snapshot_path(path.with_extension(output_ext));
         if output_path.exists() {
             output.write_to_path(&output_path)?;
         } else if let Some(output) = output.render() {
-            let raw = std::fs::read_to_string(path)
-                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
-            let mut doc = raw
-                .parse::<toml_edit::DocumentMut>()
-                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let raw = std::fs::read_to_string(path).map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            let mut doc = raw.parse::<toml_edit::DocumentMut>().map_err(|e| {
+                crate::Error::ParseToml(format!("Failed to parse {}: {}", path.display(), e))
+            })?;
             if let Some(output_value) = doc.get_mut(output_field) {
                 *output_value = toml_edit::value(output);
             }
-            std::fs::write(path, doc.to_string())
-                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            std::fs::write(path, doc.to_string()).map_err(|e| {
+                crate::Error::Io(format!("Failed to write {}: {}", path.display(), e))
+            })?;
         } else {
             output.write_to_path(&output_path)?;
 
-            let raw = std::fs::read_to_string(path)
-                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
-            let mut doc = raw
-                .parse::<toml_edit::DocumentMut>()
-                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let raw = std::fs::read_to_string(path).map_err(|e| {
+                crate::Error::Io(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            let mut doc = raw.parse::<toml_edit::DocumentMut>().map_err(|e| {
+                crate::Error::ParseToml(format!("Failed to parse {}: {}", path.display(), e))
+            })?;
             doc[output_field] = toml_edit::Item::None;
-            std::fs::write(path, doc.to_string())
-                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            std::fs::write(path, doc.to_string()).map_err(|e| {
+                crate::Error::Io(format!("Failed to write {}: {}", path.display(), e))
+            })?;
         }
     }
 
@@ -376,9 +639,28 @@ fn overwrite_toml_output(
 
 fn overwrite_toml_status(
     status: std::process::ExitStatus,
+    id: Option<&str>,
     raw: String,
 ) -> Result<String, toml_edit::TomlError> {
     let mut doc = raw.parse::<toml_edit::DocumentMut>()?;
+    let table = match id {
+        Some(id) => doc
+            .get_mut("matrix")
+            .and_then(|m| m.as_array_of_tables_mut())
+            .and_then(|m| {
+                m.iter_mut()
+                    .find(|entry| entry.get("name").and_then(|n| n.as_str()) == Some(id))
+            }),
+        None => Some(doc.as_table_mut()),
+    };
+    if let Some(table) = table {
+        update_toml_status(status, table);
+    }
+
+    Ok(doc.to_string())
+}
+
+fn update_toml_status(status: std::process::ExitStatus, doc: &mut toml_edit::Table) {
     if let Some(code) = status.code() {
         if status.success() {
             match doc.get("status") {
@@ -430,15 +712,17 @@ fn overwrite_toml_status(
                 }
             }
         }
+    } else if doc.get("status").and_then(|s| s.get("signal")).is_some() {
+        if let Some(signal) = crate::platform::exit_signal(status) {
+            doc["status"]["signal"] = toml_edit::value(i64::from(signal));
+        }
     } else if !matches!(
         doc.get("status"),
         Some(toml_edit::Item::Value(toml_edit::Value::String(ref expected)))
-            if expected.value() == "interrupted")
+            if expected.value() == "interrupted" || expected.value() == "core-dumped")
     {
         doc["status"] = toml_edit::value("interrupted");
     }
-
-    Ok(doc.to_string())
 }
 
 fn overwrite_trycmd_status(
@@ -446,6 +730,7 @@ fn overwrite_trycmd_status(
     step: &Step,
     stdout_line_nums: &mut std::ops::Range<usize>,
     normalized: &mut String,
+    indent: &str,
 ) -> Result<(), crate::Error> {
     let status = match exit {
         Some(status) => status,
@@ -476,7 +761,10 @@ fn overwrite_trycmd_status(
             }
         }
     } else {
-        if step.expected_status == Some(CommandStatus::Interrupted) {
+        if matches!(
+            step.expected_status,
+            Some(CommandStatus::Interrupted | CommandStatus::Signal(_) | CommandStatus::CoreDumped)
+        ) {
             None
         } else {
             Some("? interrupted".into())
@@ -484,6 +772,7 @@ fn overwrite_trycmd_status(
     };
 
     if let Some(status) = formatted_status {
+        let status = format!("{indent}{status}");
         if let Some(line_num) = step.expected_status_source {
             replace_lines(normalized, line_num..(line_num + 1), &status)?;
         } else {
@@ -531,40 +820,298 @@ impl std::str::FromStr for TryCmd {
     }
 }
 
-impl From<OneShot> for TryCmd {
-    fn from(other: OneShot) -> Self {
+impl TryFrom<OneShot> for TryCmd {
+    type Error = crate::Error;
+
+    fn try_from(other: OneShot) -> Result<Self, Self::Error> {
         let OneShot {
+            schema_version,
+            warnings,
             bin,
             args,
             env,
+            pipe,
+            pipefail,
+            shell,
             stdin,
             stdout,
             stderr,
+            stdout_must_not_contain,
+            stderr_must_not_contain,
+            stdout_compare,
+            stderr_compare,
+            expected_merged,
             stderr_to_stdout,
+            tolerate_interleave,
             status,
             binary,
+            normalize_line_endings,
+            strip_ansi,
+            normalize_paths,
+            trim_trailing_whitespace,
+            require_final_newline,
+            expected_output_format,
             timeout,
+            timeout_grace,
+            background,
+            retries,
+            max_output,
+            max_duration,
+            limits,
+            priority,
+            cpus,
+            wrapper,
+            term,
+            color,
+            net,
+            fake_time,
+            interaction,
+            signals,
+            expect_line,
             fs,
+            matrix,
+            locales,
+            skip_on,
+            target,
+            skip,
+            only,
+            expected_failure,
+            depends_on,
+            order,
+            tags,
+            description,
+            links,
+            include: _,
+            substitutions,
+            http_stub,
         } = other;
-        Self {
-            steps: vec![Step {
-                id: None,
-                bin,
-                args: args.into_vec(),
-                env,
-                stdin: stdin.map(crate::Data::text),
-                stderr_to_stdout,
-                expected_status_source: None,
-                expected_status: status,
-                expected_stdout_source: None,
-                expected_stdout: stdout.map(crate::Data::text),
-                expected_stderr_source: None,
-                expected_stderr: stderr.map(crate::Data::text),
-                binary,
-                timeout,
-            }],
-            fs,
+        match schema_version {
+            Some(0) => return Err("`schema-version` starts at `1`".into()),
+            Some(version) if crate::migrate::SCHEMA_VERSION < version => {
+                return Err(format!(
+                    "`schema-version = {}` needs a newer trycmd, this one reads up to `{}`",
+                    version,
+                    crate::migrate::SCHEMA_VERSION
+                )
+                .into());
+            }
+            _ => {}
+        }
+        let mut status = status;
+        let mut stdout = stdout;
+        let mut stderr = stderr;
+        for (cfg, overrides) in target {
+            if crate::platform::eval(&cfg)? {
+                status = overrides.status.or(status);
+                stdout = overrides.stdout.or(stdout);
+                stderr = overrides.stderr.or(stderr);
+            }
+        }
+        let mut skip_reason = None;
+        if let Some(platform) = skip_on.iter().find(|p| crate::platform::is_current(p)) {
+            status = Some(CommandStatus::Skipped);
+            skip_reason = Some(format!("skipped on {platform}"));
+        }
+        match skip {
+            Some(Skip::Flag(false)) | None => {}
+            Some(Skip::Flag(true)) => {
+                status = Some(CommandStatus::Skipped);
+            }
+            Some(Skip::Reason(reason)) => {
+                status = Some(CommandStatus::Skipped);
+                skip_reason = Some(reason);
+            }
+        }
+        let fake_time = fake_time
+            .map(|time| {
+                humantime::parse_rfc3339_weak(&time)
+                    .map_err(|e| format!("Invalid `fake-time` `{time}`: {e}"))
+            })
+            .transpose()?;
+
+        let stdout_check = stdout.as_deref().and_then(StreamCheck::from_keyword);
+        if stdout_check.is_some() {
+            stdout = None;
+        }
+        let stderr_check = stderr.as_deref().and_then(StreamCheck::from_keyword);
+        if stderr_check.is_some() {
+            stderr = None;
+        }
+
+        let pipe: Vec<_> = pipe
+            .into_iter()
+            .map(|mut stage| {
+                if stage.bin.is_none() {
+                    stage.bin.clone_from(&bin);
+                }
+                stage
+            })
+            .collect();
+        if stdout_compare != CompareMode::Lines && expected_output_format != OutputFormat::Text {
+            return Err("`stdout-compare` needs `expected-output-format = \"text\"`".into());
+        }
+        if expected_merged.is_some() && stderr_to_stdout {
+            return Err("`expected-merged` is unsupported with `stderr-to-stdout = true`".into());
+        }
+        if tolerate_interleave && !stderr_to_stdout {
+            return Err("`tolerate-interleave` needs `stderr-to-stdout = true`".into());
+        }
+        if stdout_must_not_contain.iter().any(String::is_empty) {
+            return Err("`stdout-must-not-contain` entries can't be empty".into());
+        }
+        if stderr_must_not_contain.iter().any(String::is_empty) {
+            return Err("`stderr-must-not-contain` entries can't be empty".into());
+        }
+        if shell && !pipe.is_empty() {
+            return Err("`pipe` is unsupported with `shell = true`".into());
+        }
+        if pipefail && pipe.is_empty() {
+            return Err("`pipefail` needs `pipe`".into());
+        }
+        if background && retries.as_ref().map(|r| r.count > 0) == Some(true) {
+            return Err("`retries` is unsupported with `background = true`".into());
+        }
+        for stub in &http_stub {
+            if !stub.path.starts_with('/') {
+                return Err(format!("`http-stub.path` must start with `/`: {}", stub.path).into());
+            }
+            if stub.body.is_some() && stub.body_file.is_some() {
+                return Err("`http-stub.body` is unsupported with `http-stub.body-file`".into());
+            }
+        }
+        let args = if shell {
+            vec![args.into_shell_line()]
+        } else {
+            args.into_vec()
+        };
+        let step = Step {
+            id: None,
+            bin,
+            args,
+            env: Env { color, ..env },
+            pipe,
+            pipefail,
+            shell,
+            stdin_close: stdin.as_ref().map(Stdin::close).unwrap_or_default(),
+            stdin: stdin.map(Stdin::into_data).transpose()?.flatten(),
+            stderr_to_stdout,
+            tolerate_interleave,
+            expected_status_source: None,
+            expected_status: status,
+            expected_stdout_source: None,
+            expected_stdout: stdout.map(crate::Data::text),
+            expected_stderr_source: None,
+            expected_stderr: stderr.map(crate::Data::text),
+            stdout_check,
+            stderr_check,
+            stdout_must_not_contain,
+            stderr_must_not_contain,
+            stdout_compare,
+            stderr_compare,
+            expected_merged: expected_merged.map(crate::Data::text),
+            binary,
+            normalize_line_endings,
+            strip_ansi,
+            normalize_paths,
+            trim_trailing_whitespace,
+            require_final_newline,
+            expected_output_format,
+            expected_stderr_format: Default::default(),
+            timeout,
+            timeout_grace,
+            retries: retries.unwrap_or_default(),
+            max_output: max_output.map(|m| m.to_bytes()).transpose()?,
+            max_duration,
+            limits: crate::limits::Rlimits::new(&limits)?,
+            scheduling: crate::sched::Scheduling::new(priority, &cpus)?,
+            wrapper,
+            term,
+            net,
+            fake_time,
+            interaction,
+            signals,
+            expect_lines: expect_line,
+            background: background.then(Default::default),
+            capture_env: None,
+            skip_reason,
+        };
+        let mut matrix = matrix;
+        if !locales.is_empty() {
+            if !matrix.is_empty() {
+                return Err("`locales` is unsupported with `[[matrix]]`".into());
+            }
+            matrix = locales
+                .into_iter()
+                .map(MatrixEntry::locale)
+                .collect::<Result<_, _>>()?;
+        }
+        if matrix.is_empty() {
+            return Ok(Self {
+                steps: vec![step],
+                fs,
+                matrix: false,
+                only,
+                expected_failure,
+                substitutions,
+                depends_on,
+                order,
+                tags,
+                description,
+                links,
+                warnings,
+                http_stubs: http_stub,
+            });
+        }
+
+        if step.expected_stdout.is_some()
+            || step.expected_stderr.is_some()
+            || step.expected_merged.is_some()
+        {
+            return Err(
+                "`stdout`, `stderr`, and `expected-merged` are unsupported with `[[matrix]]`"
+                    .into(),
+            );
+        }
+        let mut names = std::collections::BTreeSet::new();
+        let mut steps = Vec::with_capacity(matrix.len());
+        for entry in matrix {
+            if entry.name.is_empty() {
+                return Err("`matrix.name` must not be empty".into());
+            }
+            if !names.insert(entry.name.clone()) {
+                return Err(format!("Duplicate `matrix.name`: {}", entry.name).into());
+            }
+
+            let mut entry_step = step.clone();
+            entry_step.id = Some(entry.name);
+            if entry_step.shell {
+                let line = &mut entry_step.args[0];
+                line.push(' ');
+                line.push_str(&entry.args.into_shell_line());
+            } else {
+                entry_step.args.extend(entry.args.into_vec());
+            }
+            entry_step.env.update(&entry.env);
+            if entry.status.is_some() {
+                entry_step.expected_status = entry.status;
+            }
+            steps.push(entry_step);
         }
+        Ok(Self {
+            steps,
+            fs,
+            matrix: true,
+            only,
+            expected_failure,
+            substitutions,
+            depends_on,
+            order,
+            tags,
+            description,
+            links,
+            warnings,
+            http_stubs: http_stub,
+        })
     }
 }
 
@@ -574,52 +1121,491 @@ pub(crate) struct Step {
     pub(crate) bin: Option<Bin>,
     pub(crate) args: Vec<String>,
     pub(crate) env: Env,
+    pub(crate) pipe: Vec<PipeStage>,
+    /// Fail when a `pipe` command before the last one fails
+    pub(crate) pipefail: bool,
+    /// `args` holds a single line for the platform shell
+    pub(crate) shell: bool,
     pub(crate) stdin: Option<crate::Data>,
+    pub(crate) stdin_close: StdinClose,
     pub(crate) stderr_to_stdout: bool,
+    /// Merge the streams to match `stdout`, from `tolerate-interleave`
+    pub(crate) tolerate_interleave: bool,
     pub(crate) expected_status_source: Option<usize>,
     pub(crate) expected_status: Option<CommandStatus>,
     pub(crate) expected_stdout_source: Option<std::ops::Range<usize>>,
     pub(crate) expected_stdout: Option<crate::Data>,
     pub(crate) expected_stderr_source: Option<std::ops::Range<usize>>,
     pub(crate) expected_stderr: Option<crate::Data>,
+    pub(crate) stdout_check: Option<StreamCheck>,
+    pub(crate) stderr_check: Option<StreamCheck>,
+    pub(crate) stdout_must_not_contain: Vec<String>,
+    pub(crate) stderr_must_not_contain: Vec<String>,
+    pub(crate) stdout_compare: CompareMode,
+    pub(crate) stderr_compare: CompareMode,
+    pub(crate) expected_merged: Option<crate::Data>,
     pub(crate) binary: bool,
+    pub(crate) normalize_line_endings: Option<bool>,
+    pub(crate) strip_ansi: bool,
+    pub(crate) normalize_paths: bool,
+    pub(crate) trim_trailing_whitespace: bool,
+    pub(crate) require_final_newline: Option<bool>,
+    pub(crate) expected_output_format: OutputFormat,
+    /// How `stderr` is compared, only set by a `.stderr.regex` file
+    pub(crate) expected_stderr_format: OutputFormat,
     pub(crate) timeout: Option<std::time::Duration>,
+    pub(crate) timeout_grace: Option<std::time::Duration>,
+    pub(crate) retries: Retries,
+    pub(crate) max_output: Option<usize>,
+    pub(crate) max_duration: Option<std::time::Duration>,
+    pub(crate) limits: crate::limits::Rlimits,
+    pub(crate) scheduling: crate::sched::Scheduling,
+    /// Command to run the program under, like `["valgrind"]`
+    pub(crate) wrapper: Vec<String>,
+    pub(crate) term: Term,
+    /// What network the command can reach, checked for being possible once it is about to run
+    pub(crate) net: Net,
+    /// What the command's clock reads, from `fake-time`
+    pub(crate) fake_time: Option<std::time::SystemTime>,
+    pub(crate) interaction: Vec<Interaction>,
+    pub(crate) signals: Vec<ScheduledSignal>,
+    pub(crate) expect_lines: Vec<ExpectLine>,
+    /// Keep running alongside the following steps, from `background` or `$ bg: `
+    pub(crate) background: Option<crate::watch::Background>,
+    /// Where to read `env` changes for the following steps from
+    pub(crate) capture_env: Option<CaptureEnv>,
+    /// Why the step is expected to be skipped
+    pub(crate) skip_reason: Option<String>,
 }
 
 impl Step {
+    /// The command line, for naming the step
+    pub(crate) fn command_line(&self) -> String {
+        let bin = match &self.bin {
+            Some(Bin::Name(name)) => name.clone(),
+            Some(Bin::Path(path)) => path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            Some(Bin::Script(script)) => script.clone(),
+            Some(Bin::Ignore) | Some(Bin::Error(_)) | None => "<bin>".to_owned(),
+        };
+        std::iter::once(bin)
+            .chain(self.args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Fill in anything not specified inline from snapshot files next to a `cmd.toml`
+    fn load_snapshots(&mut self, path: &std::path::Path) -> Result<(), crate::Error> {
+        let snapshot_path = |ext: &str| snapshot_file(path, self.id.as_deref(), ext);
+        let is_binary = match self.binary {
+            true => snapbox::data::DataFormat::Binary,
+            false => snapbox::data::DataFormat::Text,
+        };
+
+        if self.stdin.is_none() {
+            let stdin_path = snapshot_path("stdin");
+            let stdin = if stdin_path.exists() {
+                // No `map_text` as we will trust what the user inputted
+                Some(crate::Data::try_read_from(&stdin_path, Some(is_binary))?)
+            } else {
+                None
+            };
+            self.stdin = stdin;
+        }
+
+        // Keywords like `stdout = "any"` aren't content, so they leave the files unused instead
+        for (ext, inline) in [
+            ("stdout", self.expected_stdout.is_some()),
+            ("stderr", self.expected_stderr.is_some()),
+            ("merged", self.expected_merged.is_some()),
+        ] {
+            let snapshot = snapshot_path(ext);
+            let regex = snapshot_path(&format!("{ext}.regex"));
+            let conflict = match (inline, snapshot.exists(), regex.exists()) {
+                (true, true, _) => Some((path, snapshot)),
+                (true, false, true) => Some((path, regex)),
+                (false, true, true) => Some((snapshot.as_path(), regex)),
+                _ => None,
+            };
+            if let Some((first, second)) = conflict {
+                return Err(format!(
+                    "`{ext}` is set in {} and in {}; keep only one",
+                    first.display(),
+                    second.display()
+                )
+                .into());
+            }
+        }
+
+        if self.expected_stdout.is_none() && self.stdout_check.is_none() {
+            let stdout_path = snapshot_path("stdout");
+            let regex_path = snapshot_path("stdout.regex");
+            let stdout = if stdout_path.exists() {
+                Some(FilterPaths.filter(crate::Data::read_from(&stdout_path, Some(is_binary))))
+            } else if regex_path.exists() {
+                // Not `FilterPaths`, as `\` is significant
+                self.expected_output_format = OutputFormat::Regex;
+                Some(crate::Data::try_read_from(
+                    &regex_path,
+                    Some(snapbox::data::DataFormat::Text),
+                )?)
+            } else {
+                None
+            };
+            self.expected_stdout = stdout;
+        }
+
+        if self.expected_stderr.is_none() && self.stderr_check.is_none() {
+            let stderr_path = snapshot_path("stderr");
+            let regex_path = snapshot_path("stderr.regex");
+            let stderr = if stderr_path.exists() {
+                Some(FilterPaths.filter(crate::Data::read_from(&stderr_path, Some(is_binary))))
+            } else if regex_path.exists() {
+                self.expected_stderr_format = OutputFormat::Regex;
+                Some(crate::Data::try_read_from(
+                    &regex_path,
+                    Some(snapbox::data::DataFormat::Text),
+                )?)
+            } else {
+                None
+            };
+            self.expected_stderr = stderr;
+        }
+
+        if self.expected_merged.is_none() && !self.stderr_to_stdout {
+            let merged_path = snapshot_path("merged");
+            if merged_path.exists() {
+                self.expected_merged =
+                    Some(FilterPaths.filter(crate::Data::read_from(&merged_path, Some(is_binary))));
+            }
+        }
+
+        let normalize_line_endings = self.normalize_line_endings();
+        let trim_trailing_whitespace = self.trim_trailing_whitespace;
+        let require_final_newline = self.require_final_newline();
+        for expected in [
+            &mut self.expected_stdout,
+            &mut self.expected_stderr,
+            &mut self.expected_merged,
+        ] {
+            let Some(mut data) = expected.take() else {
+                continue;
+            };
+            if normalize_line_endings {
+                data = crate::compare::normalize_line_endings(data);
+            }
+            if trim_trailing_whitespace {
+                data = crate::compare::trim_trailing_whitespace(data);
+            }
+            if !require_final_newline {
+                data = crate::compare::ensure_final_newline(data);
+            }
+            *expected = Some(data);
+        }
+
+        Ok(())
+    }
+
+    /// The command whose output is checked, the last in any `pipe`
     pub(crate) fn to_command(
         &self,
         cwd: Option<&std::path::Path>,
     ) -> Result<snapbox::cmd::Command, crate::Error> {
-        let bin = match &self.bin {
-            Some(Bin::Path(path)) => Ok(path.clone()),
-            Some(Bin::Name(name)) => Err(format!("Unknown bin.name = {}", name).into()),
-            Some(Bin::Ignore) => Err("Internal error: tried to run an ignored bin".into()),
-            Some(Bin::Error(err)) => Err(err.clone()),
-            None => Err("No bin specified".into()),
+        let mut cmd = match self.pipe.last() {
+            Some(stage) => self.stage_command(stage.bin.as_ref(), &stage.args, &stage.env, cwd),
+            None => self.stage_command(self.bin.as_ref(), &self.args, &Env::default(), cwd),
         }?;
-        if !bin.exists() {
-            return Err(format!("Bin doesn't exist: {}", bin.display()).into());
+        if !self.pipe.is_empty() {
+            // `stdin` is for the first command, with this one reading the one before
+            if self.stdin_close != StdinClose::default() {
+                return Err("`stdin.close` is unsupported with `pipe`".into());
+            }
+        } else if let Some(stdin) = &self.stdin {
+            if self.stdin_close == StdinClose::Immediately {
+                return Err(
+                    "`stdin.close = \"immediately\"` leaves no chance to write `stdin`".into(),
+                );
+            }
+            cmd = cmd.stdin(stdin);
+        }
+        cmd = cmd.stdin_close(self.stdin_close.to_snapbox());
+        // Kept apart to interleave later
+        if self.stderr_to_stdout && !self.tolerate_interleave {
+            cmd = cmd.stderr_to_stdout();
+        }
+        #[cfg(unix)]
+        for scheduled in &self.signals {
+            cmd = cmd.signal(scheduled.after, scheduled.signal.as_raw());
+        }
+        #[cfg(not(unix))]
+        if !self.signals.is_empty() {
+            return Err("`[[signals]]` is only supported on Unix".into());
         }
 
-        let mut cmd = snapbox::cmd::Command::new(bin).args(&self.args);
-        if let Some(cwd) = cwd {
-            cmd = cmd.current_dir(cwd);
+        Ok(cmd)
+    }
+
+    /// The command for `[[interaction]]`, to run on a terminal
+    pub(crate) fn to_interactive_command(
+        &self,
+        cwd: Option<&std::path::Path>,
+    ) -> Result<std::process::Command, crate::Error> {
+        if !self.pipe.is_empty() {
+            return Err("`pipe` is unsupported with `[[interaction]]`".into());
         }
-        if let Some(stdin) = &self.stdin {
-            cmd = cmd.stdin(stdin);
+        if self.stdin.is_some() || self.stdin_close != StdinClose::default() {
+            return Err("`stdin` is unsupported with `[[interaction]]`".into());
         }
-        if self.stderr_to_stdout {
-            cmd = cmd.stderr_to_stdout();
+        if !self.signals.is_empty() {
+            return Err("`[[signals]]` is unsupported with `[[interaction]]`".into());
+        }
+        if !self.expect_lines.is_empty() {
+            return Err("`[[expect-line]]` is unsupported with `[[interaction]]`".into());
+        }
+        if self.background.is_some() {
+            return Err("`background` is unsupported with `[[interaction]]`".into());
+        }
+        if self.tolerate_interleave {
+            return Err("`tolerate-interleave` is unsupported with `[[interaction]]`".into());
+        }
+        self.stage_std_command(self.bin.as_ref(), &self.args, &Env::default(), cwd)
+    }
+
+    /// The command for `[[expect-line]]` or `background`, to watch as it runs
+    ///
+    /// `stdin` is left for the caller to write.
+    pub(crate) fn to_watched_command(
+        &self,
+        cwd: Option<&std::path::Path>,
+    ) -> Result<std::process::Command, crate::Error> {
+        let watched = if self.background.is_some() {
+            "`background`"
+        } else {
+            "`[[expect-line]]`"
+        };
+        if !self.pipe.is_empty() {
+            return Err(format!("`pipe` is unsupported with {watched}").into());
+        }
+        if !self.signals.is_empty() {
+            return Err(format!("`[[signals]]` is unsupported with {watched}").into());
+        }
+        if self.stdin_close != StdinClose::default() {
+            return Err(format!("`stdin.close` is unsupported with {watched}").into());
+        }
+        if self.tolerate_interleave {
+            return Err(format!("`tolerate-interleave` is unsupported with {watched}").into());
+        }
+        self.stage_std_command(self.bin.as_ref(), &self.args, &Env::default(), cwd)
+    }
+
+    /// The commands feeding into [`Step::to_command`], in order
+    ///
+    /// `stdin` and `stdout` are left for the caller to connect.
+    pub(crate) fn to_pipe_commands(
+        &self,
+        cwd: Option<&std::path::Path>,
+    ) -> Result<Vec<std::process::Command>, crate::Error> {
+        let Some((_, upstream)) = self.pipe.split_last() else {
+            return Ok(Vec::new());
+        };
+        let mut cmds =
+            vec![self.stage_std_command(self.bin.as_ref(), &self.args, &Env::default(), cwd)?];
+        for stage in upstream {
+            cmds.push(self.stage_std_command(stage.bin.as_ref(), &stage.args, &stage.env, cwd)?);
         }
+        Ok(cmds)
+    }
+
+    fn stage_command(
+        &self,
+        bin: Option<&Bin>,
+        args: &[String],
+        env: &Env,
+        cwd: Option<&std::path::Path>,
+    ) -> Result<snapbox::cmd::Command, crate::Error> {
+        let cmd = self.stage_std_command(bin, args, env, cwd)?;
+        let mut cmd = snapbox::cmd::Command::from_std(cmd);
         if let Some(timeout) = self.timeout {
             cmd = cmd.timeout(timeout);
         }
-        cmd = self.env.apply(cmd);
+        if let Some(grace) = self.timeout_grace {
+            cmd = cmd.timeout_grace(grace);
+        }
+        if let Some(max_output) = self.max_output {
+            cmd = cmd.max_output(max_output);
+        }
+
+        Ok(cmd)
+    }
+
+    /// A shell command line to run the step by hand, with its resolved bins, `cwd`, changes to
+    /// the environment, and `stdin`
+    pub(crate) fn repro(&self, cwd: Option<&std::path::Path>) -> Result<String, crate::Error> {
+        let mut stages = vec![(
+            self.stage_std_command(self.bin.as_ref(), &self.args, &Env::default(), cwd)?,
+            self.env.inherit(),
+        )];
+        for stage in &self.pipe {
+            let mut env = self.env.clone();
+            env.update(&stage.env);
+            stages.push((
+                self.stage_std_command(stage.bin.as_ref(), &stage.args, &stage.env, cwd)?,
+                env.inherit(),
+            ));
+        }
+        let mut recipe = String::new();
+        if let Some(cwd) = cwd {
+            recipe.push_str(&format!(
+                "cd {} && ",
+                quote_for_shell(&cwd.display().to_string())
+            ));
+        }
+        let (stdin, note) = match &self.stdin {
+            Some(stdin) => match stdin.render() {
+                Some(text) => {
+                    recipe.push_str(&format!("printf '%s' {} | ", quote_for_shell(&text)));
+                    ("", "")
+                }
+                None => ("", "  # with the case's binary `stdin`"),
+            },
+            None if self.interaction.is_empty() => (" < /dev/null", ""),
+            None => ("", ""),
+        };
+        let mut pipeline = stages
+            .iter()
+            .map(|(cmd, inherit)| repro_command(cmd, *inherit))
+            .collect::<Vec<_>>();
+        pipeline[0].push_str(stdin);
+        recipe.push_str(&pipeline.join(" | "));
+        recipe.push_str(note);
+        Ok(recipe)
+    }
+
+    /// The step's program and arguments, and its changes to the environment, with `None` for
+    /// variables it removes
+    pub(crate) fn invocation(
+        &self,
+        cwd: Option<&std::path::Path>,
+    ) -> Result<Invocation, crate::Error> {
+        let cmd = self.stage_std_command(self.bin.as_ref(), &self.args, &Env::default(), cwd)?;
+        let mut argv = vec![cmd.get_program().to_string_lossy().into_owned()];
+        argv.extend(cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+        let env = cmd
+            .get_envs()
+            .map(|(name, value)| {
+                (
+                    name.to_string_lossy().into_owned(),
+                    value.map(|value| value.to_string_lossy().into_owned()),
+                )
+            })
+            .collect();
+        Ok((argv, env))
+    }
+
+    /// `wrapper` as the start of a shell command line
+    fn shell_wrapper(&self) -> String {
+        self.wrapper
+            .iter()
+            .map(|word| format!("{} ", quote_for_shell(word)))
+            .collect()
+    }
+
+    /// The program, `args`, `cwd`, `env`, `limits`, and scheduling for a command, leaving how it is run to
+    /// the caller
+    fn stage_std_command(
+        &self,
+        bin: Option<&Bin>,
+        args: &[String],
+        env: &Env,
+        cwd: Option<&std::path::Path>,
+    ) -> Result<std::process::Command, crate::Error> {
+        let mut cmd = match bin {
+            Some(Bin::Path(path)) if self.shell => {
+                if !path.exists() {
+                    return Err(crate::Error::UnknownBin(format!(
+                        "Bin doesn't exist: {}",
+                        path.display()
+                    )));
+                }
+                let program = quote_for_shell(&path.display().to_string());
+                Ok(shell_command(format!(
+                    "{}{} {}",
+                    self.shell_wrapper(),
+                    program,
+                    args.join(" ")
+                )))
+            }
+            Some(Bin::Script(script)) if self.shell => Ok(shell_command(format!(
+                "{}{} {}",
+                self.shell_wrapper(),
+                script,
+                args.join(" ")
+            ))),
+            Some(Bin::Path(path)) => {
+                if !path.exists() {
+                    return Err(crate::Error::UnknownBin(format!(
+                        "Bin doesn't exist: {}",
+                        path.display()
+                    )));
+                }
+                let mut cmd = std::process::Command::new(path);
+                cmd.args(args);
+                Ok(cmd)
+            }
+            Some(Bin::Script(script)) => {
+                let mut words = shlex::split(script)
+                    .ok_or_else(|| format!("Invalid bin.script = {}", script))?
+                    .into_iter();
+                let program = words
+                    .next()
+                    .ok_or_else(|| "bin.script is empty".to_owned())?;
+                let mut cmd = std::process::Command::new(program);
+                cmd.args(words).args(args);
+                Ok(cmd)
+            }
+            Some(Bin::Name(name)) => Err(crate::Error::UnknownBin(format!(
+                "Unknown bin.name = {}",
+                name
+            ))),
+            Some(Bin::Ignore) => Err("Internal error: tried to run an ignored bin".into()),
+            Some(Bin::Error(err)) => Err(err.clone()),
+            None => Err(crate::Error::UnknownBin("No bin specified".into())),
+        }?;
+        if let (false, Some((wrapper, wrapper_args))) = (self.shell, self.wrapper.split_first()) {
+            let mut wrapped = std::process::Command::new(wrapper);
+            wrapped
+                .args(wrapper_args)
+                .arg(cmd.get_program())
+                .args(cmd.get_args());
+            cmd = wrapped;
+        }
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+        let mut stage_env = self.env.clone();
+        stage_env.update(env);
+        stage_env.apply(&mut cmd);
+        self.term.apply(&mut cmd);
+        self.limits.apply(&mut cmd);
+        self.scheduling.apply(&mut cmd);
+        if self.net != Net::Host {
+            crate::net::isolate(&mut cmd);
+        }
 
         Ok(cmd)
     }
 
+    pub(crate) fn normalize_line_endings(&self) -> bool {
+        self.normalize_line_endings.unwrap_or(!self.binary)
+    }
+
+    pub(crate) fn require_final_newline(&self) -> bool {
+        self.require_final_newline.unwrap_or(true)
+    }
+
     pub(crate) fn expected_status(&self) -> CommandStatus {
         self.expected_status.unwrap_or_default()
     }
@@ -628,34 +1614,360 @@ impl Step {
 /// Top-level data in `cmd.toml` files
 #[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct OneShot {
+    /// The layout this case is written for, `1` when unset
+    ///
+    /// Cases for older versions are still read, with a warning for each key renamed since.
+    pub(crate) schema_version: Option<u32>,
+    /// From reading a case written for an older `schema-version`
+    #[serde(skip)]
+    pub(crate) warnings: Vec<String>,
     pub(crate) bin: Option<Bin>,
     #[serde(default)]
     pub(crate) args: Args,
     #[serde(default)]
     pub(crate) env: Env,
+    /// Commands to feed `stdout` into, in order, with `stdout`, `stderr`, and `status` coming
+    /// from the last one
+    #[serde(default)]
+    pub(crate) pipe: Vec<PipeStage>,
+    /// Fail when a command before the last one in `pipe` exits unsuccessfully
+    ///
+    /// One killed by `SIGPIPE`, for writing after the command it feeds stopped reading, like `yes`
+    /// in `yes | head -1`, still passes.
     #[serde(default)]
-    pub(crate) stdin: Option<String>,
+    pub(crate) pipefail: bool,
+    /// Run `bin` through the platform shell (`sh -c` or `cmd /C`) with `args` as written, for
+    /// redirection, globs, and `&&` chains
+    ///
+    /// This is not portable across platforms.
+    #[serde(default)]
+    pub(crate) shell: bool,
+    /// Content, or `{ generate = { ... } }` for large synthetic input, and `{ close = "never" }`
+    /// for when to close it
+    #[serde(default)]
+    pub(crate) stdin: Option<Stdin>,
+    /// Expected content, or one of `"empty"`, `"non-empty"`, or `"any"`
     #[serde(default)]
     pub(crate) stdout: Option<String>,
+    /// Expected content, or one of `"empty"`, `"non-empty"`, or `"any"`
     #[serde(default)]
     pub(crate) stderr: Option<String>,
+    /// Fail if `stdout` contains any of these, like `"panicked at"`, whatever else is expected
+    ///
+    /// Each is a literal substring, without wildcards or regular expressions.
+    #[serde(default)]
+    pub(crate) stdout_must_not_contain: Vec<String>,
+    /// Fail if `stderr` contains any of these, like `"panicked at"`, whatever else is expected
+    ///
+    /// Each is a literal substring, without wildcards or regular expressions.
+    #[serde(default)]
+    pub(crate) stderr_must_not_contain: Vec<String>,
+    /// Whether the order of `stdout` lines matters
+    #[serde(default)]
+    pub(crate) stdout_compare: CompareMode,
+    /// Whether the order of `stderr` lines matters
+    #[serde(default)]
+    pub(crate) stderr_compare: CompareMode,
+    /// Expected `stdout` and `stderr` together, in the order they were read
+    ///
+    /// Unlike `stderr-to-stdout`, each stream is still captured on its own for `stdout` and
+    /// `stderr`.  As the streams are read separately, the order is best-effort.
+    pub(crate) expected_merged: Option<String>,
     #[serde(default)]
     pub(crate) stderr_to_stdout: bool,
+    /// With `stderr-to-stdout`, accept `stdout` with the lines of each stream in order but
+    /// interleaved in any way, as the order the OS delivers them in can vary
+    #[serde(default)]
+    pub(crate) tolerate_interleave: bool,
     pub(crate) status: Option<CommandStatus>,
     #[serde(default)]
     pub(crate) binary: bool,
+    /// Convert CRLF to LF in output and snapshots before comparing (default: `true` unless
+    /// `binary`)
+    pub(crate) normalize_line_endings: Option<bool>,
+    /// Remove ANSI escape sequences, like colors, from output before comparing
     #[serde(default)]
-    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    pub(crate) strip_ansi: bool,
+    /// Convert `\` path separators to `/` and drop drive letters, like `C:`, from output before
+    /// comparing
+    #[serde(default)]
+    pub(crate) normalize_paths: bool,
+    /// Ignore spaces and tabs at the end of lines when comparing
+    #[serde(default)]
+    pub(crate) trim_trailing_whitespace: bool,
+    /// Whether a missing newline at the end of output is a difference (default: `true`)
+    pub(crate) require_final_newline: Option<bool>,
+    /// How `stdout` is compared against what is expected
+    #[serde(default)]
+    pub(crate) expected_output_format: OutputFormat,
+    #[serde(default)]
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
     pub(crate) timeout: Option<std::time::Duration>,
+    /// On `timeout`, send `SIGTERM` (`CTRL_BREAK` on Windows) and wait this long, like `"2s"`,
+    /// for the command to shut down before killing it
+    ///
+    /// What it writes while shutting down is compared like any other output.
+    #[serde(default)]
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    pub(crate) timeout_grace: Option<std::time::Duration>,
+    /// Keep the command running while the case's later steps run, like a server for them to
+    /// talk to, and stop it like on `timeout` once they are done
+    ///
+    /// Any `[[expect-line]]` are waited for before moving on.  In `.trycmd` files, start the
+    /// command with `bg:` instead.
+    #[serde(default)]
+    pub(crate) background: bool,
+    /// Run the command again when it fails, like `{ count = 3, backoff = "100ms" }`
+    pub(crate) retries: Option<Retries>,
+    /// Fail once `stdout` or `stderr` grows past this, like `"10MB"`, cutting it short
+    pub(crate) max_output: Option<ByteSize>,
+    /// Fail if the command takes longer than this, like `"2s"`, even though it completed
+    #[serde(default)]
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    pub(crate) max_duration: Option<std::time::Duration>,
+    /// Resource limits for the command, on Unix
+    #[serde(default)]
+    pub(crate) limits: Limits,
+    /// How much CPU time the command gets when competing with other processes (Unix only)
+    pub(crate) priority: Option<Priority>,
+    /// Only run the command on these CPUs, numbered from 0 (Linux only)
+    #[serde(default)]
+    pub(crate) cpus: Vec<usize>,
+    /// Run each program under this command, like `["valgrind", "--error-exitcode=99"]`
+    #[serde(default)]
+    pub(crate) wrapper: Vec<String>,
+    /// The terminal size the command sees, like `{ width = 100, height = 40 }`
+    #[serde(default)]
+    pub(crate) term: Term,
+    /// Whether the command prints colors, like `"never"`, set through `NO_COLOR`,
+    /// `CLICOLOR_FORCE`, and `TERM`
+    pub(crate) color: Option<Color>,
+    /// What network the command can reach, like `"none"` to check that it works offline (Linux
+    /// only)
+    #[serde(default)]
+    pub(crate) net: Net,
+    /// What the command's clock reads, like `"2021-01-01T00:00:00Z"`, for stable dates in output
+    ///
+    /// This sets `SOURCE_DATE_EPOCH` and `TZ=UTC`, and preloads libfaketime when it is installed
+    /// (Linux and macOS).
+    pub(crate) fake_time: Option<String>,
+    /// Prompts to answer, in order, with the command running on a terminal (Unix only)
+    #[serde(default)]
+    pub(crate) interaction: Vec<Interaction>,
+    /// Signals to send the command while it runs, like to check its Ctrl-C handling (Unix only)
+    #[serde(default)]
+    pub(crate) signals: Vec<ScheduledSignal>,
+    /// Lines to wait for, in order, while the command runs, after which it is stopped, like for a
+    /// server that doesn't exit on its own
+    #[serde(default)]
+    pub(crate) expect_line: Vec<ExpectLine>,
     #[serde(default)]
     pub(crate) fs: Filesystem,
+    /// Run the command once per entry, each as its own case
+    #[serde(default)]
+    pub(crate) matrix: Vec<MatrixEntry>,
+    /// Run the command once per locale, like `["C", "de_DE.UTF-8"]`, each as its own case
+    ///
+    /// Each sets `LC_ALL` and `LANG`, with snapshots in `<case>-<locale>.stdout` and
+    /// `<case>-<locale>.stderr`, like for `[[matrix]]`.
+    #[serde(default)]
+    pub(crate) locales: Vec<String>,
+    /// Skip the case on these operating systems (like `macos`) or families (like `windows`)
+    #[serde(default)]
+    pub(crate) skip_on: Vec<String>,
+    /// Expectations for platforms matching a `cfg(...)` expression, like `cfg(windows)`
+    #[serde(default)]
+    pub(crate) target: BTreeMap<String, PlatformOverride>,
+    /// Skip the case, either `true` or why it is skipped
+    pub(crate) skip: Option<Skip>,
+    /// Run only the cases with `only = true`, when any have it
+    #[serde(default)]
+    pub(crate) only: bool,
+    /// The case is known to fail, for this reason, and passing is an error
+    pub(crate) expected_failure: Option<String>,
+    /// Cases to run first, like `["setup-db"]` for `setup-db.toml` in the same directory
+    ///
+    /// The case starts from the sandbox the last of them left behind and is skipped if any of
+    /// them fail.
+    #[serde(default)]
+    pub(crate) depends_on: Vec<String>,
+    /// When to run the case, like `order = -1` for quick smoke tests
+    ///
+    /// Cases with a lower `order` run, and are reported, before cases with a higher one.  The
+    /// default is `0`.
+    #[serde(default)]
+    pub(crate) order: i32,
+    /// Labels to select cases by, like `["slow", "network"]`
+    ///
+    /// See [`TestCases::tags`][crate::TestCases::tags] and `TRYCMD_TAGS`.
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    /// What behavior the case guards, shown when it fails
+    pub(crate) description: Option<String>,
+    /// Where the case's behavior was discussed, like
+    /// `["https://github.com/org/repo/issues/42"]`, shown when it fails
+    #[serde(default)]
+    pub(crate) links: Vec<String>,
+    /// A file of shared settings to build this case on, relative to this one
+    ///
+    /// Tables are merged, with this case's settings taking precedence.
+    pub(crate) include: Option<String>,
+    /// Extra placeholders for this case, like `"[HOST]" = "example.com"`
+    #[serde(default)]
+    pub(crate) substitutions: BTreeMap<String, String>,
+    /// Responses for a local HTTP server that runs while the case does, at `[HTTP_STUB]`
+    #[serde(default)]
+    pub(crate) http_stub: Vec<HttpStub>,
 }
 
 impl OneShot {
+    /// The [JSON Schema](https://json-schema.org/) for `*.toml` cases
+    ///
+    /// Point an editor at this for completion and validation while writing cases.  The
+    /// `trycmd-schema` binary writes it out.
+    #[cfg(feature = "schema")]
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(OneShot);
+        serde_json::to_string_pretty(&schema).expect("schema is always valid JSON")
+    }
+
+    /// Load a case, layered over `_default.toml` in its directory and any `include`
+    fn load(path: &std::path::Path) -> Result<Self, crate::Error> {
+        let mut doc = toml_edit::DocumentMut::new();
+        let default_path = path
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join(DEFAULT_TOML);
+        if default_path.exists() && path.file_name() != Some(std::ffi::OsStr::new(DEFAULT_TOML)) {
+            merge_toml(&mut doc, &load_toml(&default_path, 0)?);
+        }
+        merge_toml(&mut doc, &load_toml(path, 0)?);
+        Self::parse_toml(&doc.to_string())
+            .map_err(|e| e.context(format!("Failed to parse {}", path.display())))
+    }
+
     fn parse_toml(s: &str) -> Result<Self, crate::Error> {
-        toml_edit::de::from_str(s).map_err(|e| e.to_string().into())
+        let (s, warnings) = crate::migrate::migrate(s, crate::migrate::RENAMES);
+        let mut one_shot: Self = toml_edit::de::from_str(&s).map_err(|e| {
+            let mut message = e.to_string();
+            if let Some(field) = suggest_field(e.message()) {
+                if !message.ends_with('\n') {
+                    message.push('\n');
+                }
+                message.push_str(&format!("help: did you mean `{field}`?"));
+            }
+            crate::Error::ParseToml(message)
+        })?;
+        one_shot.warnings = warnings;
+        Ok(one_shot)
+    }
+}
+
+/// Suggest a valid field for serde's "unknown field `x`, expected one of `y`, `z`"
+fn suggest_field(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("unknown field `")?;
+    let (unknown, expected) = rest.split_once('`')?;
+    let candidates = expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .filter(|candidate| !candidate.is_empty());
+    crate::suggest::closest(unknown, candidates)
+}
+
+/// Settings shared by every `.toml` case in the same directory
+pub(crate) const DEFAULT_TOML: &str = "_default.toml";
+
+/// Guards against `include` cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Read a TOML document with its `include` chain resolved
+fn load_toml(path: &std::path::Path, depth: usize) -> Result<toml_edit::DocumentMut, crate::Error> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| crate::Error::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+    // Check each file on its own, so errors point into it, rather than the merged settings
+    OneShot::parse_toml(&raw)
+        .map_err(|e| e.context(format!("Failed to parse {}", path.display())))?;
+    let mut doc = raw.parse::<toml_edit::DocumentMut>().map_err(|e| {
+        crate::Error::ParseToml(format!("Failed to parse {}: {}", path.display(), e))
+    })?;
+    let Some(include) = doc.remove("include") else {
+        return Ok(doc);
+    };
+    let include = include.as_str().ok_or_else(|| {
+        crate::Error::ParseToml(format!("`include` must be a path, in {}", path.display()))
+    })?;
+    if MAX_INCLUDE_DEPTH <= depth {
+        return Err(crate::Error::ParseToml(format!(
+            "Too many nested `include`s, at {}",
+            path.display()
+        )));
+    }
+    let include_path = path
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new("."))
+        .join(include);
+    let mut base = load_toml(&include_path, depth + 1)?;
+    merge_toml(&mut base, &doc);
+    Ok(base)
+}
+
+/// The files a case's settings are layered over, `_default.toml` and any `include`s, in no
+/// particular order
+///
+/// Files that can't be read or parsed end the chain, as loading the case reports them.
+pub(crate) fn includes(path: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut includes = Vec::new();
+    let default_path = path
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new("."))
+        .join(DEFAULT_TOML);
+    let mut pending = vec![path.to_owned()];
+    if default_path.exists() {
+        includes.push(default_path.clone());
+        pending.push(default_path);
+    }
+    while let Some(current) = pending.pop() {
+        if MAX_INCLUDE_DEPTH <= includes.len() {
+            break;
+        }
+        let Ok(raw) = std::fs::read_to_string(&current) else {
+            continue;
+        };
+        let Ok(doc) = raw.parse::<toml_edit::DocumentMut>() else {
+            continue;
+        };
+        let Some(include) = doc.get("include").and_then(|i| i.as_str()) else {
+            continue;
+        };
+        let include_path = current
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join(include);
+        includes.push(include_path.clone());
+        pending.push(include_path);
+    }
+    includes
+}
+
+/// Overlay `other` onto `base`, merging tables and replacing everything else, including arrays
+fn merge_toml(base: &mut toml_edit::DocumentMut, other: &toml_edit::DocumentMut) {
+    merge_table_like(base.as_table_mut(), other.as_table());
+}
+
+fn merge_table_like(base: &mut dyn toml_edit::TableLike, other: &dyn toml_edit::TableLike) {
+    for (key, item) in other.iter() {
+        if let (Some(base_table), Some(table)) = (
+            base.get_mut(key).and_then(|b| b.as_table_like_mut()),
+            item.as_table_like(),
+        ) {
+            merge_table_like(base_table, table);
+        } else {
+            base.insert(key, item.clone());
+        }
     }
 }
 
@@ -685,6 +1997,30 @@ impl Args {
             Self::Split(v) => v,
         }
     }
+
+    /// Rewrite each argument, keeping any joined form in step
+    pub(crate) fn for_each_mut(&mut self, f: impl Fn(&mut String)) {
+        match self {
+            Self::Joined(j) => {
+                for arg in j.inner.iter_mut().chain(std::iter::once(&mut j.raw)) {
+                    f(arg);
+                }
+            }
+            Self::Split(v) => {
+                for arg in v {
+                    f(arg);
+                }
+            }
+        }
+    }
+
+    /// The arguments as written, for a shell to interpret
+    fn into_shell_line(self) -> String {
+        match self {
+            Self::Joined(j) => j.raw,
+            Self::Split(v) => v.join(" "),
+        }
+    }
 }
 
 impl Default for Args {
@@ -701,16 +2037,23 @@ impl std::ops::Deref for Args {
     }
 }
 
-#[derive(Clone, Default, Debug, PartialEq, Eq)]
+#[derive(Clone, Default, Debug)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub(crate) struct JoinedArgs {
     inner: Vec<String>,
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    raw: String,
 }
 
 impl JoinedArgs {
     #[cfg(test)]
     pub(crate) fn from_vec(inner: Vec<String>) -> Self {
-        JoinedArgs { inner }
+        let mut args = JoinedArgs {
+            inner,
+            raw: String::new(),
+        };
+        args.raw = args.to_string();
+        args
     }
 
     #[allow(clippy::inherent_to_string_shadow_display)]
@@ -724,10 +2067,21 @@ impl std::str::FromStr for JoinedArgs {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let inner = shlex::Shlex::new(s).collect();
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            raw: s.to_owned(),
+        })
     }
 }
 
+impl PartialEq for JoinedArgs {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for JoinedArgs {}
+
 impl std::fmt::Display for JoinedArgs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.to_string().fmt(f)
@@ -753,15 +2107,580 @@ impl serde::ser::Serialize for JoinedArgs {
     }
 }
 
+/// A command in a `pipe`, reading the `stdout` of the command before it
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PipeStage {
+    /// Defaults to the top-level `bin`
+    pub(crate) bin: Option<Bin>,
+    #[serde(default)]
+    pub(crate) args: Args,
+    /// Layered on top of the top-level `env`
+    #[serde(default)]
+    pub(crate) env: Env,
+}
+
+/// How often to run a failing command again
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Retries {
+    /// Attempts after the first one
+    #[serde(default)]
+    pub(crate) count: usize,
+    /// Delay before the first retry, doubling with each one after
+    #[serde(default)]
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    pub(crate) backoff: Option<std::time::Duration>,
+}
+
+impl Retries {
+    /// Delay before retry `attempt`, counting from 0
+    pub(crate) fn delay(&self, attempt: usize) -> std::time::Duration {
+        let backoff = self.backoff.unwrap_or_default();
+        let factor = 1u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        backoff.saturating_mul(factor)
+    }
+}
+
+/// What to write to the command's `stdin`
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum Stdin {
+    Text(String),
+    Config(StdinConfig),
+}
+
+impl Stdin {
+    fn into_data(self) -> Result<Option<crate::Data>, crate::Error> {
+        match self {
+            Self::Text(text) => Ok(Some(crate::Data::text(text))),
+            Self::Config(StdinConfig { generate, .. }) => generate
+                .map(|generate| generate.generate().map(crate::Data::binary))
+                .transpose(),
+        }
+    }
+
+    fn close(&self) -> StdinClose {
+        match self {
+            Self::Text(_) => StdinClose::default(),
+            Self::Config(config) => config.close,
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StdinConfig {
+    /// Synthesize the content, rather than committing large fixtures
+    pub(crate) generate: Option<Generate>,
+    /// When to close `stdin`, which the command reads as the end of its input
+    #[serde(default)]
+    pub(crate) close: StdinClose,
+}
+
+/// When to close the command's `stdin`, like for a CLI that acts differently when it is an open
+/// but empty pipe
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum StdinClose {
+    /// Before writing anything, so the command reads no input
+    Immediately,
+    /// Once the content is written
+    #[default]
+    AfterWrite,
+    /// Once the command exits, so it waits on more input until then, like until `timeout`
+    Never,
+}
+
+impl StdinClose {
+    fn to_snapbox(self) -> snapbox::cmd::StdinClose {
+        match self {
+            Self::Immediately => snapbox::cmd::StdinClose::Immediately,
+            Self::AfterWrite => snapbox::cmd::StdinClose::AfterWrite,
+            Self::Never => snapbox::cmd::StdinClose::Never,
+        }
+    }
+}
+
+/// Deterministic content, like `{ bytes = "1MiB", pattern = "random", seed = 42 }`
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Generate {
+    /// Size of the content
+    pub(crate) bytes: ByteSize,
+    #[serde(default)]
+    pub(crate) pattern: Pattern,
+    /// Seed for `random`, to vary the content while keeping it reproducible
+    #[serde(default)]
+    pub(crate) seed: u64,
+}
+
+impl Generate {
+    pub(crate) fn generate(&self) -> Result<Vec<u8>, crate::Error> {
+        let len = self.bytes.to_bytes()?;
+        let mut content = Vec::with_capacity(len);
+        match self.pattern {
+            Pattern::Random => {
+                // splitmix64, for the same content on every platform and release
+                let mut state = self.seed;
+                while content.len() < len {
+                    state = state.wrapping_add(0x9e3779b97f4a7c15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                    z ^= z >> 31;
+                    content.extend_from_slice(&z.to_le_bytes());
+                }
+            }
+            Pattern::Zeros => content.resize(len, 0),
+            Pattern::Lines => {
+                let mut line = 1usize;
+                while content.len() < len {
+                    content.extend_from_slice(format!("{line}\n").as_bytes());
+                    line += 1;
+                }
+            }
+        }
+        content.truncate(len);
+        Ok(content)
+    }
+}
+
+/// The content [`Generate`] fills `stdin` with
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Pattern {
+    /// Pseudo-random bytes from `seed`
+    #[default]
+    Random,
+    /// All `0` bytes
+    Zeros,
+    /// Numbered lines, `1\n2\n3\n...`
+    Lines,
+}
+
+/// Answer a prompt from a command running on a terminal
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Interaction {
+    /// Wait for the command to print this, like `"Password:"`
+    pub(crate) expect: String,
+    /// Then type this, like `"hunter2\n"`
+    #[serde(default)]
+    pub(crate) send: String,
+}
+
+/// Wait for the command to print a line while it runs, from `[[expect-line]]`
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExpectLine {
+    /// Text the line contains, like `"Listening on"`, from `stdout` or `stderr`
+    pub(crate) pattern: String,
+    /// How long after the command starts, like `"2s"`, defaulting to `timeout` or 10s
+    #[serde(default)]
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    pub(crate) within: Option<std::time::Duration>,
+}
+
+/// A canned response from the case's local HTTP server, from `[[http-stub]]`
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HttpStub {
+    /// The path to answer, like `"/v1/items"`, which must also match any query string it has
+    pub(crate) path: String,
+    /// Only answer requests with this method, like `"POST"`, instead of any
+    pub(crate) method: Option<String>,
+    /// The response status, defaulting to 200
+    #[serde(default = "HttpStub::default_status")]
+    pub(crate) status: u16,
+    /// The response body
+    pub(crate) body: Option<String>,
+    /// A file with the response body, relative to the case
+    pub(crate) body_file: Option<std::path::PathBuf>,
+    /// Defaults to `application/json` for a `.json` `body-file`, `text/plain` otherwise
+    pub(crate) content_type: Option<String>,
+}
+
+impl HttpStub {
+    fn default_status() -> u16 {
+        200
+    }
+}
+
+/// Send a signal to the command partway through its run
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ScheduledSignal {
+    /// How long after the command starts, like `"500ms"`
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    pub(crate) after: std::time::Duration,
+    /// Which signal, like `"INT"` for Ctrl-C
+    pub(crate) signal: Signal,
+}
+
+/// A signal to send with `[[signals]]`, by name, like `"INT"` or `"SIGINT"`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Signal {
+    #[serde(alias = "SIGHUP")]
+    Hup,
+    #[serde(alias = "SIGINT")]
+    Int,
+    #[serde(alias = "SIGQUIT")]
+    Quit,
+    #[serde(alias = "SIGKILL")]
+    Kill,
+    #[serde(alias = "SIGUSR1")]
+    Usr1,
+    #[serde(alias = "SIGUSR2")]
+    Usr2,
+    #[serde(alias = "SIGALRM")]
+    Alrm,
+    #[serde(alias = "SIGTERM")]
+    Term,
+}
+
+impl Signal {
+    #[cfg(unix)]
+    pub(crate) fn as_raw(self) -> i32 {
+        match self {
+            Self::Hup => libc::SIGHUP,
+            Self::Int => libc::SIGINT,
+            Self::Quit => libc::SIGQUIT,
+            Self::Kill => libc::SIGKILL,
+            Self::Usr1 => libc::SIGUSR1,
+            Self::Usr2 => libc::SIGUSR2,
+            Self::Alrm => libc::SIGALRM,
+            Self::Term => libc::SIGTERM,
+        }
+    }
+}
+
+/// Terminal size for the command, as `COLUMNS` and `LINES` and, for `[[interaction]]`, the
+/// terminal's window size
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Term {
+    /// Number of columns, like `100`
+    pub(crate) width: Option<u16>,
+    /// Number of lines, like `40`
+    pub(crate) height: Option<u16>,
+}
+
+impl Term {
+    fn apply(&self, command: &mut std::process::Command) {
+        if let Some(width) = self.width {
+            command.env("COLUMNS", width.to_string());
+        }
+        if let Some(height) = self.height {
+            command.env("LINES", height.to_string());
+        }
+    }
+}
+
+/// Resource limits for the command, set with `setrlimit` before it runs (Unix only)
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Limits {
+    /// Size of the address space, like `"256MB"` (`RLIMIT_AS`)
+    pub(crate) memory: Option<ByteSize>,
+    /// Number of open file descriptors (`RLIMIT_NOFILE`)
+    pub(crate) nofile: Option<u64>,
+    /// Number of processes for the user (`RLIMIT_NPROC`)
+    pub(crate) nproc: Option<u64>,
+    /// CPU time, like `"10s"` (`RLIMIT_CPU`)
+    #[serde(default)]
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    pub(crate) cpu: Option<std::time::Duration>,
+    /// Size of files written, like `"1MB"` (`RLIMIT_FSIZE`)
+    pub(crate) fsize: Option<ByteSize>,
+    /// Size of core dumps (`RLIMIT_CORE`)
+    pub(crate) core: Option<ByteSize>,
+}
+
+/// Scheduling priority for the command, set with `setpriority` before it runs
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Priority {
+    /// Yield to other processes (nice 10)
+    Low,
+    /// The default (nice 0), undoing any inherited from the test
+    Normal,
+    /// Ahead of other processes (nice -10), which usually requires privileges
+    High,
+}
+
+impl Priority {
+    pub(crate) fn nice(self) -> i32 {
+        match self {
+            Self::Low => 10,
+            Self::Normal => 0,
+            Self::High => -10,
+        }
+    }
+}
+
+/// What network the command can reach
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Net {
+    /// The host's network, as usual
+    #[default]
+    Host,
+    /// Only its own loopback interface, failing where that isn't permitted
+    None,
+    /// Like `none`, but skipping the command where that isn't permitted
+    NoneOrSkip,
+}
+
+/// Built-in environment scrubbing, for `env.preset`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum EnvPreset {
+    /// Remove what varies between machines, like `TERM`, `COLUMNS`, locale variables, and most
+    /// `CARGO_*` variables, and set `TZ=UTC` and `LC_ALL=C`
+    Hermetic,
+}
+
+impl EnvPreset {
+    /// Variables removed by `hermetic`, besides the `LC_*` and `CARGO_*` ones
+    const HERMETIC_REMOVE: &'static [&'static str] = &[
+        "TERM",
+        "COLORTERM",
+        "COLUMNS",
+        "LINES",
+        "LS_COLORS",
+        "LANG",
+        "LANGUAGE",
+    ];
+
+    /// `CARGO_*` variables kept by `hermetic`, since they locate the build rather than change
+    /// the output
+    const HERMETIC_CARGO_ALLOW: &'static [&'static str] =
+        &["CARGO_HOME", "CARGO_MANIFEST_DIR", "CARGO_TARGET_DIR"];
+
+    fn apply(self, command: &mut std::process::Command) {
+        match self {
+            Self::Hermetic => {
+                for name in Self::HERMETIC_REMOVE {
+                    command.env_remove(name);
+                }
+                for (name, _) in std::env::vars_os() {
+                    let Some(name) = name.to_str() else {
+                        continue;
+                    };
+                    let is_cargo =
+                        name.starts_with("CARGO_") && !Self::HERMETIC_CARGO_ALLOW.contains(&name);
+                    if name.starts_with("LC_") || is_cargo {
+                        command.env_remove(name);
+                    }
+                }
+                command.env("TZ", "UTC");
+                command.env("LC_ALL", "C");
+            }
+        }
+    }
+}
+
+/// Whether the command should print colors, set through the conventional environment variables
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Color {
+    /// Never print colors: `NO_COLOR=1`, `CLICOLOR=0`, and `TERM=dumb`
+    Never,
+    /// Always print colors, even to a pipe: `CLICOLOR_FORCE=1` and `TERM=xterm-256color`
+    Always,
+    /// Print colors only to a terminal, like with `[[interaction]]`: `CLICOLOR=1` and
+    /// `TERM=xterm-256color`, without `NO_COLOR` or `CLICOLOR_FORCE`
+    Auto,
+}
+
+impl Color {
+    fn apply(self, command: &mut std::process::Command) {
+        match self {
+            Self::Never => {
+                command.env_remove("CLICOLOR_FORCE");
+                command.env("NO_COLOR", "1");
+                command.env("CLICOLOR", "0");
+                command.env("TERM", "dumb");
+            }
+            Self::Always => {
+                command.env_remove("NO_COLOR");
+                command.env("CLICOLOR_FORCE", "1");
+                command.env("CLICOLOR", "1");
+                command.env("TERM", "xterm-256color");
+            }
+            Self::Auto => {
+                command.env_remove("NO_COLOR");
+                command.env_remove("CLICOLOR_FORCE");
+                command.env("CLICOLOR", "1");
+                command.env("TERM", "xterm-256color");
+            }
+        }
+    }
+}
+
+/// A number of bytes, either as an integer or with a unit like `"512KiB"` or `"10MB"`
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum ByteSize {
+    Bytes(usize),
+    Text(String),
+}
+
+impl ByteSize {
+    pub(crate) fn to_bytes(&self) -> Result<usize, crate::Error> {
+        let text = match self {
+            Self::Bytes(bytes) => return Ok(*bytes),
+            Self::Text(text) => text.trim(),
+        };
+        let split = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "kb" => 1_000,
+            "mb" => 1_000_000,
+            "gb" => 1_000_000_000,
+            "kib" => 1 << 10,
+            "mib" => 1 << 20,
+            "gib" => 1 << 30,
+            _ => return Err(format!("Unknown unit in size `{text}`").into()),
+        };
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_mul(multiplier))
+            .ok_or_else(|| format!("Invalid size `{text}`").into())
+    }
+}
+
+/// Whether to skip a case
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum Skip {
+    Flag(bool),
+    /// Why the case is skipped, for reporting
+    Reason(String),
+}
+
+/// Expectations that differ on some platforms
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PlatformOverride {
+    pub(crate) status: Option<CommandStatus>,
+    pub(crate) stdout: Option<String>,
+    pub(crate) stderr: Option<String>,
+}
+
+/// One variation of a `cmd.toml` case
+///
+/// Snapshots are read from `<case>-<name>.stdin`, `<case>-<name>.stdout`, and
+/// `<case>-<name>.stderr`.
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MatrixEntry {
+    /// Suffix for the case name and snapshot files
+    pub(crate) name: String,
+    /// Appended to the top-level `args`
+    #[serde(default)]
+    pub(crate) args: Args,
+    /// Layered on top of the top-level `env`
+    #[serde(default)]
+    pub(crate) env: Env,
+    pub(crate) status: Option<CommandStatus>,
+}
+
+impl MatrixEntry {
+    /// An entry for `locales`, named after the locale
+    fn locale(locale: String) -> Result<Self, crate::Error> {
+        if locale.is_empty() || locale.contains(['/', '\\']) {
+            return Err(format!("Invalid locale in `locales`: `{locale}`").into());
+        }
+        let mut env = Env::default();
+        env.add.insert("LC_ALL".to_owned(), locale.clone());
+        env.add.insert("LANG".to_owned(), locale.clone());
+        // It takes precedence over `LC_ALL` for messages
+        env.remove.push("LANGUAGE".to_owned());
+        Ok(Self {
+            name: locale,
+            args: Args::new(),
+            env,
+            status: None,
+        })
+    }
+}
+
 /// Describe the command's filesystem context
 #[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Filesystem {
     pub(crate) cwd: Option<std::path::PathBuf>,
     /// Sandbox base
     pub(crate) base: Option<std::path::PathBuf>,
     pub(crate) sandbox: Option<bool>,
+    /// Hard-link `base`'s files into the sandbox rather than copying them
+    ///
+    /// This speeds up cases sharing a large fixture.  The commands must not write to files from
+    /// `base` in place, as that would change the fixture, though they may create, rename, and
+    /// remove them.  Writing in place is reported as a failure.
+    #[serde(default)]
+    pub(crate) base_readonly: bool,
+    /// Glob patterns, like `.gitignore`, for paths in `base` to not copy into the sandbox or
+    /// compare against `*.out/`
+    ///
+    /// More patterns are read from a `.trycmdignore` file in `base`.
+    #[serde(default)]
+    pub(crate) ignore: Vec<String>,
+    /// Files to create in the sandbox and how to compare specific files in `*.out/`, keyed by
+    /// their `/`-separated relative path
+    #[serde(default)]
+    pub(crate) files: BTreeMap<String, FileOverride>,
+    /// Check specific files the commands leave in the sandbox, without a whole `*.out/`
+    #[serde(default)]
+    pub(crate) expect: Vec<FileExpect>,
+    /// Fail if the commands leave more than this many new files in the sandbox
+    pub(crate) max_new_files: Option<usize>,
+    /// Fail if the sandbox holds more than this after the commands run, like `"1MB"`
+    pub(crate) max_size: Option<ByteSize>,
 }
 
 impl Filesystem {
@@ -785,9 +2704,54 @@ impl Filesystem {
     }
 }
 
+/// Content for one file in the sandbox or overrides for comparing it in `*.out/`
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileOverride {
+    #[serde(default)]
+    pub(crate) compare: FileCompare,
+    /// Create the file with this content in the sandbox before the command runs
+    pub(crate) content: Option<String>,
+}
+
+/// A file the commands should leave in the sandbox
+#[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileExpect {
+    /// `/`-separated path relative to the sandbox, like `"out/report.txt"`
+    pub(crate) path: String,
+    /// The whole content, with wildcards and redactions like `stdout`
+    pub(crate) content: Option<String>,
+    /// Text the content must include
+    pub(crate) contains: Option<String>,
+    /// A regular expression the whole content must match
+    pub(crate) matches: Option<String>,
+}
+
+/// How a file in `*.out/` is compared against what the command left behind
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum FileCompare {
+    /// Detect whether the file is binary, matching text with wildcards and redactions
+    #[default]
+    Auto,
+    /// Match as text with wildcards and redactions
+    Text,
+    /// Match byte-for-byte
+    Binary,
+    /// Only check that the file exists, for nondeterministic content
+    Exists,
+}
+
 /// Describe command's environment
 #[derive(Clone, Default, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Env {
     #[serde(default)]
@@ -796,6 +2760,15 @@ pub struct Env {
     pub(crate) add: BTreeMap<String, String>,
     #[serde(default)]
     pub(crate) remove: Vec<String>,
+    /// Scrub the inherited environment, before `remove` and `add`
+    #[serde(default)]
+    pub(crate) preset: Option<EnvPreset>,
+    /// From the top-level `color`, applied after `preset`
+    #[serde(skip)]
+    pub(crate) color: Option<Color>,
+    /// Forward coverage variables, from [`TestCases::coverage`][crate::TestCases::coverage]
+    #[serde(skip)]
+    pub(crate) coverage: bool,
 }
 
 impl Env {
@@ -803,24 +2776,81 @@ impl Env {
         if self.inherit.is_none() {
             self.inherit = other.inherit;
         }
+        if self.preset.is_none() {
+            self.preset = other.preset;
+        }
+        if self.color.is_none() {
+            self.color = other.color;
+        }
+        self.coverage |= other.coverage;
+        for remove in &other.remove {
+            self.add.remove(remove);
+        }
         self.add
             .extend(other.add.iter().map(|(k, v)| (k.clone(), v.clone())));
         self.remove.extend(other.remove.iter().cloned());
     }
 
-    pub(crate) fn apply(&self, mut command: snapbox::cmd::Command) -> snapbox::cmd::Command {
+    pub(crate) fn apply(&self, command: &mut std::process::Command) {
         if !self.inherit() {
-            command = command.env_clear();
+            command.env_clear();
+        }
+        if let Some(preset) = self.preset {
+            preset.apply(command);
+        }
+        if let Some(color) = self.color {
+            color.apply(command);
         }
         for remove in &self.remove {
-            command = command.env_remove(remove);
+            command.env_remove(remove);
         }
-        command.envs(&self.add)
+        command.envs(&self.add);
     }
 
     pub(crate) fn inherit(&self) -> bool {
         self.inherit.unwrap_or(true)
     }
+
+    /// Parse `export NAME=value`, `NAME=value`, and `unset NAME` lines, ignoring anything else
+    pub(crate) fn parse_exports(text: &str) -> Self {
+        let mut env = Self::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix("unset ") {
+                let name = name.trim();
+                env.add.remove(name);
+                env.remove.push(name.to_owned());
+                continue;
+            }
+            let assignment = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            let Some((name, value)) = assignment.split_once('=') else {
+                continue;
+            };
+            let is_name = !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_name {
+                continue;
+            }
+            let value = match shlex::split(value).as_deref() {
+                Some([value]) => value.to_owned(),
+                Some([]) => String::new(),
+                _ => value.to_owned(),
+            };
+            env.remove.retain(|n| n != name);
+            env.add.insert(name.to_owned(), value);
+        }
+        env
+    }
+}
+
+/// Where a step reports `env` changes, like `eval $(tool env)` would apply
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum CaptureEnv {
+    /// `export NAME=value` lines in `stdout`
+    Stdout,
+    /// A dotenv file the command writes, relative to `cwd`
+    File(std::path::PathBuf),
 }
 
 /// Target under test
@@ -830,6 +2860,10 @@ impl Env {
 pub enum Bin {
     Path(std::path::PathBuf),
     Name(String),
+    /// Command line for an interpreter, like `python3 tools/server.py`, split like a shell would
+    ///
+    /// The program is looked up in `PATH` and is run from the case's `cwd`.
+    Script(String),
     Ignore,
     #[serde(skip)]
     Error(crate::Error),
@@ -869,6 +2903,60 @@ where
     }
 }
 
+/// Assertion on a stream, instead of its content
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum StreamCheck {
+    /// `"empty"`: nothing may be printed
+    Empty,
+    /// `"non-empty"`: something must be printed
+    NonEmpty,
+    /// `"any"`: the stream is not checked, even if a snapshot file exists
+    Any,
+}
+
+impl StreamCheck {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "empty" => Some(Self::Empty),
+            "non-empty" => Some(Self::NonEmpty),
+            "any" => Some(Self::Any),
+            _ => None,
+        }
+    }
+}
+
+/// How to compare output against what is expected
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum OutputFormat {
+    /// Line-by-line, supporting `[..]`, `...`, and redactions
+    #[default]
+    Text,
+    /// Parse both sides as JSON and compare the values, ignoring key order and whitespace
+    Json,
+    /// Match a regular expression against the whole output, where `^` and `$` also match at
+    /// the start and end of lines
+    ///
+    /// A `.stdout.regex` or `.stderr.regex` file is always compared this way.
+    Regex,
+}
+
+/// Whether the order of output lines matters when comparing
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum CompareMode {
+    /// In order, line by line
+    #[default]
+    Lines,
+    /// As a set of lines in any order, for output from parallel work or hash map iteration
+    ///
+    /// Each expected line may still use `[..]` and redactions, and a `...` line allows extra
+    /// lines.
+    UnorderedLines,
+}
+
 /// Expected status for command
 #[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
@@ -881,6 +2969,10 @@ pub enum CommandStatus {
     Interrupted,
     Skipped,
     Code(i32),
+    /// Killed by this signal number, like `status.signal = 9` for `SIGKILL` (Unix only)
+    Signal(i32),
+    /// Killed by a signal that dumped core (Unix only)
+    CoreDumped,
 }
 
 impl std::str::FromStr for CommandStatus {
@@ -892,6 +2984,7 @@ impl std::str::FromStr for CommandStatus {
             "failed" => Ok(Self::Failed),
             "interrupted" => Ok(Self::Interrupted),
             "skipped" => Ok(Self::Skipped),
+            "core-dumped" => Ok(Self::CoreDumped),
             _ => s
                 .parse::<i32>()
                 .map(Self::Code)
@@ -1136,8 +3229,160 @@ Hello World
                 bin: Some(Bin::Name("cmd".into())),
                 expected_status: Some(CommandStatus::Success),
                 stderr_to_stdout: true,
-                expected_stdout_source: Some(4..7),
-                expected_stdout: Some(crate::Data::text("```\nHello World\n```")),
+                expected_stdout_source: Some(4..7),
+                expected_stdout: Some(crate::Data::text("```\nHello World\n```")),
+                expected_stderr: None,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let actual = TryCmd::parse_trycmd(
+            "
+````
+$ cmd
+```
+Hello World
+```
+````",
+        )
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_trycmd_multi_step() {
+        let expected = TryCmd {
+            steps: vec![
+                Step {
+                    id: Some("3".into()),
+                    bin: Some(Bin::Name("cmd1".into())),
+                    expected_status_source: Some(4),
+                    expected_status: Some(CommandStatus::Code(1)),
+                    stderr_to_stdout: true,
+                    expected_stdout_source: Some(5..5),
+                    expected_stdout: Some(crate::Data::new()),
+                    expected_stderr: None,
+                    ..Default::default()
+                },
+                Step {
+                    id: Some("5".into()),
+                    bin: Some(Bin::Name("cmd2".into())),
+                    expected_status: Some(CommandStatus::Success),
+                    stderr_to_stdout: true,
+                    expected_stdout_source: Some(6..6),
+                    expected_stdout: Some(crate::Data::new()),
+                    expected_stderr: None,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let actual = TryCmd::parse_trycmd(
+            "
+```
+$ cmd1
+? 1
+$ cmd2
+```
+",
+        )
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_trycmd_pipe() {
+        let expected = TryCmd {
+            steps: vec![Step {
+                id: Some("3".into()),
+                bin: Some(Bin::Name("cmd1".into())),
+                args: vec!["a|b".into()],
+                env: Env {
+                    add: IntoIterator::into_iter([("FIRST".into(), "1".into())]).collect(),
+                    ..Default::default()
+                },
+                pipe: vec![PipeStage {
+                    bin: Some(Bin::Name("cmd2".into())),
+                    args: Args::Split(vec!["--flag".into()]),
+                    env: Env {
+                        add: IntoIterator::into_iter([("KEY".into(), "value".into())]).collect(),
+                        ..Default::default()
+                    },
+                }],
+                expected_status: Some(CommandStatus::Success),
+                stderr_to_stdout: true,
+                expected_stdout_source: Some(5..5),
+                expected_stdout: Some(crate::Data::new()),
+                expected_stderr: None,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let actual = TryCmd::parse_trycmd(
+            "
+```
+$ pipe: FIRST=1 cmd1 'a|b' | KEY=value cmd2
+> --flag
+```
+",
+        )
+        .unwrap();
+        assert_eq!(expected, actual);
+
+        // Without `pipe:`, `|` is an argument like any other
+        let actual = TryCmd::parse_trycmd(
+            "
+```
+$ cmd1 | cmd2
+```
+",
+        )
+        .unwrap();
+        assert_eq!(actual.steps[0].args, ["|", "cmd2"]);
+        assert!(actual.steps[0].pipe.is_empty());
+    }
+
+    #[test]
+    fn parse_trycmd_background() {
+        let actual = TryCmd::parse_trycmd(
+            "
+```
+$ bg: server --port 8080
+$ client &
+$ client '&'
+$ bg: sh: build && test
+```
+",
+        )
+        .unwrap();
+        assert!(actual.steps[0].background.is_some());
+        assert_eq!(actual.steps[0].args, ["--port", "8080"]);
+        // Without `bg:`, `&` is an argument like any other
+        assert!(actual.steps[1].background.is_none());
+        assert_eq!(actual.steps[1].args, ["&"]);
+        assert!(actual.steps[2].background.is_none());
+        assert_eq!(actual.steps[2].args, ["&"]);
+        assert!(actual.steps[3].background.is_some());
+        assert!(actual.steps[3].shell);
+        assert_eq!(actual.steps[3].args, ["&& test"]);
+    }
+
+    #[test]
+    fn parse_trycmd_shell() {
+        let expected = TryCmd {
+            steps: vec![Step {
+                id: Some("3".into()),
+                bin: Some(Bin::Name("cmd1".into())),
+                args: vec!["'a b' > out.txt && cat out.txt | sort".into()],
+                env: Env {
+                    add: IntoIterator::into_iter([("KEY".into(), "value".into())]).collect(),
+                    ..Default::default()
+                },
+                shell: true,
+                expected_status: Some(CommandStatus::Success),
+                stderr_to_stdout: true,
+                expected_stdout_source: Some(4..4),
+                expected_stdout: Some(crate::Data::new()),
                 expected_stderr: None,
                 ..Default::default()
             }],
@@ -1145,56 +3390,114 @@ Hello World
         };
         let actual = TryCmd::parse_trycmd(
             "
-````
-$ cmd
 ```
-Hello World
+$ sh: KEY=value cmd1 'a b' > out.txt && cat out.txt | sort
 ```
-````",
+",
         )
         .unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
-    fn parse_trycmd_multi_step() {
-        let expected = TryCmd {
-            steps: vec![
-                Step {
-                    id: Some("3".into()),
-                    bin: Some(Bin::Name("cmd1".into())),
-                    expected_status_source: Some(4),
-                    expected_status: Some(CommandStatus::Code(1)),
-                    stderr_to_stdout: true,
-                    expected_stdout_source: Some(5..5),
-                    expected_stdout: Some(crate::Data::new()),
-                    expected_stderr: None,
-                    ..Default::default()
-                },
-                Step {
-                    id: Some("5".into()),
-                    bin: Some(Bin::Name("cmd2".into())),
-                    expected_status: Some(CommandStatus::Success),
-                    stderr_to_stdout: true,
-                    expected_stdout_source: Some(6..6),
-                    expected_stdout: Some(crate::Data::new()),
-                    expected_stderr: None,
-                    ..Default::default()
-                },
-            ],
-            ..Default::default()
-        };
+    fn parse_trycmd_capture_env() {
         let actual = TryCmd::parse_trycmd(
             "
-```
+```console,capture-env
 $ cmd1
-? 1
+```
+
+```console,capture-env=.env
 $ cmd2
 ```
+
+```console
+$ cmd3
+```
 ",
         )
         .unwrap();
-        assert_eq!(expected, actual);
+        let capture_env = actual
+            .steps
+            .iter()
+            .map(|s| s.capture_env.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            capture_env,
+            [
+                Some(CaptureEnv::Stdout),
+                Some(CaptureEnv::File(".env".into())),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn env_parse_exports() {
+        let actual = Env::parse_exports(
+            "\
+export FOO=bar
+BAZ='a b'
+export QUX=\"quoted value\"
+EMPTY=
+unset OLD
+# comment
+Not an assignment
+1BAD=value
+",
+        );
+        let expected = Env {
+            add: IntoIterator::into_iter([
+                ("FOO".into(), "bar".into()),
+                ("BAZ".into(), "a b".into()),
+                ("QUX".into(), "quoted value".into()),
+                ("EMPTY".into(), "".into()),
+            ])
+            .collect(),
+            remove: vec!["OLD".into()],
+            ..Default::default()
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn split_pipeline_quoting() {
+        assert_eq!(split_pipeline("cmd"), ["cmd"]);
+        assert_eq!(
+            split_pipeline("cmd1 | cmd2|cmd3"),
+            ["cmd1 ", " cmd2", "cmd3"]
+        );
+        assert_eq!(
+            split_pipeline(r#"cmd1 '|' "|" \| "\"|" | cmd2"#),
+            [r#"cmd1 '|' "|" \| "\"|" "#, " cmd2"]
+        );
+        assert_eq!(split_pipeline("cmd1 || cmd2"), ["cmd1 ", "", " cmd2"]);
+    }
+
+    #[test]
+    fn parse_trycmd_indented_fence() {
+        let actual = TryCmd::parse_trycmd(
+            "
+1. Item
+
+   ~~~console
+   $ cmd
+     indented
+   ~~~
+",
+        )
+        .unwrap();
+        assert_eq!(actual.steps.len(), 1);
+        let step = &actual.steps[0];
+        assert_eq!(step.id.as_deref(), Some("5"));
+        assert_eq!(step.bin, Some(Bin::Name("cmd".into())));
+        assert_eq!(step.expected_stdout, Some(crate::Data::text("  indented")));
+        assert_eq!(step.expected_stdout_source, Some(6..7));
+    }
+
+    #[test]
+    fn indent_lines_skips_blank() {
+        assert_eq!(indent_lines("foo\n\nbar\n", "   "), "   foo\n\n   bar\n");
     }
 
     #[test]
@@ -1317,6 +3620,16 @@ $ rust-cmd1
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn parse_toml_bin_script() {
+        let expected = OneShot {
+            bin: Some(Bin::Script("python3 tools/server.py".into())),
+            ..Default::default()
+        };
+        let actual = OneShot::parse_toml(r#"bin.script = "python3 tools/server.py""#).unwrap();
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn parse_toml_args_split() {
         let expected = OneShot {
@@ -1360,6 +3673,18 @@ $ rust-cmd1
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn parse_toml_status_signal() {
+        let actual = OneShot::parse_toml("status.signal = 9").unwrap();
+        assert_eq!(actual.status, Some(CommandStatus::Signal(9)));
+        let actual = OneShot::parse_toml("status = 'core-dumped'").unwrap();
+        assert_eq!(actual.status, Some(CommandStatus::CoreDumped));
+        assert_eq!(
+            "core-dumped".parse::<CommandStatus>(),
+            Ok(CommandStatus::CoreDumped)
+        );
+    }
+
     #[test]
     fn replace_lines_same_line_count() {
         let input = "One\nTwo\nThree";
@@ -1427,6 +3752,7 @@ bin.name = "cmd"
 "#;
         let actual = overwrite_toml_status(
             exit_code_to_status(0),
+            None,
             r#"
 bin.name = "cmd"
 status = "failed"
@@ -1445,6 +3771,7 @@ status.code = 1
 "#;
         let actual = overwrite_toml_status(
             exit_code_to_status(1),
+            None,
             r#"
 bin.name = "cmd"
 "#
@@ -1462,6 +3789,7 @@ status = { code = 1 } # comment
 "#;
         let actual = overwrite_toml_status(
             exit_code_to_status(1),
+            None,
             r#"
 bin.name = "cmd"
 status = { code = 2 } # comment
@@ -1472,6 +3800,579 @@ status = { code = 2 } # comment
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn overwrite_toml_status_matrix() {
+        let expected = r#"
+bin.name = "cmd"
+
+[[matrix]]
+name = "a"
+
+[[matrix]]
+name = "b"
+status.code = 1
+"#;
+        let actual = overwrite_toml_status(
+            exit_code_to_status(1),
+            Some("b"),
+            r#"
+bin.name = "cmd"
+
+[[matrix]]
+name = "a"
+
+[[matrix]]
+name = "b"
+"#
+            .into(),
+        )
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_toml_matrix() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+bin.name = "cmd"
+args = ["--base"]
+env.add.BASE = "1"
+
+[[matrix]]
+name = "plain"
+
+[[matrix]]
+name = "verbose"
+args = ["-v"]
+env.add.LEVEL = "debug"
+status = "failed"
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert!(actual.matrix);
+        assert_eq!(
+            actual
+                .steps
+                .iter()
+                .map(|s| (s.id.as_deref(), s.args.clone(), s.env.add.len()))
+                .collect::<Vec<_>>(),
+            [
+                (Some("plain"), vec!["--base".to_owned()], 1),
+                (
+                    Some("verbose"),
+                    vec!["--base".to_owned(), "-v".to_owned()],
+                    2
+                ),
+            ]
+        );
+        assert_eq!(actual.steps[1].expected_status, Some(CommandStatus::Failed));
+    }
+
+    #[test]
+    fn parse_toml_target() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+bin.name = "cmd"
+stdout = "default"
+
+[target.'cfg(windows)']
+status = "failed"
+stdout = "windows"
+
+[target.'cfg(unix)']
+stdout = "unix"
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        let step = &actual.steps[0];
+        if cfg!(windows) {
+            assert_eq!(step.expected_status, Some(CommandStatus::Failed));
+            assert_eq!(step.expected_stdout, Some(crate::Data::text("windows")));
+        } else {
+            assert_eq!(step.expected_status, None);
+            assert_eq!(step.expected_stdout, Some(crate::Data::text("unix")));
+        }
+    }
+
+    #[test]
+    fn parse_toml_skip_on() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+skip-on = ["linux", "macos", "windows"]
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(
+            actual.steps[0].expected_status,
+            Some(CommandStatus::Skipped)
+        );
+    }
+
+    #[test]
+    fn parse_toml_skip_reason() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+skip = "flaky on CI"
+only = true
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert!(actual.only);
+        assert_eq!(
+            actual.steps[0].expected_status,
+            Some(CommandStatus::Skipped)
+        );
+        assert_eq!(actual.steps[0].skip_reason.as_deref(), Some("flaky on CI"));
+
+        let one_shot = OneShot::parse_toml("skip = false").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert!(!actual.only);
+        assert_eq!(actual.steps[0].expected_status, None);
+    }
+
+    #[test]
+    fn parse_toml_net_deferred() {
+        // Whether isolation is possible is only checked when running, on any platform
+        let one_shot = OneShot::parse_toml(r#"net = "none""#).unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].net, Net::None);
+        assert_eq!(actual.steps[0].expected_status, None);
+    }
+
+    #[test]
+    fn parse_toml_retries() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+retries = { count = 3, backoff = "100ms" }
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        let retries = &actual.steps[0].retries;
+        assert_eq!(retries.count, 3);
+        assert_eq!(retries.delay(0), std::time::Duration::from_millis(100));
+        assert_eq!(retries.delay(2), std::time::Duration::from_millis(400));
+
+        let one_shot = OneShot::parse_toml("").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].retries, Retries::default());
+    }
+
+    #[test]
+    fn parse_toml_max_output() {
+        let one_shot = OneShot::parse_toml(r#"max-output = "10MB""#).unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].max_output, Some(10_000_000));
+
+        let one_shot = OneShot::parse_toml("max-output = 1024").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].max_output, Some(1024));
+    }
+
+    #[test]
+    fn parse_toml_interaction() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+[[interaction]]
+expect = "Continue? [y/N]"
+send = "y\n"
+
+[[interaction]]
+expect = "Done"
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(
+            actual.steps[0].interaction,
+            [
+                Interaction {
+                    expect: "Continue? [y/N]".into(),
+                    send: "y\n".into(),
+                },
+                Interaction {
+                    expect: "Done".into(),
+                    send: "".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_toml_stdin_generate() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+stdin.generate = { bytes = 1048576, pattern = "random", seed = 42 }
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        let stdin = actual.steps[0].stdin.as_ref().unwrap().to_bytes().unwrap();
+        assert_eq!(stdin.len(), 1_048_576);
+        assert_eq!(&stdin[..8], &[149, 110, 235, 47, 38, 50, 215, 189]);
+
+        let one_shot = OneShot::parse_toml(r#"stdin = "hello""#).unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].stdin, Some(crate::Data::text("hello")));
+    }
+
+    #[test]
+    fn parse_toml_stdin_close() {
+        let one_shot = OneShot::parse_toml(r#"stdin.close = "never""#).unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].stdin, None);
+        assert_eq!(actual.steps[0].stdin_close, StdinClose::Never);
+
+        let one_shot = OneShot::parse_toml(r#"stdin = "hello""#).unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.steps[0].stdin_close, StdinClose::AfterWrite);
+
+        let one_shot = OneShot::parse_toml(
+            r#"
+stdin = { close = "immediately", generate = { bytes = 4 } }
+"#,
+        )
+        .unwrap();
+        let mut actual = TryCmd::try_from(one_shot).unwrap();
+        actual.steps[0].bin = Some(Bin::Path(std::env::current_exe().unwrap()));
+        let err = actual.steps[0].to_command(None).unwrap_err().to_string();
+        assert!(err.contains("leaves no chance"), "{err}");
+    }
+
+    #[test]
+    fn generate_patterns() {
+        let generate = |pattern, seed| {
+            Generate {
+                bytes: ByteSize::Bytes(10),
+                pattern,
+                seed,
+            }
+            .generate()
+            .unwrap()
+        };
+        assert_eq!(generate(Pattern::Zeros, 0), [0; 10]);
+        assert_eq!(generate(Pattern::Lines, 0), b"1\n2\n3\n4\n5\n");
+        assert_eq!(generate(Pattern::Random, 1), generate(Pattern::Random, 1));
+        assert_ne!(generate(Pattern::Random, 1), generate(Pattern::Random, 2));
+    }
+
+    #[test]
+    fn merge_toml_tables() {
+        let mut base: toml_edit::DocumentMut = r#"
+bin.name = "cmd"
+args = ["--base"]
+timeout = "1s"
+env.add = { BASE = "1", SHARED = "base" }
+"#
+        .parse()
+        .unwrap();
+        let case: toml_edit::DocumentMut = r#"
+args = ["--case"]
+
+[env.add]
+SHARED = "case"
+"#
+        .parse()
+        .unwrap();
+        merge_toml(&mut base, &case);
+        let actual = OneShot::parse_toml(&base.to_string()).unwrap();
+        assert_eq!(actual.bin, Some(Bin::Name("cmd".into())));
+        assert_eq!(actual.args, Args::Split(vec!["--case".into()]));
+        assert_eq!(actual.timeout, Some(std::time::Duration::from_secs(1)));
+        assert_eq!(
+            actual.env.add,
+            BTreeMap::from([
+                ("BASE".to_owned(), "1".to_owned()),
+                ("SHARED".to_owned(), "case".to_owned())
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_toml_max_duration() {
+        let one_shot = OneShot::parse_toml(r#"max-duration = "1s 500ms""#).unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(
+            actual.steps[0].max_duration,
+            Some(std::time::Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn parse_toml_signals() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+[[signals]]
+after = "500ms"
+signal = "INT"
+
+[[signals]]
+after = "1s"
+signal = "SIGTERM"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            one_shot.signals,
+            vec![
+                ScheduledSignal {
+                    after: std::time::Duration::from_millis(500),
+                    signal: Signal::Int,
+                },
+                ScheduledSignal {
+                    after: std::time::Duration::from_secs(1),
+                    signal: Signal::Term,
+                },
+            ]
+        );
+        assert!(OneShot::parse_toml("[[signals]]\nafter = \"1s\"\nsignal = \"STOP\"").is_err());
+    }
+
+    #[test]
+    fn parse_toml_http_stub() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+[[http-stub]]
+path = "/v1/items"
+body-file = "items.json"
+
+[[http-stub]]
+path = "/v1/items"
+method = "POST"
+status = 201
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(
+            actual.http_stubs,
+            vec![
+                HttpStub {
+                    path: "/v1/items".into(),
+                    method: None,
+                    status: 200,
+                    body: None,
+                    body_file: Some("items.json".into()),
+                    content_type: None,
+                },
+                HttpStub {
+                    path: "/v1/items".into(),
+                    method: Some("POST".into()),
+                    status: 201,
+                    body: None,
+                    body_file: None,
+                    content_type: None,
+                },
+            ]
+        );
+
+        let relative = OneShot::parse_toml("[[http-stub]]\npath = \"v1/items\"").unwrap();
+        assert!(TryCmd::try_from(relative).is_err());
+        let both = OneShot::parse_toml(
+            "[[http-stub]]\npath = \"/\"\nbody = \"[]\"\nbody-file = \"items.json\"",
+        )
+        .unwrap();
+        assert!(TryCmd::try_from(both).is_err());
+    }
+
+    #[test]
+    fn parse_toml_tolerate_interleave() {
+        let one_shot =
+            OneShot::parse_toml("stderr-to-stdout = true\ntolerate-interleave = true").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert!(actual.steps[0].tolerate_interleave);
+        let unmerged = OneShot::parse_toml("tolerate-interleave = true").unwrap();
+        assert!(TryCmd::try_from(unmerged).is_err());
+    }
+
+    #[test]
+    fn parse_toml_locales() {
+        let one_shot = OneShot::parse_toml("locales = [\"C\", \"de_DE.UTF-8\"]").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert!(actual.matrix);
+        let ids = actual
+            .steps
+            .iter()
+            .map(|s| s.id.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["C", "de_DE.UTF-8"]);
+        assert_eq!(actual.steps[1].env.add["LC_ALL"], "de_DE.UTF-8");
+        assert_eq!(actual.steps[1].env.remove, ["LANGUAGE"]);
+
+        let both = OneShot::parse_toml("locales = [\"C\"]\n[[matrix]]\nname = \"a\"").unwrap();
+        assert!(TryCmd::try_from(both).is_err());
+        let invalid = OneShot::parse_toml("locales = [\"../C\"]").unwrap();
+        assert!(TryCmd::try_from(invalid).is_err());
+    }
+
+    #[test]
+    fn parse_toml_must_not_contain() {
+        let one_shot = OneShot::parse_toml("stderr-must-not-contain = [\"panicked at\"]").unwrap();
+        assert!(TryCmd::try_from(one_shot).is_ok());
+        let empty = OneShot::parse_toml("stdout-must-not-contain = [\"\"]").unwrap();
+        assert_eq!(
+            TryCmd::try_from(empty).unwrap_err().message(),
+            "`stdout-must-not-contain` entries can't be empty"
+        );
+        let empty = OneShot::parse_toml("stderr-must-not-contain = [\"error\", \"\"]").unwrap();
+        assert!(TryCmd::try_from(empty).is_err());
+    }
+
+    #[test]
+    fn parse_toml_schema_version() {
+        let current = OneShot::parse_toml("schema-version = 1").unwrap();
+        assert!(TryCmd::try_from(current).is_ok());
+        let newer = OneShot::parse_toml("schema-version = 2").unwrap();
+        assert_eq!(
+            TryCmd::try_from(newer).unwrap_err().message(),
+            "`schema-version = 2` needs a newer trycmd, this one reads up to `1`"
+        );
+        let zero = OneShot::parse_toml("schema-version = 0").unwrap();
+        assert!(TryCmd::try_from(zero).is_err());
+    }
+
+    #[test]
+    fn parse_toml_description() {
+        let one_shot = OneShot::parse_toml(
+            "description = \"Exits early\"\nlinks = [\"https://example.com/issues/1\"]",
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(actual.description.as_deref(), Some("Exits early"));
+        assert_eq!(actual.links, ["https://example.com/issues/1"]);
+    }
+
+    #[test]
+    fn parse_toml_fake_time() {
+        let one_shot = OneShot::parse_toml("fake-time = \"2021-01-01T00:00:00Z\"").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(
+            actual.steps[0].fake_time,
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_609_459_200))
+        );
+        let invalid = OneShot::parse_toml("fake-time = \"yesterday\"").unwrap();
+        assert!(TryCmd::try_from(invalid).is_err());
+    }
+
+    #[test]
+    fn parse_toml_timeout_grace() {
+        let one_shot = OneShot::parse_toml("timeout = \"1s\"\ntimeout-grace = \"250ms\"").unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        assert_eq!(
+            actual.steps[0].timeout_grace,
+            Some(std::time::Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn byte_size() {
+        let size = |s: &str| ByteSize::Text(s.to_owned()).to_bytes();
+        assert_eq!(size("12").unwrap(), 12);
+        assert_eq!(size("12B").unwrap(), 12);
+        assert_eq!(size("3 kb").unwrap(), 3_000);
+        assert_eq!(size("512KiB").unwrap(), 512 * 1024);
+        assert_eq!(size("1GiB").unwrap(), 1 << 30);
+        assert_eq!(
+            size("10 parsecs").unwrap_err().to_string(),
+            "Unknown unit in size `10 parsecs`\n"
+        );
+        assert_eq!(size("MB").unwrap_err().to_string(), "Invalid size `MB`\n");
+    }
+
+    #[test]
+    fn parse_toml_stream_check() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+stdout = "non-empty"
+stderr = "anything else"
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap();
+        let step = &actual.steps[0];
+        assert_eq!(step.stdout_check, Some(StreamCheck::NonEmpty));
+        assert_eq!(step.expected_stdout, None);
+        assert_eq!(step.stderr_check, None);
+        assert_eq!(
+            step.expected_stderr,
+            Some(crate::Data::text("anything else"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn json_schema_up_to_date() {
+        let actual = OneShot::json_schema();
+        snapbox::assert_data_eq!(actual, snapbox::file!["../schema.json"].raw());
+    }
+
+    #[test]
+    fn parse_toml_unknown_field() {
+        let err = OneShot::parse_toml("bin.name = 'cmd'\ntimeot = '1s'").unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("line 2, column 1"), "{err}");
+        assert!(err.contains("unknown field `timeot`"), "{err}");
+        assert!(err.contains("help: did you mean `timeout`?"), "{err}");
+
+        let err = OneShot::parse_toml("fs.sandbx = true").unwrap_err();
+        assert!(
+            err.to_string().contains("help: did you mean `sandbox`?"),
+            "{err}"
+        );
+
+        let err = OneShot::parse_toml("completely-different = 1").unwrap_err();
+        assert!(!err.to_string().contains("help:"), "{err}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn repro() {
+        let bin = std::env::current_exe().unwrap();
+        let step = Step {
+            bin: Some(Bin::Path(bin.clone())),
+            args: vec!["--flag".into(), "it's".into()],
+            env: Env {
+                add: IntoIterator::into_iter([("KEY".into(), "value".into())]).collect(),
+                remove: vec!["TERM".into()],
+                ..Default::default()
+            },
+            stdin: Some(crate::Data::text("input\n")),
+            ..Default::default()
+        };
+        let actual = step.repro(Some(std::path::Path::new("/tmp"))).unwrap();
+        let expected = format!(
+            "cd '/tmp' && printf '%s' 'input\n' | env -u 'TERM' KEY='value' '{}' '--flag' 'it'\\''s'",
+            bin.display()
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_toml_error_kind() {
+        let err = OneShot::parse_toml("bin.name = ").unwrap_err();
+        assert!(matches!(err, crate::Error::ParseToml(_)), "{err:?}");
+    }
+
+    #[test]
+    fn parse_toml_matrix_duplicate() {
+        let one_shot = OneShot::parse_toml(
+            r#"
+[[matrix]]
+name = "a"
+
+[[matrix]]
+name = "a"
+"#,
+        )
+        .unwrap();
+        let actual = TryCmd::try_from(one_shot).unwrap_err();
+        assert_eq!(actual.to_string(), "Duplicate `matrix.name`: a\n");
+    }
+
     #[test]
     fn overwrite_trycmd_status_success() {
         let expected = r#"
@@ -1498,6 +4399,7 @@ bar
             step,
             &mut step.expected_stdout_source.clone().unwrap(),
             &mut actual,
+            "",
         )
         .unwrap();
 
@@ -1531,6 +4433,7 @@ bar
             step,
             &mut step.expected_stdout_source.clone().unwrap(),
             &mut actual,
+            "",
         )
         .unwrap();
 
@@ -1564,6 +4467,7 @@ bar
             step,
             &mut step.expected_stdout_source.clone().unwrap(),
             &mut actual,
+            "",
         )
         .unwrap();
 
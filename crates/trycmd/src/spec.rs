@@ -3,20 +3,45 @@ use std::collections::BTreeMap;
 #[derive(Debug)]
 pub(crate) struct RunnerSpec {
     cases: Vec<CaseSpec>,
+    built: Vec<crate::Case>,
     include: Option<Vec<String>>,
+    filter: Vec<String>,
     default_bin: Option<crate::schema::Bin>,
     timeout: Option<std::time::Duration>,
+    wrapper: Vec<String>,
     env: crate::schema::Env,
+    sandbox_root: Option<std::path::PathBuf>,
+    max_failures: Option<usize>,
+    deadline: Option<std::time::Duration>,
+    remove_unused: bool,
+    /// The variable to pass each case's seed in
+    seed_var: Option<String>,
+    /// What the seeds are derived from, along with the case names
+    seed: Option<u64>,
+    tags: crate::runner::TagFilter,
+    /// Where to write bundles of what failed
+    artifacts: Option<std::path::PathBuf>,
 }
 
 impl RunnerSpec {
     pub(crate) fn new() -> Self {
         Self {
             cases: Default::default(),
+            built: Default::default(),
             include: None,
+            filter: Default::default(),
             default_bin: None,
             timeout: Default::default(),
+            wrapper: Default::default(),
             env: Default::default(),
+            sandbox_root: None,
+            max_failures: None,
+            deadline: None,
+            remove_unused: false,
+            seed_var: None,
+            seed: None,
+            tags: Default::default(),
+            artifacts: crate::artifacts::default_dir(),
         }
     }
 
@@ -31,13 +56,36 @@ impl RunnerSpec {
             expected,
             #[cfg(miri)]
             expected: Some(crate::schema::CommandStatus::Skipped),
+            root: None,
         });
     }
 
+    /// Cases anywhere under `dir`, named by their path relative to it
+    pub(crate) fn case_dir(&mut self, dir: &std::path::Path) {
+        for ext in ["toml", "trycmd"] {
+            self.cases.push(CaseSpec {
+                glob: dir.join("**").join(format!("*.{ext}")),
+                #[cfg(not(miri))]
+                expected: None,
+                #[cfg(miri)]
+                expected: Some(crate::schema::CommandStatus::Skipped),
+                root: Some(dir.to_owned()),
+            });
+        }
+    }
+
+    pub(crate) fn built(&mut self, case: crate::Case) {
+        self.built.push(case);
+    }
+
     pub(crate) fn include(&mut self, include: Option<Vec<String>>) {
         self.include = include;
     }
 
+    pub(crate) fn filter(&mut self, glob: impl Into<String>) {
+        self.filter.push(glob.into());
+    }
+
     pub(crate) fn default_bin(&mut self, bin: Option<crate::schema::Bin>) {
         self.default_bin = bin;
     }
@@ -46,15 +94,73 @@ impl RunnerSpec {
         self.timeout = time;
     }
 
+    pub(crate) fn color(&mut self, color: crate::schema::Color) {
+        self.env.color = Some(color);
+    }
+
+    pub(crate) fn coverage(&mut self, yes: bool) {
+        self.env.coverage = yes;
+    }
+
+    pub(crate) fn wrapper(&mut self, wrapper: Vec<String>) {
+        self.wrapper = wrapper;
+    }
+
+    pub(crate) fn sandbox_root(&mut self, root: Option<std::path::PathBuf>) {
+        self.sandbox_root = root;
+    }
+
+    pub(crate) fn max_failures(&mut self, max: Option<usize>) {
+        self.max_failures = max;
+    }
+
+    pub(crate) fn deadline(&mut self, deadline: Option<std::time::Duration>) {
+        self.deadline = deadline;
+    }
+
+    pub(crate) fn remove_unused(&mut self, yes: bool) {
+        self.remove_unused = yes;
+    }
+
+    pub(crate) fn seed_var(&mut self, var: impl Into<String>) {
+        self.seed_var = Some(var.into());
+    }
+
+    pub(crate) fn seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    pub(crate) fn artifacts(&mut self, dir: Option<std::path::PathBuf>) {
+        self.artifacts = dir;
+    }
+
+    pub(crate) fn include_tag(&mut self, tag: impl Into<String>) {
+        self.tags.include.push(tag.into());
+    }
+
+    pub(crate) fn exclude_tag(&mut self, tag: impl Into<String>) {
+        self.tags.exclude.push(tag.into());
+    }
+
+    pub(crate) fn tags(&mut self, tags: crate::runner::TagFilter) {
+        self.tags = tags;
+    }
+
     pub(crate) fn env(&mut self, key: impl Into<String>, value: impl Into<String>) {
         self.env.add.insert(key.into(), value.into());
     }
 
     pub(crate) fn prepare(&mut self) -> crate::Runner {
         let mut runner = crate::Runner::new();
+        runner.max_failures(self.max_failures);
+        runner.deadline(self.deadline);
+        runner.remove_unused(self.remove_unused);
+        runner.tags(self.tags.clone());
 
         // Both sort and let the last writer win to allow overriding specific cases within a glob
-        let mut cases: BTreeMap<std::path::PathBuf, crate::Case> = BTreeMap::new();
+        let mut cases: BTreeMap<std::path::PathBuf, crate::runner::Case> = BTreeMap::new();
+        // Where cases are found by a glob, so any snapshots there should belong to one
+        let mut case_dirs = std::collections::BTreeSet::new();
 
         for spec in &self.cases {
             if let Some(glob) = get_glob(&spec.glob) {
@@ -62,23 +168,36 @@ impl RunnerSpec {
                     Ok(paths) => {
                         for path in paths {
                             match path {
+                                Ok(path)
+                                    if path.file_name()
+                                        == Some(std::ffi::OsStr::new(
+                                            crate::schema::DEFAULT_TOML,
+                                        )) =>
+                                {
+                                    // Shared settings, not a case
+                                }
+                                Ok(path) if is_backup(&path) => {}
                                 Ok(path) => {
-                                    cases.insert(
-                                        path.clone(),
-                                        crate::Case {
-                                            path,
-                                            expected: spec.expected,
-                                            default_bin: self.default_bin.clone(),
-                                            timeout: self.timeout,
-                                            env: self.env.clone(),
-                                            error: None,
-                                        },
-                                    );
+                                    let mut case = self.runner_case(path.clone(), spec.expected);
+                                    if let Some(root) = &spec.root {
+                                        let Some(name) = nested_name(root, &path) else {
+                                            // Part of another case's `.in` or `.out`
+                                            continue;
+                                        };
+                                        case.name = Some(name);
+                                    }
+                                    if let Some(dir) = path.parent() {
+                                        case_dirs.insert(dir.to_owned());
+                                    }
+                                    cases.insert(path, case);
                                 }
                                 Err(err) => {
                                     let path = err.path().to_owned();
                                     let err = crate::Error::new(err.into_error().to_string());
-                                    cases.insert(path.clone(), crate::Case::with_error(path, err));
+                                    cases.insert(
+                                        path.clone(),
+                                        crate::runner::Case::with_error(path, err),
+                                    );
                                 }
                             }
                         }
@@ -87,36 +206,120 @@ impl RunnerSpec {
                         let err = crate::Error::new(err.to_string());
                         cases.insert(
                             spec.glob.clone(),
-                            crate::Case::with_error(spec.glob.clone(), err),
+                            crate::runner::Case::with_error(spec.glob.clone(), err),
                         );
                     }
                 }
             } else {
                 let path = spec.glob.as_path();
+                cases.insert(path.into(), self.runner_case(path.into(), spec.expected));
+            }
+        }
+        for case in &self.built {
+            #[cfg(not(miri))]
+            let expected = None;
+            #[cfg(miri)]
+            let expected = Some(crate::schema::CommandStatus::Skipped);
+            let case = case.clone().into_runner(
+                expected,
+                self.default_bin.clone(),
+                self.timeout,
+                self.wrapper.clone(),
+                self.env.clone(),
+                self.sandbox_root.clone(),
+            );
+            // Unlike globs, built cases don't override each other, so a repeated name is a mistake
+            match cases.entry(case.path.clone()) {
+                std::collections::btree_map::Entry::Occupied(mut entry) => {
+                    let err = crate::Error::new(format!(
+                        "More than one case is named `{}`, give each a unique `name`",
+                        case.path.display()
+                    ));
+                    entry.insert(crate::runner::Case::with_error(case.path, err));
+                }
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    entry.insert(case);
+                }
+            }
+        }
+
+        for (path, other) in collisions(
+            cases
+                .values()
+                .filter(|c| c.built.is_none())
+                .map(|c| &c.path),
+        ) {
+            let err = crate::Error::new(format!(
+                "`{}` and `{}` are the same case; rename one",
+                other.display(),
+                path.display()
+            ));
+            for path in [path, other] {
                 cases.insert(
-                    path.into(),
-                    crate::Case {
-                        path: path.into(),
-                        expected: spec.expected,
-                        default_bin: self.default_bin.clone(),
-                        timeout: self.timeout,
-                        env: self.env.clone(),
-                        error: None,
-                    },
+                    path.clone(),
+                    crate::runner::Case::with_error(path, err.clone()),
                 );
             }
         }
 
-        for case in cases.into_values() {
-            if self.is_included(&case) {
-                runner.case(case);
+        if self.filter.is_empty() && self.include.is_none() {
+            let referenced: Vec<_> = cases
+                .values()
+                .flat_map(|case| case.referenced_paths())
+                .collect();
+            runner.unused(unused_snapshots(
+                case_dirs.iter().map(|d| d.as_path()),
+                &referenced,
+            ));
+        }
+
+        let seed_var = match (&self.seed_var, self.seed) {
+            (Some(var), _) => Some(var.as_str()),
+            (None, Some(_)) => Some(DEFAULT_SEED_VAR),
+            (None, None) => None,
+        };
+        for mut case in cases.into_values() {
+            if let Some(var) = seed_var {
+                let seed = case_seed(
+                    self.seed.unwrap_or_default(),
+                    &case.name().to_string_lossy(),
+                );
+                case.seed = Some((var.to_owned(), seed));
             }
+            case.artifacts = self.artifacts.clone();
+            runner.case(case);
         }
+        runner.retain_cases(|case| self.is_included(case));
 
         runner
     }
 
-    fn is_included(&self, case: &crate::Case) -> bool {
+    /// A case loaded from `path`, with the suite's defaults
+    pub(crate) fn runner_case(
+        &self,
+        path: std::path::PathBuf,
+        expected: Option<crate::schema::CommandStatus>,
+    ) -> crate::runner::Case {
+        crate::runner::Case {
+            path,
+            name: None,
+            expected,
+            default_bin: self.default_bin.clone(),
+            timeout: self.timeout,
+            wrapper: self.wrapper.clone(),
+            env: self.env.clone(),
+            sandbox_root: self.sandbox_root.clone(),
+            error: None,
+            built: None,
+            seed: None,
+            artifacts: None,
+        }
+    }
+
+    fn is_included(&self, case: &crate::runner::Case) -> bool {
+        if !self.filter.is_empty() && !self.filter.iter().any(|f| matches_filter(f, &case.path)) {
+            return false;
+        }
         if let Some(include) = self.include.as_deref() {
             include
                 .iter()
@@ -137,6 +340,156 @@ impl Default for RunnerSpec {
 struct CaseSpec {
     glob: std::path::PathBuf,
     expected: Option<crate::schema::CommandStatus>,
+    /// Name the cases relative to this directory
+    root: Option<std::path::PathBuf>,
+}
+
+/// Match `filter` against the file name or, if it has a `/`, the whole path
+///
+/// Invalid globs fall back to matching a substring of the path.
+fn matches_filter(filter: &str, path: &std::path::Path) -> bool {
+    let Ok(pattern) = ::glob::Pattern::new(filter) else {
+        return path.to_string_lossy().contains(filter);
+    };
+    if filter.contains('/') {
+        pattern.matches_path(path)
+    } else {
+        path.file_name()
+            .map(|name| pattern.matches(&name.to_string_lossy()))
+            .unwrap_or(false)
+    }
+}
+
+/// The name of a case under `root`, like `subcmd::users::add` for `subcmd/users/add.toml`
+///
+/// Files within a case's `.in` or `.out` directory aren't cases.
+fn nested_name(root: &std::path::Path, path: &std::path::Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?.with_extension("");
+    let names = rel
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    let (_, dirs) = names.split_last()?;
+    if dirs
+        .iter()
+        .any(|dir| dir.ends_with(".in") || dir.ends_with(".out"))
+    {
+        return None;
+    }
+    Some(names.join("::"))
+}
+
+/// Editor backups, like `foo.toml~` and `.#foo.toml`
+fn is_backup(path: &std::path::Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    name.ends_with('~') || name.starts_with(".#")
+}
+
+/// Case files that share a name, like `foo.toml` and `foo.trycmd`, and so would share snapshots
+fn collisions<'p>(
+    paths: impl Iterator<Item = &'p std::path::PathBuf>,
+) -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+    let mut seen = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for path in paths {
+        let key = (path.parent(), path.file_stem());
+        if let Some(other) = seen.insert(key, path) {
+            collisions.push((path.clone(), other.clone()));
+        }
+    }
+    collisions
+}
+
+/// Snapshots in `dirs` that no case file is named after, like `help.stdout` left behind by
+/// renaming `help.toml`, and that no case reads from, as with a shared `fs.base`
+fn unused_snapshots<'p>(
+    dirs: impl Iterator<Item = &'p std::path::Path>,
+    referenced: &[std::path::PathBuf],
+) -> Vec<std::path::PathBuf> {
+    let referenced: Vec<_> = referenced.iter().map(|p| canonical(p)).collect();
+    let mut unused = Vec::new();
+    for dir in dirs {
+        let listed = if dir.as_os_str().is_empty() {
+            std::path::Path::new(".")
+        } else {
+            dir
+        };
+        let Ok(entries) = std::fs::read_dir(listed) else {
+            continue;
+        };
+        let names: Vec<_> = entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        let stems: Vec<_> = names
+            .iter()
+            .filter_map(|name| {
+                let (stem, ext) = name.rsplit_once('.')?;
+                matches!(ext, "toml" | "trycmd" | "md").then_some(stem)
+            })
+            .collect();
+        unused.extend(
+            names
+                .iter()
+                .filter(|name| {
+                    is_snapshot(name) && !stems.iter().any(|stem| is_named_after(name, stem))
+                })
+                .map(|name| dir.join(name))
+                .filter(|path| {
+                    let path = canonical(path);
+                    !referenced.iter().any(|r| r.starts_with(&path))
+                }),
+        );
+    }
+    unused.sort();
+    unused
+}
+
+/// `path` with symlinks and `..` resolved, to compare paths however they were written
+fn canonical(path: &std::path::Path) -> std::path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// `*.stdout` and `*.stderr`, including platform-specific and custom ones, and `*.in` and `*.out`
+fn is_snapshot(name: &str) -> bool {
+    let mut parts = name.split('.').skip(1).peekable();
+    if parts.peek().is_none() {
+        return false;
+    }
+    let mut last = "";
+    for part in parts {
+        if part == "stdout" || part == "stderr" {
+            return true;
+        }
+        last = part;
+    }
+    last == "in" || last == "out"
+}
+
+/// Like `help.stdout` for `help.toml`, or `help-<id>.stdout` for one of its `[[matrix]]` entries
+fn is_named_after(name: &str, stem: &str) -> bool {
+    name.strip_prefix(stem)
+        .map(|rest| rest.starts_with('.') || rest.starts_with('-'))
+        .unwrap_or(false)
+}
+
+/// The variable commands get their seed in, unless another is chosen
+pub(crate) const DEFAULT_SEED_VAR: &str = "TRYCMD_SEED";
+
+/// A seed for the case named `name`, the same from run to run and across platforms
+///
+/// This is the FNV-1a hash of `seed` and `name`.
+fn case_seed(seed: u64, name: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    seed.to_le_bytes()
+        .iter()
+        .chain(name.as_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+        })
 }
 
 fn get_glob(path: &std::path::Path) -> Option<&str> {
@@ -148,3 +501,122 @@ fn get_glob(path: &std::path::Path) -> Option<&str> {
 
     None
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filter_file_name() {
+        let path = std::path::Path::new("tests/cmd/help.toml");
+        assert!(matches_filter("help.toml", path));
+        assert!(matches_filter("help*", path));
+        assert!(matches_filter("*.toml", path));
+        assert!(!matches_filter("*.trycmd", path));
+        assert!(!matches_filter("cmd", path));
+    }
+
+    #[test]
+    fn filter_path() {
+        let path = std::path::Path::new("tests/cmd/help.toml");
+        assert!(matches_filter("tests/cmd/*.toml", path));
+        assert!(matches_filter("tests/**/help.toml", path));
+        assert!(!matches_filter("tests/ui/*.toml", path));
+    }
+
+    #[test]
+    fn nested() {
+        let root = std::path::Path::new("tests/cmd");
+        let name = |path: &str| nested_name(root, std::path::Path::new(path));
+        assert_eq!(name("tests/cmd/help.toml").as_deref(), Some("help"));
+        assert_eq!(
+            name("tests/cmd/subcmd/users/add.toml").as_deref(),
+            Some("subcmd::users::add")
+        );
+        assert_eq!(name("tests/cmd/subcmd/add.in/Cargo.toml"), None);
+        assert_eq!(name("tests/cmd/add.out/nested/config.toml"), None);
+    }
+
+    #[test]
+    fn backup() {
+        assert!(is_backup(std::path::Path::new("tests/cmd/help.toml~")));
+        assert!(is_backup(std::path::Path::new("tests/cmd/.#help.toml")));
+        assert!(!is_backup(std::path::Path::new("tests/cmd/help.toml")));
+    }
+
+    #[test]
+    fn same_name() {
+        let paths = [
+            "tests/cmd/help.md",
+            "tests/cmd/help.toml",
+            "tests/cmd/help.trycmd",
+            "tests/ui/help.toml",
+            "tests/cmd/version.toml",
+        ]
+        .map(std::path::PathBuf::from);
+        let actual = collisions(paths.iter());
+        assert_eq!(
+            actual,
+            [
+                (paths[1].clone(), paths[0].clone()),
+                (paths[2].clone(), paths[1].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_names() {
+        assert!(is_snapshot("help.stdout"));
+        assert!(is_snapshot("help.stderr.windows"));
+        assert!(is_snapshot("report.stdout.csv"));
+        assert!(is_snapshot("help.in"));
+        assert!(is_snapshot("help.out"));
+        assert!(!is_snapshot("help.toml"));
+        assert!(!is_snapshot("help.stdin"));
+        assert!(!is_snapshot("stdout"));
+        assert!(!is_snapshot("help.out.bak"));
+
+        assert!(is_named_after("help.stdout", "help"));
+        assert!(is_named_after("matrix-default.stdout", "matrix"));
+        assert!(!is_named_after("helper.stdout", "help"));
+        assert!(!is_named_after("usage.stdout", "help"));
+    }
+
+    #[test]
+    fn unused() {
+        let dirs = [std::path::Path::new("tests/cmd/unused")];
+        let case = RunnerSpec::new().runner_case("tests/cmd/unused/shared-base.toml".into(), None);
+        assert_eq!(
+            unused_snapshots(dirs.into_iter(), &case.referenced_paths()),
+            [
+                std::path::PathBuf::from("tests/cmd/unused/orphan.in"),
+                std::path::PathBuf::from("tests/cmd/unused/renamed.stdout"),
+            ]
+        );
+    }
+
+    #[test]
+    fn seeds() {
+        assert_eq!(
+            case_seed(0, "tests/cmd/help.toml"),
+            case_seed(0, "tests/cmd/help.toml")
+        );
+        assert_ne!(
+            case_seed(0, "tests/cmd/help.toml"),
+            case_seed(1, "tests/cmd/help.toml")
+        );
+        assert_ne!(
+            case_seed(0, "tests/cmd/help.toml"),
+            case_seed(0, "tests/cmd/usage.toml")
+        );
+        // Snapshots depend on these staying the same
+        assert_eq!(case_seed(0, ""), 0xa8c7_f832_281a_39c5);
+    }
+
+    #[test]
+    fn filter_invalid_glob() {
+        let path = std::path::Path::new("tests/cmd/[help].toml");
+        assert!(matches_filter("[help", path));
+        assert!(!matches_filter("[usage", path));
+    }
+}
@@ -0,0 +1,88 @@
+//! Suggest what was meant when a name isn't recognized
+
+/// The candidate closest to `name`, if any is close enough to be a likely typo
+pub(crate) fn closest<'c>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'c str>,
+) -> Option<&'c str> {
+    // Allow about one typo for every three characters
+    let max_distance = std::cmp::max(1, name.chars().count() / 3);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// How alike two texts are, from `0.0` to `1.0`, by how many lines they share
+pub(crate) fn line_similarity(a: &str, b: &str) -> f64 {
+    let mut remaining = std::collections::BTreeMap::<&str, usize>::new();
+    for line in a.lines() {
+        *remaining.entry(line).or_default() += 1;
+    }
+    let shared = b
+        .lines()
+        .filter(|line| match remaining.get_mut(line) {
+            Some(count) if *count != 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        })
+        .count();
+    let total = std::cmp::max(a.lines().count(), b.lines().count());
+    if total == 0 {
+        1.0
+    } else {
+        shared as f64 / total as f64
+    }
+}
+
+/// Levenshtein distance, counting insertions, deletions, and substitutions
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("timeout", "timeout"), 0);
+        assert_eq!(edit_distance("timeot", "timeout"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn similarity() {
+        assert_eq!(line_similarity("", ""), 1.0);
+        assert_eq!(line_similarity("a\nb\n", "b\na\n"), 1.0);
+        assert_eq!(line_similarity("a\nb\nc\nd\n", "a\nb\nc\n"), 0.75);
+        assert_eq!(line_similarity("a\na\n", "a\nb\n"), 0.5);
+        assert_eq!(line_similarity("a\n", "b\n"), 0.0);
+    }
+
+    #[test]
+    fn closest_candidate() {
+        let candidates = ["bin", "args", "timeout", "stdout", "stderr"];
+        assert_eq!(closest("timeot", candidates), Some("timeout"));
+        assert_eq!(closest("stdot", candidates), Some("stdout"));
+        assert_eq!(closest("arg", candidates), Some("args"));
+        assert_eq!(closest("completely-different", candidates), None);
+    }
+}
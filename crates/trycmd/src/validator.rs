@@ -0,0 +1,110 @@
+/// A domain-specific comparison of output against a snapshot, like for CSV or protobuf text
+///
+/// Register it for an extension with [`TestCases::validator`][crate::TestCases::validator], and
+/// `cmd.stdout.<ext>` and `cmd.stderr.<ext>` are checked with it rather than compared as text.
+///
+/// Closures taking the same arguments as [`OutputValidator::validate`] implement this.
+pub trait OutputValidator: Send + Sync {
+    /// Check what the command wrote, after normalizing it, against the snapshot at `expected`
+    fn validate(&self, actual: &[u8], expected: &std::path::Path) -> Result<(), Vec<Difference>>;
+}
+
+impl<F> OutputValidator for F
+where
+    F: Fn(&[u8], &std::path::Path) -> Result<(), Vec<Difference>> + Send + Sync,
+{
+    fn validate(&self, actual: &[u8], expected: &std::path::Path) -> Result<(), Vec<Difference>> {
+        self(actual, expected)
+    }
+}
+
+/// One way the output differs from its snapshot, as found by an [`OutputValidator`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    location: Option<String>,
+    message: String,
+}
+
+impl Difference {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            location: None,
+            message: message.into(),
+        }
+    }
+
+    /// Where in the output the difference is, like `row 3, column "price"`
+    pub fn at(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}: {}", location, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+pub(crate) type Validator = std::sync::Arc<dyn OutputValidator>;
+
+/// The snapshot a registered [`OutputValidator`] checks a stream against
+#[derive(Clone)]
+pub(crate) struct CustomSnapshot {
+    pub(crate) validator: Validator,
+    pub(crate) path: std::path::PathBuf,
+}
+
+impl std::fmt::Debug for CustomSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomSnapshot")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Find the snapshot for `stream`, like `cmd.stdout.csv`, among the registered extensions
+pub(crate) fn find_snapshot(
+    validators: &[(String, Validator)],
+    path: &std::path::Path,
+    id: Option<&str>,
+    stream: &str,
+) -> Option<CustomSnapshot> {
+    validators.iter().find_map(|(ext, validator)| {
+        let snapshot = crate::schema::snapshot_file(path, id, &format!("{stream}.{ext}"));
+        snapshot.exists().then(|| CustomSnapshot {
+            validator: validator.clone(),
+            path: snapshot,
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Difference::new("expected 3 rows").to_string(),
+            "expected 3 rows"
+        );
+        assert_eq!(
+            Difference::new("expected `1.50`, got `1.5`")
+                .at("row 2, column \"price\"")
+                .to_string(),
+            "row 2, column \"price\": expected `1.50`, got `1.5`"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Watch a command's output as it runs for `[[expect-line]]` and `background` steps
+
+/// Run `cmd`, waiting for each of the step's `[[expect-line]]` in order, then stop it
+///
+/// A `background` step instead keeps running until [`Background::stop`], or its `timeout`.
+///
+/// The command is stopped like on `timeout`, honoring `timeout-grace`.  With `stderr-to-stdout`,
+/// the streams are merged in the order they were read.
+pub(crate) fn run(
+    mut cmd: std::process::Command,
+    step: &crate::schema::Step,
+) -> Result<std::process::Output, crate::Error> {
+    use std::io::Write as _;
+
+    let stdin = step
+        .stdin
+        .as_ref()
+        .map(|stdin| stdin.to_bytes())
+        .transpose()?;
+    cmd.stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    let start = std::time::Instant::now();
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| crate::Error::SpawnFailed(e.to_string()))?;
+
+    let mut writer = child.stdin.take();
+    let writing = std::thread::spawn(move || {
+        if let (Some(writer), Some(stdin)) = (writer.as_mut(), stdin) {
+            let _ = writer.write_all(&stdin);
+        }
+    });
+    let (sender, receiver) = std::sync::mpsc::channel::<(Stream, Vec<u8>)>();
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|s| read(s, Stream::Stdout, sender.clone())),
+        child
+            .stderr
+            .take()
+            .map(|s| read(s, Stream::Stderr, sender.clone())),
+    ];
+    drop(sender);
+
+    let mut output = Collected::default();
+    let default_within = step.timeout.unwrap_or(crate::pty::DEFAULT_EXPECT_TIMEOUT);
+    for expected in &step.expect_lines {
+        let deadline = start + expected.within.unwrap_or(default_within);
+        loop {
+            if output.find(&expected.pattern) {
+                break;
+            }
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let (kind, problem): (fn(String) -> crate::Error, _) =
+                match receiver.recv_timeout(remaining) {
+                    Ok((stream, chunk)) => {
+                        output.push(stream, chunk);
+                        continue;
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        let _ = child.kill();
+                        (crate::Error::Timeout, "Timed out waiting for")
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                        // Closing its output doesn't mean the command exited
+                        let exited = wait_until(&mut child, deadline)
+                            .map_err(|e| e.to_string())?
+                            .is_some();
+                        if exited {
+                            (crate::Error::Other, "Exited before printing")
+                        } else {
+                            let _ = child.kill();
+                            (crate::Error::Timeout, "Timed out waiting for")
+                        }
+                    }
+                };
+            let _ = child.wait();
+            return Err(kind(format!(
+                "{} a line with `{}`, after:\n{}",
+                problem,
+                expected.pattern,
+                String::from_utf8_lossy(&output.merged())
+            )));
+        }
+    }
+
+    if let Some(background) = &step.background {
+        background.set_ready();
+        let deadline = step.timeout.map(|timeout| start + timeout);
+        loop {
+            let exited = child.try_wait().map_err(|e| e.to_string())?.is_some();
+            if exited
+                || background.is_stopped()
+                || deadline.map(|d| d <= std::time::Instant::now()) == Some(true)
+            {
+                break;
+            }
+            if let Ok((stream, chunk)) = receiver.recv_timeout(POLL) {
+                output.push(stream, chunk);
+            }
+        }
+    }
+
+    let status = stop(&mut child, step.timeout_grace).map_err(|e| e.to_string())?;
+    let _ = writing.join();
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    for (stream, chunk) in receiver.try_iter() {
+        output.push(stream, chunk);
+    }
+
+    let (stdout, stderr) = if step.stderr_to_stdout {
+        (output.merged(), Vec::new())
+    } else {
+        (output.stream(Stream::Stdout), output.stream(Stream::Stderr))
+    };
+    Ok(std::process::Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// How often to check on a command that is left running
+const POLL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Lets a case run a `background` step alongside its later ones, stopping it once they are done
+#[derive(Clone, Default)]
+pub(crate) struct Background {
+    state: std::sync::Arc<(std::sync::Mutex<BackgroundState>, std::sync::Condvar)>,
+}
+
+#[derive(Default)]
+struct BackgroundState {
+    /// Started, with any `[[expect-line]]` found, or failed to get there
+    ready: bool,
+    stopped: bool,
+}
+
+impl Background {
+    /// Let the case move on to its next steps
+    pub(crate) fn set_ready(&self) {
+        let (state, ready) = &*self.state;
+        state.lock().unwrap().ready = true;
+        ready.notify_all();
+    }
+
+    /// Block until the command is started and any `[[expect-line]]` are found
+    pub(crate) fn wait_ready(&self) {
+        let (state, ready) = &*self.state;
+        let _ready = ready
+            .wait_while(state.lock().unwrap(), |state| !state.ready)
+            .unwrap();
+    }
+
+    /// Let the case move on once the guard is dropped, like when the step panics before starting
+    /// the command
+    pub(crate) fn ready_on_drop(&self) -> ReadyOnDrop {
+        ReadyOnDrop(self.clone())
+    }
+
+    /// Have the command stopped, now that the case's other steps are done
+    pub(crate) fn stop(&self) {
+        self.state.0.lock().unwrap().stopped = true;
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.state.0.lock().unwrap().stopped
+    }
+}
+
+/// Calls [`Background::set_ready`] when dropped
+pub(crate) struct ReadyOnDrop(Background);
+
+impl Drop for ReadyOnDrop {
+    fn drop(&mut self) {
+        self.0.set_ready();
+    }
+}
+
+impl std::fmt::Debug for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Background").finish_non_exhaustive()
+    }
+}
+
+/// Only whether a step is `background` matters when comparing steps
+impl PartialEq for Background {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Background {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// What the command wrote, in the order it was read
+#[derive(Default)]
+struct Collected {
+    chunks: Vec<(Stream, Vec<u8>)>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// Where the search for the next line starts in `stdout` and `stderr`
+    unmatched: [usize; 2],
+}
+
+impl Collected {
+    fn push(&mut self, stream: Stream, chunk: Vec<u8>) {
+        match stream {
+            Stream::Stdout => self.stdout.extend(&chunk),
+            Stream::Stderr => self.stderr.extend(&chunk),
+        }
+        self.chunks.push((stream, chunk));
+    }
+
+    /// Look for a complete line with `pattern` in either stream, past any found before
+    fn find(&mut self, pattern: &str) -> bool {
+        for (i, content) in [&self.stdout, &self.stderr].into_iter().enumerate() {
+            let unmatched = &content[self.unmatched[i]..];
+            let found = unmatched
+                .split_inclusive(|b| *b == b'\n')
+                .scan(0, |offset, line| {
+                    *offset += line.len();
+                    Some((*offset, line))
+                })
+                .find(|(_, line)| {
+                    line.ends_with(b"\n") && String::from_utf8_lossy(line).contains(pattern)
+                });
+            if let Some((end, _)) = found {
+                self.unmatched[i] += end;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn stream(&self, stream: Stream) -> Vec<u8> {
+        match stream {
+            Stream::Stdout => self.stdout.clone(),
+            Stream::Stderr => self.stderr.clone(),
+        }
+    }
+
+    fn merged(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .flat_map(|(_, chunk)| chunk.iter().copied())
+            .collect()
+    }
+}
+
+fn read(
+    mut reader: impl std::io::Read + Send + 'static,
+    stream: Stream,
+    sender: std::sync::mpsc::Sender<(Stream, Vec<u8>)>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(len) => {
+                    if sender.send((stream, buffer[..len].to_vec())).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Stop the command now that it's done what was expected, asking first with `grace`
+fn stop(
+    child: &mut std::process::Child,
+    grace: Option<std::time::Duration>,
+) -> std::io::Result<std::process::ExitStatus> {
+    if let Some(status) = child.try_wait()? {
+        return Ok(status);
+    }
+    #[cfg(unix)]
+    if let Some(grace) = grace {
+        // SAFETY: `kill` has no memory safety requirements
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+        if let Some(status) = wait_until(child, std::time::Instant::now() + grace)? {
+            return Ok(status);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = grace;
+    let _ = child.kill();
+    child.wait()
+}
+
+/// Wait for the command to exit, giving up at `deadline`
+fn wait_until(
+    child: &mut std::process::Child,
+    deadline: std::time::Instant,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if deadline <= std::time::Instant::now() {
+            return Ok(None);
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find() {
+        let mut output = Collected::default();
+        output.push(Stream::Stdout, b"Starting\nListening".to_vec());
+        assert!(!output.find("Listening"));
+        output.push(Stream::Stderr, b"warning: no config\n".to_vec());
+        output.push(Stream::Stdout, b" on 8080\n".to_vec());
+        assert!(output.find("Listening on"));
+        // Each line is only found once
+        assert!(!output.find("Listening on"));
+        assert!(output.find("no config"));
+        assert_eq!(
+            output.merged(),
+            b"Starting\nListeningwarning: no config\n on 8080\n"
+        );
+        assert_eq!(
+            output.stream(Stream::Stdout),
+            b"Starting\nListening on 8080\n"
+        );
+    }
+}
@@ -1,7 +1,10 @@
 #[test]
 fn cli_tests() {
     let t = trycmd::TestCases::new();
-    t.case("tests/cmd/*.trycmd").case("tests/cmd/*.toml");
+    t.case("tests/cmd/*.trycmd")
+        .case("tests/cmd/*.toml")
+        .case("tests/cmd/*.md")
+        .case("tests/cmd/defaults/*.toml");
     #[cfg(not(feature = "schema"))]
     {
         t.skip("tests/cmd/schema.toml");
@@ -10,10 +13,24 @@ fn cli_tests() {
     {
         t.skip("tests/cmd/diff_subset.toml");
         t.skip("tests/cmd/sandbox.toml");
+        t.skip("tests/cmd/base-readonly.toml");
+        t.skip("tests/cmd/depends-setup.toml");
+        t.skip("tests/cmd/depends-on.toml");
+        t.skip("tests/cmd/hooks.toml");
         t.skip("tests/cmd/multistep.trycmd");
         t.skip("tests/cmd/normalize.toml");
         t.skip("tests/cmd/symlink.toml");
         t.skip("tests/cmd/subst-cwd.toml");
+        t.skip("tests/cmd/fs-expect.toml");
+        t.skip("tests/cmd/fs-expect-mismatch.toml");
+        t.skip("tests/cmd/fs-limits.toml");
+        t.skip("tests/cmd/fs-limits-exceeded.toml");
+        t.skip("tests/cmd/fs-files.toml");
+        t.skip("tests/cmd/cwd-create.toml");
+        t.skip("tests/cmd/files.toml");
+        t.skip("tests/cmd/ignore.toml");
+        t.skip("tests/cmd/retries.toml");
+        t.skip("tests/cmd/shell.toml");
     }
     #[cfg(target_os = "windows")]
     {
@@ -22,4 +39,802 @@ fn cli_tests() {
     }
     t.extend_vars([("[EXAMPLE]", "example")]).unwrap();
     t.register_bin("ignored-bin", trycmd::schema::Bin::Ignore);
+    t.setup(|case| {
+        if case.name() == "tests/cmd/hooks.toml" {
+            let sandbox = case.sandbox().expect("sandboxed");
+            std::fs::write(sandbox.join("seeded.txt"), "Seeded by setup")
+                .map_err(|e| trycmd::Error::new(e.to_string()))?;
+        }
+        Ok(())
+    });
+    t.teardown(|case| {
+        if case.name() == "tests/cmd/hooks.toml" {
+            let sandbox = case.sandbox().expect("sandboxed");
+            std::fs::remove_file(sandbox.join("seeded.txt"))
+                .map_err(|e| trycmd::Error::new(e.to_string()))?;
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn built_cases() {
+    let t = trycmd::TestCases::new();
+    t.default_bin_name("bin-fixture");
+    t.add(
+        trycmd::Case::new()
+            .name("built/stdout")
+            .env("stdout", "hello")
+            .expect_stdout("hello\n"),
+    )
+    .add(
+        trycmd::Case::new()
+            .name("built/failure")
+            .env("stderr", "oops")
+            .env("exit", "2")
+            .expect_status(trycmd::schema::CommandStatus::Code(2))
+            .expect_stderr("oops\n"),
+    )
+    .add(
+        trycmd::Case::new()
+            .name("built/stdin")
+            .env("echo_stdin", "1")
+            .stdin("piped\n")
+            .expect_stdout("piped\n"),
+    );
+}
+
+#[test]
+fn built_cases_same_name() {
+    let results = trycmd::TestCases::new()
+        .default_bin_name("bin-fixture")
+        .add(
+            trycmd::Case::new()
+                .env("stdout", "hello")
+                .expect_stdout("hello\n"),
+        )
+        .add(
+            trycmd::Case::new()
+                .env("stdout", "goodbye")
+                .expect_stdout("goodbye\n"),
+        )
+        .collect();
+    assert_eq!(results.len(), 1);
+    assert!(!results[0].is_ok());
+    assert!(
+        results[0]
+            .to_string()
+            .contains("More than one case is named"),
+        "{}",
+        results[0]
+    );
+}
+
+#[test]
+fn run_single_case() {
+    let result = trycmd::Case::from_path("tests/cmd/basic.toml").run();
+    assert!(result.is_ok(), "{result}");
+    assert_eq!(result.name(), "tests/cmd/basic.toml");
+
+    let result = trycmd::Case::new()
+        .bin(trycmd::schema::Bin::Name("bin-fixture".into()))
+        .env("stdout", "hello")
+        .expect_stdout("goodbye\n")
+        .run();
+    assert!(!result.is_ok());
+    assert_eq!(result.name(), "bin-fixture");
+    let step = &result.steps()[0];
+    assert!(step.exit_status().unwrap().success());
+    assert_eq!(step.stdout().unwrap().to_string(), "hello\n");
+    assert!(step.to_string().contains("goodbye"), "{step}");
+}
+
+#[test]
+fn collect_results() {
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/basic.toml")
+        .add(
+            trycmd::Case::new()
+                .name("collect/mismatch")
+                .bin(trycmd::schema::Bin::Name("bin-fixture".into()))
+                .env("stdout", "hello")
+                .expect_stdout("goodbye\n"),
+        )
+        .collect();
+    let names: Vec<_> = results.iter().map(|r| r.name()).collect();
+    assert_eq!(names, ["collect/mismatch", "tests/cmd/basic.toml"]);
+    assert!(!results[0].is_ok());
+    assert!(results[1].is_ok(), "{}", results[1]);
+}
+
+#[test]
+fn nested_cases() {
+    let results = trycmd::TestCases::new()
+        .case_dir("tests/cmd/nested")
+        .collect();
+    let names: Vec<_> = results.iter().map(|r| r.name()).collect();
+    assert_eq!(names, ["help", "users::add"]);
+    for result in &results {
+        assert!(result.is_ok(), "{result}");
+    }
+}
+
+#[test]
+fn max_failures() {
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/max-failures/*.toml")
+        .max_failures(1)
+        .collect();
+    let names: Vec<_> = results.iter().map(|r| r.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "tests/cmd/max-failures/broken.toml",
+            "tests/cmd/max-failures/later.toml"
+        ]
+    );
+    assert!(!results[0].is_ok());
+    assert!(results[1].is_ok(), "{}", results[1]);
+    assert!(results[1].steps()[0].is_skipped());
+}
+
+#[test]
+fn assert_output() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_bin-fixture"))
+        .env("stdout", "Hello\nWorld!\n")
+        .env(
+            "stderr",
+            format!("Running in {}", std::env::current_dir().unwrap().display()),
+        )
+        .output()
+        .unwrap();
+    trycmd::assert(output)
+        .success()
+        .stdout_matches_path("tests/cmd/stdout.stdout")
+        .stderr_eq("Running in [CWD]\n");
+}
+
+#[test]
+#[should_panic(expected = "stdout")]
+fn assert_output_mismatch() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_bin-fixture"))
+        .env("stdout", "Goodbye")
+        .output()
+        .unwrap();
+    trycmd::assert(output)
+        .success()
+        .stdout_matches_path("tests/cmd/stdout.stdout");
+}
+
+#[test]
+fn assert_matches_path() {
+    let rendered = format!(
+        "name = \"demo\"\nversion = \"{}\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    trycmd::assert_matches_path(rendered.as_str(), "tests/cmd/matches-path/config.toml");
+    trycmd::assert_matches_path(rendered.into_bytes(), "tests/cmd/matches-path/config.toml");
+    trycmd::assert_matches_path(
+        std::path::Path::new("tests/cmd/stdout.stdout"),
+        "tests/cmd/stdout.stdout",
+    );
+}
+
+#[test]
+#[should_panic(expected = "other")]
+fn assert_matches_path_mismatch() {
+    trycmd::assert_matches_path(
+        "name = \"other\"\nversion = \"1.0.0\"\n",
+        "tests/cmd/matches-path/config.toml",
+    );
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+fn assert_dirs() {
+    trycmd::DirAssert::new()
+        .compare("stamp.txt", trycmd::schema::FileCompare::Exists)
+        .subset_eq("tests/cmd/dirs/expected", "tests/cmd/dirs/actual");
+    trycmd::DirAssert::new()
+        .ignore("*.log")
+        .compare("stamp.txt", trycmd::schema::FileCompare::Exists)
+        .eq("tests/cmd/dirs/expected", "tests/cmd/dirs/actual");
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+#[should_panic(expected = "build.log")]
+fn assert_eq_dirs_extra() {
+    trycmd::DirAssert::new()
+        .compare("stamp.txt", trycmd::schema::FileCompare::Exists)
+        .eq("tests/cmd/dirs/expected", "tests/cmd/dirs/actual");
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+#[should_panic(expected = "stamp.txt")]
+fn assert_subset_eq_mismatch() {
+    trycmd::assert_subset_eq("tests/cmd/dirs/expected", "tests/cmd/dirs/actual");
+}
+
+#[test]
+fn register_fn() {
+    let results = trycmd::TestCases::new()
+        .add(
+            trycmd::Case::new()
+                .name("in-process/echo")
+                .bin(trycmd::schema::Bin::Name("echo".into()))
+                .args(["Hello", "World!"])
+                .stdin("from stdin")
+                .expect_stdout("echo Hello World!\nfrom stdin\n"),
+        )
+        .add(
+            trycmd::Case::new()
+                .name("in-process/panic")
+                .bin(trycmd::schema::Bin::Name("echo".into()))
+                .arg("--panic")
+                .expect_status(trycmd::schema::CommandStatus::Code(101))
+                .expect_stderr("panicked: told to\n"),
+        )
+        .register_fn("echo", |args: &[String], stdin: &[u8]| {
+            if args.iter().any(|arg| arg == "--panic") {
+                panic!("told to");
+            }
+            let stdout = format!("{}\n{}\n", args.join(" "), String::from_utf8_lossy(stdin));
+            (0, stdout.into_bytes(), Vec::new())
+        })
+        .collect();
+    for result in &results {
+        assert!(result.is_ok(), "{}", result);
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn register_fn_unsupported() {
+    #[allow(unused_mut)]
+    let mut options = vec![
+        ("shell", "`shell`"),
+        ("wrapper", "`wrapper`"),
+        ("priority", "`priority`"),
+        ("env", "`env`"),
+        ("limits", "`[limits]`"),
+    ];
+    #[cfg(feature = "filesystem")]
+    options.push(("sandbox", "`fs.sandbox`"));
+    let cases = trycmd::TestCases::new();
+    for (stem, _) in &options {
+        cases.case(format!("tests/cmd/in-process/{stem}.toml"));
+    }
+    let results = cases
+        .register_fn("echo", |_: &[String], _: &[u8]| (0, Vec::new(), Vec::new()))
+        .collect();
+    assert_eq!(results.len(), options.len());
+    for result in &results {
+        assert!(!result.is_ok(), "{}", result);
+        let report = result.to_string();
+        let name = result.name();
+        let stem = std::path::Path::new(&name)
+            .file_stem()
+            .unwrap()
+            .to_string_lossy();
+        let (_, option) = options.iter().find(|(s, _)| *s == stem).unwrap();
+        assert!(
+            report.contains(option) && report.contains("in-process bin `echo`"),
+            "{report}"
+        );
+    }
+}
+
+#[test]
+fn seed() {
+    let seeds = |seed: u64| {
+        let results = trycmd::TestCases::new()
+            .add(
+                trycmd::Case::new()
+                    .name("seed/a")
+                    .bin(trycmd::schema::Bin::Name("bin-fixture".into())),
+            )
+            .add(
+                trycmd::Case::new()
+                    .name("seed/b")
+                    .bin(trycmd::schema::Bin::Name("bin-fixture".into())),
+            )
+            // `bin-fixture` prints `stdout`
+            .seed_env("stdout")
+            .seed(seed)
+            .collect();
+        results
+            .iter()
+            .map(|r| r.steps()[0].stdout().unwrap().render().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let first = seeds(1);
+    assert_eq!(first, seeds(1));
+    assert_ne!(first[0], first[1]);
+    assert_ne!(first, seeds(2));
+    assert!(first[0].trim_end().parse::<u64>().is_ok(), "{}", first[0]);
+}
+
+#[test]
+fn tags() {
+    let skipped = |filter: &dyn Fn(&trycmd::TestCases)| {
+        let cases = trycmd::TestCases::new();
+        cases.case("tests/cmd/tags/*.toml");
+        filter(&cases);
+        let results = cases.collect();
+        assert!(results.iter().all(trycmd::CaseResult::is_ok));
+        let mut skipped = results
+            .iter()
+            .filter(|r| r.steps()[0].is_skipped())
+            .map(|r| r.name().rsplit(['/', '\\']).next().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        skipped.sort();
+        skipped
+    };
+    // `quick` depends on `setup`, so it runs despite its tags
+    assert_eq!(
+        skipped(&|cases| {
+            cases.tags(["fast"]);
+        }),
+        ["fetch.toml", "untagged.toml"]
+    );
+    assert_eq!(
+        skipped(&|cases| {
+            cases.exclude_tags(["network"]);
+        }),
+        ["fetch.toml"]
+    );
+    assert_eq!(
+        skipped(&|cases| {
+            cases.tags(["slow"]).exclude_tags(["network"]);
+        }),
+        ["fetch.toml", "quick.toml", "untagged.toml"]
+    );
+}
+
+#[test]
+fn filter_depends_on() {
+    // `quick` depends on `setup`, so it runs despite the filter
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/tags/*.toml")
+        .filter("quick.toml")
+        .collect();
+    let mut names = results
+        .iter()
+        .inspect(|r| assert!(r.is_ok(), "{}", r))
+        .map(|r| r.name().rsplit(['/', '\\']).next().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["quick.toml", "setup.toml"]);
+}
+
+#[test]
+fn normalizer() {
+    let results = trycmd::TestCases::new()
+        .add(
+            trycmd::Case::new()
+                .name("normalizer/cores")
+                .bin(trycmd::schema::Bin::Name("bin-fixture".into()))
+                .env("stdout", "Building on 16 cores")
+                .expect_stdout("Building on [N] cores\n"),
+        )
+        .normalizer(|text: String| {
+            text.split(' ')
+                .map(|word| match word.parse::<usize>() {
+                    Ok(_) => "[N]",
+                    Err(_) => word,
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    assert!(results[0].is_ok(), "{}", results[0]);
+}
+
+#[test]
+fn output_validator() {
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/validator/*.toml")
+        .validator("csv", compare_csv)
+        .collect();
+    let names: Vec<_> = results.iter().map(|r| r.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "tests/cmd/validator/prices.toml",
+            "tests/cmd/validator/totals.toml"
+        ]
+    );
+    assert!(results[0].is_ok(), "{}", results[0]);
+    assert!(!results[1].is_ok());
+    let report = results[1].to_string();
+    assert!(
+        report.contains("row 2, column 2: expected `3.00`, got `2.5`"),
+        "{report}"
+    );
+}
+
+/// Compare CSV cell by cell, with numbers compared by value
+fn compare_csv(actual: &[u8], expected: &std::path::Path) -> Result<(), Vec<trycmd::Difference>> {
+    let expected = std::fs::read_to_string(expected)
+        .map_err(|e| vec![trycmd::Difference::new(e.to_string())])?;
+    let actual = String::from_utf8_lossy(actual);
+    let mut differences = Vec::new();
+    if actual.lines().count() != expected.lines().count() {
+        differences.push(trycmd::Difference::new("the row counts differ"));
+    }
+    for (row, (actual, expected)) in actual.lines().zip(expected.lines()).enumerate() {
+        for (column, (actual, expected)) in actual.split(',').zip(expected.split(',')).enumerate() {
+            let same = match (actual.parse::<f64>(), expected.parse::<f64>()) {
+                (Ok(actual), Ok(expected)) => actual == expected,
+                _ => actual == expected,
+            };
+            if !same {
+                differences.push(
+                    trycmd::Difference::new(format!("expected `{expected}`, got `{actual}`"))
+                        .at(format!("row {}, column {}", row + 1, column + 1)),
+                );
+            }
+        }
+    }
+    match differences.is_empty() {
+        true => Ok(()),
+        false => Err(differences),
+    }
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+fn sandbox_root() {
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("sandbox-root");
+    let _ = std::fs::remove_dir_all(&root);
+
+    let t = trycmd::TestCases::new();
+    t.case("tests/cmd/sandbox.toml").sandbox_root(&root);
+    let expected_root = root.clone();
+    t.setup(move |case| {
+        let sandbox = case.sandbox().expect("sandboxed");
+        let expected_root = expected_root.canonicalize().unwrap();
+        if sandbox.starts_with(&expected_root) {
+            Ok(())
+        } else {
+            Err(trycmd::Error::new(format!(
+                "{} isn't in {}",
+                sandbox.display(),
+                expected_root.display()
+            )))
+        }
+    });
+    let results = t.collect();
+    assert!(results[0].is_ok(), "{}", results[0]);
+}
+
+#[test]
+fn record() {
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("record");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let path = root.join("recorded.toml");
+
+    let t = trycmd::TestCases::new();
+    t.env("stdout", "hello").env("exit", "2");
+    t.record(&path, "$ bin-fixture --flag 'two words'").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "bin.name = \"bin-fixture\"\nargs = [\"--flag\", \"two words\"]\nstatus.code = 2\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("recorded.stdout")).unwrap(),
+        "hello\n"
+    );
+    assert!(t.record(&path, "$ bin-fixture").is_err());
+
+    let results = t.case(&path).collect();
+    assert!(results[0].is_ok(), "{}", results[0]);
+}
+
+#[test]
+#[cfg(unix)]
+fn wrapper() {
+    let results = trycmd::TestCases::new()
+        .wrapper(["env", "stdout=wrapped"])
+        .add(
+            trycmd::Case::new()
+                .bin(trycmd::schema::Bin::Name("bin-fixture".into()))
+                .expect_stdout("wrapped\n"),
+        )
+        .collect();
+    assert!(results[0].is_ok(), "{}", results[0]);
+}
+
+#[test]
+#[cfg(unix)]
+fn timeout_grace() {
+    let results = trycmd::TestCases::new()
+        .add(
+            trycmd::Case::new()
+                .bin(trycmd::schema::Bin::Name("bin-fixture".into()))
+                .env("sleep", "30")
+                .env("on_term", "shutting down")
+                .timeout(std::time::Duration::from_millis(100))
+                .timeout_grace(std::time::Duration::from_secs(20))
+                .expect_stdout("shutting down\n"),
+        )
+        .collect();
+    assert!(results[0].is_ok(), "{}", results[0]);
+    assert!(results[0].duration() < std::time::Duration::from_secs(20));
+}
+
+#[test]
+#[cfg(unix)]
+fn signals() {
+    let result = trycmd::Case::from_path("tests/cmd/signals/interrupt.toml").run();
+    assert!(result.is_ok(), "{result}");
+    assert!(result.duration() < std::time::Duration::from_secs(30));
+}
+
+#[test]
+fn fake_time() {
+    trycmd::TestCases::new().case("tests/cmd/fake-time/*.toml");
+}
+
+#[test]
+fn tolerate_interleave() {
+    trycmd::TestCases::new().case("tests/cmd/tolerate-interleave/*.toml");
+}
+
+#[test]
+fn locales() {
+    trycmd::TestCases::new().case("tests/cmd/locales/*.toml");
+}
+
+#[test]
+fn http_stub() {
+    trycmd::TestCases::new().case("tests/cmd/http-stub/*.toml");
+}
+
+#[test]
+fn port() {
+    trycmd::TestCases::new().case("tests/cmd/port/*.toml");
+}
+
+#[test]
+#[cfg(unix)]
+fn stdin_close() {
+    trycmd::TestCases::new().case("tests/cmd/stdin-close/*.toml");
+}
+
+#[test]
+#[cfg(unix)]
+fn expect_line() {
+    let result = trycmd::Case::from_path("tests/cmd/expect-line/serve.toml").run();
+    assert!(result.is_ok(), "{result}");
+    assert!(result.duration() < std::time::Duration::from_secs(10));
+
+    let result = trycmd::Case::from_path("tests/cmd/expect-line/missing.toml").run();
+    assert!(!result.is_ok());
+    let report = result.to_string();
+    assert!(
+        report.contains("Timed out waiting for a line with `Listening on`, after:\nStarting"),
+        "{report}"
+    );
+
+    // Closing its output without exiting still times out
+    #[cfg(unix)]
+    {
+        let result = trycmd::Case::from_path("tests/cmd/expect-line/closed.toml").run();
+        assert!(!result.is_ok(), "{result}");
+        assert!(result.duration() < std::time::Duration::from_secs(10));
+        let report = result.to_string();
+        assert!(
+            report.contains("Timed out waiting for a line with `Listening on`"),
+            "{report}"
+        );
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn background() {
+    let result = trycmd::Case::from_path("tests/cmd/background/server.trycmd").run();
+    assert!(result.is_ok(), "{result}");
+    assert_eq!(result.steps().len(), 2);
+    assert!(result.duration() < std::time::Duration::from_secs(30));
+}
+
+#[test]
+fn inline_output_conflict() {
+    let result = trycmd::Case::from_path("tests/cmd/conflict/inline.toml").run();
+    assert!(!result.is_ok());
+    assert!(result.to_string().contains("keep only one"), "{result}");
+}
+
+#[test]
+fn inline_trycmd() {
+    trycmd::trycmd!(
+        r#"
+        $ stdout=hello bin-fixture
+        hello
+
+        $ exit=1 bin-fixture
+        ? 1
+        "#
+    );
+}
+
+#[test]
+#[should_panic(expected = "case failed")]
+fn inline_trycmd_failure() {
+    trycmd::trycmd!("$ stdout=hello bin-fixture\ngoodbye");
+}
+
+#[test]
+fn artifacts() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("artifacts");
+    let _ = std::fs::remove_dir_all(&dir);
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/artifacts/mismatch.toml")
+        .artifacts_dir(&dir)
+        .collect();
+    assert!(!results[0].is_ok());
+
+    let bundle = dir.join("tests_cmd_artifacts_mismatch.toml");
+    let read = |file: &str| std::fs::read_to_string(bundle.join(file)).unwrap();
+    assert_eq!(read("actual.stdout"), "Goodbye\n");
+    assert_eq!(read("expected.stdout"), "Hello\n");
+    let meta: serde_json::Value = serde_json::from_str(&read("meta.json")).unwrap();
+    assert_eq!(meta["steps"][0]["passed"], false);
+    assert_eq!(meta["steps"][0]["env"]["stdout"], "Goodbye");
+    assert!(meta["steps"][0]["argv"][0]
+        .as_str()
+        .unwrap()
+        .contains("bin-fixture"));
+    #[cfg(feature = "diff")]
+    assert_eq!(
+        read("diff.patch"),
+        "--- a/tests/cmd/artifacts/mismatch.stdout\n+++ b/tests/cmd/artifacts/mismatch.stdout\n@@ -1 +1 @@\n-Hello\n+Goodbye\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "diff")]
+fn artifacts_binary() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("artifacts-binary");
+    let _ = std::fs::remove_dir_all(&dir);
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/artifacts/binary.toml")
+        .artifacts_dir(&dir)
+        .collect();
+    assert!(!results[0].is_ok());
+
+    let bundle = dir.join("tests_cmd_artifacts_binary.toml");
+    assert!(!bundle.join("diff.patch").exists());
+    let meta = std::fs::read_to_string(bundle.join("meta.json")).unwrap();
+    let meta: serde_json::Value = serde_json::from_str(&meta).unwrap();
+    assert_eq!(
+        meta["binary"],
+        serde_json::json!(["tests/cmd/artifacts/binary.stdout"])
+    );
+}
+
+#[test]
+#[cfg(feature = "diff")]
+fn accept() {
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("accept");
+    let _ = std::fs::remove_dir_all(&root);
+    let cases = root.join("cmd");
+    std::fs::create_dir_all(&cases).unwrap();
+    for file in ["mismatch.toml", "mismatch.stdout"] {
+        std::fs::copy(
+            std::path::Path::new("tests/cmd/artifacts").join(file),
+            cases.join(file),
+        )
+        .unwrap();
+    }
+    let dir = root.join("artifacts");
+    let run = || {
+        trycmd::TestCases::new()
+            .case(cases.join("mismatch.toml"))
+            .artifacts_dir(&dir)
+            .collect()
+    };
+    assert!(!run()[0].is_ok());
+
+    trycmd::TestCases::new()
+        .case(cases.join("mismatch.toml"))
+        .artifacts_dir(&dir)
+        .accept();
+    assert_eq!(
+        std::fs::read_to_string(cases.join("mismatch.stdout")).unwrap(),
+        "Goodbye\n"
+    );
+    assert!(run()[0].is_ok());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+}
+
+#[test]
+fn deadline() {
+    let results = trycmd::TestCases::new()
+        .case("tests/cmd/stdout.toml")
+        .deadline(std::time::Duration::ZERO)
+        .collect();
+    assert!(results[0].is_ok());
+    assert!(results[0].steps()[0].is_skipped());
+}
+
+#[test]
+#[should_panic(expected = "1 of 1 tests not run before the 0s deadline")]
+fn deadline_fails_run() {
+    trycmd::TestCases::new()
+        .case("tests/cmd/stdout.toml")
+        .deadline(std::time::Duration::ZERO)
+        .quiet();
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+fn fs_expect_escape() {
+    let result = trycmd::Case::from_path("tests/cmd/fs-expect/escape.toml").run();
+    assert!(!result.is_ok());
+    let report = result.to_string();
+    assert!(
+        report.contains(
+            "`fs.expect` path `../escape.toml` must be a relative path within the sandbox"
+        ),
+        "{report}"
+    );
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+fn base_readonly_files() {
+    let result = trycmd::Case::from_path("tests/cmd/base-readonly/files.toml").run();
+    assert!(result.is_ok(), "{}", result);
+    assert_eq!(
+        std::fs::read_to_string("tests/cmd/base-readonly/files.in/data.txt").unwrap(),
+        "shared\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "filesystem")]
+fn base_readonly_write_through() {
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("base-readonly-write");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("write.in")).unwrap();
+    std::fs::write(root.join("write.in/data.txt"), "shared\n").unwrap();
+    std::fs::write(
+        root.join("write.toml"),
+        "bin.name = \"bin-fixture\"\n\
+         fs.sandbox = true\n\
+         fs.base-readonly = true\n\
+         env.add.write = \"data.txt=changed\"\n",
+    )
+    .unwrap();
+
+    let result = trycmd::Case::from_path(root.join("write.toml")).run();
+    assert!(!result.is_ok(), "{}", result);
+    assert!(
+        result.to_string().contains("written to in place"),
+        "{}",
+        result
+    );
+    let original = std::fs::metadata(root.join("write.in/data.txt")).unwrap();
+    assert!(!original.permissions().readonly());
+}
+
+#[test]
+fn description() {
+    let result = trycmd::Case::from_path("tests/cmd/description/mismatch.toml").run();
+    assert!(!result.is_ok());
+    let report = result.to_string();
+    assert!(
+        report.contains("Greets in the configured language rather than English"),
+        "{report}"
+    );
+    assert!(
+        report.contains("See https://github.com/assert-rs/trycmd/issues/42"),
+        "{report}"
+    );
 }
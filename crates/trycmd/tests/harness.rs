@@ -0,0 +1,7 @@
+fn main() {
+    trycmd::TestCases::new()
+        .case("tests/cmd/basic.toml")
+        .case("tests/cmd/code.toml")
+        .case("tests/cmd/skip.toml")
+        .run_harness();
+}